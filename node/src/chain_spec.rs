@@ -1,4 +1,10 @@
+use bulletproofs::BulletproofGens;
 use frame_benchmarking::whitelisted_caller;
+use merkle::utils::{
+	keys::get_bp_gen_bytes,
+	setup::{Backend, Curve, HashFunction, LeafFormat, Setup},
+};
+use mixer::MixerGenesisConfig;
 use sc_service::ChainType;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{sr25519, Pair, Public, H160, U256};
@@ -6,8 +12,8 @@ use sp_finality_grandpa::AuthorityId as GrandpaId;
 use sp_runtime::traits::{IdentifyAccount, Verify};
 use std::{collections::BTreeMap, str::FromStr};
 use webb_runtime::{
-	AccountId, AuraConfig, BalancesConfig, EVMConfig, GenesisConfig, GrandpaConfig, Signature, SudoConfig,
-	SystemConfig, WASM_BINARY,
+	currency::DOLLARS, AccountId, AuraConfig, BalancesConfig, EVMConfig, GenesisConfig, GrandpaConfig, MaxTreeDepth,
+	MixerConfig, NativeCurrencyId, Signature, SudoConfig, SystemConfig, WASM_BINARY,
 };
 
 // The URL for the telemetry server.
@@ -166,5 +172,25 @@ fn testnet_genesis(
 		dynamic_fee: Default::default(),
 		ethereum: Default::default(),
 		evm: EVMConfig { accounts: evm_accounts },
+		mixer: mixer_genesis(),
+	}
+}
+
+/// Mixers of the native currency, sharing a Bulletproofs verifying key.
+fn mixer_genesis() -> MixerConfig {
+	let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+	MixerConfig {
+		verifying_keys: vec![get_bp_gen_bytes(&BulletproofGens::new(16400, 1))],
+		mixers: [1_000, 10_000, 100_000, 1_000_000]
+			.iter()
+			.map(|&size| MixerGenesisConfig {
+				currency_id: NativeCurrencyId::get(),
+				size: DOLLARS * size,
+				setup: setup.clone(),
+				depth: MaxTreeDepth::get(),
+				verifying_key: 0,
+				leaf_format: LeafFormat::Opaque,
+			})
+			.collect(),
 	}
 }
@@ -32,6 +32,8 @@ pub struct Cli {
 pub enum Subcommand {
 	/// Key management cli utilities
 	Key(sc_cli::KeySubcommand),
+	/// Viewing keys for the selective disclosure of mixer deposits
+	ViewingKey(crate::viewing_key::ViewingKeySubcommand),
 	/// Build a chain specification.
 	BuildSpec(sc_cli::BuildSpecCmd),
 
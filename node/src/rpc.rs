@@ -17,7 +17,8 @@ use webb_runtime::{opaque::Block, AccountId, Balance, BlockNumber, Hash, Index};
 
 use fc_rpc::{OverrideHandle, RuntimeApiStorageOverride, SchemaV1Override, StorageOverride};
 use jsonrpc_pubsub::manager::SubscriptionManager;
-use merkle_rpc::{MerkleApi, MerkleClient};
+use merkle_rpc::{MerkleApi, MerkleClient, MerkleMemoApi, MerkleMemoClient};
+use mixer_rpc::{MixerApi, MixerClient};
 use pallet_ethereum::EthereumStorageSchema;
 use sc_finality_grandpa::{FinalityProofProvider, GrandpaJustificationStream, SharedAuthoritySet, SharedVoterState};
 use sc_finality_grandpa_rpc::GrandpaRpcHandler;
@@ -52,7 +53,7 @@ pub struct GrandpaDeps<B> {
 }
 
 /// Full client dependencies.
-pub struct FullDeps<C, P, SC, B> {
+pub struct FullDeps<C, P, SC, B: sc_client_api::Backend<Block>> {
 	/// The client instance to use.
 	pub client: Arc<C>,
 	/// Transaction pool instance.
@@ -77,6 +78,8 @@ pub struct FullDeps<C, P, SC, B> {
 	pub backend: Arc<fc_db::Backend<Block>>,
 	/// Maximum number of logs in a query.
 	pub max_past_logs: u32,
+	/// Offchain database, holding the memos of merkle tree leaves.
+	pub offchain_storage: Option<B::OffchainStorage>,
 }
 
 /// Instantiate all Full RPC extensions.
@@ -96,6 +99,7 @@ where
 	C::Api: fp_rpc::EthereumRuntimeRPCApi<Block>,
 	C::Api: BlockBuilder<Block>,
 	C::Api: merkle::MerkleApi<Block>,
+	C::Api: mixer::MixerApi<Block, u32, BlockNumber>,
 	P: TransactionPool<Block = Block> + 'static,
 	SC: SelectChain<Block> + 'static,
 	B: sc_client_api::Backend<Block> + Send + Sync + 'static,
@@ -122,6 +126,7 @@ where
 		enable_dev_signer,
 		grandpa,
 		max_past_logs,
+		offchain_storage,
 	} = deps;
 
 	let GrandpaDeps {
@@ -200,6 +205,10 @@ where
 	)));
 
 	io.extend_with(MerkleApi::to_delegate(MerkleClient::new(client.clone())));
+	io.extend_with(MixerApi::to_delegate(MixerClient::new(client.clone())));
+	if let Some(offchain_storage) = offchain_storage {
+		io.extend_with(MerkleMemoApi::to_delegate(MerkleMemoClient::new(offchain_storage)));
+	}
 
 	io.extend_with(sc_finality_grandpa_rpc::GrandpaApi::to_delegate(
 		GrandpaRpcHandler::new(
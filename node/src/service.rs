@@ -8,7 +8,7 @@ use fc_rpc::EthTask;
 use fc_rpc_core::types::{FilterPool, PendingTransactions};
 use futures::StreamExt;
 use sc_cli::SubstrateCli;
-use sc_client_api::{BlockchainEvents, ExecutorProvider, RemoteBackend};
+use sc_client_api::{Backend, BlockchainEvents, ExecutorProvider, RemoteBackend};
 use sc_consensus_aura::{ImportQueueParams, SlotProportion, StartAuraParams};
 use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
@@ -30,6 +30,7 @@ native_executor_instance!(
 	pub Executor,
 	webb_runtime::api::dispatch,
 	webb_runtime::native_version,
+	merkle::utils::host::merkle_crypto::HostFunctions,
 );
 
 type FullClient = sc_service::TFullClient<Block, RuntimeApi, Executor>;
@@ -236,6 +237,7 @@ pub fn new_full(mut config: Configuration, cli: &Cli) -> Result<TaskManager, Ser
 			Some(shared_authority_set.clone()),
 		);
 		let max_past_logs = cli.run.max_past_logs;
+		let offchain_storage = backend.offchain_storage();
 		let rpc_setup = (shared_voter_state.clone(), finality_proof_provider.clone());
 
 		let rpc_extensions_builder = move |deny_unsafe, _| {
@@ -258,6 +260,7 @@ pub fn new_full(mut config: Configuration, cli: &Cli) -> Result<TaskManager, Ser
 					finality_provider: finality_proof_provider.clone(),
 				},
 				max_past_logs,
+				offchain_storage: offchain_storage.clone(),
 			};
 			crate::rpc::create_full(deps, subscription_task_executor.clone())
 		};
@@ -0,0 +1,179 @@
+//! Viewing key cli utilities for the selective disclosure of mixer deposits
+use crate::{cli::Cli, service};
+use bulletproofs::BulletproofGens;
+use codec::{Decode, Encode};
+use curve25519_dalek::ristretto::CompressedRistretto;
+use merkle::{utils::setup::default_bulletproofs_poseidon_hasher, UsedNullifiers};
+use mixer::disclosure::{Disclosure, DisclosureError, ViewingKey};
+use rand::rngs::OsRng;
+use sc_cli::{CliConfiguration, Error, SharedParams, SubstrateCli};
+use sc_client_api::{Backend, StorageProvider};
+use sc_service::PartialComponents;
+use sp_blockchain::HeaderBackend;
+use sp_core::{bytes::from_hex, hexdisplay::HexDisplay, storage::StorageKey};
+use sp_runtime::generic::BlockId;
+use structopt::StructOpt;
+use webb_runtime::{opaque::Block, Runtime};
+
+/// Viewing key cli utilities
+#[derive(Debug, StructOpt)]
+pub enum ViewingKeySubcommand {
+	/// Generate a random viewing key and print it with its public key
+	Generate,
+
+	/// Decrypt the note of a `DepositDisclosure` event. The note can withdraw
+	/// the deposit if it hasn't been withdrawn yet
+	Decrypt {
+		/// Hex encoded viewing key
+		#[structopt(long)]
+		key: String,
+		/// Hex encoded ciphertext of the event
+		#[structopt(long)]
+		ciphertext: String,
+	},
+
+	/// Disclose a withdrawn deposit to an auditor
+	Disclose(DiscloseCmd),
+
+	/// Open a disclosure with the viewing key of the auditor
+	Verify {
+		/// Hex encoded viewing key of the auditor
+		#[structopt(long)]
+		key: String,
+		/// Hex encoded disclosure
+		#[structopt(long)]
+		disclosure: String,
+	},
+}
+
+impl ViewingKeySubcommand {
+	/// Run the command
+	pub fn run(&self, cli: &Cli) -> Result<(), Error> {
+		let hasher = default_bulletproofs_poseidon_hasher(BulletproofGens::new(1, 1));
+		match self {
+			ViewingKeySubcommand::Generate => {
+				let key = ViewingKey::random(&mut OsRng);
+				println!("Viewing key: 0x{}", HexDisplay::from(&key.to_bytes()));
+				println!("Public key:  0x{}", HexDisplay::from(key.public_key().as_bytes()));
+			}
+			ViewingKeySubcommand::Decrypt { key, ciphertext } => {
+				let note = parse_key(key)?
+					.decrypt(&parse_hex(ciphertext)?)
+					.ok_or_else(|| input_error("Ciphertext can't be decrypted with the viewing key"))?;
+				println!("Randomness:     0x{}", HexDisplay::from(note.r.as_bytes()));
+				println!("Nullifier:      0x{}", HexDisplay::from(note.nullifier.as_bytes()));
+				println!("Leaf:           0x{}", HexDisplay::from(note.leaf(&hasher).as_bytes()));
+				println!(
+					"Nullifier hash: 0x{}",
+					HexDisplay::from(note.nullifier_hash(&hasher).as_bytes())
+				);
+			}
+			ViewingKeySubcommand::Disclose(cmd) => {
+				let runner = cli.create_runner(cmd)?;
+				runner.sync_run(|config| {
+					let PartialComponents { client, .. } = service::new_partial(&config, cli)?;
+					cmd.run(&*client)
+				})?;
+			}
+			ViewingKeySubcommand::Verify { key, disclosure } => {
+				let disclosure = Disclosure::decode(&mut &parse_hex(disclosure)?[..])
+					.map_err(|_| input_error("Invalid disclosure encoding"))?;
+				disclosure
+					.open(&parse_key(key)?, &hasher)
+					.map_err(|e| input_error(&format!("Invalid disclosure: {:?}", e)))?;
+				println!("Mixer id:       {}", disclosure.mixer_id);
+				println!("Leaf index:     {}", disclosure.leaf_index);
+				println!("Leaf:           0x{}", HexDisplay::from(&disclosure.leaf));
+				println!("Nullifier hash: 0x{}", HexDisplay::from(&disclosure.nullifier_hash));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Disclose a withdrawn deposit to an auditor.
+///
+/// The disclosed note could still withdraw a deposit which hasn't been
+/// withdrawn, so the command refuses to disclose notes whose nullifier hash
+/// isn't spent at the best block of the local chain database.
+#[derive(Debug, StructOpt)]
+pub struct DiscloseCmd {
+	/// Hex encoded viewing key the deposit was encrypted to
+	#[structopt(long)]
+	key: String,
+	/// Hex encoded ciphertext of the `DepositDisclosure` event
+	#[structopt(long)]
+	ciphertext: String,
+	/// Id of the mixer of the deposit
+	#[structopt(long)]
+	mixer_id: u32,
+	/// Leaf index of the `DepositDisclosure` event
+	#[structopt(long)]
+	leaf_index: u32,
+	/// Hex encoded public key of the auditor
+	#[structopt(long)]
+	auditor: String,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	shared_params: SharedParams,
+}
+
+impl DiscloseCmd {
+	/// Run the command against the state of `client`
+	pub fn run<B, C>(&self, client: &C) -> Result<(), Error>
+	where
+		B: Backend<Block>,
+		C: StorageProvider<Block, B> + HeaderBackend<Block>,
+	{
+		let hasher = default_bulletproofs_poseidon_hasher(BulletproofGens::new(1, 1));
+		let note = parse_key(&self.key)?
+			.decrypt(&parse_hex(&self.ciphertext)?)
+			.ok_or_else(|| input_error("Ciphertext can't be decrypted with the viewing key"))?;
+		let auditor = parse_hex(&self.auditor)?;
+		if auditor.len() != 32 {
+			return Err(input_error("Auditor public key must be 32 bytes"));
+		}
+		let best = BlockId::Hash(client.info().best_hash);
+		// The nullifiers of a mixer are those of its tree, which has the same id
+		let is_spent = |nullifier_hash: &[u8; 32]| {
+			let key = UsedNullifiers::<Runtime>::hashed_key_for((self.mixer_id, nullifier_hash.to_vec()));
+			matches!(client.storage(&best, &StorageKey(key)), Ok(Some(_)))
+		};
+		let disclosure = Disclosure::new(
+			&note,
+			self.mixer_id,
+			self.leaf_index,
+			&CompressedRistretto::from_slice(&auditor),
+			&hasher,
+			is_spent,
+			&mut OsRng,
+		)
+		.map_err(|e| match e {
+			DisclosureError::UnspentNote => {
+				input_error("The deposit hasn't been withdrawn, its note can still spend it")
+			}
+			_ => input_error("Invalid auditor public key"),
+		})?;
+		println!("0x{}", HexDisplay::from(&disclosure.encode()));
+		Ok(())
+	}
+}
+
+impl CliConfiguration for DiscloseCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
+
+fn input_error(message: &str) -> Error {
+	Error::Input(message.into())
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, Error> {
+	from_hex(hex).map_err(|_| input_error("Invalid hex"))
+}
+
+fn parse_key(hex: &str) -> Result<ViewingKey, Error> {
+	ViewingKey::from_bytes(&parse_hex(hex)?).ok_or_else(|| input_error("Invalid viewing key"))
+}
@@ -22,8 +22,8 @@
 //!
 //! - Depositing some currency into the mixer.
 //! - Withdrawing the deposit from the mixer.
-//! - Stopping mixer operations.
-//! - Transfering the admin of the mixer.
+//! - Stopping the operations of a mixer.
+//! - Pausing the deposits or withdrawals of a mixer in an emergency.
 //!
 //! ### Terminology
 //!
@@ -36,11 +36,55 @@
 //! ### Dispatchable Functions
 //!
 //! - `deposit` - Deposit a fixed amount of cryptocurrency into the mixer.
+//! - `deposit_many` - Deposit into several mixers at once.
 //! - `withdraw` - Provide a zero-knowladge proof of the deposit and withdraw
 //!   from the mixer.
-//! - `set_stopped` - Stops the operation of all mixers.
-//! - `transfer_admin` - Transfers the admin role from sender to specified
-//!   account.
+//! - `set_stopped` - Stops the operation of a mixer.
+//! - `set_paused` - Pauses the deposits and/or the withdrawals of a mixer.
+//! - `create_with_bond` - Creates a mixer for any existing asset by reserving
+//!   a bond.
+//! - `set_key_approved` - Approves or revokes a verifying key for the mixers
+//!   made with `create_with_bond`.
+//! - `deregister_mixer` - Removes a mixer from the registry, releasing or
+//!   slashing the bond of its creator.
+//! - `reconcile` - Checks the invariants of the mixers and reports violations.
+//!
+//! Mixers are administered by the `AdminOrigin`, which creates and stops
+//! them. The `PauseOrigin` can only pause their deposits or withdrawals.
+//!
+//! Any account can also create a mixer with `create_with_bond`, for a deposit
+//! size between `MinimumDepositSize` and `MaximumDepositSize` and with a
+//! verifying key approved by the `AdminOrigin`. The
+//! `CreationBond` stays reserved from the creator until the mixer is
+//! deregistered. A mixer is registered under its asset, deposit size and
+//! setup, so each combination of them has at most one mixer.
+//!
+//! The `AdminOrigin` can take the slot of a mixer back with
+//! `deregister_mixer`, e.g. from a creator squatting it. The bond of the
+//! creator is then released, or slashed and burned. Deregistered mixers take
+//! no more deposits, but their deposits can still be withdrawn.
+//!
+//! ### Invariants
+//!
+//! The total value locked of each mixer is updated with checked arithmetic,
+//! and the following invariants must hold:
+//!
+//! - The balance of the pallet account in each asset is at least the sum of
+//!   the total value locked of the mixers of that asset.
+//! - The total value locked of each mixer is the fixed deposit size times the
+//!   number of deposits outstanding, i.e. its leaf count minus the number of
+//!   spent nullifiers.
+//!
+//! The `AdminOrigin` can check them with `reconcile`, which emits an
+//! `InvariantViolated` event for each [`InvariantViolation`]. With the
+//! `try-runtime` feature, they are also checked after runtime upgrades.
+//!
+//! ### Genesis
+//!
+//! The mixers of a chain are declared in the [`GenesisConfig`], together with
+//! the verifying keys of their trees. They are created and initialized when
+//! the genesis storage is built, which fails on an invalid mixer or key. The
+//! verifying keys of the genesis are approved for `create_with_bond`.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -50,28 +94,27 @@ pub mod mock;
 #[cfg(test)]
 pub mod tests;
 
+pub mod migrations;
 pub mod traits;
 pub mod weights;
 
-use bulletproofs::BulletproofGens;
 use codec::{Decode, Encode};
 use frame_support::{
 	dispatch, ensure,
-	traits::{tokens::fungibles, Get, ReservableCurrency},
+	traits::{tokens::fungibles, Currency, EnsureOrigin, Get, Imbalance, ReservableCurrency},
+	transactional,
 	weights::Weight,
 	PalletId,
 };
 use frame_system::ensure_signed;
 use fungibles::{Inspect, Transfer};
 use merkle::{
-	utils::{
-		keys::ScalarBytes,
-		permissions::ensure_admin,
-		setup::{Backend, Curve, HashFunction, Setup},
-	},
+	utils::{keys::ScalarBytes, setup::Setup},
 	Pallet as MerklePallet, Tree as TreeTrait,
 };
-use sp_runtime::traits::{AccountIdConversion, Saturating, Zero};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_runtime::traits::{AccountIdConversion, CheckedAdd, CheckedMul, CheckedSub, Saturating, Zero};
 use sp_std::prelude::*;
 use traits::ExtendedMixer;
 use weights::WeightInfo;
@@ -106,25 +149,24 @@ pub mod pallet {
 		/// The small deposit length
 		#[pallet::constant]
 		type DepositLength: Get<Self::BlockNumber>;
-		/// Default admin key
+		/// Bond reserved from the creator of a mixer made with
+		/// `create_with_bond`
 		#[pallet::constant]
-		type DefaultAdmin: Get<Self::AccountId>;
+		type CreationBond: Get<NativeBalanceOf<Self>>;
+		/// Smallest deposit size of a mixer made with `create_with_bond`
+		#[pallet::constant]
+		type MinimumDepositSize: Get<BalanceOf<Self>>;
+		/// Largest deposit size of a mixer made with `create_with_bond`
+		#[pallet::constant]
+		type MaximumDepositSize: Get<BalanceOf<Self>>;
+		/// Origin allowed to create and stop mixers
+		type AdminOrigin: EnsureOrigin<Self::Origin>;
+		/// Origin allowed to pause the deposits or withdrawals of a mixer
+		type PauseOrigin: EnsureOrigin<Self::Origin>;
 		/// Weight information for extrinsics in this pallet
 		type WeightInfo: WeightInfo;
-		// Available mixes sizes (Size is determend by the deposit amount)
-		type MixerSizes: Get<Vec<BalanceOf<Self>>>;
 	}
 
-	/// Flag indicating if the mixer trees are created
-	#[pallet::storage]
-	#[pallet::getter(fn first_stage_initialized)]
-	pub type FirstStageInitialized<T: Config> = StorageValue<_, bool, ValueQuery>;
-
-	/// Flag indicating if the mixers are initialized
-	#[pallet::storage]
-	#[pallet::getter(fn second_stage_initialized)]
-	pub type SecondStageInitialized<T: Config> = StorageValue<_, bool, ValueQuery>;
-
 	/// The map of mixer trees to their metadata
 	#[pallet::storage]
 	#[pallet::getter(fn mixer_trees)]
@@ -135,20 +177,116 @@ pub mod pallet {
 	#[pallet::getter(fn mixer_group_ids)]
 	pub type MixerTreeIds<T: Config> = StorageValue<_, Vec<T::TreeId>, ValueQuery>;
 
-	/// Administrator of the mixer pallet.
-	/// This account that can stop/start operations of the mixer
+	/// Whether the deposits and withdrawals of a mixer are paused
 	#[pallet::storage]
-	#[pallet::getter(fn admin)]
-	pub type Admin<T: Config> = StorageValue<_, T::AccountId, ValueQuery>;
+	#[pallet::getter(fn paused)]
+	pub type Paused<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, PauseState, ValueQuery>;
 
 	/// The TVL per group
 	#[pallet::storage]
 	#[pallet::getter(fn total_value_locked)]
 	pub type TotalValueLocked<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, BalanceOf<T>, ValueQuery>;
 
+	/// The registry of mixers by their asset, deposit size and setup
+	#[pallet::storage]
+	#[pallet::getter(fn mixer_registry)]
+	pub type MixerRegistry<T: Config> =
+		StorageMap<_, Blake2_128Concat, (CurrencyIdOf<T>, BalanceOf<T>, Setup), T::TreeId, OptionQuery>;
+
+	/// The creators of mixers made with `create_with_bond` and their reserved
+	/// bonds
+	#[pallet::storage]
+	#[pallet::getter(fn creation_bonds)]
+	pub type CreationBonds<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::TreeId, (T::AccountId, NativeBalanceOf<T>), OptionQuery>;
+
+	/// Verifying keys the mixers made with `create_with_bond` can be
+	/// initialized with
+	#[pallet::storage]
+	#[pallet::getter(fn is_key_approved)]
+	pub type ApprovedKeys<T: Config> = StorageMap<_, Blake2_128Concat, T::KeyId, bool, ValueQuery>;
+
+	/// The number of nullifiers spent in each mixer
+	#[pallet::storage]
+	#[pallet::getter(fn nullifier_count)]
+	pub type NullifierCount<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, u32, ValueQuery>;
+
+	/// Storage layout release the pallet's storage was last migrated to
+	#[pallet::storage]
+	#[pallet::getter(fn storage_version)]
+	pub type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig<T: Config>
+	where
+		CurrencyIdOf<T>: MaybeSerializeDeserialize,
+		BalanceOf<T>: MaybeSerializeDeserialize,
+	{
+		/// Verifying keys added to the merkle pallet, referred to by their
+		/// index from `mixers`
+		pub verifying_keys: Vec<Vec<u8>>,
+		/// Mixers created and initialized at genesis
+		pub mixers: Vec<MixerGenesisConfig<CurrencyIdOf<T>, BalanceOf<T>>>,
+	}
+
+	#[cfg(feature = "std")]
+	impl<T: Config> Default for GenesisConfig<T>
+	where
+		CurrencyIdOf<T>: MaybeSerializeDeserialize,
+		BalanceOf<T>: MaybeSerializeDeserialize,
+	{
+		fn default() -> Self {
+			GenesisConfig {
+				verifying_keys: vec![],
+				mixers: vec![],
+			}
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig<T>
+	where
+		CurrencyIdOf<T>: MaybeSerializeDeserialize,
+		BalanceOf<T>: MaybeSerializeDeserialize,
+	{
+		fn build(&self) {
+			let key_ids: Vec<T::KeyId> = self
+				.verifying_keys
+				.iter()
+				.map(|key| T::Tree::add_verifying_key(key.clone()).expect("invalid verifying key in genesis."))
+				.collect();
+			for key_id in &key_ids {
+				ApprovedKeys::<T>::insert(key_id, true);
+			}
+
+			for mixer in &self.mixers {
+				assert!(mixer.size > Zero::zero(), "zero deposit size of a mixer in genesis.");
+				let key_id = *key_ids
+					.get(mixer.verifying_key as usize)
+					.expect("unknown verifying key of a mixer in genesis.");
+				let mixer_id = Pallet::<T>::create_mixer(
+					Pallet::<T>::account_id(),
+					mixer.currency_id,
+					mixer.setup.clone(),
+					mixer.depth,
+					mixer.size,
+				)
+				.expect("invalid mixer in genesis.");
+				T::Tree::initialize_tree(mixer_id, key_id)
+					.expect("mixer tree can't be initialized with its verifying key in genesis.");
+			}
+		}
+	}
+
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
-	#[pallet::metadata(T::AccountId = "AccountId", T::TreeId = "TreeId", BalanceOf<T> = "Balance")]
+	#[pallet::metadata(
+		T::AccountId = "AccountId",
+		T::KeyId = "KeyId",
+		T::TreeId = "TreeId",
+		CurrencyIdOf<T> = "AssetId",
+		BalanceOf<T> = "Balance"
+	)]
 	pub enum Event<T: Config> {
 		/// New deposit added to the specific mixer
 		Deposit(
@@ -172,6 +310,63 @@ pub mod pallet {
 			/// Merkle root
 			ScalarBytes,
 		),
+		/// Deposits and withdrawals of the mixer paused or resumed
+		PauseSet(
+			/// Id of the tree
+			T::TreeId,
+			/// Whether the deposits are paused
+			bool,
+			/// Whether the withdrawals are paused
+			bool,
+		),
+		/// New mixer created by an account with a bond
+		MixerCreated(
+			/// Id of the tree
+			T::TreeId,
+			/// Account id of the creator
+			T::AccountId,
+			/// Id of the asset
+			CurrencyIdOf<T>,
+			/// Deposit size
+			BalanceOf<T>,
+		),
+		/// Verifying key approved or revoked for `create_with_bond`
+		KeyApprovalSet(
+			/// Id of the verifying key
+			T::KeyId,
+			/// Whether the key is approved
+			bool,
+		),
+		/// Mixer removed from the registry by the `AdminOrigin`
+		MixerDeregistered(
+			/// Id of the tree
+			T::TreeId,
+		),
+		/// Bond of the creator of a deregistered mixer unreserved
+		BondReleased(
+			/// Id of the tree
+			T::TreeId,
+			/// Account id of the creator
+			T::AccountId,
+			/// Amount unreserved
+			NativeBalanceOf<T>,
+		),
+		/// Bond of the creator of a deregistered mixer slashed
+		BondSlashed(
+			/// Id of the tree
+			T::TreeId,
+			/// Account id of the creator
+			T::AccountId,
+			/// Amount slashed
+			NativeBalanceOf<T>,
+		),
+		/// Invariant of the mixers found violated by a reconciliation
+		InvariantViolated(InvariantViolation<CurrencyIdOf<T>, BalanceOf<T>, T::TreeId>),
+		/// Invariants of the mixers checked
+		Reconciled(
+			/// Number of violations found
+			u32,
+		),
 	}
 
 	#[pallet::error]
@@ -190,6 +385,28 @@ pub mod pallet {
 		UnauthorizedCall,
 		/// Mixer is stopped
 		MixerStopped,
+		/// Deposits into the mixer are paused
+		DepositsPaused,
+		/// Withdrawals from the mixer are paused
+		WithdrawalsPaused,
+		/// A mixer already exists for the asset, deposit size and setup
+		MixerAlreadyExists,
+		/// The asset of the mixer doesn't exist
+		UnknownAsset,
+		/// Deposit size is smaller than the minimum deposit size
+		DepositSizeTooSmall,
+		/// Deposit size is larger than the maximum deposit size
+		DepositSizeTooLarge,
+		/// No deposits were given
+		EmptyDeposits,
+		/// Verifying key isn't approved for `create_with_bond`
+		KeyNotApproved,
+		/// Total value locked of the mixer overflowed
+		Overflow,
+		/// Withdrawal takes more than the total value locked of the mixer
+		TvlUnderflow,
+		/// Mixer isn't in the registry
+		MixerNotRegistered,
 	}
 
 	#[pallet::pallet]
@@ -201,55 +418,34 @@ pub mod pallet {
 		fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
 			// We make sure that we return the correct weight for the block according to
 			// on_finalize
-			if Self::second_stage_initialized() {
-				// In case mixer is initialized, we expect the weights for merkle cache update
-				<T as Config>::WeightInfo::on_finalize_initialized()
-			} else {
-				// In case mixer is not initialized, we expect the weights for initialization
-				<T as Config>::WeightInfo::on_finalize_uninitialized()
-			}
+			<T as Config>::WeightInfo::on_finalize()
 		}
 
 		fn on_finalize(_n: BlockNumberFor<T>) {
-			if Self::first_stage_initialized() && !Self::second_stage_initialized() {
-				let mixer_ids = MixerTreeIds::<T>::get();
-				// check if first tree has been initialized, otherwise intialize the parameters
-				for i in 0..mixer_ids.len() {
-					if let Ok(initialized) = T::Tree::is_initialized(mixer_ids[i]) {
-						if !initialized {
-							match Self::initialize_second_stage() {
-								Ok(_) => {}
-								Err(e) => {
-									log::error!("Error initialising trees: {:?}", e);
-								}
-							}
-						}
-					}
-				}
-			}
+			// check if any deposits happened (by checking the size of the collection at
+			// this block) if none happened, carry over previous Merkle roots for the cache.
+			let mixer_ids = MixerTreeIds::<T>::get();
 
-			if Self::first_stage_initialized() && Self::second_stage_initialized() {
-				// check if any deposits happened (by checking the size of the collection at
-				// this block) if none happened, carry over previous Merkle roots for the cache.
-				let mixer_ids = MixerTreeIds::<T>::get();
-
-				for i in 0..mixer_ids.len() {
-					let cached_roots = <merkle::Pallet<T>>::cached_roots(_n, mixer_ids[i]);
-					// if there are no cached roots, carry forward the current root
-					if cached_roots.len() == 0 {
-						let _ = <merkle::Pallet<T>>::add_root_to_cache(mixer_ids[i], _n);
-					}
+			for i in 0..mixer_ids.len() {
+				let cached_roots = <merkle::Pallet<T>>::cached_roots(_n, mixer_ids[i]);
+				// if there are no cached roots, carry forward the current root
+				if cached_roots.len() == 0 {
+					let _ = <merkle::Pallet<T>>::add_root_to_cache(mixer_ids[i], _n);
 				}
 			}
+		}
 
-			if !Self::first_stage_initialized() {
-				match Self::initialize_first_stage() {
-					Ok(_) => {}
-					Err(e) => {
-						log::error!("Error initialising: {:?}", e);
-					}
-				}
-			}
+		fn on_runtime_upgrade() -> Weight {
+			migrations::migrate::<T>()
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			ensure!(
+				Self::check_invariants().is_empty(),
+				"Assets mixer invariants violated after the upgrade"
+			);
+			Ok(())
 		}
 	}
 
@@ -275,19 +471,16 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(!<MerklePallet<T>>::stopped(mixer_id), Error::<T>::MixerStopped);
+			ensure!(!Self::paused(mixer_id).deposits, Error::<T>::DepositsPaused);
 			// get mixer info, should always exist if the module is initialized
 			let mixer_info = Self::get_mixer(mixer_id)?;
 			// ensure the sender has enough balance to cover deposit
 			let balance = T::AssetSystem::balance(mixer_info.currency_id, &sender);
-			// TODO: Multiplication by usize should be possible
-			// using this hack for now, though we should optimise with regular
-			// multiplication `data_points.len() * mixer_info.fixed_deposit_size`
-			let deposit: BalanceOf<T> = data_points
-				.iter()
-				.map(|_| mixer_info.fixed_deposit_size)
-				.fold(Zero::zero(), |acc, elt| acc + elt);
-			println!("{:?}", deposit);
+			let deposit = Self::deposit_amount(&mixer_info, data_points.len())?;
 			ensure!(balance >= deposit, Error::<T>::InsufficientBalance);
+			let tvl = Self::total_value_locked(mixer_id)
+				.checked_add(&deposit)
+				.ok_or(Error::<T>::Overflow)?;
 			// transfer the deposit to the module and keep the account
 			let keep_alive = false;
 			T::AssetSystem::transfer(
@@ -298,8 +491,7 @@ pub mod pallet {
 				keep_alive,
 			)?;
 			// update the total value locked
-			let tvl = Self::total_value_locked(mixer_id);
-			<TotalValueLocked<T>>::insert(mixer_id, tvl.saturating_add(deposit));
+			<TotalValueLocked<T>>::insert(mixer_id, tvl);
 			// add elements to the mixer group's merkle tree and save the leaves
 			T::Tree::add_members(Self::account_id(), mixer_id.into(), data_points.clone())?;
 
@@ -310,6 +502,36 @@ pub mod pallet {
 			Ok(().into())
 		}
 
+		/// Deposits into several mixers at once, e.g. to split an amount
+		/// across their deposit sizes. `deposits` holds the data points of
+		/// each mixer, whose assets can differ.
+		///
+		/// The balance of the sender is checked once for each asset, and
+		/// either all or none of the deposits are made.
+		///
+		/// Fails if `deposits` is empty, or if any of the mixers is stopped,
+		/// paused or not initialized.
+		///
+		/// Weights:
+		/// - Dependent on arguments: `deposits` and their data points
+		///
+		/// - Base weight: 417_168_400_000
+		/// - DB weights: 1 + 8 * deposits.len() reads, 1 + 4 * deposits.len()
+		///   writes
+		/// - Additional weights: 21_400_442_000 * total number of data points
+		#[pallet::weight(<T as Config>::WeightInfo::deposit_many(
+			deposits.len() as u32,
+			deposits.iter().map(|(_, data_points)| data_points.len() as u32).sum(),
+		))]
+		pub fn deposit_many(
+			origin: OriginFor<T>,
+			deposits: Vec<(T::TreeId, Vec<ScalarBytes>)>,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			Self::do_deposit_many(sender, deposits)?;
+			Ok(().into())
+		}
+
 		/// Withdraws a deposited amount from the mixer. Can only withdraw one
 		/// deposit. Accepts proof of membership along with the mixer id.
 		///
@@ -327,12 +549,19 @@ pub mod pallet {
 				!<MerklePallet<T>>::stopped(withdraw_proof.mixer_id),
 				Error::<T>::MixerStopped
 			);
+			ensure!(
+				!Self::paused(withdraw_proof.mixer_id).withdrawals,
+				Error::<T>::WithdrawalsPaused
+			);
 			let recipient = withdraw_proof.recipient.unwrap_or(sender.clone());
 			let relayer = withdraw_proof.relayer.unwrap_or(sender.clone());
 			// get mixer info, should fail if tree isn't initialized
 			let mixer_info = Self::get_mixer(withdraw_proof.mixer_id)?;
 			// check if the nullifier has been used
 			T::Tree::has_used_nullifier(withdraw_proof.mixer_id.into(), withdraw_proof.nullifier_hash.clone())?;
+			let tvl = Self::total_value_locked(withdraw_proof.mixer_id)
+				.checked_sub(&mixer_info.fixed_deposit_size)
+				.ok_or(Error::<T>::TvlUnderflow)?;
 			// Verify the zero-knowledge proof of membership provided
 			T::Tree::verify_zk(
 				withdraw_proof.mixer_id.into(),
@@ -356,17 +585,14 @@ pub mod pallet {
 				keep_alive,
 			)?;
 			// update the total value locked
-			let tvl = Self::total_value_locked(withdraw_proof.mixer_id);
-			<TotalValueLocked<T>>::insert(
-				withdraw_proof.mixer_id,
-				tvl.saturating_sub(mixer_info.fixed_deposit_size),
-			);
+			<TotalValueLocked<T>>::insert(withdraw_proof.mixer_id, tvl);
 			// Add the nullifier on behalf of the module
 			T::Tree::add_nullifier(
 				Self::account_id(),
 				withdraw_proof.mixer_id.into(),
 				withdraw_proof.nullifier_hash,
 			)?;
+			NullifierCount::<T>::mutate(withdraw_proof.mixer_id, |count| *count += 1);
 
 			Self::deposit_event(Event::Withdraw(
 				withdraw_proof.mixer_id,
@@ -385,7 +611,7 @@ pub mod pallet {
 			setup: Setup,
 			size: BalanceOf<T>,
 		) -> DispatchResultWithPostInfo {
-			ensure_admin(origin, &Self::admin())?;
+			T::AdminOrigin::ensure_origin(origin)?;
 
 			<Self as ExtendedMixer<_>>::create_new(Self::account_id(), currency_id, setup, size)?;
 			Ok(().into())
@@ -399,47 +625,187 @@ pub mod pallet {
 			size: BalanceOf<T>,
 			key_id: T::KeyId,
 		) -> DispatchResultWithPostInfo {
-			ensure_admin(origin, &Self::admin())?;
+			T::AdminOrigin::ensure_origin(origin)?;
 
 			let tree_id = <Self as ExtendedMixer<_>>::create_new(Self::account_id(), currency_id, setup, size)?;
 			T::Tree::initialize_tree(tree_id, key_id)?;
 			Ok(().into())
 		}
 
-		/// Stops the operation of all the mixers managed by the pallet.
-		/// Can only be called by the admin or the root origin.
+		/// Stops or restarts the deposits and withdrawals of the mixer with id
+		/// of `mixer_id`. Can only be called by the `AdminOrigin`.
 		///
 		/// Weights:
 		/// - Independent of the arguments.
 		///
 		/// - Base weight: 36_000_000
-		/// - DB weights: 6 reads, 4 writes
+		/// - DB weights: 3 reads, 1 write
 		#[pallet::weight(<T as Config>::WeightInfo::set_stopped())]
-		pub fn set_stopped(origin: OriginFor<T>, stopped: bool) -> DispatchResultWithPostInfo {
-			// Ensure the caller is admin or root
-			ensure_admin(origin, &Self::admin())?;
-			// Set the mixer state, `stopped` can be true or false
-			let mixer_ids = MixerTreeIds::<T>::get();
-			for i in 0..mixer_ids.len() {
-				T::Tree::set_stopped(Self::account_id(), mixer_ids[i], stopped)?;
-			}
+		pub fn set_stopped(origin: OriginFor<T>, mixer_id: T::TreeId, stopped: bool) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(MixerTrees::<T>::contains_key(mixer_id), Error::<T>::NoMixerForId);
+			T::Tree::set_stopped(Self::account_id(), mixer_id, stopped)?;
 			Ok(().into())
 		}
 
-		/// Transfers the admin from the caller to the specified `to` account.
-		/// Can only be called by the current admin or the root origin.
+		/// Pauses or resumes the deposits and the withdrawals of the mixer with
+		/// id of `mixer_id`, independently of each other. Can only be called
+		/// by the `PauseOrigin`.
 		///
 		/// Weights:
 		/// - Independent of the arguments.
 		///
-		/// - Base weight: 7_000_000
+		/// - Base weight: 10_000_000
 		/// - DB weights: 1 read, 1 write
-		#[pallet::weight(<T as Config>::WeightInfo::transfer_admin())]
-		pub fn transfer_admin(origin: OriginFor<T>, to: T::AccountId) -> DispatchResultWithPostInfo {
-			// Ensures that the caller is the root or the current admin
-			ensure_admin(origin, &Self::admin())?;
-			// Updating the admin
-			Admin::<T>::set(to);
+		#[pallet::weight(<T as Config>::WeightInfo::set_paused())]
+		pub fn set_paused(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			deposits: bool,
+			withdrawals: bool,
+		) -> DispatchResultWithPostInfo {
+			T::PauseOrigin::ensure_origin(origin)?;
+			ensure!(MixerTrees::<T>::contains_key(mixer_id), Error::<T>::NoMixerForId);
+			Paused::<T>::insert(mixer_id, PauseState { deposits, withdrawals });
+			Self::deposit_event(Event::PauseSet(mixer_id, deposits, withdrawals));
+			Ok(().into())
+		}
+
+		/// Creates a mixer of the existing asset `currency_id` with a fixed
+		/// deposit of `size`, and initializes its tree with the verifying key
+		/// of `key_id`. Can be called by any account, which has the
+		/// `CreationBond` reserved until the mixer is deregistered.
+		///
+		/// Fails if a mixer already exists for the asset, size and setup, if
+		/// the size is out of the configured bounds, or if the verifying key
+		/// isn't approved by the `AdminOrigin`.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 180_000_000
+		/// - DB weights: 8 reads, 9 writes
+		#[pallet::weight(<T as Config>::WeightInfo::create_with_bond())]
+		#[transactional]
+		pub fn create_with_bond(
+			origin: OriginFor<T>,
+			currency_id: CurrencyIdOf<T>,
+			setup: Setup,
+			size: BalanceOf<T>,
+			key_id: T::KeyId,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			// assets can't be created with a zero minimum balance, so it marks a missing one
+			ensure!(
+				!T::AssetSystem::minimum_balance(currency_id).is_zero(),
+				Error::<T>::UnknownAsset
+			);
+			ensure!(size >= T::MinimumDepositSize::get(), Error::<T>::DepositSizeTooSmall);
+			ensure!(size <= T::MaximumDepositSize::get(), Error::<T>::DepositSizeTooLarge);
+			ensure!(Self::is_key_approved(key_id), Error::<T>::KeyNotApproved);
+
+			let bond = T::CreationBond::get();
+			T::Currency::reserve(&sender, bond)?;
+			let mixer_id = <Self as ExtendedMixer<_>>::create_new(Self::account_id(), currency_id, setup, size)?;
+			T::Tree::initialize_tree(mixer_id, key_id)?;
+			CreationBonds::<T>::insert(mixer_id, (sender.clone(), bond));
+
+			Self::deposit_event(Event::MixerCreated(mixer_id, sender, currency_id, size));
+			Ok(().into())
+		}
+
+		/// Approves or revokes the verifying key of `key_id` for the mixers
+		/// made with `create_with_bond`. Can only be called by the
+		/// `AdminOrigin`. Revoking a key doesn't affect the mixers already
+		/// using it.
+		///
+		/// Fails if an approved key doesn't exist.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 10_000_000
+		/// - DB weights: 2 reads, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::set_key_approved())]
+		pub fn set_key_approved(origin: OriginFor<T>, key_id: T::KeyId, approved: bool) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			if approved {
+				<MerklePallet<T>>::get_verifying_key(key_id)?;
+				ApprovedKeys::<T>::insert(key_id, true);
+			} else {
+				ApprovedKeys::<T>::remove(key_id);
+			}
+			Self::deposit_event(Event::KeyApprovalSet(key_id, approved));
+			Ok(().into())
+		}
+
+		/// Removes the mixer with id of `mixer_id` from the registry, so that
+		/// another mixer can be created for its asset, deposit size and setup.
+		/// Can only be called by the `AdminOrigin`.
+		///
+		/// Deposits into the mixer are paused, withdrawals are left as they
+		/// are so that its deposits can still be withdrawn. If the mixer was
+		/// made with `create_with_bond`, the bond of its creator is unreserved,
+		/// or slashed and burned if `slash_bond` is set.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 45_000_000
+		/// - DB weights: 5 reads, 5 writes
+		#[pallet::weight(<T as Config>::WeightInfo::deregister_mixer())]
+		pub fn deregister_mixer(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			slash_bond: bool,
+		) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			let mixer_info = Self::get_mixer(mixer_id)?;
+			let setup = <MerklePallet<T>>::get_tree(mixer_id)?.setup;
+			let registry_key = (mixer_info.currency_id, mixer_info.fixed_deposit_size, setup);
+			ensure!(
+				Self::mixer_registry(&registry_key) == Some(mixer_id),
+				Error::<T>::MixerNotRegistered
+			);
+
+			MixerRegistry::<T>::remove(registry_key);
+			Paused::<T>::mutate(mixer_id, |paused| paused.deposits = true);
+			Self::deposit_event(Event::MixerDeregistered(mixer_id));
+
+			if let Some((creator, bond)) = CreationBonds::<T>::take(mixer_id) {
+				if slash_bond {
+					// the imbalance is dropped, burning the slashed amount
+					let (slashed, _) = T::Currency::slash_reserved(&creator, bond);
+					Self::deposit_event(Event::BondSlashed(mixer_id, creator, slashed.peek()));
+				} else {
+					let remaining = T::Currency::unreserve(&creator, bond);
+					Self::deposit_event(Event::BondReleased(mixer_id, creator, bond.saturating_sub(remaining)));
+				}
+			}
+			Ok(().into())
+		}
+
+		/// Checks the invariants of all the mixers, emitting an
+		/// `InvariantViolated` event for each violation found. Can only be
+		/// called by the `AdminOrigin`.
+		///
+		/// Doesn't change the state of the mixers, mismatches have to be
+		/// investigated and fixed separately.
+		///
+		/// Weights:
+		/// - Dependent on the number of mixers
+		///
+		/// - Base weight: 20_000_000 + 12_000_000 * m
+		/// - DB weights: 1 + 5 * m reads
+		#[pallet::weight(<T as Config>::WeightInfo::reconcile(MixerTreeIds::<T>::decode_len().unwrap_or(0) as u32))]
+		pub fn reconcile(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			let violations = Self::check_invariants();
+			let count = violations.len() as u32;
+			for violation in violations {
+				Self::deposit_event(Event::InvariantViolated(violation));
+			}
+			Self::deposit_event(Event::Reconciled(count));
 			Ok(().into())
 		}
 	}
@@ -513,6 +879,86 @@ pub type BalanceOf<T> =
 /// Type alias for the webb_traits::MultiCurrency::CurrencyId type
 pub type CurrencyIdOf<T> =
 	<<T as pallet::Config>::AssetSystem as fungibles::Inspect<<T as frame_system::Config>::AccountId>>::AssetId;
+/// Type alias for the balance of the currency the creation bonds are reserved
+/// in
+pub type NativeBalanceOf<T> = <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+/// Pause flags of a mixer, set by the `PauseOrigin`
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq)]
+pub struct PauseState {
+	/// Whether deposits into the mixer are paused
+	pub deposits: bool,
+	/// Whether withdrawals from the mixer are paused
+	pub withdrawals: bool,
+}
+
+/// Violated invariant of the mixers, reported by `reconcile`
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub enum InvariantViolation<AssetId, Balance, TreeId> {
+	/// Balance of the pallet is lower than the total value locked in the
+	/// mixers of the asset
+	Undercollateralized {
+		currency_id: AssetId,
+		balance: Balance,
+		total_value_locked: Balance,
+	},
+	/// Total value locked of the mixer doesn't match its outstanding deposits
+	DepositsMismatch {
+		mixer_id: TreeId,
+		leaf_count: u32,
+		spent_nullifier_count: u32,
+		total_value_locked: Balance,
+	},
+}
+
+/// Storage layouts of the pallet, in the order they were released
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Releases {
+	/// Spent nullifiers are only stored by the merkle pallet
+	V1,
+	/// The number of spent nullifiers of each mixer is stored
+	V2NullifierCounts,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1
+	}
+}
+
+/// Mixer declared in the genesis config
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+#[derive(Clone, PartialEq)]
+pub struct MixerGenesisConfig<AssetId, Balance> {
+	/// Asset of the deposits
+	pub currency_id: AssetId,
+	/// Fixed deposit size of the mixer
+	pub size: Balance,
+	/// Hash function and proving backend of the tree
+	pub setup: Setup,
+	/// Depth of the tree
+	pub depth: u8,
+	/// Index of the verifying key of the tree in the genesis `verifying_keys`
+	pub verifying_key: u32,
+}
+
+/// Mixer listed by the runtime API, together with its total value locked
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct MixerSummary<TreeId, AssetId, Balance> {
+	/// Id of the tree
+	pub mixer_id: TreeId,
+	/// Id of the asset in the mixer
+	pub currency_id: AssetId,
+	/// Deposit size of the mixer
+	pub fixed_deposit_size: Balance,
+	/// Total value locked in the mixer
+	pub total_value_locked: Balance,
+}
 
 /// Info about the mixer and it's leaf data
 #[derive(Encode, Decode, PartialEq)]
@@ -565,48 +1011,157 @@ impl<T: Config> Pallet<T> {
 		Ok(mixer_info)
 	}
 
-	pub fn initialize_first_stage() -> dispatch::DispatchResult {
-		ensure!(!Self::first_stage_initialized(), Error::<T>::AlreadyInitialized);
+	/// Creates a mixer of `currency_id` with a fixed deposit of `size`,
+	/// whose tree of `depth` is managed by `account_id`.
+	pub fn create_mixer(
+		account_id: T::AccountId,
+		currency_id: CurrencyIdOf<T>,
+		setup: Setup,
+		depth: u8,
+		size: BalanceOf<T>,
+	) -> Result<T::TreeId, dispatch::DispatchError> {
+		let registry_key = (currency_id, size, setup.clone());
+		ensure!(
+			!MixerRegistry::<T>::contains_key(&registry_key),
+			Error::<T>::MixerAlreadyExists
+		);
+		let mixer_id: T::TreeId = T::Tree::create_tree(account_id, true, setup, depth)?;
+		let mixer_info = MixerInfo::<T>::new(T::DepositLength::get(), size, currency_id);
+		MixerTrees::<T>::insert(mixer_id, mixer_info);
+		MixerRegistry::<T>::insert(registry_key, mixer_id);
+		// Add new id to list
+		let mut ids = MixerTreeIds::<T>::get();
+		ids.push(mixer_id);
+		MixerTreeIds::<T>::set(ids);
+		Ok(mixer_id)
+	}
 
-		// Get default admin from trait params
-		let default_admin = T::DefaultAdmin::get();
-		// Initialize the admin in storage with default one
-		Admin::<T>::set(default_admin);
+	/// Deposits into several mixers, checking the balance of the sender and
+	/// transferring the deposits once for each asset. Either all or none of
+	/// the deposits are made.
+	#[transactional]
+	pub fn do_deposit_many(
+		sender: T::AccountId,
+		deposits: Vec<(T::TreeId, Vec<ScalarBytes>)>,
+	) -> dispatch::DispatchResult {
+		ensure!(!deposits.is_empty(), Error::<T>::EmptyDeposits);
+		let mut deposit_sizes = Vec::with_capacity(deposits.len());
+		let mut totals: Vec<(CurrencyIdOf<T>, BalanceOf<T>)> = Vec::new();
+		for (mixer_id, data_points) in &deposits {
+			ensure!(!<MerklePallet<T>>::stopped(*mixer_id), Error::<T>::MixerStopped);
+			ensure!(!Self::paused(*mixer_id).deposits, Error::<T>::DepositsPaused);
+			let mixer_info = Self::get_mixer(*mixer_id)?;
+			let deposit = Self::deposit_amount(&mixer_info, data_points.len())?;
+			match totals
+				.iter_mut()
+				.find(|(currency_id, _)| *currency_id == mixer_info.currency_id)
+			{
+				Some((_, total)) => *total = total.checked_add(&deposit).ok_or(Error::<T>::Overflow)?,
+				None => totals.push((mixer_info.currency_id, deposit)),
+			}
+			deposit_sizes.push((mixer_info.fixed_deposit_size, deposit));
+		}
+		// ensure the sender has enough balance to cover the deposits of each asset
+		for (currency_id, total) in &totals {
+			ensure!(
+				T::AssetSystem::balance(*currency_id, &sender) >= *total,
+				Error::<T>::InsufficientBalance
+			);
+		}
+		let keep_alive = false;
+		for (currency_id, total) in totals {
+			T::AssetSystem::transfer(currency_id, &sender, &Self::account_id(), total, keep_alive)?;
+		}
+		for ((mixer_id, data_points), (deposit_size, deposit)) in deposits.into_iter().zip(deposit_sizes) {
+			let tvl = Self::total_value_locked(mixer_id)
+				.checked_add(&deposit)
+				.ok_or(Error::<T>::Overflow)?;
+			<TotalValueLocked<T>>::insert(mixer_id, tvl);
+			T::Tree::add_members(Self::account_id(), mixer_id.into(), data_points)?;
+			Self::deposit_event(Event::Deposit(mixer_id, sender.clone(), deposit_size));
+		}
+		Ok(())
+	}
 
-		// Getting the sizes from the config
-		let sizes = T::MixerSizes::get();
+	/// Amount of `count` deposits into the mixer
+	fn deposit_amount(mixer_info: &MixerInfo<T>, count: usize) -> Result<BalanceOf<T>, dispatch::DispatchError> {
+		let count: BalanceOf<T> = (count as u32).into();
+		let amount = mixer_info
+			.fixed_deposit_size
+			.checked_mul(&count)
+			.ok_or(Error::<T>::Overflow)?;
+		Ok(amount)
+	}
 
-		let hasher = HashFunction::PoseidonDefault;
-		let backend = Backend::Bulletproofs(Curve::Curve25519);
-		let setup = Setup::new(hasher.clone(), backend.clone());
+	/// Checks the invariants of all the mixers, returning the violations
+	/// found
+	pub fn check_invariants() -> Vec<InvariantViolation<CurrencyIdOf<T>, BalanceOf<T>, T::TreeId>> {
+		let mut violations = Vec::new();
+		let mut locked: Vec<(CurrencyIdOf<T>, BalanceOf<T>)> = Vec::new();
+		for mixer_id in MixerTreeIds::<T>::get() {
+			let mixer_info = MixerTrees::<T>::get(mixer_id);
+			let total_value_locked = Self::total_value_locked(mixer_id);
+			match locked.iter_mut().find(|(id, _)| *id == mixer_info.currency_id) {
+				Some((_, total)) => *total = total.saturating_add(total_value_locked),
+				None => locked.push((mixer_info.currency_id, total_value_locked)),
+			}
 
-		// Iterating over configured sizes and initializing the mixers
-		for size in sizes.into_iter() {
-			<Self as ExtendedMixer<_>>::create_new(
-				Self::account_id(),
-				T::DefaultCurrencyId::get(),
-				setup.clone(),
-				size,
-			)?;
+			// every leaf not spent yet is a deposit of the fixed size
+			let leaf_count = T::Tree::get_leaf_count(mixer_id).unwrap_or_default();
+			let spent_nullifier_count = Self::nullifier_count(mixer_id);
+			let outstanding = leaf_count
+				.checked_sub(spent_nullifier_count)
+				.and_then(|count| Self::deposit_amount(&mixer_info, count as usize).ok());
+			if outstanding != Some(total_value_locked) {
+				violations.push(InvariantViolation::DepositsMismatch {
+					mixer_id,
+					leaf_count,
+					spent_nullifier_count,
+					total_value_locked,
+				});
+			}
 		}
 
-		FirstStageInitialized::<T>::set(true);
-		Ok(())
+		let account_id = Self::account_id();
+		for (currency_id, total_value_locked) in locked {
+			let balance = T::AssetSystem::balance(currency_id, &account_id);
+			if balance < total_value_locked {
+				violations.push(InvariantViolation::Undercollateralized {
+					currency_id,
+					balance,
+					total_value_locked,
+				});
+			}
+		}
+		violations
 	}
 
-	pub fn initialize_second_stage() -> dispatch::DispatchResult {
-		ensure!(Self::first_stage_initialized(), Error::<T>::AlreadyInitialized);
-
-		let key_data = merkle::utils::keys::get_bp_gen_bytes(&BulletproofGens::new(16400, 1));
-		let key_id = T::Tree::add_verifying_key(key_data)?;
-		let mixer_ids = MixerTreeIds::<T>::get();
-		for i in 0..mixer_ids.len() {
-			let tree_id = mixer_ids[i];
-			T::Tree::initialize_tree(tree_id, key_id)?;
-		}
+	/// Lists all the mixers with their total value locked
+	pub fn get_mixers() -> Vec<MixerSummary<T::TreeId, CurrencyIdOf<T>, BalanceOf<T>>> {
+		MixerTreeIds::<T>::get()
+			.into_iter()
+			.map(|mixer_id| {
+				let mixer_info = MixerTrees::<T>::get(mixer_id);
+				MixerSummary {
+					mixer_id,
+					currency_id: mixer_info.currency_id,
+					fixed_deposit_size: mixer_info.fixed_deposit_size,
+					total_value_locked: TotalValueLocked::<T>::get(mixer_id),
+				}
+			})
+			.collect()
+	}
+}
 
-		SecondStageInitialized::<T>::set(true);
-		Ok(())
+sp_api::decl_runtime_apis! {
+	pub trait AssetsMixerApi<TreeId, AssetId, Balance>
+	where
+		TreeId: codec::Codec,
+		AssetId: codec::Codec,
+		Balance: codec::Codec,
+	{
+		/// Get all the mixers with their total value locked.
+		fn get_mixers() -> Vec<MixerSummary<TreeId, AssetId, Balance>>;
 	}
 }
 
@@ -618,13 +1173,6 @@ impl<T: Config> ExtendedMixer<T> for Pallet<T> {
 		size: BalanceOf<T>,
 	) -> Result<T::TreeId, dispatch::DispatchError> {
 		let depth: u8 = <T as merkle::Config>::MaxTreeDepth::get();
-		let mixer_id: T::TreeId = T::Tree::create_tree(account_id, true, setup, depth)?;
-		let mixer_info = MixerInfo::<T>::new(T::DepositLength::get(), size, currency_id);
-		MixerTrees::<T>::insert(mixer_id, mixer_info);
-		// Add new id to list
-		let mut ids = MixerTreeIds::<T>::get();
-		ids.push(mixer_id);
-		MixerTreeIds::<T>::set(ids);
-		Ok(mixer_id)
+		Self::create_mixer(account_id, currency_id, setup, depth, size)
 	}
 }
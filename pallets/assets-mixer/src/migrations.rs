@@ -0,0 +1,35 @@
+//! Storage migrations of the assets mixer pallet
+//!
+//! Each migration runs once, when the stored [`Releases`] is older than the
+//! release introducing it, and bumps the stored version when done.
+use crate::{Config, MixerTreeIds, NullifierCount, Releases, StorageVersion};
+use frame_support::{traits::Get, weights::Weight};
+use merkle::UsedNullifiers;
+use sp_std::prelude::*;
+
+/// Runs the migrations the stored version hasn't been through yet.
+pub fn migrate<T: Config>() -> Weight {
+	let version = StorageVersion::<T>::get();
+	if version >= Releases::V2NullifierCounts {
+		return T::DbWeight::get().reads(1);
+	}
+	count_nullifiers::<T>();
+	StorageVersion::<T>::put(Releases::V2NullifierCounts);
+	// Counting goes through every nullifier spent in any tree
+	T::BlockWeights::get().max_block
+}
+
+/// Stores the number of nullifiers spent in each mixer before they were
+/// counted, from the nullifiers stored by the merkle pallet.
+fn count_nullifiers<T: Config>() {
+	let mixer_ids = MixerTreeIds::<T>::get();
+	let mut counts = vec![0u32; mixer_ids.len()];
+	for ((tree_id, _), _) in UsedNullifiers::<T>::iter() {
+		if let Some(i) = mixer_ids.iter().position(|mixer_id| *mixer_id == tree_id) {
+			counts[i] = counts[i].saturating_add(1);
+		}
+	}
+	for (mixer_id, count) in mixer_ids.into_iter().zip(counts) {
+		NullifierCount::<T>::insert(mixer_id, count);
+	}
+}
@@ -1,13 +1,22 @@
 use super::*;
 
 use crate as pallet_mixer;
+use bulletproofs::BulletproofGens;
 use frame_benchmarking::whitelisted_caller;
-use frame_support::{construct_runtime, parameter_types, weights::Weight, PalletId};
+use frame_support::{
+	construct_runtime, ord_parameter_types, parameter_types, traits::GenesisBuild, weights::Weight, PalletId,
+};
 use frame_system::{
 	mocking::{MockBlock, MockUncheckedExtrinsic},
-	EnsureRoot,
+	EnsureOneOf, EnsureRoot, EnsureSignedBy,
+};
+use merkle::{
+	utils::{
+		keys::get_bp_gen_bytes,
+		setup::{Backend, Curve, HashFunction},
+	},
+	weights::Weights as MerkleWeights,
 };
-use merkle::weights::Weights as MerkleWeights;
 
 use sp_core::H256;
 use sp_runtime::{
@@ -36,9 +45,8 @@ construct_runtime!(
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
 		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
 		MerkleTrees: merkle::{Pallet, Call, Storage, Event<T>},
-		Mixer: pallet_mixer::{Pallet, Call, Storage, Event<T>},
+		Mixer: pallet_mixer::{Pallet, Call, Storage, Config<T>, Event<T>},
 		Assets: pallet_assets::{Pallet, Storage, Event<T>},
-		Randomness: pallet_randomness_collective_flip::{Pallet, Call, Storage},
 	}
 );
 
@@ -82,6 +90,7 @@ parameter_types! {
 	pub const MaxReserves: u32 = 50;
 	pub const MaxTreeDepth: u8 = 32;
 	pub const CacheBlockLength: u64 = 5;
+	pub const MaxMemoLength: u32 = 256;
 	// Minimum deposit length is 1 month w/ 6 second blocks
 	pub const MinimumDepositLength: u64 = 10 * 60 * 24 * 28;
 }
@@ -126,34 +135,41 @@ impl merkle::Config for Test {
 	type CacheBlockLength = CacheBlockLength;
 	type Event = Event;
 	type KeyId = u32;
+	type MaxMemoLength = MaxMemoLength;
 	type MaxTreeDepth = MaxTreeDepth;
-	type Randomness = Randomness;
 	type TreeId = u32;
 	type WeightInfo = MerkleWeights<Self>;
 }
 
 parameter_types! {
 	pub const MixerPalletId: PalletId = PalletId(*b"py/mixer");
-	pub const DefaultAdmin: u64 = 4;
-	pub MixerSizes: Vec<Balance> = [1_000, 10_000, 100_000, 1_000_000].to_vec();
 	pub const DefaultCurrencyId: AssetId = 0;
+	pub const CreationBond: Balance = 1_000;
+	pub const MinimumDepositSize: Balance = 10;
+	pub const MaximumDepositSize: Balance = 1_000_000;
+}
+
+ord_parameter_types! {
+	pub const Admin: u64 = 4;
+	pub const PauseGuardian: u64 = 5;
 }
 
 impl Config for Test {
+	type AdminOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<Admin, u64>>;
 	type AssetSystem = Assets;
+	type CreationBond = CreationBond;
 	type Currency = Balances;
-	type DefaultAdmin = DefaultAdmin;
 	type DefaultCurrencyId = DefaultCurrencyId;
 	type DepositLength = MinimumDepositLength;
 	type Event = Event;
-	type MixerSizes = MixerSizes;
+	type MaximumDepositSize = MaximumDepositSize;
+	type MinimumDepositSize = MinimumDepositSize;
 	type PalletId = MixerPalletId;
+	type PauseOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<PauseGuardian, u64>>;
 	type Tree = MerkleTrees;
 	type WeightInfo = Weights<Self>;
 }
 
-impl pallet_randomness_collective_flip::Config for Test {}
-
 pub type MixerCall = pallet_mixer::Call<Test>;
 
 // Build genesis storage according to the mock runtime.
@@ -174,5 +190,22 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 	.assimilate_storage(&mut t)
 	.unwrap();
 
+	// the mixers of the default asset, sharing the first verifying key
+	MixerConfig {
+		verifying_keys: vec![get_bp_gen_bytes(&BulletproofGens::new(16400, 1))],
+		mixers: [1_000, 10_000, 100_000, 1_000_000]
+			.iter()
+			.map(|&size| MixerGenesisConfig {
+				currency_id: DefaultCurrencyId::get(),
+				size,
+				setup: Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+				depth: MaxTreeDepth::get(),
+				verifying_key: 0,
+			})
+			.collect(),
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+
 	t.into()
 }
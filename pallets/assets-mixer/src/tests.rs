@@ -1,6 +1,7 @@
 use super::*;
 use crate::mock::{
-	new_test_ext, AccountId, AssetId, Assets, Balance, Balances, MerkleTrees, Mixer, MixerCall, Origin, System, Test,
+	new_test_ext, AccountId, AssetId, Assets, Balance, Balances, Event as TestEvent, MerkleTrees, Mixer, MixerCall,
+	MixerConfig, Origin, System, Test,
 };
 use bulletproofs::{r1cs::Prover, BulletproofGens, PedersenGens};
 use bulletproofs_gadgets::{
@@ -13,11 +14,14 @@ use bulletproofs_gadgets::{
 use curve25519_dalek::scalar::Scalar;
 use frame_support::{
 	assert_err, assert_ok,
-	traits::{OnFinalize, UnfilteredDispatchable},
+	traits::{GenesisBuild, OnFinalize, OnRuntimeUpgrade, UnfilteredDispatchable},
 };
 use frame_system::RawOrigin;
 use merkle::{
-	utils::keys::{slice_to_bytes_32, ScalarBytes},
+	utils::{
+		keys::{get_bp_gen_bytes, slice_to_bytes_32, ScalarBytes},
+		setup::{Backend, Curve, HashFunction},
+	},
 	HighestCachedBlock,
 };
 use merlin::Transcript;
@@ -34,14 +38,13 @@ fn default_hasher(bp_gens: BulletproofGens) -> Poseidon {
 #[test]
 fn should_initialize_successfully() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
-		// the mixer creates 4 groups, they should all initialise to 0
+		// the genesis creates 4 groups, they should all initialise to 0
 		let val = 1_000;
 		for i in 0..4 {
 			let g = MerkleTrees::get_tree(i).unwrap();
 			let mng = MerkleTrees::get_manager(i).unwrap();
 			let m = Mixer::get_mixer(i).unwrap();
+			assert!(g.initialized);
 			assert_eq!(g.leaf_count, 0);
 			assert_eq!(mng.required, true);
 			assert_eq!(m.fixed_deposit_size, val * 10_u64.pow(i))
@@ -50,117 +53,153 @@ fn should_initialize_successfully() {
 }
 
 #[test]
-fn should_initialize_successfully_on_finalize() {
-	new_test_ext().execute_with(|| {
-		<Mixer as OnFinalize<u64>>::on_finalize(1);
-		<Mixer as OnFinalize<u64>>::on_finalize(2);
-		// the mixer creates 4 groups, they should all initialise to 0
-		let val = 1_000;
-		for i in 0..4 {
-			let g = MerkleTrees::get_tree(i).unwrap();
-			let mng = MerkleTrees::get_manager(i).unwrap();
-			let m = Mixer::get_mixer(i).unwrap();
-			assert_eq!(g.leaf_count, 0);
-			assert_eq!(mng.required, true);
-			assert_eq!(m.fixed_deposit_size, val * 10_u64.pow(i))
-		}
-	})
+#[should_panic(expected = "unknown verifying key of a mixer in genesis.")]
+fn should_fail_genesis_with_unknown_verifying_key() {
+	let config = MixerConfig {
+		verifying_keys: vec![get_bp_gen_bytes(&BulletproofGens::new(16, 1))],
+		mixers: vec![MixerGenesisConfig {
+			currency_id: 0,
+			size: 1_000,
+			setup: Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+			depth: 32,
+			verifying_key: 1,
+		}],
+	};
+	let _ = GenesisBuild::<Test>::build_storage(&config);
 }
 
 #[test]
-fn should_be_able_to_change_admin_with_root() {
+fn should_be_able_to_stop_mixer_with_root() {
 	new_test_ext().execute_with(|| {
-		let call = Box::new(MixerCall::transfer_admin(2));
-		let res = call.dispatch_bypass_filter(RawOrigin::Root.into());
-		assert_ok!(res);
-		let admin = Mixer::admin();
-		assert_eq!(admin, 2);
-
-		let call = Box::new(MixerCall::transfer_admin(3));
-		let res = call.dispatch_bypass_filter(RawOrigin::Signed(0).into());
-		assert_err!(res, BadOrigin);
-	})
-}
-
-#[test]
-fn should_be_able_to_stop_mixers_with_root() {
-	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		let call = Box::new(MixerCall::set_stopped(true));
+		let call = Box::new(MixerCall::set_stopped(1, true));
 		let res = call.dispatch_bypass_filter(RawOrigin::Root.into());
 		assert_ok!(res);
 
+		// Only the given mixer is stopped
 		for i in 0..4 {
-			let stopped = MerkleTrees::stopped(i);
-			assert!(stopped);
+			assert_eq!(MerkleTrees::stopped(i), i == 1);
 		}
 	})
 }
 
 #[test]
-fn should_be_able_to_change_admin() {
+fn should_only_allow_admin_origin_to_manage_mixers() {
 	new_test_ext().execute_with(|| {
-		let default_admin = 4;
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_err!(Mixer::transfer_admin(Origin::signed(1), 2), BadOrigin);
-		assert_ok!(Mixer::transfer_admin(Origin::signed(default_admin), 2));
-		let admin = Mixer::admin();
-
-		assert_eq!(admin, 2);
+		let admin = 4;
+		let pause_guardian = 5;
+		assert_err!(Mixer::set_stopped(Origin::signed(1), 0, true), BadOrigin);
+		assert_err!(Mixer::set_stopped(Origin::signed(pause_guardian), 0, true), BadOrigin);
+		assert_err!(
+			Mixer::create_new(
+				Origin::signed(pause_guardian),
+				0,
+				Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+				10
+			),
+			BadOrigin
+		);
+		assert_err!(
+			Mixer::set_stopped(Origin::signed(admin), 4, true),
+			Error::<Test>::NoMixerForId
+		);
+		assert_ok!(Mixer::set_stopped(Origin::signed(admin), 0, true));
+		assert!(MerkleTrees::stopped(0));
 	})
 }
 
+fn empty_withdraw_proof(mixer_id: u32) -> WithdrawProof<Test> {
+	WithdrawProof::new(
+		mixer_id,
+		0,
+		Scalar::zero().to_bytes().to_vec(),
+		Vec::new(),
+		Scalar::zero().to_bytes().to_vec(),
+		Vec::new(),
+		Vec::new(),
+		Vec::new(),
+		None,
+		None,
+	)
+}
+
 #[test]
 fn should_stop_and_start_mixer() {
 	new_test_ext().execute_with(|| {
-		let default_admin = 4;
+		let admin = 4;
 		let currency_id = 0;
 		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 0, 10000000));
 
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 		let leaf = tree.generate_secrets().to_bytes().to_vec();
 		assert_ok!(Mixer::deposit(Origin::signed(0), 0, vec![leaf.clone()]));
 
 		// Stopping deposits and withdrawal
-		assert_ok!(Mixer::set_stopped(Origin::signed(default_admin), true));
+		assert_ok!(Mixer::set_stopped(Origin::signed(admin), 0, true));
 		assert_err!(
 			Mixer::deposit(Origin::signed(0), 0, vec![]),
 			Error::<Test>::MixerStopped
 		);
 		assert_err!(
-			Mixer::withdraw(
-				Origin::signed(0),
-				WithdrawProof::new(
-					0,
-					0,
-					Scalar::zero().to_bytes().to_vec(),
-					Vec::new(),
-					Scalar::zero().to_bytes().to_vec(),
-					Vec::new(),
-					Vec::new(),
-					Vec::new(),
-					None,
-					None,
-				)
-			),
+			Mixer::withdraw(Origin::signed(0), empty_withdraw_proof(0)),
 			Error::<Test>::MixerStopped
 		);
 
 		// Starting mixer
-		assert_ok!(Mixer::set_stopped(Origin::signed(default_admin), false));
+		assert_ok!(Mixer::set_stopped(Origin::signed(admin), 0, false));
 		assert_ok!(Mixer::deposit(Origin::signed(0), 0, vec![leaf]));
 	})
 }
 
+#[test]
+fn should_pause_deposits_and_withdrawals_separately() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let pause_guardian = 5;
+		let currency_id = 0;
+		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
+
+		let leaf = [1u8; 32].to_vec();
+
+		assert_err!(Mixer::set_paused(Origin::signed(4), 0, true, false), BadOrigin);
+		assert_err!(
+			Mixer::set_paused(Origin::signed(pause_guardian), 4, true, false),
+			Error::<Test>::NoMixerForId
+		);
+
+		// Pausing withdrawals leaves deposits open
+		assert_ok!(Mixer::set_paused(Origin::signed(pause_guardian), 0, false, true));
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![leaf.clone()]));
+		assert_err!(
+			Mixer::withdraw(Origin::signed(1), empty_withdraw_proof(0)),
+			Error::<Test>::WithdrawalsPaused
+		);
+
+		// Pausing deposits leaves withdrawals open, and other mixers untouched
+		assert_ok!(Mixer::set_paused(Origin::root(), 0, true, false));
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 0, vec![leaf.clone()]),
+			Error::<Test>::DepositsPaused
+		);
+		assert_err!(
+			Mixer::withdraw(Origin::signed(1), empty_withdraw_proof(0)),
+			merkle::Error::<Test>::InvalidMerkleRoot
+		);
+		assert_ok!(Mixer::deposit(Origin::signed(1), 1, vec![leaf]));
+		assert_eq!(Mixer::paused(0), PauseState {
+			deposits: true,
+			withdrawals: false
+		});
+		let event = TestEvent::Mixer(Event::PauseSet(0, true, false));
+		assert!(System::events().iter().any(|record| record.event == event));
+	})
+}
+
 #[test]
 fn should_fail_to_deposit_with_insufficient_balance() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 
 		for i in 0..4 {
@@ -184,8 +223,6 @@ fn should_deposit_into_each_mixer_successfully() {
 		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
 
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 
 		for i in 0..4 {
@@ -214,13 +251,11 @@ fn should_withdraw_from_each_mixer_successfully() {
 		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
 
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let pc_gens = PedersenGens::default();
 
 		let key_id = 0;
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params);
+		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		for i in 0..4 {
@@ -274,7 +309,9 @@ fn should_withdraw_from_each_mixer_successfully() {
 			// ensure TVL is 0 after withdrawing
 			let tvl = Mixer::total_value_locked(i);
 			assert_eq!(tvl, 0);
+			assert_eq!(Mixer::nullifier_count(i), 1);
 		}
+		assert_eq!(Mixer::check_invariants(), vec![]);
 	})
 }
 
@@ -286,8 +323,6 @@ fn should_cache_roots_if_no_new_deposits_show() {
 		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
 
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 		let mut merkle_roots: Vec<ScalarBytes> = vec![];
 		for i in 0..4 {
@@ -325,8 +360,6 @@ fn should_not_have_cache_once_cache_length_exceeded() {
 		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
 
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 		let mut merkle_roots: Vec<ScalarBytes> = vec![];
 		for i in 0..4 {
@@ -372,8 +405,6 @@ fn should_make_mixer_with_non_native_token() {
 		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
 		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10000000));
 
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
 		assert_ok!(<Mixer as ExtendedMixer<Test>>::create_new(
 			Mixer::account_id(),
@@ -391,7 +422,7 @@ fn should_make_mixer_with_non_native_token() {
 		));
 
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params);
+		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		let pc_gens = PedersenGens::default();
@@ -460,26 +491,342 @@ fn should_make_mixer_with_non_native_token() {
 }
 
 #[test]
-fn should_initialize_in_two_steps_on_finalize() {
+fn should_create_mixer_with_bond() {
 	new_test_ext().execute_with(|| {
 		System::set_block_number(1);
-		<Mixer as OnFinalize<u64>>::on_finalize(1);
-		assert!(Mixer::first_stage_initialized());
+		let creator = 1;
+		let currency_id = 2;
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+		assert_ok!(Assets::mint(Origin::signed(1), currency_id, creator, 10000000));
 
-		for i in 0..4 {
-			let tree_id = i;
-			let tree = MerkleTrees::get_tree(tree_id).unwrap();
-			assert!(!tree.initialized);
+		let reserved_before = Balances::reserved_balance(&creator);
+		assert_ok!(Mixer::create_with_bond(
+			Origin::signed(creator),
+			currency_id,
+			setup.clone(),
+			500,
+			0
+		));
+
+		let tree_id = 4u32;
+		assert_eq!(Balances::reserved_balance(&creator), reserved_before + 1_000);
+		assert_eq!(Mixer::creation_bonds(tree_id), Some((creator, 1_000)));
+		assert_eq!(Mixer::mixer_registry((currency_id, 500, setup)), Some(tree_id));
+		let event = TestEvent::Mixer(Event::MixerCreated(tree_id, creator, currency_id, 500));
+		assert!(System::events().iter().any(|record| record.event == event));
+
+		// The new mixer is initialized and listed with its TVL
+		let leaf = [1u8; 32].to_vec();
+		assert_ok!(Mixer::deposit(Origin::signed(creator), tree_id, vec![leaf]));
+		let mixers = Mixer::get_mixers();
+		assert_eq!(mixers.len(), 5);
+		assert_eq!(mixers[4], MixerSummary {
+			mixer_id: tree_id,
+			currency_id,
+			fixed_deposit_size: 500,
+			total_value_locked: 500,
+		});
+		assert_eq!(mixers[0].total_value_locked, 0);
+	})
+}
+
+#[test]
+fn should_not_create_duplicate_or_invalid_mixers_with_bond() {
+	new_test_ext().execute_with(|| {
+		let creator = 1;
+		let currency_id = 0;
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(creator), currency_id, setup.clone(), 500, 0),
+			Error::<Test>::UnknownAsset
+		);
+		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(creator), currency_id, setup.clone(), 5, 0),
+			Error::<Test>::DepositSizeTooSmall
+		);
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(creator), currency_id, setup.clone(), 2_000_000, 0),
+			Error::<Test>::DepositSizeTooLarge
+		);
+		// The genesis mixers are registered as well
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(creator), currency_id, setup.clone(), 1_000, 0),
+			Error::<Test>::MixerAlreadyExists
+		);
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(3), currency_id, setup, 500, 0),
+			pallet_balances::Error::<Test, _>::InsufficientBalance
+		);
+		assert_eq!(Balances::reserved_balance(&creator), 0);
+		assert_eq!(Mixer::get_mixers().len(), 4);
+	})
+}
+
+#[test]
+fn should_deregister_mixers_and_release_or_slash_bonds() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let currency_id = 2;
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10_000));
+		assert_ok!(Mixer::create_with_bond(
+			Origin::signed(1),
+			currency_id,
+			setup.clone(),
+			500,
+			0
+		));
+		assert_ok!(Mixer::deposit(Origin::signed(1), 4, vec![[1u8; 32].to_vec()]));
+
+		assert_err!(Mixer::deregister_mixer(Origin::signed(1), 4, false), BadOrigin);
+		let free_before = Balances::free_balance(&1);
+		assert_ok!(Mixer::deregister_mixer(Origin::signed(4), 4, false));
+		assert_eq!(Balances::reserved_balance(&1), 0);
+		assert_eq!(Balances::free_balance(&1), free_before + 1_000);
+		assert_eq!(Mixer::creation_bonds(4), None);
+		assert_eq!(Mixer::mixer_registry((currency_id, 500, setup.clone())), None);
+		let event = TestEvent::Mixer(Event::BondReleased(4, 1, 1_000));
+		assert!(System::events().iter().any(|record| record.event == event));
+
+		// The deposits stay withdrawable, no new ones are taken
+		assert_eq!(Mixer::paused(4), PauseState {
+			deposits: true,
+			withdrawals: false,
+		});
+		assert_eq!(Mixer::total_value_locked(4), 500);
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 4, vec![[2u8; 32].to_vec()]),
+			Error::<Test>::DepositsPaused
+		);
+		assert_err!(
+			Mixer::deregister_mixer(Origin::signed(4), 4, false),
+			Error::<Test>::MixerNotRegistered
+		);
+
+		// The slot can be taken again, and the bond of a squatter slashed
+		assert_ok!(Mixer::create_with_bond(
+			Origin::signed(2),
+			currency_id,
+			setup.clone(),
+			500,
+			0
+		));
+		assert_eq!(Mixer::mixer_registry((currency_id, 500, setup)), Some(5));
+		let total_before = Balances::total_balance(&2);
+		assert_ok!(Mixer::deregister_mixer(RawOrigin::Root.into(), 5, true));
+		assert_eq!(Balances::reserved_balance(&2), 0);
+		assert_eq!(Balances::total_balance(&2), total_before - 1_000);
+		let event = TestEvent::Mixer(Event::BondSlashed(5, 2, 1_000));
+		assert!(System::events().iter().any(|record| record.event == event));
+
+		// Mixers without a bond are only removed from the registry
+		assert_ok!(Assets::force_create(Origin::root(), 0, 1, true, 1));
+		assert_ok!(Mixer::deregister_mixer(Origin::signed(4), 0, true));
+		let event = TestEvent::Mixer(Event::MixerDeregistered(0));
+		assert!(System::events().iter().any(|record| record.event == event));
+		assert_ok!(Mixer::create_with_bond(
+			Origin::signed(1),
+			0,
+			MerkleTrees::get_tree(0).unwrap().setup,
+			1_000,
+			0
+		));
+	})
+}
+
+#[test]
+fn should_only_create_mixers_with_approved_keys() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let creator = 1;
+		let currency_id = 2;
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+
+		// The key of the genesis mixers is approved, a newly added key isn't
+		assert!(Mixer::is_key_approved(0));
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(16400, 1));
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(creator), key_data));
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(creator), currency_id, setup.clone(), 500, 1),
+			Error::<Test>::KeyNotApproved
+		);
+		assert_err!(Mixer::set_key_approved(Origin::signed(creator), 1, true), BadOrigin);
+		assert_err!(
+			Mixer::set_key_approved(Origin::signed(4), 2, true),
+			merkle::Error::<Test>::InvalidVerifierKey
+		);
+
+		assert_ok!(Mixer::set_key_approved(Origin::signed(4), 1, true));
+		let event = TestEvent::Mixer(Event::KeyApprovalSet(1, true));
+		assert!(System::events().iter().any(|record| record.event == event));
+		assert_ok!(Mixer::create_with_bond(
+			Origin::signed(creator),
+			currency_id,
+			setup.clone(),
+			500,
+			1
+		));
+
+		// Revoking the key leaves the mixers using it alone
+		assert_ok!(Mixer::set_key_approved(Origin::signed(4), 1, false));
+		assert!(!Mixer::is_key_approved(1));
+		assert_err!(
+			Mixer::create_with_bond(Origin::signed(creator), currency_id, setup, 1_000, 1),
+			Error::<Test>::KeyNotApproved
+		);
+		assert!(MerkleTrees::get_tree(4).unwrap().initialized);
+	})
+}
+
+#[test]
+fn should_deposit_into_many_mixers_of_different_assets() {
+	new_test_ext().execute_with(|| {
+		let sender = 1;
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		for currency_id in 0..2 {
+			assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+			assert_ok!(Assets::mint(Origin::signed(1), currency_id, sender, 10_000));
 		}
+		assert_ok!(Mixer::create_with_bond(Origin::signed(sender), 1, setup, 500, 0));
+
+		let deposits = vec![
+			(0, vec![[1u8; 32].to_vec()]),
+			(4, vec![[2u8; 32].to_vec(), [3u8; 32].to_vec()]),
+		];
+		assert_ok!(Mixer::deposit_many(Origin::signed(sender), deposits));
+
+		assert_eq!(Assets::balance(0, &sender), 10_000 - 1_000);
+		assert_eq!(Assets::balance(1, &sender), 10_000 - 2 * 500);
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 1);
+		assert_eq!(MerkleTrees::get_tree(4).unwrap().leaf_count, 2);
+		assert_eq!(Mixer::total_value_locked(4), 2 * 500);
+
+		// The balance of each asset covers all of its deposits, or none are made
+		let deposits = vec![(1, vec![[4u8; 32].to_vec()]), (4, vec![[5u8; 32].to_vec(); 20])];
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(sender), deposits),
+			Error::<Test>::InsufficientBalance
+		);
+		assert_eq!(MerkleTrees::get_tree(1).unwrap().leaf_count, 0);
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(sender), vec![]),
+			Error::<Test>::EmptyDeposits
+		);
+	})
+}
 
-		System::set_block_number(2);
-		<Mixer as OnFinalize<u64>>::on_finalize(2);
-		assert!(Mixer::second_stage_initialized());
+#[test]
+fn should_fail_deposits_on_tvl_overflow_and_report_violated_invariants() {
+	new_test_ext().execute_with(|| {
+		let currency_id = 0;
+		assert_ok!(Assets::force_create(Origin::root(), currency_id, 1, true, 1));
+		assert_ok!(Assets::mint(Origin::signed(1), currency_id, 1, 10_000));
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![[1u8; 32].to_vec()]));
+		assert_eq!(Mixer::check_invariants(), vec![]);
 
-		for i in 0..4 {
-			let tree_id = i;
-			let tree = MerkleTrees::get_tree(tree_id).unwrap();
-			assert!(tree.initialized);
+		TotalValueLocked::<Test>::insert(0, Balance::max_value());
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 0, vec![[2u8; 32].to_vec()]),
+			Error::<Test>::Overflow
+		);
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(1), vec![(0, vec![[2u8; 32].to_vec()])]),
+			Error::<Test>::Overflow
+		);
+		assert_eq!(Assets::balance(currency_id, 1), 10_000 - size);
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 1);
+		assert_eq!(Mixer::check_invariants(), vec![
+			InvariantViolation::DepositsMismatch {
+				mixer_id: 0,
+				leaf_count: 1,
+				spent_nullifier_count: 0,
+				total_value_locked: Balance::max_value(),
+			},
+			InvariantViolation::Undercollateralized {
+				currency_id,
+				balance: size,
+				total_value_locked: Balance::max_value(),
+			},
+		]);
+	})
+}
+
+fn reconcile_events() -> (Vec<InvariantViolation<AssetId, Balance, u32>>, Vec<u32>) {
+	let mut violations = Vec::new();
+	let mut reconciled = Vec::new();
+	for record in System::events() {
+		match record.event {
+			TestEvent::Mixer(Event::InvariantViolated(violation)) => violations.push(violation),
+			TestEvent::Mixer(Event::Reconciled(count)) => reconciled.push(count),
+			_ => {}
 		}
+	}
+	(violations, reconciled)
+}
+
+#[test]
+fn should_reconcile_outstanding_deposits_with_tvl() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(Assets::force_create(Origin::root(), 0, 1, true, 1));
+		assert_ok!(Assets::mint(Origin::signed(1), 0, 1, 10_000));
+		let leaves = vec![[1u8; 32].to_vec(), [2u8; 32].to_vec()];
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, leaves));
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+
+		assert_err!(Mixer::reconcile(Origin::signed(1)), BadOrigin);
+		assert_ok!(Mixer::reconcile(Origin::signed(4)));
+		assert_ok!(Mixer::reconcile(RawOrigin::Root.into()));
+		assert_eq!(reconcile_events(), (vec![], vec![0, 0]));
+
+		// A nullifier spent without the deposit leaving the total value locked
+		NullifierCount::<Test>::insert(0, 1);
+		System::reset_events();
+		assert_ok!(Mixer::reconcile(Origin::signed(4)));
+		let violation = InvariantViolation::DepositsMismatch {
+			mixer_id: 0,
+			leaf_count: 2,
+			spent_nullifier_count: 1,
+			total_value_locked: 2 * size,
+		};
+		assert_eq!(reconcile_events(), (vec![violation], vec![1]));
+		// Reconciling only reports the mismatch
+		assert_eq!(Mixer::total_value_locked(0), 2 * size);
+		assert_eq!(Mixer::nullifier_count(0), 1);
+	})
+}
+
+#[test]
+fn should_migrate_nullifier_counts() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Assets::force_create(Origin::root(), 0, 1, true, 1));
+		assert_ok!(Assets::mint(Origin::signed(1), 0, 1, 10_000));
+		let leaves = vec![[1u8; 32].to_vec(), [2u8; 32].to_vec()];
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, leaves));
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+
+		// A withdrawal made before nullifiers were counted, next to a
+		// nullifier of a tree that isn't a mixer
+		merkle::UsedNullifiers::<Test>::insert((0, [3u8; 32].to_vec()), true);
+		merkle::UsedNullifiers::<Test>::insert((100, [3u8; 32].to_vec()), true);
+		TotalValueLocked::<Test>::insert(0, size);
+		assert!(!Mixer::check_invariants().is_empty());
+
+		Mixer::on_runtime_upgrade();
+		assert_eq!(Mixer::storage_version(), Releases::V2NullifierCounts);
+		assert_eq!(Mixer::nullifier_count(0), 1);
+		assert_eq!(Mixer::nullifier_count(1), 0);
+		assert_eq!(Mixer::check_invariants(), vec![]);
+
+		// Migrations only run once
+		NullifierCount::<Test>::remove(0);
+		Mixer::on_runtime_upgrade();
+		assert_eq!(Mixer::nullifier_count(0), 0);
 	})
 }
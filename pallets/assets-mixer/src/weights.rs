@@ -34,11 +34,15 @@ use sp_std::marker::PhantomData;
 /// Weight functions needed for pallet_mixer.
 pub trait WeightInfo {
 	fn deposit(n: u32) -> Weight;
+	fn deposit_many(m: u32, d: u32) -> Weight;
 	fn withdraw() -> Weight;
 	fn set_stopped() -> Weight;
-	fn transfer_admin() -> Weight;
-	fn on_finalize_uninitialized() -> Weight;
-	fn on_finalize_initialized() -> Weight;
+	fn set_paused() -> Weight;
+	fn create_with_bond() -> Weight;
+	fn set_key_approved() -> Weight;
+	fn deregister_mixer() -> Weight;
+	fn reconcile(m: u32) -> Weight;
+	fn on_finalize() -> Weight;
 }
 
 /// Weight functions for pallet_mixer.
@@ -53,6 +57,15 @@ impl<T: frame_system::Config + Config + merkle::Config> WeightInfo for Weights<T
 			.saturating_add(T::DbWeight::get().writes(5 as Weight))
 	}
 
+	fn deposit_many(m: u32, d: u32) -> Weight {
+		(417_168_400_000 as Weight)
+			.saturating_add((21_400_442_000 as Weight).saturating_mul(d as Weight))
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().reads((8 as Weight).saturating_mul(m as Weight)))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes((4 as Weight).saturating_mul(m as Weight)))
+	}
+
 	fn withdraw() -> Weight {
 		(1_078_562_000_000 as Weight)
 			.saturating_add(T::DbWeight::get().reads(9 as Weight))
@@ -61,23 +74,42 @@ impl<T: frame_system::Config + Config + merkle::Config> WeightInfo for Weights<T
 
 	fn set_stopped() -> Weight {
 		(36_000_000 as Weight)
-			.saturating_add(T::DbWeight::get().reads(6 as Weight))
-			.saturating_add(T::DbWeight::get().writes(4 as Weight))
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
 
-	fn transfer_admin() -> Weight {
-		(7_000_000 as Weight)
+	fn set_paused() -> Weight {
+		(10_000_000 as Weight)
 			.saturating_add(T::DbWeight::get().reads(1 as Weight))
 			.saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
 
-	fn on_finalize_uninitialized() -> Weight {
-		(53_000_000 as Weight)
+	fn create_with_bond() -> Weight {
+		(180_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(9 as Weight))
+	}
+
+	fn set_key_approved() -> Weight {
+		(10_000_000 as Weight)
 			.saturating_add(T::DbWeight::get().reads(2 as Weight))
-			.saturating_add(T::DbWeight::get().writes(16 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+
+	fn deregister_mixer() -> Weight {
+		(45_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(5 as Weight))
+			.saturating_add(T::DbWeight::get().writes(5 as Weight))
+	}
+
+	fn reconcile(m: u32) -> Weight {
+		(20_000_000 as Weight)
+			.saturating_add((12_000_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().reads((5 as Weight).saturating_mul(m as Weight)))
 	}
 
-	fn on_finalize_initialized() -> Weight {
+	fn on_finalize() -> Weight {
 		(93_000_000 as Weight)
 			.saturating_add(T::DbWeight::get().reads(10 as Weight))
 			.saturating_add(T::DbWeight::get().writes(4 as Weight))
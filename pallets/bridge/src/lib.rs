@@ -11,10 +11,14 @@ pub mod traits;
 pub use traits::*;
 
 use codec::{Decode, Encode};
-use frame_support::{dispatch, ensure, traits::Get, PalletId};
+use frame_support::{
+	dispatch, ensure,
+	traits::{EnsureOrigin, Get},
+	PalletId,
+};
 use frame_system::ensure_signed;
 use pallet_merkle::{
-	utils::{keys::ScalarBytes, permissions::ensure_admin},
+	utils::keys::ScalarBytes,
 	Tree as TreeTrait,
 };
 use sp_runtime::traits::{AccountIdConversion, AtLeast32Bit, One, Zero};
@@ -56,9 +60,10 @@ pub mod pallet {
 		/// Native currency id
 		#[pallet::constant]
 		type NativeCurrencyId: Get<CurrencyIdOf<Self>>;
-		/// Default admin key
-		#[pallet::constant]
-		type DefaultAdmin: Get<<Self as frame_system::Config>::AccountId>;
+		/// Origin allowed to stop anchors
+		type AdminOrigin: EnsureOrigin<Self::Origin>;
+		/// Origin allowed to pause the deposits or withdrawals of an anchor
+		type PauseOrigin: EnsureOrigin<Self::Origin>;
 		/// The overarching merkle tree trait
 		type Tree: TreeTrait<Self>;
 	}
@@ -100,11 +105,10 @@ pub mod pallet {
 	pub type ReverseWrappedTokenRegistry<T: Config> =
 		StorageMap<_, Blake2_128Concat, CurrencyIdOf<T>, Vec<CurrencyIdOf<T>>>;
 
-	/// Administrator of the bridge pallet.
-	/// This account that can stop/start operations of the bridge
+	/// Whether the deposits and withdrawals of an anchor are paused
 	#[pallet::storage]
-	#[pallet::getter(fn admin)]
-	pub type Admin<T: Config> = StorageValue<_, <T as frame_system::Config>::AccountId, ValueQuery>;
+	#[pallet::getter(fn paused)]
+	pub type Paused<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, PauseState, ValueQuery>;
 
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
@@ -132,6 +136,15 @@ pub mod pallet {
 			/// Merkle root
 			ScalarBytes,
 		),
+		/// Deposits and withdrawals of the anchor paused or resumed
+		PauseSet(
+			/// Id of the anchor
+			T::TreeId,
+			/// Whether the deposits are paused
+			bool,
+			/// Whether the withdrawals are paused
+			bool,
+		),
 	}
 
 	#[pallet::error]
@@ -150,6 +163,10 @@ pub mod pallet {
 		UnauthorizedCall,
 		/// Anchor is stopped
 		AnchorStopped,
+		/// Deposits into the anchor are paused
+		DepositsPaused,
+		/// Withdrawals from the anchor are paused
+		WithdrawalsPaused,
 	}
 	#[pallet::pallet]
 	#[pallet::generate_store(pub(super) trait Store)]
@@ -207,49 +224,49 @@ pub mod pallet {
 
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
 			ensure!(!T::Tree::is_stopped(tree_id), Error::<T>::AnchorStopped);
+			ensure!(!Self::paused(tree_id).deposits, Error::<T>::DepositsPaused);
 
 			<Self as PrivacyBridgeSystem>::deposit(sender, tree_id, leaf)?;
 
 			Ok(().into())
 		}
 
-		/// Stops the operation of all the anchors managed by the pallet.
-		/// Can only be called by the admin or the root origin.
+		/// Stops or restarts the anchor with id of `tree_id`.
+		/// Can only be called by the `AdminOrigin`.
 		///
 		/// Weights:
 		/// - Independent of the arguments.
 		///
 		/// - Base weight: 36_000_000
-		/// - DB weights: 6 reads, 4 writes
+		/// - DB weights: 3 reads, 1 write
 		#[pallet::weight(5_000_000)]
-		pub fn set_stopped(origin: OriginFor<T>, stopped: bool) -> DispatchResultWithPostInfo {
-			// Ensure the caller is admin or root
-			ensure_admin(origin, &Self::admin())?;
-			// Set the anchor state, `stopped` can be true or false
-			let tree_ids = BridgeTreeIds::<T>::get();
-			for i in 0..tree_ids.len() {
-				T::Tree::set_stopped(Self::account_id(), tree_ids[i], stopped)?;
-			}
+		pub fn set_stopped(origin: OriginFor<T>, tree_id: T::TreeId, stopped: bool) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			Self::get_anchor_info(tree_id)?;
+			T::Tree::set_stopped(Self::account_id(), tree_id, stopped)?;
 			Ok(().into())
 		}
 
-		/// Transfers the admin from the caller to the specified `to` account.
-		/// Can only be called by the current admin or the root origin.
+		/// Pauses or resumes the deposits and the withdrawals of the anchor
+		/// with id of `tree_id`, independently of each other. Can only be
+		/// called by the `PauseOrigin`.
 		///
 		/// Weights:
 		/// - Independent of the arguments.
 		///
-		/// - Base weight: 7_000_000
+		/// - Base weight: 10_000_000
 		/// - DB weights: 1 read, 1 write
 		#[pallet::weight(5_000_000)]
-		pub fn transfer_admin(
+		pub fn set_paused(
 			origin: OriginFor<T>,
-			to: <T as frame_system::Config>::AccountId,
+			tree_id: T::TreeId,
+			deposits: bool,
+			withdrawals: bool,
 		) -> DispatchResultWithPostInfo {
-			// Ensures that the caller is the root or the current admin
-			ensure_admin(origin, &Self::admin())?;
-			// Updating the admin
-			Admin::<T>::set(to);
+			T::PauseOrigin::ensure_origin(origin)?;
+			Self::get_anchor_info(tree_id)?;
+			Paused::<T>::insert(tree_id, PauseState { deposits, withdrawals });
+			Self::deposit_event(Event::PauseSet(tree_id, deposits, withdrawals));
 			Ok(().into())
 		}
 
@@ -262,6 +279,7 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			ensure!(!Self::paused(tree_id).deposits, Error::<T>::DepositsPaused);
 
 			<Self as PrivacyBridgeSystem>::wrap_and_deposit(sender, currency_id, tree_id, leaf)?;
 
@@ -272,6 +290,7 @@ pub mod pallet {
 		pub fn withdraw_zk(origin: OriginFor<T>, tree_id: T::TreeId, proof: Vec<u8>) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			ensure!(!Self::paused(tree_id).withdrawals, Error::<T>::WithdrawalsPaused);
 
 			<Self as PrivacyBridgeSystem>::withdraw_zk(sender, tree_id, proof)?;
 
@@ -282,6 +301,7 @@ pub mod pallet {
 		pub fn withdraw_public(origin: OriginFor<T>, tree_id: T::TreeId, proof: Vec<u8>) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			ensure!(!Self::paused(tree_id).withdrawals, Error::<T>::WithdrawalsPaused);
 
 			<Self as PrivacyBridgeSystem>::withdraw_public(sender, tree_id, proof)?;
 
@@ -296,6 +316,7 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			ensure!(!Self::paused(tree_id).withdrawals, Error::<T>::WithdrawalsPaused);
 
 			<Self as PrivacyBridgeSystem>::withdraw_zk_and_unwrap(sender, tree_id, proof)?;
 
@@ -310,6 +331,7 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			ensure!(!Self::paused(tree_id).withdrawals, Error::<T>::WithdrawalsPaused);
 
 			<Self as PrivacyBridgeSystem>::withdraw_public_and_unwrap(sender, tree_id, proof)?;
 
@@ -325,6 +347,9 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			let paused = Self::paused(tree_id);
+			ensure!(!paused.withdrawals, Error::<T>::WithdrawalsPaused);
+			ensure!(!paused.deposits, Error::<T>::DepositsPaused);
 
 			<Self as PrivacyBridgeSystem>::remix_zk(sender, tree_id, proof, leaf)?;
 
@@ -340,6 +365,9 @@ pub mod pallet {
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			ensure!(Self::initialised(), Error::<T>::NotInitialised);
+			let paused = Self::paused(tree_id);
+			ensure!(!paused.withdrawals, Error::<T>::WithdrawalsPaused);
+			ensure!(!paused.deposits, Error::<T>::DepositsPaused);
 
 			<Self as PrivacyBridgeSystem>::remix_public(sender, tree_id, proof, leaf)?;
 
@@ -450,10 +478,6 @@ impl<T: Config> Pallet<T> {
 	pub fn initialize() -> dispatch::DispatchResult {
 		ensure!(!Self::initialised(), Error::<T>::AlreadyInitialised);
 
-		// Get default admin from trait params
-		let default_admin = T::DefaultAdmin::get();
-		// Initialize the admin in storage with default one
-		Admin::<T>::set(default_admin);
 		Initialised::<T>::set(true);
 		Ok(())
 	}
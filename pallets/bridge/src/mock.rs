@@ -1,8 +1,11 @@
 use super::*;
 use crate as webb_bridge;
 use frame_benchmarking::whitelisted_caller;
-use frame_support::{construct_runtime, parameter_types, weights::Weight, PalletId};
-use frame_system::mocking::{MockBlock, MockUncheckedExtrinsic};
+use frame_support::{construct_runtime, ord_parameter_types, parameter_types, weights::Weight, PalletId};
+use frame_system::{
+	mocking::{MockBlock, MockUncheckedExtrinsic},
+	EnsureOneOf, EnsureRoot, EnsureSignedBy,
+};
 use pallet_merkle::weights::Weights as MerkleWeights;
 use webb_currencies::BasicCurrencyAdapter;
 
@@ -31,7 +34,6 @@ construct_runtime!(
 	{
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
 		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
-		Randomness: pallet_randomness_collective_flip::{Pallet, Call, Storage},
 		MerkleTrees: pallet_merkle::{Pallet, Call, Storage, Event<T>},
 		Bridge: webb_bridge::{Pallet, Call, Storage, Event<T>},
 		Currencies: webb_currencies::{Pallet, Storage, Event<T>},
@@ -79,6 +81,7 @@ parameter_types! {
 	pub const MaxReserves: u32 = 50;
 	pub const MaxTreeDepth: u8 = 32;
 	pub const CacheBlockLength: u64 = 5;
+	pub const MaxMemoLength: u32 = 256;
 	// Minimum deposit length is 1 month w/ 6 second blocks
 	pub const MinimumDepositLength: u64 = 10 * 60 * 24 * 28;
 }
@@ -138,31 +141,34 @@ impl pallet_merkle::Config for Test {
 	type CacheBlockLength = CacheBlockLength;
 	type Event = Event;
 	type KeyId = u32;
+	type MaxMemoLength = MaxMemoLength;
 	type MaxTreeDepth = MaxTreeDepth;
-	type Randomness = Randomness;
 	type TreeId = u32;
 	type WeightInfo = MerkleWeights<Self>;
 }
 
 parameter_types! {
 	pub const BridgePalletId: PalletId = PalletId(*b"py/brdge");
-	pub const DefaultAdmin: u64 = 4;
 	pub const NativeCurrencyId: CurrencyId = 0;
 }
 
+ord_parameter_types! {
+	pub const Admin: u64 = 4;
+	pub const PauseGuardian: u64 = 5;
+}
+
 impl Config for Test {
+	type AdminOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<Admin, u64>>;
 	type ChainId = u32;
 	type Currency = Tokens;
-	type DefaultAdmin = DefaultAdmin;
 	type Event = Event;
 	type NativeCurrencyId = NativeCurrencyId;
 	type PalletId = BridgePalletId;
+	type PauseOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<PauseGuardian, u64>>;
 	type ThresholdSignature = [u8; 32];
 	type Tree = MerkleTrees;
 }
 
-impl pallet_randomness_collective_flip::Config for Test {}
-
 pub type TokenPallet = webb_tokens::Pallet<Test>;
 
 // Build genesis storage according to the mock runtime.
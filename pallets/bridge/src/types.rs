@@ -1,5 +1,15 @@
 use super::*;
 
+/// Pause flags of an anchor, set by the `PauseOrigin`
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq)]
+pub struct PauseState {
+	/// Whether deposits into the anchor are paused
+	pub deposits: bool,
+	/// Whether withdrawals from the anchor are paused
+	pub withdrawals: bool,
+}
+
 /// Info about the mixer and it's leaf data
 #[derive(Encode, Decode, PartialEq)]
 pub struct AnchorInfo<T: Config> {
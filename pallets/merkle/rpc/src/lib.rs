@@ -6,9 +6,16 @@ use jsonrpc_core::{Error, ErrorCode, Result};
 use jsonrpc_derive::rpc;
 use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
+use sp_core::{
+	offchain::{OffchainStorage, STORAGE_PREFIX},
+	Bytes,
+};
 use sp_runtime::{generic::BlockId, traits::Block as BlockT};
 
-use merkle::{utils::keys::ScalarBytes, MerkleApi as MerkleRuntimeApi};
+use merkle::{memo_key, utils::keys::ScalarBytes, MerkleApi as MerkleRuntimeApi};
+
+/// Maximum number of leaves queried at once
+const MAX_RANGE: usize = 512;
 
 /// Merkle RPC methods.
 #[rpc]
@@ -56,12 +63,8 @@ where
 	) -> Result<Vec<ScalarBytes>> {
 		let api = self.client.runtime_api();
 		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
-		if to - from >= 512 {
-			return Err(Error {
-				code: ErrorCode::ServerError(1512), // Too many leaves
-				message: "TooManyLeaves".into(),
-				data: Some("MaxRange512".into()),
-			});
+		if to - from >= MAX_RANGE {
+			return Err(too_many_leaves());
 		}
 		let leaves = (from..to)
 			.into_iter()
@@ -72,3 +75,55 @@ where
 		Ok(leaves)
 	}
 }
+
+/// Merkle memo RPC methods.
+#[rpc]
+pub trait MerkleMemoApi {
+	/// Get the memos attached to the MerkleTree leaves.
+	///
+	/// Memos are read from the offchain database, which only holds them if the
+	/// node runs with offchain indexing enabled. Wallets scan a range of leaves
+	/// and trial decrypt the memos to find the notes sent to them.
+	///
+	/// Returns the index and memo of each leaf in the range that has a memo.
+	#[rpc(name = "merkle_treeMemos")]
+	fn tree_memos(&self, tree_id: u32, from: usize, to: usize) -> Result<Vec<(u32, Bytes)>>;
+}
+
+/// A struct that implements the `MerkleMemoApi`.
+pub struct MerkleMemoClient<S> {
+	storage: S,
+}
+
+impl<S> MerkleMemoClient<S> {
+	/// Create new `MerkleMemo` instance with the given offchain storage.
+	pub fn new(storage: S) -> Self {
+		Self { storage }
+	}
+}
+
+impl<S> MerkleMemoApi for MerkleMemoClient<S>
+where
+	S: OffchainStorage + 'static,
+{
+	fn tree_memos(&self, tree_id: u32, from: usize, to: usize) -> Result<Vec<(u32, Bytes)>> {
+		if to.saturating_sub(from) >= MAX_RANGE {
+			return Err(too_many_leaves());
+		}
+		let memos = (from..to)
+			.filter_map(|i| {
+				let memo = self.storage.get(STORAGE_PREFIX, &memo_key(tree_id, i as u32))?;
+				Some((i as u32, memo.into()))
+			})
+			.collect();
+		Ok(memos)
+	}
+}
+
+fn too_many_leaves() -> Error {
+	Error {
+		code: ErrorCode::ServerError(1512), // Too many leaves
+		message: "TooManyLeaves".into(),
+		data: Some("MaxRange512".into()),
+	}
+}
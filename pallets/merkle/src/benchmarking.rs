@@ -1,15 +1,30 @@
 use super::*;
-use crate::{utils::keys::get_bp_gen_bytes, Pallet as Merkle};
-use bulletproofs::BulletproofGens;
+use crate::{
+	utils::keys::{get_bp_gen_bytes, get_groth16_vk_bytes},
+	Pallet as Merkle,
+};
+use ark_ec::{AffineCurve, PairingEngine};
+use ark_groth16::{Proof, VerifyingKey};
+use ark_serialize::CanonicalSerialize;
+use arkworks_gadgets::prelude::{ark_bls12_381::Bls12_381, ark_bn254::Bn254};
+use bulletproofs::{r1cs::Prover, BulletproofGens, PedersenGens};
+use bulletproofs_gadgets::fixed_deposit_tree::builder::FixedDepositTreeBuilder;
 use curve25519_dalek::scalar::Scalar;
 use frame_benchmarking::{account, benchmarks, whitelisted_caller};
 use frame_support::traits::OnFinalize;
 use frame_system::{Pallet as System, RawOrigin};
-use utils::setup::{Backend, HashFunction};
+use merlin::Transcript;
+use utils::{
+	keys::{from_bytes_to_bp_gens, slice_to_bytes_32},
+	setup::{default_bulletproofs_poseidon_hasher, Backend, HashFunction, SetupError, Snark},
+};
 
 const MAX_DEPTH: u8 = 32;
 const NUM_LEAVES: u32 = 10;
 const VERIFY_DEPTH: u8 = 10;
+const MAX_GROTH16_INPUTS: u32 = 32;
+// Generators of the verifying keys of the mixers
+const BULLETPROOFS_GENS: usize = 16_400;
 
 fn setup_tree<T: Config>(caller: T::AccountId, depth: u32) {
 	let manager_required = true;
@@ -21,6 +36,8 @@ fn setup_tree<T: Config>(caller: T::AccountId, depth: u32) {
 	let key_data = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
 	<Merkle<T> as Tree<T>>::add_verifying_key(key_data).unwrap();
 	<Merkle<T> as Tree<T>>::initialize_tree(0u32.into(), 0u32.into()).unwrap();
+	// Weights must hold for a cold cache of prepared keys
+	utils::prepared::clear();
 }
 
 fn get_proof<T: Config>(tree_id: T::TreeId, depth: u32) -> Vec<(bool, ScalarBytes)> {
@@ -35,6 +52,25 @@ fn get_proof<T: Config>(tree_id: T::TreeId, depth: u32) -> Vec<(bool, ScalarByte
 	path
 }
 
+/// Groth16 verifying key with `inputs` public inputs and a proof for it, made
+/// of generators. Preparing the key and rejecting the proof cost as much as
+/// with a real circuit.
+fn groth16_key_and_proof<E: PairingEngine>(inputs: u32) -> (Vec<u8>, Vec<u8>) {
+	let g1 = E::G1Affine::prime_subgroup_generator();
+	let g2 = E::G2Affine::prime_subgroup_generator();
+	let vk = VerifyingKey::<E> {
+		alpha_g1: g1,
+		beta_g2: g2,
+		gamma_g2: g2,
+		delta_g2: g2,
+		gamma_abc_g1: vec![g1; inputs as usize + 1],
+	};
+	let proof = Proof::<E> { a: g1, b: g2, c: g1 };
+	let mut proof_bytes = Vec::new();
+	proof.serialize(&mut proof_bytes).unwrap();
+	(get_groth16_vk_bytes(&vk).unwrap(), proof_bytes)
+}
+
 benchmarks! {
 	create_tree {
 		// Testing the function for all depths between 0 to 32
@@ -101,6 +137,18 @@ benchmarks! {
 		assert!(stopped);
 	}
 
+	set_leaf_format {
+		let caller: T::AccountId = whitelisted_caller();
+		// Bound leaves require a Groth16 tree
+		let backend = Backend::Arkworks(Curve::Bn254, Snark::Groth16);
+		let setup = Setup::new(HashFunction::PoseidonDefault, backend);
+		<Merkle<T> as Tree<T>>::create_tree(caller.clone(), true, setup, 32).unwrap();
+	}: _(RawOrigin::Signed(caller.clone()), 0u32.into(), LeafFormat::Bound)
+	verify {
+		let tree_id: T::TreeId = 0u32.into();
+		assert_eq!(LeafFormats::<T>::get(tree_id), LeafFormat::Bound);
+	}
+
 	add_members {
 		// This means that the test will run `NUM_LEAVES` times
 		// Each time it runs, new value of `n` will be set
@@ -162,6 +210,89 @@ benchmarks! {
 		assert_eq!(latest_block, block_number);
 	}
 
+	verify_bulletproofs {
+		// Membership proof of a leaf of a tree of the maximum depth, against a
+		// key with the generators of the mixers
+		let caller: T::AccountId = whitelisted_caller();
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		let tree_id = <Merkle<T> as Tree<T>>::create_tree(caller.clone(), false, setup, MAX_DEPTH).unwrap();
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(BULLETPROOFS_GENS, 1));
+		let key_id = <Merkle<T> as Tree<T>>::add_verifying_key(key_data.clone()).unwrap();
+		<Merkle<T> as Tree<T>>::initialize_tree(tree_id, key_id).unwrap();
+
+		let hasher = default_bulletproofs_poseidon_hasher(from_bytes_to_bp_gens(&key_data).unwrap());
+		let mut ftree = FixedDepositTreeBuilder::new().hash_params(hasher).depth(MAX_DEPTH as usize).build();
+		let leaf = ftree.generate_secrets().to_bytes();
+		ftree.tree.add_leaves(vec![leaf], None);
+		<Merkle<T> as Tree<T>>::add_members(caller, tree_id, vec![leaf.to_vec()]).unwrap();
+		let root = Merkle::<T>::get_merkle_root(tree_id).unwrap();
+
+		let pc_gens = PedersenGens::default();
+		let mut prover_transcript = Transcript::new(b"zk_membership_proof");
+		let prover = Prover::new(&pc_gens, &mut prover_transcript);
+		let (proof, (comms, nullifier_hash, leaf_index_comms, proof_comms)) = ftree.prove_zk(
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&root)),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&leaf)),
+			Scalar::zero(),
+			Scalar::zero(),
+			&ftree.hash_params.bp_gens,
+			prover,
+		);
+		let block_number = System::<T>::block_number();
+		// Weights must hold for a cold cache of prepared keys
+		utils::prepared::clear();
+	}: {
+		<Merkle<T> as Tree<T>>::verify_zk(
+			tree_id,
+			block_number,
+			root,
+			comms.iter().map(|x| x.to_bytes().to_vec()).collect(),
+			nullifier_hash.to_bytes().to_vec(),
+			proof.to_bytes(),
+			leaf_index_comms.iter().map(|x| x.to_bytes().to_vec()).collect(),
+			proof_comms.iter().map(|x| x.to_bytes().to_vec()).collect(),
+			Scalar::zero().to_bytes().to_vec(),
+			Scalar::zero().to_bytes().to_vec(),
+		).unwrap();
+	}
+
+	prepare_bulletproofs_key {
+		let key = get_bp_gen_bytes(&BulletproofGens::new(BULLETPROOFS_GENS, 1));
+	}: {
+		PreparedKey::prepare(&Backend::Bulletproofs(Curve::Curve25519), &key).unwrap();
+	}
+
+	prepare_groth16_bn254_key {
+		let n in 1 .. MAX_GROTH16_INPUTS;
+		let (key, _) = groth16_key_and_proof::<Bn254>(n);
+	}: {
+		PreparedKey::prepare(&Backend::Arkworks(Curve::Bn254, Snark::Groth16), &key).unwrap();
+	}
+
+	verify_groth16_bn254 {
+		let n in 1 .. MAX_GROTH16_INPUTS;
+		let (key, proof) = groth16_key_and_proof::<Bn254>(n);
+		let prepared = PreparedKey::prepare(&Backend::Arkworks(Curve::Bn254, Snark::Groth16), &key).unwrap();
+		let inputs = vec![[0u8; 32]; n as usize];
+	}: {
+		assert!(matches!(prepared.verify_groth16(&proof, &inputs), Err(SetupError::ZkVerificationFailed)));
+	}
+
+	prepare_groth16_bls381_key {
+		let n in 1 .. MAX_GROTH16_INPUTS;
+		let (key, _) = groth16_key_and_proof::<Bls12_381>(n);
+	}: {
+		PreparedKey::prepare(&Backend::Arkworks(Curve::Bls381, Snark::Groth16), &key).unwrap();
+	}
+
+	verify_groth16_bls381 {
+		let n in 1 .. MAX_GROTH16_INPUTS;
+		let (key, proof) = groth16_key_and_proof::<Bls12_381>(n);
+		let prepared = PreparedKey::prepare(&Backend::Arkworks(Curve::Bls381, Snark::Groth16), &key).unwrap();
+		let inputs = vec![[0u8; 32]; n as usize];
+	}: {
+		assert!(matches!(prepared.verify_groth16(&proof, &inputs), Err(SetupError::ZkVerificationFailed)));
+	}
 }
 
 // TODO: replace with impl_benchmark_test_suite macro:
@@ -200,6 +331,13 @@ mod bench_tests {
 		});
 	}
 
+	#[test]
+	fn test_set_leaf_format() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_set_leaf_format::<Test>());
+		});
+	}
+
 	#[test]
 	fn test_add_members() {
 		new_test_ext().execute_with(|| {
@@ -220,4 +358,46 @@ mod bench_tests {
 			assert_ok!(test_benchmark_on_finalize::<Test>());
 		});
 	}
+
+	#[test]
+	fn test_verify_bulletproofs() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_verify_bulletproofs::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_prepare_bulletproofs_key() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_prepare_bulletproofs_key::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_prepare_groth16_bn254_key() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_prepare_groth16_bn254_key::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_verify_groth16_bn254() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_verify_groth16_bn254::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_prepare_groth16_bls381_key() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_prepare_groth16_bls381_key::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_verify_groth16_bls381() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_verify_groth16_bls381::<Test>());
+		});
+	}
 }
@@ -23,10 +23,13 @@
 //! - Creating Merkle trees.
 //! - Adding the manager and setting whether the manager is required.
 //! - Adding leaf data to the Merkle tree.
+//! - Attaching encrypted memos to leaves, indexed in the offchain database.
 //! - Adding nullifiers to the storage.
 //! - Managing start/stop flags.
 //! - Caching Merkle tree states.
 //! - Verifying regular and zero-knowledge membership proofs
+//! - Verifying proofs of bound leaves against the asset, amount and chain id
+//!   of their deposit.
 //!
 //! ### Terminology
 //!
@@ -37,10 +40,21 @@
 //!   arithmetic circuit which includes hashing several values. Proving to know
 //!   all these values are called proof of creation.
 //!
+//! - **Memo:** Bounded encrypted message attached to a leaf, e.g. the secrets
+//!   of a note for its recipient. Memos aren't kept in the runtime storage,
+//!   they are emitted in the `NewMemos` event and indexed in the offchain
+//!   database of nodes running with offchain indexing, under [`memo_key`].
+//!
 //! - **Nullifier:** Nullifier is a part of this leaf circuit and is revealed
 //!   when proving membership in zero-knowledge. The nullifier's role is to
 //!   prevent double-spending.
 //!
+//! - **Leaf format:** Proofs of bound leaves take the asset id, amount and
+//!   chain id of the deposit as extra public inputs, unlike proofs of opaque
+//!   leaves (see [`LeafFormat`]). The leaves only commit to these values if
+//!   the circuit of the tree's verifying key does, and the circuits aren't
+//!   part of this pallet. Only Groth16 trees can have bound leaves.
+//!
 //! ### Implementations
 //!
 //! The Merkle pallet provides implementations for the following traits:
@@ -60,6 +74,9 @@
 //! - `set_stopped` - Sets stopped storage flag. This flag by itself doesn't do
 //!   anything. It's up to higher-level pallets to make appropriate use of it.
 //!   Can only be called by the root or the manager;
+//! - `set_leaf_format` - Sets the format of the leaves of a tree, before any
+//!   leaf is added. Bound leaves require a Groth16 tree. Can only be called by
+//!   the root or the manager.
 //! - `add_members` Adds an array of leaves to the tree. Can only be called by
 //!   the manager if the manager is required.
 //! - `verify` - Verifies the membership proof.
@@ -78,6 +95,8 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
 pub mod traits;
 pub mod utils;
 
@@ -89,24 +108,28 @@ pub mod tests;
 
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
+pub mod migrations;
 pub mod weights;
 
+use alloc::sync::Arc;
 use codec::{Decode, Encode};
 use frame_support::{
-	dispatch::DispatchError,
+	dispatch::{DispatchError, DispatchResult},
 	ensure,
-	traits::{Get, Randomness},
+	traits::Get,
 	weights::Weight,
 	Parameter,
 };
 use frame_system::ensure_signed;
 use sp_runtime::traits::{AtLeast32Bit, One};
 use sp_std::prelude::*;
-pub use traits::Tree;
+pub use traits::{Tree, ZkProof};
 use utils::{
-	keys::ScalarBytes,
+	host,
+	keys::{check_key_header, ScalarBytes},
 	permissions::ensure_admin,
-	setup::{Curve, Setup},
+	prepared::{self, PreparedKey},
+	setup::{Backend, Curve, LeafFormat, Setup, Snark, ZkInputs},
 };
 use weights::WeightInfo;
 
@@ -132,9 +155,9 @@ pub mod pallet {
 		type MaxTreeDepth: Get<u8>;
 		/// The amount of blocks to cache roots over
 		type CacheBlockLength: Get<Self::BlockNumber>;
-		/// The generator used to supply randomness to contracts through
-		/// `seal_random`.
-		type Randomness: Randomness<Self::Hash, Self::BlockNumber>;
+		/// The max length of the memo attached to a leaf
+		#[pallet::constant]
+		type MaxMemoLength: Get<u32>;
 		/// Weight information for extrinsics in this pallet.
 		type WeightInfo: WeightInfo;
 	}
@@ -187,6 +210,17 @@ pub mod pallet {
 		Unimplemented,
 		/// Unexpected/Unknown error
 		Unknown,
+		/// Number of memos differs from the number of leaves
+		MemoCountMismatch,
+		/// Memo is longer than `MaxMemoLength`
+		MemoTooLong,
+		/// Leaf format can't change once the tree has leaves
+		LeafFormatLocked,
+		/// Backend of the tree doesn't support the leaf format
+		LeafFormatUnsupported,
+		/// Proof binds its leaf to an asset, amount and chain id iff the tree
+		/// has bound leaves
+		LeafBindingMismatch,
 	}
 
 	#[pallet::event]
@@ -199,6 +233,10 @@ pub mod pallet {
 		NewMembers(T::TreeId, T::AccountId, u32, Vec<ScalarBytes>),
 		/// New nullifier submitted on verification
 		NewNullifier(T::TreeId, T::AccountId, ScalarBytes),
+		/// Memos attached to new leaves, starting at the given leaf index
+		NewMemos(T::TreeId, u32, Vec<Vec<u8>>),
+		/// Format of the leaves of the tree set
+		LeafFormatSet(T::TreeId, LeafFormat),
 	}
 
 	/// Old name generated by `decl_event`.
@@ -230,6 +268,12 @@ pub mod pallet {
 	#[pallet::getter(fn verifying_keys)]
 	pub type VerifyingKeys<T: Config> = StorageMap<_, Blake2_128Concat, T::KeyId, Option<Vec<u8>>, ValueQuery>;
 
+	/// The map of verifying keys to the blake2 hash of their bytes, used to
+	/// look up prepared keys without reading the key itself
+	#[pallet::storage]
+	#[pallet::getter(fn verifying_key_hashes)]
+	pub type VerifyingKeyHashes<T: Config> = StorageMap<_, Blake2_128Concat, T::KeyId, [u8; 32]>;
+
 	/// The map of (tree_id, index) to the leaf commitment
 	#[pallet::storage]
 	#[pallet::getter(fn leaves)]
@@ -277,6 +321,16 @@ pub mod pallet {
 	#[pallet::getter(fn stopped)]
 	pub type Stopped<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, bool, ValueQuery>;
 
+	/// Format of the leaves of each tree
+	#[pallet::storage]
+	#[pallet::getter(fn leaf_format)]
+	pub type LeafFormats<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, LeafFormat, ValueQuery>;
+
+	/// Storage layout release the pallet's storage was last migrated to
+	#[pallet::storage]
+	#[pallet::getter(fn storage_version)]
+	pub type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
+
 	#[pallet::pallet]
 	pub struct Pallet<T>(PhantomData<T>);
 
@@ -288,6 +342,10 @@ pub mod pallet {
 			<T as Config>::WeightInfo::on_finalize()
 		}
 
+		fn on_runtime_upgrade() -> Weight {
+			migrations::migrate::<T>()
+		}
+
 		fn on_finalize(n: T::BlockNumber) {
 			// update highest block in cache
 			if HighestCachedBlock::<T>::get() < n {
@@ -406,6 +464,29 @@ pub mod pallet {
 			Ok(().into())
 		}
 
+		/// Sets the format of the leaves of the tree. The format is locked
+		/// once the tree has leaves, and only Groth16 trees can have bound
+		/// leaves.
+		///
+		/// Can only be called by the root or the current manager.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 8_000_000
+		/// - DB weights: 2 reads, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::set_leaf_format())]
+		pub fn set_leaf_format(
+			origin: OriginFor<T>,
+			tree_id: T::TreeId,
+			format: LeafFormat,
+		) -> DispatchResultWithPostInfo {
+			let manager_data = Managers::<T>::get(tree_id).ok_or(Error::<T>::ManagerDoesntExist)?;
+			ensure_admin(origin, &manager_data.account_id)?;
+			<Self as Tree<_>>::set_leaf_format(manager_data.account_id, tree_id, format)?;
+			Ok(().into())
+		}
+
 		/// Adds an array of leaf data into the tree and adds calculated root to
 		/// the cache.
 		///
@@ -473,6 +554,9 @@ pub mod pallet {
 
 		/// Adds a verifying key to the storage.
 		///
+		/// The key must be encoded with the versioned format from
+		/// [`utils::keys`], otherwise it is rejected with `InvalidVerifierKey`.
+		///
 		/// Can only be called by the root.
 		#[pallet::weight(5_000_000)]
 		pub fn add_verifying_key(origin: OriginFor<T>, key: Vec<u8>) -> DispatchResultWithPostInfo {
@@ -516,6 +600,33 @@ sp_api::decl_runtime_apis! {
 	}
 }
 
+/// Prefix of the keys of memos in the offchain database
+pub const MEMO_KEY_PREFIX: &[u8] = b"merkle::memo";
+
+/// Key of the memo of the leaf at `leaf_index` of the tree in the offchain
+/// database
+pub fn memo_key<TreeId: Encode>(tree_id: TreeId, leaf_index: u32) -> Vec<u8> {
+	(MEMO_KEY_PREFIX, tree_id, leaf_index).encode()
+}
+
+/// Storage layouts of the pallet, in the order they were released
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Releases {
+	/// Verifying keys are stored without a header
+	V1,
+	/// Verifying keys are prefixed with a `[version, kind]` header
+	V2VersionedKeys,
+	/// Hashes of verifying keys are stored along with the keys
+	V3KeyHashes,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1
+	}
+}
+
 /// Data about the manager of the MerkleTree
 #[derive(Clone, Encode, Decode, PartialEq)]
 pub struct Manager<T: Config> {
@@ -608,8 +719,9 @@ impl<T: Config> Tree<T> for Pallet<T> {
 	fn initialize_tree(tree_id: T::TreeId, key_id: T::KeyId) -> Result<(), DispatchError> {
 		let mut tree = Trees::<T>::get(tree_id).ok_or(Error::<T>::TreeDoesntExist)?;
 		ensure!(!tree.initialized, Error::<T>::AlreadyInitialized);
-		let params = Self::get_verifying_key(key_id)?;
-		let (zero_tree, root_hash) = tree.setup.generate_zero_tree::<T>(tree.depth as usize, &params)?;
+		// Makes sure the key can be used for hashing before the tree accepts leaves
+		Self::get_hasher_key(key_id, &tree.setup)?;
+		let (zero_tree, root_hash) = tree.setup.generate_zero_tree::<T>(tree.depth as usize)?;
 		tree.root_hash = root_hash;
 		tree.edge_nodes = zero_tree.clone();
 		tree.zero_tree = zero_tree;
@@ -625,9 +737,11 @@ impl<T: Config> Tree<T> for Pallet<T> {
 	}
 
 	fn add_verifying_key(key: Vec<u8>) -> Result<T::KeyId, DispatchError> {
+		check_key_header(&key).map_err(Error::<T>::from)?;
 		let key_id = Self::next_key_id();
 		// Setting the next key id
 		NextKeyId::<T>::mutate(|id| *id += One::one());
+		VerifyingKeyHashes::<T>::insert(key_id, sp_io::hashing::blake2_256(&key));
 		VerifyingKeys::<T>::insert(key_id, Some(key));
 		Ok(key_id)
 	}
@@ -635,6 +749,8 @@ impl<T: Config> Tree<T> for Pallet<T> {
 	fn set_verifying_key(key_id: T::KeyId, key: Vec<u8>) -> Result<(), DispatchError> {
 		let next_id = Self::next_key_id();
 		ensure!(key_id < next_id, Error::<T>::InvalidVerifierKey);
+		check_key_header(&key).map_err(Error::<T>::from)?;
+		VerifyingKeyHashes::<T>::insert(key_id, sp_io::hashing::blake2_256(&key));
 		VerifyingKeys::<T>::insert(key_id, Some(key));
 		Ok(())
 	}
@@ -655,6 +771,38 @@ impl<T: Config> Tree<T> for Pallet<T> {
 		Stopped::<T>::get(tree_id)
 	}
 
+	fn get_root(tree_id: T::TreeId) -> Result<ScalarBytes, DispatchError> {
+		Self::get_merkle_root(tree_id)
+	}
+
+	fn get_cached_roots(tree_id: T::TreeId, block_number: T::BlockNumber) -> Vec<ScalarBytes> {
+		Self::cached_roots(block_number, tree_id)
+	}
+
+	fn get_leaf_count(tree_id: T::TreeId) -> Result<u32, DispatchError> {
+		Ok(Self::get_tree(tree_id)?.leaf_count)
+	}
+
+	fn get_leaf_format(tree_id: T::TreeId) -> LeafFormat {
+		Self::leaf_format(tree_id)
+	}
+
+	fn set_leaf_format(sender: T::AccountId, tree_id: T::TreeId, format: LeafFormat) -> Result<(), DispatchError> {
+		let manager_data = Managers::<T>::get(tree_id).ok_or(Error::<T>::ManagerDoesntExist)?;
+		ensure!(sender == manager_data.account_id, Error::<T>::ManagerIsRequired);
+		let tree = Self::get_tree(tree_id)?;
+		// Proofs of existing leaves are tied to the format they were made with
+		ensure!(tree.leaf_count == 0, Error::<T>::LeafFormatLocked);
+		// Only Groth16 proofs take the binding of bound leaves as public inputs
+		ensure!(
+			format == LeafFormat::Opaque || matches!(tree.setup.backend, Backend::Arkworks(_, Snark::Groth16)),
+			Error::<T>::LeafFormatUnsupported
+		);
+		LeafFormats::<T>::insert(tree_id, format);
+		Self::deposit_event(Event::LeafFormatSet(tree_id, format));
+		Ok(())
+	}
+
 	fn set_manager_required(sender: T::AccountId, id: T::TreeId, manager_required: bool) -> Result<(), DispatchError> {
 		let mut manager_data = Managers::<T>::get(id).ok_or(Error::<T>::ManagerDoesntExist)?;
 		// Changing manager required should always require an extrinsic from the
@@ -689,14 +837,14 @@ impl<T: Config> Tree<T> for Pallet<T> {
 			Error::<T>::ExceedsMaxLeaves
 		);
 
-		let params = Self::get_verifying_key_for_tree(id)?;
+		let key = Self::get_hasher_key(VerifyingKeyForTree::<T>::get(id), &tree.setup)?;
 		for data in &members {
 			if tree.should_store_leaves {
 				Leaves::<T>::insert(id, tree.leaf_count, data);
 			}
 			// then we add it to the tree itself.
 			// note that, this method internally increments the leaves count.
-			Self::add_leaf(&mut tree, data, &params)?;
+			Self::add_leaf(&mut tree, data, key.as_deref())?;
 		}
 		let block_number: T::BlockNumber = <frame_system::Pallet<T>>::block_number();
 		CachedRoots::<T>::append(block_number, id, tree.root_hash.clone());
@@ -707,6 +855,23 @@ impl<T: Config> Tree<T> for Pallet<T> {
 		Ok(())
 	}
 
+	fn add_members_with_memos(
+		sender: T::AccountId,
+		id: T::TreeId,
+		members: Vec<ScalarBytes>,
+		memos: Vec<Vec<u8>>,
+	) -> Result<(), DispatchError> {
+		Self::ensure_valid_memos(members.len(), &memos)?;
+		let first_leaf_index = Self::get_tree(id)?.leaf_count;
+		Self::add_members(sender, id, members)?;
+		// Memos are only indexed offchain, wallets fetch them through the rpc
+		for (i, memo) in memos.iter().enumerate() {
+			sp_io::offchain_index::set(&memo_key(id, first_leaf_index + i as u32), memo);
+		}
+		Self::deposit_event(Event::NewMemos(id, first_leaf_index, memos));
+		Ok(())
+	}
+
 	fn add_nullifier(sender: T::AccountId, id: T::TreeId, nullifier_hash: ScalarBytes) -> Result<(), DispatchError> {
 		let manager_data = Managers::<T>::get(id).ok_or(Error::<T>::ManagerDoesntExist)?;
 		// Check if the tree requires extrinsics to be called from a manager
@@ -732,12 +897,12 @@ impl<T: Config> Tree<T> for Pallet<T> {
 		let tree = Trees::<T>::get(id).ok_or(Error::<T>::TreeDoesntExist)?;
 
 		ensure!(tree.edge_nodes.len() == path.len(), Error::<T>::InvalidPathLength);
-		let params = Self::get_verifying_key_for_tree(id)?;
+		let key = Self::get_hasher_key(VerifyingKeyForTree::<T>::get(id), &tree.setup)?;
 		let mut hash = leaf;
 		for (is_right, node) in path {
 			hash = match is_right {
-				true => tree.setup.hash::<T>(&hash, &node, &params)?,
-				false => tree.setup.hash::<T>(&node, &hash, &params)?,
+				true => tree.setup.hash_with_key::<T>(&hash, &node, key.as_deref())?,
+				false => tree.setup.hash_with_key::<T>(&node, &hash, key.as_deref())?,
 			}
 		}
 
@@ -757,25 +922,59 @@ impl<T: Config> Tree<T> for Pallet<T> {
 		recipient: ScalarBytes,
 		relayer: ScalarBytes,
 	) -> Result<(), DispatchError> {
-		let tree = Trees::<T>::get(tree_id).ok_or(Error::<T>::TreeDoesntExist)?;
-		let key_id = VerifyingKeyForTree::<T>::get(tree_id);
-		let verifying_key = VerifyingKeys::<T>::get(key_id);
-		// Ensure that root being checked against is in the cache
-		let old_roots = Self::cached_roots(block_number, tree_id);
-		ensure!(old_roots.iter().any(|r| *r == root), Error::<T>::InvalidMerkleRoot);
-		tree.setup.verify_zk::<T>(
-			tree.depth as usize,
-			root,
-			private_inputs,
+		let proof = ZkProof {
+			tree_id,
+			cached_block: block_number,
+			cached_root: root,
+			comms: private_inputs,
 			nullifier_hash,
 			proof_bytes,
-			verifying_key,
-			path_indices,
-			path_nodes,
+			leaf_index_commitments: path_indices,
+			proof_commitments: path_nodes,
 			recipient,
 			relayer,
-		)?;
-		Ok(())
+			binding: None,
+		};
+		<Self as Tree<_>>::verify_zk_proof(proof)
+	}
+
+	fn verify_zk_proof(proof: ZkProof<T>) -> Result<(), DispatchError> {
+		let (key_id, setup, inputs) = Self::zk_inputs(proof)?;
+		Self::check_zk_proofs(key_id, &setup, &[inputs])
+	}
+
+	fn verify_zk_batch(proofs: Vec<ZkProof<T>>) -> Vec<DispatchResult> {
+		let mut results = Vec::with_capacity(proofs.len());
+		// Proofs that passed the root checks, grouped by verifying key
+		let mut batches: Vec<((T::KeyId, Setup), Vec<(usize, ZkInputs)>)> = Vec::new();
+		for (index, proof) in proofs.into_iter().enumerate() {
+			match Self::zk_inputs(proof) {
+				Ok((key_id, setup, inputs)) => {
+					results.push(Ok(()));
+					let batch_key = (key_id, setup);
+					match batches.iter_mut().find(|(k, _)| *k == batch_key) {
+						Some((_, batch)) => batch.push((index, inputs)),
+						None => batches.push((batch_key, vec![(index, inputs)])),
+					}
+				}
+				Err(e) => results.push(Err(e)),
+			}
+		}
+
+		for ((key_id, setup), batch) in batches {
+			if batch.len() > 1 && setup.batches_proofs() {
+				let inputs: Vec<ZkInputs> = batch.iter().map(|(_, inputs)| inputs.clone()).collect();
+				if Self::check_zk_proofs(key_id, &setup, &inputs).is_ok() {
+					continue;
+				}
+			}
+			// A failed batch doesn't tell which proofs are invalid, so check them
+			// one by one.
+			for (index, inputs) in batch {
+				results[index] = Self::check_zk_proofs(key_id, &setup, &[inputs]);
+			}
+		}
+		results
 	}
 }
 
@@ -796,8 +995,20 @@ impl<T: Config> Pallet<T> {
 		Ok(())
 	}
 
+	/// Checks that there is a memo for each of `members` leaves and that none
+	/// is longer than `MaxMemoLength`, so callers can check memos before
+	/// writing anything.
+	pub fn ensure_valid_memos(members: usize, memos: &[Vec<u8>]) -> Result<(), DispatchError> {
+		ensure!(memos.len() == members, Error::<T>::MemoCountMismatch);
+		ensure!(
+			memos.iter().all(|memo| memo.len() as u32 <= T::MaxMemoLength::get()),
+			Error::<T>::MemoTooLong
+		);
+		Ok(())
+	}
+
 	pub fn get_tree(tree_id: T::TreeId) -> Result<MerkleTree, DispatchError> {
-		let tree = Trees::<T>::get(tree_id).ok_or(Error::<T>::TreeDoesntExist).unwrap();
+		let tree = Trees::<T>::get(tree_id).ok_or(Error::<T>::TreeDoesntExist)?;
 		Ok(tree)
 	}
 
@@ -809,7 +1020,7 @@ impl<T: Config> Pallet<T> {
 		}
 	}
 
-	pub fn add_leaf(tree: &mut MerkleTree, data: &ScalarBytes, params: &[u8]) -> Result<(), DispatchError> {
+	pub fn add_leaf(tree: &mut MerkleTree, data: &ScalarBytes, key: Option<&PreparedKey>) -> Result<(), DispatchError> {
 		let mut edge_index = tree.leaf_count;
 		let mut hash = data.clone();
 		let mut edge_nodes = tree.edge_nodes.clone();
@@ -817,9 +1028,9 @@ impl<T: Config> Pallet<T> {
 		for i in 0..edge_nodes.len() {
 			hash = if edge_index % 2 == 0 {
 				edge_nodes[i] = hash.clone();
-				tree.setup.hash::<T>(&hash, &tree.zero_tree[i], params)?
+				tree.setup.hash_with_key::<T>(&hash, &tree.zero_tree[i], key)?
 			} else {
-				tree.setup.hash::<T>(&edge_nodes[i], &hash, params)?
+				tree.setup.hash_with_key::<T>(&edge_nodes[i], &hash, key)?
 			};
 
 			edge_index /= 2;
@@ -842,4 +1053,86 @@ impl<T: Config> Pallet<T> {
 		ensure!(maybe_verifying_key.is_some(), Error::<T>::InvalidVerifierKey);
 		Ok(maybe_verifying_key.unwrap())
 	}
+
+	/// Returns the key stored under `id` prepared for `backend`, reusing a
+	/// previously prepared copy when the stored key hasn't changed since.
+	pub fn get_prepared_key(id: T::KeyId, backend: &Backend) -> Result<Arc<PreparedKey>, DispatchError> {
+		let key_hash = VerifyingKeyHashes::<T>::get(id).ok_or(Error::<T>::InvalidVerifierKey)?;
+		let cache_key = prepared::cache_key(&id.encode(), &key_hash, backend);
+		if let Some(prepared) = prepared::get(&cache_key) {
+			return Ok(prepared);
+		}
+
+		let key = Self::get_verifying_key(id)?;
+		let prepared = Arc::new(PreparedKey::prepare(backend, &key).map_err(Error::<T>::from)?);
+		prepared::insert(cache_key, prepared.clone());
+		Ok(prepared)
+	}
+
+	/// Checks that `proof` is made against a cached root of its tree and
+	/// returns the tree's verifying key id and setup along with the proof's
+	/// inputs.
+	fn zk_inputs(proof: ZkProof<T>) -> Result<(T::KeyId, Setup, ZkInputs), DispatchError> {
+		let tree = Trees::<T>::get(proof.tree_id).ok_or(Error::<T>::TreeDoesntExist)?;
+		// Ensure that root being checked against is in the cache
+		let old_roots = Self::cached_roots(proof.cached_block, proof.tree_id);
+		ensure!(
+			old_roots.iter().any(|r| *r == proof.cached_root),
+			Error::<T>::InvalidMerkleRoot
+		);
+		ensure!(
+			proof.binding.is_some() == (Self::leaf_format(proof.tree_id) == LeafFormat::Bound),
+			Error::<T>::LeafBindingMismatch
+		);
+		let key_id = VerifyingKeyForTree::<T>::get(proof.tree_id);
+
+		let inputs = ZkInputs {
+			depth: tree.depth as u32,
+			root: proof.cached_root,
+			private_inputs: proof.comms,
+			nullifier_hash: proof.nullifier_hash,
+			proof: proof.proof_bytes,
+			path_indices: proof.leaf_index_commitments,
+			path_nodes: proof.proof_commitments,
+			recipient: proof.recipient,
+			relayer: proof.relayer,
+			binding: proof.binding,
+		};
+		Ok((key_id, tree.setup, inputs))
+	}
+
+	/// Verifies proofs against the key stored under `key_id`, on the host when
+	/// possible. More than one proof is verified as a batch, which only
+	/// succeeds if every proof is valid.
+	fn check_zk_proofs(key_id: T::KeyId, setup: &Setup, inputs: &[ZkInputs]) -> DispatchResult {
+		if host::ENABLED {
+			let key = Self::get_verifying_key(key_id)?;
+			let res = match inputs {
+				[single] => setup.verify_zk_on_host(&key, single),
+				_ => setup.verify_zk_batch_on_host(&key, inputs),
+			};
+			if let Some(res) = res {
+				return res.map_err(|e| Error::<T>::from(e).into());
+			}
+		}
+		let verifying_key = Self::get_prepared_key(key_id, &setup.backend)?;
+		match inputs {
+			[single] => setup.verify_zk::<T>(single, &verifying_key)?,
+			_ => setup.verify_zk_batch::<T>(inputs, &verifying_key)?,
+		}
+		Ok(())
+	}
+
+	/// Returns the prepared key needed to hash with `setup`, or `None` if its
+	/// backend hashes without one. Fails if no key is stored under `id`.
+	pub fn get_hasher_key(id: T::KeyId, setup: &Setup) -> Result<Option<Arc<PreparedKey>>, DispatchError> {
+		if setup.hashes_with_key() {
+			return Self::get_prepared_key(id, &setup.backend).map(Some);
+		}
+		ensure!(
+			VerifyingKeyHashes::<T>::contains_key(id),
+			Error::<T>::InvalidVerifierKey
+		);
+		Ok(None)
+	}
 }
@@ -0,0 +1,55 @@
+//! Storage migrations of the merkle pallet
+//!
+//! Each migration runs once, when the stored [`Releases`] is older than the
+//! release introducing it, and bumps the stored version when done.
+use crate::{
+	utils::keys::{check_key_header, from_legacy_bytes_to_bp_gens, get_bp_gen_bytes},
+	Config, Releases, StorageVersion, VerifyingKeyHashes, VerifyingKeys,
+};
+use frame_support::{traits::Get, weights::Weight};
+use sp_std::prelude::*;
+
+/// Runs the migrations the stored version hasn't been through yet.
+pub fn migrate<T: Config>() -> Weight {
+	let version = StorageVersion::<T>::get();
+	if version >= Releases::V3KeyHashes {
+		return T::DbWeight::get().reads(1);
+	}
+	if version < Releases::V2VersionedKeys {
+		version_keys::<T>();
+	}
+	hash_keys::<T>();
+	StorageVersion::<T>::put(Releases::V3KeyHashes);
+	// Decoding generators decompresses every point of every key
+	T::BlockWeights::get().max_block
+}
+
+/// Rewrites keys stored before keys were versioned, when only Bulletproofs
+/// generators could be stored, with a `[version, kind]` header. Keys which
+/// already carry a header, or which can't be decoded in either format, are
+/// left as they are.
+fn version_keys<T: Config>() {
+	let keys: Vec<_> = VerifyingKeys::<T>::iter().collect();
+	for (key_id, key) in keys {
+		let key = match key {
+			Some(key) if check_key_header(&key).is_err() => key,
+			_ => continue,
+		};
+		if let Ok(gens) = from_legacy_bytes_to_bp_gens(&key) {
+			let key = get_bp_gen_bytes(&gens);
+			VerifyingKeyHashes::<T>::insert(key_id, sp_io::hashing::blake2_256(&key));
+			VerifyingKeys::<T>::insert(key_id, Some(key));
+		}
+	}
+}
+
+/// Stores the hash of keys stored before prepared keys were cached by key
+/// hash, without which the keys can't be prepared.
+fn hash_keys<T: Config>() {
+	let keys: Vec<_> = VerifyingKeys::<T>::iter().collect();
+	for (key_id, key) in keys {
+		if let (Some(key), false) = (key, VerifyingKeyHashes::<T>::contains_key(key_id)) {
+			VerifyingKeyHashes::<T>::insert(key_id, sp_io::hashing::blake2_256(&key));
+		}
+	}
+}
@@ -24,7 +24,6 @@ construct_runtime!(
 	{
 		System: system::{Pallet, Call, Config, Storage, Event<T>},
 		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
-		Randomness: pallet_randomness_collective_flip::{Pallet, Call, Storage},
 		MerkleTrees: pallet_merkle::{Pallet, Call, Storage, Event<T>},
 	}
 );
@@ -69,6 +68,7 @@ parameter_types! {
 	pub const MaxTreeDepth: u8 = 32;
 	pub const MaxReserves: u32 = 50;
 	pub const CacheBlockLength: u64 = 5;
+	pub const MaxMemoLength: u32 = 256;
 	pub const MinimumDepositLength: u64 = 10;
 }
 
@@ -88,14 +88,12 @@ impl Config for Test {
 	type CacheBlockLength = CacheBlockLength;
 	type Event = Event;
 	type KeyId = u32;
+	type MaxMemoLength = MaxMemoLength;
 	type MaxTreeDepth = MaxTreeDepth;
-	type Randomness = Randomness;
 	type TreeId = u32;
 	type WeightInfo = Weights<Self>;
 }
 
-impl pallet_randomness_collective_flip::Config for Test {}
-
 pub type MerkleCall = pallet_merkle::Call<Test>;
 
 // Build genesis storage according to the mock runtime.
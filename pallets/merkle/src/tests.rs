@@ -2,8 +2,13 @@ use super::*;
 use crate::{
 	mock::*,
 	utils::{
-		keys::{from_bytes_to_bp_gens, get_bp_gen_bytes, slice_to_bytes_32},
-		setup::{Backend, HashFunction, Setup, Snark},
+		keys::{
+			check_key_header, from_bytes_to_bp_gens, from_bytes_to_groth16_vk, get_bp_gen_bytes, get_groth16_vk_bytes,
+			groth16_vk_input_count, slice_to_bytes_32, KeyError, KeyKind, KEY_FORMAT_VERSION, KEY_HEADER_LEN, MAX_BP_GENERATORS,
+		},
+		host::merkle_crypto,
+		prepared::PreparedKey,
+		setup::{bulletproofs_verifier_rng, Backend, HashFunction, LeafBinding, Setup, SetupError, Snark, ZkInputs},
 	},
 };
 use ark_serialize::CanonicalSerialize;
@@ -15,7 +20,7 @@ use arkworks_gadgets::{
 		ark_ff::to_bytes,
 	},
 	setup::mixer::{
-		prove_groth16_mimc220, prove_groth16_x5, setup_circuit_mimc_220, setup_circuit_x5,
+		get_public_inputs, prove_groth16_mimc220, prove_groth16_x5, setup_circuit_mimc_220, setup_circuit_x5,
 		setup_random_groth16_mimc_220, setup_random_groth16_x5,
 	},
 };
@@ -28,11 +33,20 @@ use bulletproofs_gadgets::{
 	},
 	smt::gen_zero_tree,
 };
-use curve25519_dalek::{ristretto::RistrettoPoint, scalar::Scalar};
-use frame_support::{assert_err, assert_ok, traits::UnfilteredDispatchable};
+use curve25519_dalek::{
+	ristretto::{CompressedRistretto, RistrettoPoint},
+	scalar::Scalar,
+};
+use frame_support::{
+	assert_err, assert_ok,
+	traits::{OnRuntimeUpgrade, UnfilteredDispatchable},
+};
 use frame_system::RawOrigin;
 use merlin::Transcript;
-use rand_chacha::rand_core::OsRng;
+use rand_chacha::{
+	rand_core::{OsRng, RngCore, SeedableRng},
+	ChaChaRng,
+};
 use sp_runtime::traits::BadOrigin;
 
 fn key_bytes(x: u8) -> [u8; 32] {
@@ -264,6 +278,43 @@ fn should_be_able_to_set_stopped_merkle() {
 	});
 }
 
+#[test]
+fn should_set_leaf_format_until_tree_has_leaves() {
+	new_test_ext().execute_with(|| {
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+		assert_eq!(MerkleTrees::leaf_format(0), LeafFormat::Opaque);
+
+		assert_err!(
+			MerkleTrees::set_leaf_format(Origin::signed(2), 0, LeafFormat::Opaque),
+			BadOrigin
+		);
+		// Bulletproofs proofs can't take the binding of bound leaves
+		assert_err!(
+			MerkleTrees::set_leaf_format(Origin::signed(1), 0, LeafFormat::Bound),
+			Error::<Test>::LeafFormatUnsupported
+		);
+		assert_ok!(MerkleTrees::set_leaf_format(Origin::signed(1), 0, LeafFormat::Opaque));
+		assert_eq!(MerkleTrees::leaf_format(0), LeafFormat::Opaque);
+
+		let backend = Backend::Arkworks(Curve::Bn254, Snark::Groth16);
+		let setup = Setup::new(HashFunction::PoseidonDefault, backend);
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+		assert_ok!(MerkleTrees::set_leaf_format(Origin::signed(1), 1, LeafFormat::Bound));
+		assert_eq!(MerkleTrees::leaf_format(1), LeafFormat::Bound);
+
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key_data));
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+		let leaf = key_bytes(1).to_vec();
+		assert_ok!(MerkleTrees::add_members(Origin::signed(1), 0, vec![leaf]));
+		assert_err!(
+			MerkleTrees::set_leaf_format(RawOrigin::Root.into(), 0, LeafFormat::Opaque),
+			Error::<Test>::LeafFormatLocked
+		);
+	});
+}
+
 #[test]
 fn should_be_able_to_change_manager_with_root() {
 	new_test_ext().execute_with(|| {
@@ -376,7 +427,7 @@ fn should_have_correct_root_hash_after_insertion() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
@@ -435,7 +486,7 @@ fn should_have_correct_root_hash() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 		let mut keys = Vec::new();
@@ -533,7 +584,7 @@ fn should_not_verify_invalid_proof() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 		let key0 = key_bytes(0).to_vec();
@@ -593,7 +644,7 @@ fn should_verify_proof_of_membership() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 		let mut keys = Vec::new();
@@ -675,7 +726,7 @@ fn should_verify_simple_zk_proof_of_membership() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let mut ftree = FixedDepositTreeBuilder::new().hash_params(h).depth(1).build();
 
@@ -744,7 +795,7 @@ fn should_not_verify_invalid_commitments_for_leaf_creation() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		let mut ftree = FixedDepositTreeBuilder::new().hash_params(h).depth(1).build();
@@ -816,7 +867,7 @@ fn should_not_verify_invalid_private_inputs() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		let mut ftree = FixedDepositTreeBuilder::new().hash_params(h).depth(1).build();
@@ -892,7 +943,7 @@ fn should_not_verify_invalid_path_commitments_for_membership() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 
@@ -969,7 +1020,7 @@ fn should_not_verify_invalid_transcript() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 
@@ -1041,7 +1092,7 @@ fn should_verify_zk_proof_of_membership() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 
@@ -1122,7 +1173,7 @@ fn should_verify_large_zk_proof_of_membership() {
 		let key_id = 0;
 		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, key_id));
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = from_bytes_to_bp_gens(&params);
+		let bp_gens = from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 		let zero_tree = gen_zero_tree(h.width, &h.sbox);
 
@@ -1189,8 +1240,7 @@ fn should_verify_simple_zk_proof_of_membership_arkworks() {
 		));
 
 		let (pk, vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, curve);
-		let mut vk_bytes = Vec::new();
-		vk.serialize(&mut vk_bytes).unwrap();
+		let vk_bytes = get_groth16_vk_bytes(&vk).unwrap();
 
 		let tree_id = 0;
 		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), vk_bytes));
@@ -1250,8 +1300,7 @@ fn should_verify_simple_zk_proof_of_membership_arkworks_mimc() {
 		));
 
 		let (pk, vk) = setup_random_groth16_mimc_220::<_, Bn254>(&mut rng, curve);
-		let mut vk_bytes = Vec::new();
-		vk.serialize(&mut vk_bytes).unwrap();
+		let vk_bytes = get_groth16_vk_bytes(&vk).unwrap();
 
 		let tree_id = 0;
 		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), vk_bytes));
@@ -1308,8 +1357,7 @@ fn should_fail_to_verify_empty_public_inputs_arkworks() {
 		));
 
 		let (pk, vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, curve);
-		let mut vk_bytes = Vec::new();
-		vk.serialize(&mut vk_bytes).unwrap();
+		let vk_bytes = get_groth16_vk_bytes(&vk).unwrap();
 
 		let tree_id = 0;
 		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), vk_bytes));
@@ -1404,8 +1452,7 @@ fn should_fail_to_verify_arkworks() {
 		));
 
 		let (pk, vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, curve);
-		let mut vk_bytes = Vec::new();
-		vk.serialize(&mut vk_bytes).unwrap();
+		let vk_bytes = get_groth16_vk_bytes(&vk).unwrap();
 
 		let tree_id = 0;
 		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), vk_bytes));
@@ -1537,10 +1584,9 @@ fn should_fail_to_verify_with_invalid_key_arkworks() {
 		));
 
 		let (pk, vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, curve);
-		let mut vk_bytes = Vec::new();
-		vk.serialize(&mut vk_bytes).unwrap();
+		let mut vk_bytes = get_groth16_vk_bytes(&vk).unwrap();
 		// pushing invalid byte
-		vk_bytes[0] = 1u8;
+		vk_bytes[KEY_HEADER_LEN] = 1u8;
 		let tree_id = 0;
 		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), vk_bytes));
 		let key_id = 0;
@@ -1581,7 +1627,7 @@ fn should_fail_to_verify_with_invalid_key_arkworks() {
 fn encode_bulletproof_gens_and_back() {
 	let gens = BulletproofGens::new(16400, 1);
 	let gen_bytes = get_bp_gen_bytes(&gens);
-	let new_gens = from_bytes_to_bp_gens(&gen_bytes);
+	let new_gens = from_bytes_to_bp_gens(&gen_bytes).unwrap();
 	// println!("{:?}, {:?}", gens.gens_capacity, new_gens.gens_capacity);
 	// println!("{:?}, {:?}", gens.party_capacity, new_gens.party_capacity);
 	assert!(gens.gens_capacity == new_gens.gens_capacity);
@@ -1589,3 +1635,596 @@ fn encode_bulletproof_gens_and_back() {
 	assert!(gens.G_vec == new_gens.G_vec);
 	assert!(gens.H_vec == new_gens.H_vec);
 }
+
+#[test]
+fn should_reject_unversioned_verifying_key() {
+	new_test_ext().execute_with(|| {
+		assert_err!(
+			MerkleTrees::add_verifying_key(Origin::signed(1), vec![0u8; 64]),
+			Error::<Test>::InvalidVerifierKey
+		);
+	});
+}
+
+/// Encodes `bp_gens` the way keys were stored before they were versioned
+fn legacy_bp_gen_bytes(bp_gens: &BulletproofGens) -> Vec<u8> {
+	let vecs_bytes = |vecs: &Vec<Vec<RistrettoPoint>>| {
+		let mut bytes = vec![];
+		for points in vecs {
+			bytes.extend_from_slice(&(points.len() as u32 * 32).to_be_bytes());
+			for point in points {
+				bytes.extend_from_slice(&point.compress().to_bytes());
+			}
+		}
+		bytes
+	};
+	let g_bytes = vecs_bytes(&bp_gens.G_vec);
+	let h_bytes = vecs_bytes(&bp_gens.H_vec);
+
+	let mut bytes = vec![];
+	bytes.extend_from_slice(&(bp_gens.gens_capacity as u32).to_be_bytes());
+	bytes.extend_from_slice(&(bp_gens.party_capacity as u32).to_be_bytes());
+	bytes.extend_from_slice(&(g_bytes.len() as u32).to_be_bytes());
+	bytes.extend_from_slice(&g_bytes);
+	bytes.extend_from_slice(&(h_bytes.len() as u32).to_be_bytes());
+	bytes.extend_from_slice(&h_bytes);
+	bytes
+}
+
+#[test]
+fn should_migrate_unversioned_verifying_keys() {
+	new_test_ext().execute_with(|| {
+		let gens = BulletproofGens::new(16, 1);
+		let key_data = get_bp_gen_bytes(&gens);
+		let legacy_key = legacy_bp_gen_bytes(&gens);
+		assert!(check_key_header(&legacy_key).is_err());
+
+		// Key 0 was stored unversioned, key 1 is already versioned and key 2 is
+		// invalid in both formats
+		VerifyingKeys::<Test>::insert(0, Some(legacy_key.clone()));
+		VerifyingKeys::<Test>::insert(1, Some(key_data.clone()));
+		VerifyingKeyHashes::<Test>::insert(1, sp_io::hashing::blake2_256(&key_data));
+		VerifyingKeys::<Test>::insert(2, Some(legacy_key[..legacy_key.len() - 1].to_vec()));
+		NextKeyId::<Test>::put(3);
+		assert_eq!(MerkleTrees::storage_version(), Releases::V1);
+
+		MerkleTrees::on_runtime_upgrade();
+		assert_eq!(MerkleTrees::storage_version(), Releases::V3KeyHashes);
+		assert_eq!(MerkleTrees::get_verifying_key(0), Ok(key_data.clone()));
+		assert_eq!(
+			MerkleTrees::verifying_key_hashes(0),
+			Some(sp_io::hashing::blake2_256(&key_data))
+		);
+		assert_eq!(MerkleTrees::get_verifying_key(1), Ok(key_data.clone()));
+		assert_eq!(
+			MerkleTrees::get_verifying_key(2),
+			Ok(legacy_key[..legacy_key.len() - 1].to_vec())
+		);
+
+		// The migrated key can be used by trees
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+		assert_ok!(MerkleTrees::add_members(Origin::signed(1), 0, vec![key_bytes(1).to_vec()]));
+
+		// Migrations only run once
+		VerifyingKeys::<Test>::insert(0, Some(legacy_key));
+		MerkleTrees::on_runtime_upgrade();
+		assert!(check_key_header(&MerkleTrees::get_verifying_key(0).unwrap()).is_err());
+	});
+}
+
+#[test]
+fn should_migrate_verifying_keys_without_hashes() {
+	new_test_ext().execute_with(|| {
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+		// Keys were versioned before their hashes were stored
+		VerifyingKeys::<Test>::insert(0, Some(key_data.clone()));
+		NextKeyId::<Test>::put(1);
+		StorageVersion::<Test>::put(Releases::V2VersionedKeys);
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+		assert_err!(
+			MerkleTrees::initialize_tree(Origin::signed(1), 0, 0),
+			Error::<Test>::InvalidVerifierKey
+		);
+
+		MerkleTrees::on_runtime_upgrade();
+		assert_eq!(MerkleTrees::storage_version(), Releases::V3KeyHashes);
+		assert_eq!(
+			MerkleTrees::verifying_key_hashes(0),
+			Some(sp_io::hashing::blake2_256(&key_data))
+		);
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+	});
+}
+
+#[test]
+fn should_fail_to_initialize_with_invalid_bulletproof_gens() {
+	new_test_ext().execute_with(|| {
+		let hasher = HashFunction::PoseidonDefault;
+		let backend = Backend::Bulletproofs(Curve::Curve25519);
+		let setup = Setup::new(hasher, backend);
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+
+		let mut key_data = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+		// 0xff.. is not a canonical encoding of any point
+		let last = key_data.len();
+		key_data[last - 32..].copy_from_slice(&[0xffu8; 32]);
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key_data));
+		assert_err!(
+			MerkleTrees::initialize_tree(Origin::signed(1), 0, 0),
+			Error::<Test>::InvalidVerifierKey
+		);
+	});
+}
+
+#[test]
+fn should_not_reuse_prepared_key_after_key_is_replaced() {
+	new_test_ext().execute_with(|| {
+		let hasher = HashFunction::PoseidonDefault;
+		let backend = Backend::Bulletproofs(Curve::Curve25519);
+		let setup = Setup::new(hasher, backend);
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key_data.clone()));
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+		// Prepares the key and caches it
+		assert_ok!(MerkleTrees::add_members(Origin::signed(1), 0, vec![key_bytes(1).to_vec()]));
+
+		let mut invalid_key = key_data;
+		let last = invalid_key.len();
+		invalid_key[last - 32..].copy_from_slice(&[0xffu8; 32]);
+		assert_ok!(MerkleTrees::set_verifying_key(RawOrigin::Root.into(), 0, invalid_key));
+		assert_err!(
+			MerkleTrees::add_members(Origin::signed(1), 0, vec![key_bytes(2).to_vec()]),
+			Error::<Test>::InvalidVerifierKey
+		);
+	});
+}
+
+fn fuzz_rng() -> ChaChaRng {
+	ChaChaRng::from_seed([7u8; 32])
+}
+
+fn random_bytes(rng: &mut ChaChaRng, max_len: u32) -> Vec<u8> {
+	let mut bytes = vec![0u8; (rng.next_u32() % max_len) as usize];
+	rng.fill_bytes(&mut bytes);
+	bytes
+}
+
+#[test]
+fn key_header_is_checked() {
+	let key = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+	assert!(check_key_header(&key).is_ok());
+	assert_eq!(check_key_header(&[]).err(), Some(KeyError::UnexpectedEnd));
+
+	let mut wrong_version = key.clone();
+	wrong_version[0] = KEY_FORMAT_VERSION + 1;
+	assert_eq!(
+		from_bytes_to_bp_gens(&wrong_version).err(),
+		Some(KeyError::UnsupportedVersion)
+	);
+
+	let mut wrong_kind = key.clone();
+	wrong_kind[1] = u8::MAX;
+	assert_eq!(from_bytes_to_bp_gens(&wrong_kind).err(), Some(KeyError::UnexpectedKind));
+	assert_eq!(
+		from_bytes_to_groth16_vk::<Bn254>(&key).err(),
+		Some(KeyError::UnexpectedKind)
+	);
+}
+
+#[test]
+fn bulletproof_gens_decoding_survives_random_bytes() {
+	let mut rng = fuzz_rng();
+	for _ in 0..1000 {
+		let mut bytes = random_bytes(&mut rng, 1024);
+		// Give half of the inputs a valid header so decoding reaches the body.
+		if rng.next_u32() % 2 == 0 && bytes.len() >= KEY_HEADER_LEN {
+			bytes[0] = KEY_FORMAT_VERSION;
+			bytes[1] = KeyKind::BulletproofGens as u8;
+		}
+		if let Ok(gens) = from_bytes_to_bp_gens(&bytes) {
+			assert_eq!(get_bp_gen_bytes(&gens), bytes);
+		}
+	}
+}
+
+#[test]
+fn bulletproof_gens_decoding_rejects_or_round_trips_mutations() {
+	let mut rng = fuzz_rng();
+	let key = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+	for _ in 0..1000 {
+		let mut bytes = key.clone();
+		let index = rng.next_u32() as usize % bytes.len();
+		bytes[index] ^= (rng.next_u32() % 255 + 1) as u8;
+		if let Ok(gens) = from_bytes_to_bp_gens(&bytes) {
+			assert_eq!(get_bp_gen_bytes(&gens), bytes);
+		}
+	}
+}
+
+#[test]
+fn bulletproof_gens_decoding_rejects_truncated_and_extended_keys() {
+	let key = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+	for len in 0..key.len() {
+		assert!(from_bytes_to_bp_gens(&key[..len]).is_err());
+	}
+
+	let mut extended = key.clone();
+	extended.push(0);
+	assert_eq!(from_bytes_to_bp_gens(&extended).err(), Some(KeyError::TrailingBytes));
+}
+
+#[test]
+fn bulletproof_gens_decoding_rejects_huge_capacities() {
+	let mut rng = fuzz_rng();
+	for _ in 0..1000 {
+		let gens_capacity = rng.next_u32();
+		let party_capacity = rng.next_u32();
+		let mut bytes = vec![KEY_FORMAT_VERSION, KeyKind::BulletproofGens as u8];
+		bytes.extend_from_slice(&gens_capacity.to_be_bytes());
+		bytes.extend_from_slice(&party_capacity.to_be_bytes());
+		bytes.extend_from_slice(&[0u8; 64]);
+
+		let res = from_bytes_to_bp_gens(&bytes);
+		if gens_capacity as u64 * party_capacity as u64 > MAX_BP_GENERATORS as u64 {
+			assert_eq!(res.err(), Some(KeyError::TooLarge));
+		}
+	}
+}
+
+#[test]
+fn groth16_key_decoding_rejects_malformed_keys() {
+	let mut rng = test_rng();
+	let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+	let (_, vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, curve);
+	let key = get_groth16_vk_bytes(&vk).unwrap();
+	let decoded = from_bytes_to_groth16_vk::<Bn254>(&key).unwrap();
+	assert_eq!(get_groth16_vk_bytes(&decoded).unwrap(), key);
+
+	for len in 0..key.len() {
+		assert!(from_bytes_to_groth16_vk::<Bn254>(&key[..len]).is_err());
+	}
+
+	let mut extended = key.clone();
+	extended.push(0);
+	assert_eq!(
+		from_bytes_to_groth16_vk::<Bn254>(&extended).err(),
+		Some(KeyError::TrailingBytes)
+	);
+
+	// A huge length prefix for `gamma_abc_g1` must fail instead of allocating.
+	let mut huge = key.clone();
+	let offset = key.len() - vk.gamma_abc_g1.serialized_size();
+	huge[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+	assert!(from_bytes_to_groth16_vk::<Bn254>(&huge).is_err());
+
+	let mut fuzz = fuzz_rng();
+	for _ in 0..500 {
+		let mut bytes = key.clone();
+		let index = KEY_HEADER_LEN + fuzz.next_u32() as usize % (bytes.len() - KEY_HEADER_LEN);
+		bytes[index] ^= (fuzz.next_u32() % 255 + 1) as u8;
+		let _ = from_bytes_to_groth16_vk::<Bn254>(&bytes);
+
+		let mut bytes = random_bytes(&mut fuzz, 512);
+		if bytes.len() >= KEY_HEADER_LEN {
+			bytes[0] = KEY_FORMAT_VERSION;
+			bytes[1] = KeyKind::Groth16 as u8;
+		}
+		let _ = from_bytes_to_groth16_vk::<Bn254>(&bytes);
+	}
+}
+
+#[test]
+fn groth16_key_input_count_matches_decoded_key() {
+	let mut rng = test_rng();
+	let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+	let (_, vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, curve);
+	let key = get_groth16_vk_bytes(&vk).unwrap();
+	assert_eq!(groth16_vk_input_count::<Bn254>(&key), Ok(vk.gamma_abc_g1.len() - 1));
+
+	for len in 0..key.len() {
+		assert!(groth16_vk_input_count::<Bn254>(&key[..len]).is_err());
+	}
+	let mut extended = key.clone();
+	extended.push(0);
+	assert_eq!(groth16_vk_input_count::<Bn254>(&extended), Err(KeyError::TrailingBytes));
+
+	let offset = key.len() - vk.gamma_abc_g1.serialized_size();
+	let mut huge = key.clone();
+	huge[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+	assert_eq!(groth16_vk_input_count::<Bn254>(&huge), Err(KeyError::TooLarge));
+
+	// Keys of another curve have points of another size
+	assert!(groth16_vk_input_count::<Bls12_381>(&key).is_err());
+}
+
+/// Setups to compare host and runtime hashing for, with a key for each
+fn host_setups() -> Vec<(Setup, Vec<u8>)> {
+	let mut rng = test_rng();
+	let bp_key = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
+	let (_, bls_vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, arkworks_gadgets::setup::common::Curve::Bls381);
+	let (_, bn_vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, arkworks_gadgets::setup::common::Curve::Bn254);
+	let bls_key = get_groth16_vk_bytes(&bls_vk).unwrap();
+	let bn_key = get_groth16_vk_bytes(&bn_vk).unwrap();
+
+	let bls = Backend::Arkworks(Curve::Bls381, Snark::Groth16);
+	let bn = Backend::Arkworks(Curve::Bn254, Snark::Groth16);
+	vec![
+		(
+			Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+			bp_key.clone(),
+		),
+		(Setup::new(HashFunction::PoseidonDefault, bls.clone()), bls_key.clone()),
+		(Setup::new(HashFunction::Poseidon(3, 17), bls.clone()), bls_key.clone()),
+		(Setup::new(HashFunction::MiMC, bls), bls_key),
+		(Setup::new(HashFunction::PoseidonDefault, bn.clone()), bn_key.clone()),
+		(Setup::new(HashFunction::Poseidon(3, 17), bn.clone()), bn_key.clone()),
+		(Setup::new(HashFunction::MiMC, bn), bn_key),
+		(
+			Setup::new(HashFunction::MiMC, Backend::Bulletproofs(Curve::Curve25519)),
+			bp_key,
+		),
+	]
+}
+
+#[test]
+fn hash_bytes_matches_hash_with_tree_key() {
+	let mut rng = fuzz_rng();
+	for (setup, key) in host_setups() {
+		let prepared = PreparedKey::prepare(&setup.backend, &key).unwrap();
+		let tree_key = if setup.hashes_with_key() { Some(&prepared) } else { None };
+		for _ in 0..8 {
+			let mut xl = vec![0u8; 32];
+			let mut xr = vec![0u8; 32];
+			rng.fill_bytes(&mut xl);
+			rng.fill_bytes(&mut xr);
+			xl[31] = 0;
+			xr[31] = 0;
+
+			let without_key = setup.hash_bytes(&xl, &xr);
+			let with_key = setup.compute_hash(&xl, &xr, tree_key);
+			assert_eq!(without_key, with_key, "hash mismatch for {:?}", setup);
+		}
+	}
+}
+
+#[test]
+fn host_hash_of_bulletproofs_tree_matches_runtime() {
+	new_test_ext().execute_with(|| {
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup.clone(), Some(2)));
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key_data));
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+
+		let leaves: Vec<ScalarBytes> = (0..3).map(|x| key_bytes(x).to_vec()).collect();
+		assert_ok!(MerkleTrees::add_members(Origin::signed(1), 0, leaves.clone()));
+
+		// Recompute the root with the host functions only, which hash without
+		// the tree's generators.
+		let zero_tree = gen_zero_tree(6, &PoseidonSbox::Exponentiation3);
+		let hash = |xl: &ScalarBytes, xr: &ScalarBytes| merkle_crypto::hash(setup.clone(), xl, xr).unwrap();
+		let h1 = hash(&leaves[0], &leaves[1]);
+		let h2 = hash(&leaves[2], &zero_tree[0].to_vec());
+		assert_eq!(MerkleTrees::get_merkle_root(0).unwrap(), hash(&h1, &h2));
+	});
+}
+
+/// Proof of membership in a fresh Groth16 tree, created with key 0
+fn groth16_bn254_membership(
+	rng: &mut arkworks_gadgets::ark_std::rand::rngs::StdRng,
+	pk: &ark_groth16::ProvingKey<Bn254>,
+	relayer: Bn254Fr,
+) -> ZkProof<Test> {
+	let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+	let recipient = Bn254Fr::from(0u8);
+	let (circuit, leaf, nullifier, root, _) = setup_circuit_x5(&Vec::new(), 0, recipient, relayer, rng, curve);
+
+	let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Arkworks(Curve::Bn254, Snark::Groth16));
+	let tree_id = MerkleTrees::next_tree_id();
+	assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(30)));
+	assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), tree_id, 0));
+	assert_ok!(MerkleTrees::add_members(
+		Origin::signed(1),
+		tree_id,
+		vec![to_bytes![leaf].unwrap()]
+	));
+	assert_eq!(MerkleTrees::get_merkle_root(tree_id).unwrap(), to_bytes![root].unwrap());
+
+	let proof = prove_groth16_x5(pk, circuit, rng);
+	let mut proof_bytes = vec![0u8; proof.serialized_size()];
+	proof.serialize(&mut proof_bytes[..]).unwrap();
+	ZkProof {
+		tree_id,
+		cached_block: System::block_number(),
+		cached_root: to_bytes![root].unwrap(),
+		comms: Vec::new(),
+		nullifier_hash: to_bytes![nullifier].unwrap(),
+		proof_bytes,
+		leaf_index_commitments: Vec::new(),
+		proof_commitments: Vec::new(),
+		recipient: to_bytes![recipient].unwrap(),
+		relayer: to_bytes![relayer].unwrap(),
+		binding: None,
+	}
+}
+
+#[test]
+fn should_batch_verify_groth16_proofs() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let mut rng = test_rng();
+		let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+		let (pk, vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, curve);
+		assert_ok!(MerkleTrees::add_verifying_key(
+			Origin::signed(1),
+			get_groth16_vk_bytes(&vk).unwrap()
+		));
+
+		let valid_0 = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		let valid_1 = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(1u8));
+		let mut wrong_relayer = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		wrong_relayer.relayer = to_bytes![Bn254Fr::from(2u8)].unwrap();
+		let mut unknown_root = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		unknown_root.cached_root = valid_0.cached_root.clone();
+
+		let results = <MerkleTrees as Tree<Test>>::verify_zk_batch(vec![valid_0, valid_1, wrong_relayer, unknown_root]);
+		assert_eq!(
+			results,
+			vec![
+				Ok(()),
+				Ok(()),
+				Err(Error::<Test>::ZkVerificationFailed.into()),
+				Err(Error::<Test>::InvalidMerkleRoot.into()),
+			]
+		);
+	});
+}
+
+#[test]
+fn should_require_leaf_binding_iff_tree_has_bound_leaves() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let mut rng = test_rng();
+		let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+		let (pk, vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, curve);
+		assert_ok!(MerkleTrees::add_verifying_key(
+			Origin::signed(1),
+			get_groth16_vk_bytes(&vk).unwrap()
+		));
+		let binding = LeafBinding {
+			asset_id: to_bytes![Bn254Fr::from(0u8)].unwrap(),
+			amount: to_bytes![Bn254Fr::from(100u8)].unwrap(),
+			chain_id: to_bytes![Bn254Fr::from(42u8)].unwrap(),
+		};
+
+		let opaque = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		let mut opaque_with_binding = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		opaque_with_binding.binding = Some(binding.clone());
+		let unbound = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		let mut bound = groth16_bn254_membership(&mut rng, &pk, Bn254Fr::from(0u8));
+		bound.binding = Some(binding);
+		// Tests the binding checks on trees with leaves, which can't be made bound
+		// with `set_leaf_format`
+		LeafFormats::<Test>::insert(unbound.tree_id, LeafFormat::Bound);
+		LeafFormats::<Test>::insert(bound.tree_id, LeafFormat::Bound);
+
+		assert_ok!(<MerkleTrees as Tree<Test>>::verify_zk_proof(opaque));
+		assert_err!(
+			<MerkleTrees as Tree<Test>>::verify_zk_proof(opaque_with_binding),
+			Error::<Test>::LeafBindingMismatch
+		);
+		assert_err!(
+			<MerkleTrees as Tree<Test>>::verify_zk_proof(unbound),
+			Error::<Test>::LeafBindingMismatch
+		);
+		// The key of the opaque leaf circuit doesn't take the binding inputs
+		assert_err!(
+			<MerkleTrees as Tree<Test>>::verify_zk_proof(bound),
+			Error::<Test>::ZkVerificationFailed
+		);
+	});
+}
+
+#[test]
+fn should_verify_groth16_proof_with_prepared_key() {
+	let mut rng = test_rng();
+	let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+	let recipient = Bn254Fr::from(0u8);
+	let relayer = Bn254Fr::from(0u8);
+	let (circuit, _, nullifier_hash, root, _) = setup_circuit_x5(&Vec::new(), 0, recipient, relayer, &mut rng, curve);
+	let (pk, vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, curve);
+	let backend = Backend::Arkworks(Curve::Bn254, Snark::Groth16);
+	let key = PreparedKey::prepare(&backend, &get_groth16_vk_bytes(&vk).unwrap()).unwrap();
+
+	let proof = prove_groth16_x5(&pk, circuit, &mut rng);
+	let mut proof_bytes = Vec::new();
+	proof.serialize(&mut proof_bytes).unwrap();
+	let inputs: Vec<[u8; 32]> = get_public_inputs::<Bn254Fr>(nullifier_hash, root, recipient, relayer)
+		.iter()
+		.map(|input| slice_to_bytes_32(&to_bytes![input].unwrap()))
+		.collect();
+	assert_ok!(key.verify_groth16(&proof_bytes, &inputs));
+
+	let mut wrong_inputs = inputs.clone();
+	wrong_inputs.swap(0, 1);
+	assert_eq!(
+		key.verify_groth16(&proof_bytes, &wrong_inputs),
+		Err(SetupError::ZkVerificationFailed)
+	);
+	assert_eq!(
+		key.verify_groth16(&proof_bytes, &inputs[1..]),
+		Err(SetupError::InvalidPublicInputs)
+	);
+	// Inputs must be canonical field elements
+	assert_eq!(
+		key.verify_groth16(&proof_bytes, &vec![[0xff; 32]; inputs.len()]),
+		Err(SetupError::InvalidPublicInputs)
+	);
+	assert_eq!(
+		key.verify_groth16(&proof_bytes[1..], &inputs),
+		Err(SetupError::InvalidZkProof)
+	);
+}
+
+#[test]
+fn bulletproofs_verifier_rng_is_bound_to_proof_and_inputs() {
+	fn draw(commitments: &[CompressedRistretto], inputs: &[Scalar], proof: &[u8]) -> u64 {
+		bulletproofs_verifier_rng(commitments, inputs, proof).next_u64()
+	}
+	let commitments = vec![PedersenGens::default().B.compress()];
+	let inputs = vec![Scalar::from(1u32), Scalar::from(2u32)];
+	let proof = vec![3u8; 64];
+
+	let value = draw(&commitments, &inputs, &proof);
+	assert_eq!(value, draw(&commitments, &inputs, &proof));
+	assert_ne!(value, draw(&[], &inputs, &proof));
+	assert_ne!(value, draw(&commitments, &inputs[..1], &proof));
+	assert_ne!(value, draw(&commitments, &inputs, &proof[1..]));
+}
+
+#[test]
+fn should_index_memos_of_new_members() {
+	let mut ext = new_test_ext();
+	ext.execute_with(|| {
+		System::set_block_number(1);
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key_data));
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+		assert_ok!(MerkleTrees::add_members(Origin::signed(1), 0, vec![key_bytes(1).to_vec()]));
+
+		let members = vec![key_bytes(2).to_vec(), key_bytes(3).to_vec()];
+		assert_err!(
+			<MerkleTrees as Tree<Test>>::add_members_with_memos(1, 0, members.clone(), vec![vec![1]]),
+			Error::<Test>::MemoCountMismatch
+		);
+		assert_err!(
+			<MerkleTrees as Tree<Test>>::add_members_with_memos(1, 0, members.clone(), vec![vec![0u8; 257], vec![]]),
+			Error::<Test>::MemoTooLong
+		);
+
+		let memos = vec![vec![1, 2, 3], vec![0u8; 256]];
+		assert_ok!(<MerkleTrees as Tree<Test>>::add_members_with_memos(
+			1,
+			0,
+			members,
+			memos.clone()
+		));
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 3);
+		let event = mock::Event::MerkleTrees(crate::Event::NewMemos(0, 1, memos));
+		assert!(System::events().iter().any(|record| record.event == event));
+	});
+
+	// Memos are indexed by leaf index, the first leaf was added without one
+	ext.persist_offchain_overlay();
+	let offchain_db = ext.offchain_db();
+	assert_eq!(offchain_db.get(&memo_key(0u32, 0)), None);
+	assert_eq!(offchain_db.get(&memo_key(0u32, 1)), Some(vec![1, 2, 3]));
+	assert_eq!(offchain_db.get(&memo_key(0u32, 2)), Some(vec![0u8; 256]));
+}
@@ -1,11 +1,41 @@
 //! All the traits exposed to be used in other custom pallets
 use crate::{
-	utils::{keys::ScalarBytes, setup::Setup},
+	utils::{
+		keys::ScalarBytes,
+		setup::{LeafBinding, LeafFormat, Setup},
+	},
 	Config,
 };
 pub use frame_support::dispatch;
 use sp_std::vec::Vec;
 
+/// Zero-knowledge membership proof, as checked by [`Tree::verify_zk_batch`]
+pub struct ZkProof<T: Config> {
+	/// Tree the membership is proven in
+	pub tree_id: T::TreeId,
+	/// The cached block for the cached root being proven against
+	pub cached_block: T::BlockNumber,
+	/// The cached root being proven against
+	pub cached_root: ScalarBytes,
+	/// The individual scalar commitments (to the randomness and nullifier)
+	pub comms: Vec<ScalarBytes>,
+	/// The nullifier hash with itself
+	pub nullifier_hash: ScalarBytes,
+	/// The proof in bytes representation
+	pub proof_bytes: Vec<u8>,
+	/// The leaf index scalar commitments to decide on which side to hash
+	pub leaf_index_commitments: Vec<ScalarBytes>,
+	/// The scalar commitments to merkle proof path elements
+	pub proof_commitments: Vec<ScalarBytes>,
+	/// The recipient bound to the proof
+	pub recipient: ScalarBytes,
+	/// The relayer bound to the proof
+	pub relayer: ScalarBytes,
+	/// The asset, amount and chain id bound to the leaf, required by trees of
+	/// bound leaves
+	pub binding: Option<LeafBinding>,
+}
+
 /// Tree trait definition to be used in other pallets
 pub trait Tree<T: Config> {
 	/// Check if nullifier is already used, in which case return an error
@@ -15,6 +45,21 @@ pub trait Tree<T: Config> {
 	/// Can only be called by the manager, regardless if the manager is required
 	fn set_stopped(sender: T::AccountId, tree_id: T::TreeId, stopped: bool) -> Result<(), dispatch::DispatchError>;
 	fn is_stopped(tree_id: T::TreeId) -> bool;
+	/// Returns the current root of an initialized tree
+	fn get_root(tree_id: T::TreeId) -> Result<ScalarBytes, dispatch::DispatchError>;
+	/// Returns the roots of the tree cached at `block_number`
+	fn get_cached_roots(tree_id: T::TreeId, block_number: T::BlockNumber) -> Vec<ScalarBytes>;
+	/// Returns the number of leaves in the tree
+	fn get_leaf_count(tree_id: T::TreeId) -> Result<u32, dispatch::DispatchError>;
+	/// Returns the format of the leaves of the tree
+	fn get_leaf_format(tree_id: T::TreeId) -> LeafFormat;
+	/// Sets the format of the leaves of the tree, before any leaf is added.
+	/// Bound leaves require a Groth16 tree. Can only be called by the manager
+	fn set_leaf_format(
+		sender: T::AccountId,
+		tree_id: T::TreeId,
+		format: LeafFormat,
+	) -> Result<(), dispatch::DispatchError>;
 	/// Sets whether the manager is required for guarded calls.
 	/// Can only be called by the current manager
 	fn set_manager_required(
@@ -53,6 +98,13 @@ pub trait Tree<T: Config> {
 		id: T::TreeId,
 		members: Vec<ScalarBytes>,
 	) -> Result<(), dispatch::DispatchError>;
+	/// Adds members/leaves to the tree, attaching a memo to each of them
+	fn add_members_with_memos(
+		sender: T::AccountId,
+		id: T::TreeId,
+		members: Vec<ScalarBytes>,
+		memos: Vec<Vec<u8>>,
+	) -> Result<(), dispatch::DispatchError>;
 	/// Adds a nullifier to the storage
 	/// Can only be called by the manager if the manager is required
 	fn add_nullifier(
@@ -62,7 +114,7 @@ pub trait Tree<T: Config> {
 	) -> Result<(), dispatch::DispatchError>;
 	/// Verify membership proof
 	fn verify(id: T::TreeId, leaf: ScalarBytes, path: Vec<(bool, ScalarBytes)>) -> Result<(), dispatch::DispatchError>;
-	/// Verify zero-knowladge membership proof
+	/// Verify zero-knowladge membership proof of an opaque leaf
 	fn verify_zk(
 		tree_id: T::TreeId,
 		cached_block: T::BlockNumber,
@@ -75,4 +127,10 @@ pub trait Tree<T: Config> {
 		recipient: ScalarBytes,
 		relayer: ScalarBytes,
 	) -> Result<(), dispatch::DispatchError>;
+	/// Verify zero-knowledge membership proof, of an opaque or a bound leaf
+	fn verify_zk_proof(proof: ZkProof<T>) -> Result<(), dispatch::DispatchError>;
+	/// Verify many zero-knowledge membership proofs at once, returning the
+	/// result of each proof in order. Proofs against the same verifying key
+	/// are batch verified.
+	fn verify_zk_batch(proofs: Vec<ZkProof<T>>) -> Vec<dispatch::DispatchResult>;
 }
@@ -0,0 +1,70 @@
+//! Host functions for hashing and proof verification
+//!
+//! Hashing and verifying proofs in Wasm is an order of magnitude slower than
+//! running the same code natively. The node registers [`merkle_crypto`] with
+//! its executor (see `merkle_crypto::HostFunctions`) and runtimes built with
+//! the `host-functions` feature call into it from [`Setup`].
+//!
+//! A Wasm runtime built with the feature imports these functions, so it can
+//! only be executed by nodes which provide them. The feature is off by
+//! default, in which case everything runs in Wasm.
+//!
+//! The host runs [`Setup::compute_hash`], [`Setup::check_proof`] and
+//! [`Setup::check_proof_batch`], the same code the runtime falls back to, so
+//! results never depend on where they were computed. `pallet-merkle-test-wasm`
+//! checks this against that code compiled to Wasm. Setups the host doesn't
+//! support are reported as [`SetupError::Unimplemented`] and handled by the
+//! runtime instead.
+use crate::utils::setup::{Setup, SetupError, ZkInputs};
+#[cfg(feature = "std")]
+use crate::utils::{
+	prepared::{self, PreparedKey, BULLETPROOFS_HASHER},
+	setup::Backend,
+};
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use sp_runtime_interface::runtime_interface;
+use sp_std::prelude::*;
+
+/// Whether the runtime is built to use the merkle host functions
+pub const ENABLED: bool = cfg!(feature = "host-functions");
+
+/// Returns `key` prepared for `backend`, caching it by the hash of its bytes.
+#[cfg(feature = "std")]
+fn prepared_key(backend: &Backend, key: &[u8]) -> Result<Arc<PreparedKey>, SetupError> {
+	let cache_key = prepared::cache_key(b"host", &sp_io::hashing::blake2_256(key), backend);
+	if let Some(prepared) = prepared::get(&cache_key) {
+		return Ok(prepared);
+	}
+
+	let prepared = Arc::new(PreparedKey::prepare(backend, key)?);
+	prepared::insert(cache_key, prepared.clone());
+	Ok(prepared)
+}
+
+/// Native hashing and proof verification for merkle trees
+#[runtime_interface]
+pub trait MerkleCrypto {
+	/// Hashes `xl` and `xr` with `setup`.
+	fn hash(setup: Setup, xl: &[u8], xr: &[u8]) -> Result<Vec<u8>, SetupError> {
+		let key = match setup.backend {
+			Backend::Bulletproofs(_) => Some(&*BULLETPROOFS_HASHER),
+			_ => None,
+		};
+		setup.compute_hash(xl, xr, key)
+	}
+
+	/// Verifies a membership proof for `setup` against the encoded verifying
+	/// key `key`.
+	fn verify_zk(setup: Setup, key: &[u8], inputs: ZkInputs) -> Result<(), SetupError> {
+		let prepared = prepared_key(&setup.backend, key)?;
+		setup.check_proof(&inputs, &prepared)
+	}
+
+	/// Verifies a batch of membership proofs for `setup` against the encoded
+	/// verifying key `key`, succeeding only if all of them are valid.
+	fn verify_zk_batch(setup: Setup, key: &[u8], inputs: Vec<ZkInputs>) -> Result<(), SetupError> {
+		let prepared = prepared_key(&setup.backend, key)?;
+		setup.check_proof_batch(&inputs, &prepared)
+	}
+}
@@ -1,10 +1,77 @@
 //! Type definitions used in merkle pallet
+//!
+//! Verifying keys are stored on chain as opaque bytes, so every key is
+//! prefixed with a `[version, kind]` header and decoded with explicit bounds.
+//! Decoding never panics: malformed keys are reported as a [`KeyError`],
+//! which the pallet surfaces as `InvalidVerifierKey`.
+use crate::{Config, Error};
+use ark_ec::PairingEngine;
+use ark_ff::Zero;
+use ark_groth16::VerifyingKey;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use bulletproofs::BulletproofGens;
-use codec::Input;
-use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use sp_std::prelude::*;
 pub type ScalarBytes = Vec<u8>;
 
+/// Version of the key encoding produced by this module.
+pub const KEY_FORMAT_VERSION: u8 = 1;
+/// Length of the `[version, kind]` header prefixing every encoded key.
+pub const KEY_HEADER_LEN: usize = 2;
+/// Maximum number of generators (`gens_capacity * party_capacity`) an
+/// encoded `BulletproofGens` may declare.
+pub const MAX_BP_GENERATORS: usize = 1 << 16;
+/// Maximum length of an encoded Groth16 verifying key, header included.
+pub const MAX_GROTH16_KEY_LEN: usize = 1 << 16;
+/// Maximum length of any encoded verifying key, header included.
+pub const MAX_KEY_LEN: usize = KEY_HEADER_LEN + 8 + 2 * MAX_BP_GENERATORS * POINT_LEN;
+
+const POINT_LEN: usize = 32;
+
+/// Kind of key stored after the header.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KeyKind {
+	BulletproofGens = 0,
+	Groth16 = 1,
+}
+
+impl KeyKind {
+	fn from_u8(kind: u8) -> Option<Self> {
+		match kind {
+			0 => Some(KeyKind::BulletproofGens),
+			1 => Some(KeyKind::Groth16),
+			_ => None,
+		}
+	}
+}
+
+/// Reasons an encoded key can be rejected
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyError {
+	/// Key ends before its declared contents
+	UnexpectedEnd,
+	/// Key was encoded with an unknown format version
+	UnsupportedVersion,
+	/// Key is of an unknown kind or not the kind expected by the caller
+	UnexpectedKind,
+	/// Key declares more generators or bytes than allowed
+	TooLarge,
+	/// Key has bytes past its declared contents
+	TrailingBytes,
+	/// Key contains an invalid curve point
+	InvalidPoint,
+	/// Key could not be (de)serialized by arkworks
+	Malformed,
+}
+
+impl<T: Config> From<KeyError> for Error<T> {
+	fn from(_: KeyError) -> Self {
+		Error::<T>::InvalidVerifierKey
+	}
+}
+
 pub fn slice_to_bytes_32(vec: &[u8]) -> [u8; 32] {
 	let mut bytes_array = [0u8; 32];
 	bytes_array
@@ -14,120 +81,207 @@ pub fn slice_to_bytes_32(vec: &[u8]) -> [u8; 32] {
 	bytes_array
 }
 
+/// Checks that `key` carries a supported header and fits the size bounds,
+/// without decoding its contents.
+pub fn check_key_header(key: &[u8]) -> Result<KeyKind, KeyError> {
+	if key.len() < KEY_HEADER_LEN {
+		return Err(KeyError::UnexpectedEnd);
+	}
+	if key[0] != KEY_FORMAT_VERSION {
+		return Err(KeyError::UnsupportedVersion);
+	}
+	let kind = KeyKind::from_u8(key[1]).ok_or(KeyError::UnexpectedKind)?;
+	let max_len = match kind {
+		KeyKind::BulletproofGens => MAX_KEY_LEN,
+		KeyKind::Groth16 => MAX_GROTH16_KEY_LEN,
+	};
+	if key.len() > max_len {
+		return Err(KeyError::TooLarge);
+	}
+	Ok(kind)
+}
+
+/// Returns the key contents following a header of the `expected` kind.
+fn strip_header(key: &[u8], expected: KeyKind) -> Result<&[u8], KeyError> {
+	let kind = check_key_header(key)?;
+	if kind != expected {
+		return Err(KeyError::UnexpectedKind);
+	}
+	Ok(&key[KEY_HEADER_LEN..])
+}
+
+fn header(kind: KeyKind) -> Vec<u8> {
+	vec![KEY_FORMAT_VERSION, kind as u8]
+}
+
+fn read_u32(input: &mut &[u8]) -> Result<u32, KeyError> {
+	if input.len() < 4 {
+		return Err(KeyError::UnexpectedEnd);
+	}
+	let mut bytes = [0u8; 4];
+	bytes.copy_from_slice(&input[..4]);
+	*input = &input[4..];
+	Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_points(input: &[u8], parties: usize, gens: usize) -> Result<Vec<Vec<RistrettoPoint>>, KeyError> {
+	let mut chunks = input.chunks_exact(POINT_LEN);
+	let mut vecs = Vec::with_capacity(parties);
+	for _ in 0..parties {
+		let mut points = Vec::with_capacity(gens);
+		for _ in 0..gens {
+			let chunk = chunks.next().ok_or(KeyError::UnexpectedEnd)?;
+			let point = CompressedRistretto::from_slice(chunk)
+				.decompress()
+				.ok_or(KeyError::InvalidPoint)?;
+			points.push(point);
+		}
+		vecs.push(points);
+	}
+	Ok(vecs)
+}
+
+/// Encodes `bp_gens` as
+/// `[version, kind, gens_capacity: u32 BE, party_capacity: u32 BE, G points, H points]`,
+/// where each point is a 32 byte compressed Ristretto point, ordered by party.
 pub fn get_bp_gen_bytes(bp_gens: &BulletproofGens) -> Vec<u8> {
-	let g_vec_bytes = bp_gens
-		.G_vec
-		.iter()
-		.map(|vec| {
-			vec.iter()
-				.map(|pt| pt.compress().to_bytes())
-				.fold(vec![], |mut acc, curr| {
-					// inside inner len (except this should always be 32 bytes so we can drop the
-					// length) NOT_NEEDED - acc.append(&mut
-					// curr.to_vec().len().to_be_bytes().to_vec()); inside inner data
-					acc.append(&mut curr.to_vec());
-					acc
-				})
-		})
-		.fold(vec![], |mut acc, curr| {
-			// inner len
-			acc.append(&mut (curr.to_vec().len() as u32).to_be_bytes().to_vec());
-			// inner data
-			acc.append(&mut curr.to_vec());
-			acc
-		});
-
-	let h_vec_bytes = bp_gens
-		.H_vec
-		.iter()
-		.map(|vec| {
-			vec.iter()
-				.map(|pt| pt.compress().to_bytes())
-				.fold(vec![], |mut acc, curr| {
-					// inside inner len (except this should always be 32 bytes so we can drop the
-					// length) NOT_NEEDED - acc.append(&mut
-					// curr.to_vec().len().to_be_bytes().to_vec()); inside inner data
-					acc.append(&mut curr.to_vec());
-					acc
-				})
-		})
-		.fold(vec![], |mut acc, curr| {
-			// inner len
-			acc.append(&mut (curr.to_vec().len() as u32).to_be_bytes().to_vec());
-			// inner data
-			acc.append(&mut curr.to_vec());
-			acc
-		});
-
-	let mut bytes = vec![];
+	let mut bytes = header(KeyKind::BulletproofGens);
 	bytes.extend_from_slice(&(bp_gens.gens_capacity as u32).to_be_bytes());
 	bytes.extend_from_slice(&(bp_gens.party_capacity as u32).to_be_bytes());
-	bytes.extend_from_slice(&(g_vec_bytes.len() as u32).to_be_bytes());
-	bytes.extend_from_slice(&g_vec_bytes);
-	bytes.extend_from_slice(&(h_vec_bytes.len() as u32).to_be_bytes());
-	bytes.extend_from_slice(&h_vec_bytes);
+	for party in bp_gens.G_vec.iter().chain(bp_gens.H_vec.iter()) {
+		for point in party {
+			bytes.extend_from_slice(&point.compress().to_bytes());
+		}
+	}
 	bytes
 }
 
-pub fn from_bytes_to_bp_gens(mut input: &[u8]) -> BulletproofGens {
-	let mut gens_capacity_bytes = [0u8; 4];
-	let _ = input.read(&mut gens_capacity_bytes);
-	let gens_capacity: usize = u32::from_be_bytes(gens_capacity_bytes) as usize;
-
-	let mut party_capacity_bytes = [0u8; 4];
-	let _ = input.read(&mut party_capacity_bytes);
-	let party_capacity: usize = u32::from_be_bytes(party_capacity_bytes) as usize;
-
-	let mut g_vec_len_bytes = [0u8; 4];
-	let _ = input.read(&mut g_vec_len_bytes);
-	let g_vec_len: usize = u32::from_be_bytes(g_vec_len_bytes) as usize;
-	let mut g_vec = vec![0u8; g_vec_len];
-	let _ = input.read(&mut g_vec);
-	let mut g_slice = g_vec.as_slice();
-	let mut vec_of_vecs_g = vec![];
-	while g_slice.len() > 0 {
-		let mut inner_vec_len = [0u8; 4];
-		let _ = g_slice.read(&mut inner_vec_len);
-		let inner_vec_len_u32 = u32::from_be_bytes(inner_vec_len) as usize;
-		let mut inner_vec = vec![0u8; inner_vec_len_u32];
-		let _ = g_slice.read(&mut inner_vec);
-		let mut inner_g_slice = inner_vec.as_slice();
-		let mut vec_of_points = vec![];
-		while inner_g_slice.len() > 0 {
-			let mut inside_inner_g_slice = [0u8; 32];
-			let _ = inner_g_slice.read(&mut inside_inner_g_slice);
-			vec_of_points.push(CompressedRistretto(inside_inner_g_slice).decompress().unwrap());
-		}
-		vec_of_vecs_g.push(vec_of_points);
-	}
-
-	let mut h_vec_len_bytes = [0u8; 4];
-	let _ = input.read(&mut h_vec_len_bytes);
-	let h_vec_len: usize = u32::from_be_bytes(h_vec_len_bytes) as usize;
-	let mut h_vec = vec![0u8; h_vec_len];
-	let _ = input.read(&mut h_vec);
-	let mut h_slice = h_vec.as_slice();
-	let mut vec_of_vecs_h = vec![];
-	while h_slice.len() > 0 {
-		let mut inner_vec_len = [0u8; 4];
-		let _ = h_slice.read(&mut inner_vec_len);
-		let inner_vec_len_u32 = u32::from_be_bytes(inner_vec_len) as usize;
-		let mut inner_vec = vec![0u8; inner_vec_len_u32];
-		let _ = h_slice.read(&mut inner_vec);
-		let mut inner_h_slice = inner_vec.as_slice();
-		let mut vec_of_points = vec![];
-		while inner_h_slice.len() > 0 {
-			let mut inside_inner_h_slice = [0u8; 32];
-			let _ = inner_h_slice.read(&mut inside_inner_h_slice);
-			vec_of_points.push(CompressedRistretto(inside_inner_h_slice).decompress().unwrap());
-		}
-		vec_of_vecs_h.push(vec_of_points);
+/// Decodes generators encoded with [`get_bp_gen_bytes`].
+///
+/// The declared capacities are bounded by [`MAX_BP_GENERATORS`] and checked
+/// against the input length before anything is allocated.
+pub fn from_bytes_to_bp_gens(input: &[u8]) -> Result<BulletproofGens, KeyError> {
+	let mut body = strip_header(input, KeyKind::BulletproofGens)?;
+	let gens_capacity = read_u32(&mut body)? as usize;
+	let party_capacity = read_u32(&mut body)? as usize;
+
+	let generators = gens_capacity
+		.checked_mul(party_capacity)
+		.filter(|n| *n <= MAX_BP_GENERATORS)
+		.ok_or(KeyError::TooLarge)?;
+	// One G and one H point per generator.
+	let points_len = generators * POINT_LEN;
+	if body.len() < 2 * points_len {
+		return Err(KeyError::UnexpectedEnd);
+	}
+	if body.len() > 2 * points_len {
+		return Err(KeyError::TrailingBytes);
 	}
 
-	BulletproofGens {
+	let (g_bytes, h_bytes) = body.split_at(points_len);
+	Ok(BulletproofGens {
 		gens_capacity,
 		party_capacity,
-		G_vec: vec_of_vecs_g,
-		H_vec: vec_of_vecs_h,
+		G_vec: read_points(g_bytes, party_capacity, gens_capacity)?,
+		H_vec: read_points(h_bytes, party_capacity, gens_capacity)?,
+	})
+}
+
+/// Encodes a Groth16 verifying key as `[version, kind, arkworks bytes]`.
+pub fn get_groth16_vk_bytes<E: PairingEngine>(vk: &VerifyingKey<E>) -> Result<Vec<u8>, KeyError> {
+	let mut bytes = header(KeyKind::Groth16);
+	vk.serialize(&mut bytes).map_err(|_| KeyError::Malformed)?;
+	if bytes.len() > MAX_GROTH16_KEY_LEN {
+		return Err(KeyError::TooLarge);
+	}
+	Ok(bytes)
+}
+
+/// Decodes a Groth16 verifying key encoded with [`get_groth16_vk_bytes`].
+pub fn from_bytes_to_groth16_vk<E: PairingEngine>(input: &[u8]) -> Result<VerifyingKey<E>, KeyError> {
+	let mut body = strip_header(input, KeyKind::Groth16)?;
+	let vk = VerifyingKey::<E>::deserialize(&mut body).map_err(|_| KeyError::Malformed)?;
+	if !body.is_empty() {
+		return Err(KeyError::TrailingBytes);
+	}
+	Ok(vk)
+}
+
+/// Returns the number of public inputs of a Groth16 verifying key encoded
+/// with [`get_groth16_vk_bytes`], without decoding any of its points.
+///
+/// The length of the key is checked against the number of points it declares,
+/// so its size is bounded by the number of inputs.
+pub fn groth16_vk_input_count<E: PairingEngine>(input: &[u8]) -> Result<usize, KeyError> {
+	let body = strip_header(input, KeyKind::Groth16)?;
+	let g1_len = E::G1Affine::zero().serialized_size();
+	let g2_len = E::G2Affine::zero().serialized_size();
+	// `alpha_g1`, `beta_g2`, `gamma_g2` and `delta_g2` precede the length of
+	// `gamma_abc_g1`, which arkworks encodes as a little-endian u64
+	let offset = g1_len + 3 * g2_len;
+	if body.len() < offset + 8 {
+		return Err(KeyError::UnexpectedEnd);
+	}
+	let mut len_bytes = [0u8; 8];
+	len_bytes.copy_from_slice(&body[offset..offset + 8]);
+	let points = u64::from_le_bytes(len_bytes);
+	if points > (MAX_GROTH16_KEY_LEN / g1_len) as u64 {
+		return Err(KeyError::TooLarge);
+	}
+	let points = points as usize;
+	let expected_len = offset + 8 + points * g1_len;
+	if body.len() < expected_len {
+		return Err(KeyError::UnexpectedEnd);
+	}
+	if body.len() > expected_len {
+		return Err(KeyError::TrailingBytes);
+	}
+	points.checked_sub(1).ok_or(KeyError::Malformed)
+}
+
+/// Decodes generators stored before keys were versioned, as
+/// `[gens_capacity, party_capacity, G len, G vecs, H len, H vecs]`, where
+/// every length is a `u32` BE and each vec of points is prefixed with its
+/// byte length. Only used to migrate keys stored in that format.
+pub fn from_legacy_bytes_to_bp_gens(mut input: &[u8]) -> Result<BulletproofGens, KeyError> {
+	let gens_capacity = read_u32(&mut input)? as usize;
+	let party_capacity = read_u32(&mut input)? as usize;
+	gens_capacity
+		.checked_mul(party_capacity)
+		.filter(|n| *n <= MAX_BP_GENERATORS)
+		.ok_or(KeyError::TooLarge)?;
+	let g_vec = read_legacy_vecs(&mut input, party_capacity, gens_capacity)?;
+	let h_vec = read_legacy_vecs(&mut input, party_capacity, gens_capacity)?;
+	if !input.is_empty() {
+		return Err(KeyError::TrailingBytes);
+	}
+	Ok(BulletproofGens {
+		gens_capacity,
+		party_capacity,
+		G_vec: g_vec,
+		H_vec: h_vec,
+	})
+}
+
+fn read_legacy_vecs(input: &mut &[u8], parties: usize, gens: usize) -> Result<Vec<Vec<RistrettoPoint>>, KeyError> {
+	let vec_len = gens * POINT_LEN;
+	let len = read_u32(input)? as usize;
+	if len != parties * (4 + vec_len) {
+		return Err(KeyError::Malformed);
+	}
+	if input.len() < len {
+		return Err(KeyError::UnexpectedEnd);
+	}
+	let (mut vecs, rest) = input.split_at(len);
+	*input = rest;
+
+	let mut points = Vec::with_capacity(parties * vec_len);
+	for _ in 0..parties {
+		if read_u32(&mut vecs)? as usize != vec_len {
+			return Err(KeyError::Malformed);
+		}
+		points.extend_from_slice(&vecs[..vec_len]);
+		vecs = &vecs[vec_len..];
 	}
+	read_points(&points, parties, gens)
 }
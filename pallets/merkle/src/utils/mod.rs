@@ -2,6 +2,8 @@
 
 //! Utility functions and data type definitions
 
+pub mod host;
 pub mod keys;
 pub mod permissions;
+pub mod prepared;
 pub mod setup;
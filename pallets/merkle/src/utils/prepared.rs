@@ -0,0 +1,148 @@
+//! Verifying keys prepared for use by [`Setup`](super::setup::Setup)
+//!
+//! Decoding a verifying key is expensive: Bulletproofs generators are
+//! decompressed point by point and Groth16 keys need a pairing before they
+//! can be used. Prepared keys are kept in a small in-memory cache keyed by the
+//! key id, the hash of the key bytes and the backend, so a replaced key is
+//! never served from the cache.
+//!
+//! The cache lives as long as the runtime instance. In Wasm, every extrinsic
+//! is applied by a runtime call of its own, with fresh memory, so the cache
+//! only saves work within a call, e.g. when a batch verifies many proofs
+//! against the same key. It never changes the outcome of a call. Weights are
+//! benchmarked against a cold cache, so every call pays for preparing the
+//! keys it uses once.
+use crate::utils::{
+	keys::{from_bytes_to_bp_gens, from_bytes_to_groth16_vk, groth16_vk_input_count},
+	setup::{default_bulletproofs_poseidon_hasher, Backend, Curve, SetupError, Snark},
+};
+use alloc::sync::Arc;
+use ark_ec::PairingEngine;
+use ark_groth16::{prepare_verifying_key, verify_proof, PreparedVerifyingKey, Proof};
+use ark_serialize::CanonicalDeserialize;
+use arkworks_gadgets::prelude::{ark_bls12_381::Bls12_381, ark_bn254::Bn254};
+use bulletproofs::BulletproofGens;
+use bulletproofs_gadgets::poseidon::builder::Poseidon;
+use codec::Encode;
+use lazy_static::lazy_static;
+use sp_io::hashing::blake2_256;
+use sp_std::prelude::*;
+use spin::Mutex;
+
+/// Maximum number of prepared keys kept in memory
+pub const MAX_CACHED_KEYS: usize = 4;
+
+/// Identifier of a prepared key in the cache
+pub type CacheKey = [u8; 32];
+
+/// Verifying key in the form consumed by the proving backends
+pub enum PreparedKey {
+	/// Poseidon hasher built over the decoded `BulletproofGens`
+	Bulletproofs(Poseidon),
+	/// Prepared Groth16 key over BLS12-381
+	Groth16Bls381(PreparedVerifyingKey<Bls12_381>),
+	/// Prepared Groth16 key over BN254
+	Groth16Bn254(PreparedVerifyingKey<Bn254>),
+}
+
+impl PreparedKey {
+	/// Decodes `key` and prepares it for `backend`.
+	pub fn prepare(backend: &Backend, key: &[u8]) -> Result<Self, SetupError> {
+		match backend {
+			Backend::Bulletproofs(Curve::Curve25519) => {
+				let bp_gens = from_bytes_to_bp_gens(key)?;
+				Ok(PreparedKey::Bulletproofs(default_bulletproofs_poseidon_hasher(bp_gens)))
+			}
+			Backend::Arkworks(Curve::Bls381, Snark::Groth16) => {
+				let vk = from_bytes_to_groth16_vk::<Bls12_381>(key)?;
+				Ok(PreparedKey::Groth16Bls381(prepare_verifying_key(&vk)))
+			}
+			Backend::Arkworks(Curve::Bn254, Snark::Groth16) => {
+				let vk = from_bytes_to_groth16_vk::<Bn254>(key)?;
+				Ok(PreparedKey::Groth16Bn254(prepare_verifying_key(&vk)))
+			}
+			_ => Err(SetupError::Unimplemented),
+		}
+	}
+
+	/// Returns the number of public inputs of the Groth16 `key` for `backend`
+	/// without preparing it, so callers can check and charge for the key
+	/// first.
+	pub fn groth16_input_count(backend: &Backend, key: &[u8]) -> Result<usize, SetupError> {
+		match backend {
+			Backend::Arkworks(Curve::Bls381, Snark::Groth16) => Ok(groth16_vk_input_count::<Bls12_381>(key)?),
+			Backend::Arkworks(Curve::Bn254, Snark::Groth16) => Ok(groth16_vk_input_count::<Bn254>(key)?),
+			_ => Err(SetupError::Unimplemented),
+		}
+	}
+
+	/// Verifies a Groth16 `proof` of the circuit of this key against
+	/// `public_inputs`, each a canonical field element serialized in 32
+	/// little-endian bytes.
+	pub fn verify_groth16(&self, proof: &[u8], public_inputs: &[[u8; 32]]) -> Result<(), SetupError> {
+		match self {
+			PreparedKey::Groth16Bls381(pvk) => verify_groth16_with(pvk, proof, public_inputs),
+			PreparedKey::Groth16Bn254(pvk) => verify_groth16_with(pvk, proof, public_inputs),
+			PreparedKey::Bulletproofs(_) => Err(SetupError::InvalidVerifierKey),
+		}
+	}
+}
+
+fn verify_groth16_with<E: PairingEngine>(
+	pvk: &PreparedVerifyingKey<E>,
+	proof: &[u8],
+	public_inputs: &[[u8; 32]],
+) -> Result<(), SetupError> {
+	let proof = Proof::<E>::deserialize(proof).map_err(|_| SetupError::InvalidZkProof)?;
+	let public_inputs = public_inputs
+		.iter()
+		.map(|input| E::Fr::deserialize(&input[..]).map_err(|_| SetupError::InvalidPublicInputs))
+		.collect::<Result<Vec<_>, _>>()?;
+	// The key has one more input element than the circuit has public inputs
+	if public_inputs.len() + 1 != pvk.vk.gamma_abc_g1.len() {
+		return Err(SetupError::InvalidPublicInputs);
+	}
+	match verify_proof(pvk, &proof, &public_inputs) {
+		Ok(true) => Ok(()),
+		_ => Err(SetupError::ZkVerificationFailed),
+	}
+}
+
+lazy_static! {
+	/// Key for Bulletproofs Poseidon hashing without a tree's verifying key.
+	/// Hashing only depends on the Poseidon parameters and not on the
+	/// generators, so minimal generators are used.
+	pub static ref BULLETPROOFS_HASHER: PreparedKey =
+		PreparedKey::Bulletproofs(default_bulletproofs_poseidon_hasher(BulletproofGens::new(1, 1)));
+	static ref CACHE: Mutex<Vec<(CacheKey, Arc<PreparedKey>)>> = Mutex::new(Vec::new());
+}
+
+/// Cache key for the key stored under `key_id` with hash `key_hash`, prepared
+/// for `backend`.
+pub fn cache_key(key_id: &[u8], key_hash: &[u8; 32], backend: &Backend) -> CacheKey {
+	(key_id, key_hash, backend).using_encoded(blake2_256)
+}
+
+/// Returns the prepared key cached under `key`, if any.
+pub fn get(key: &CacheKey) -> Option<Arc<PreparedKey>> {
+	CACHE
+		.lock()
+		.iter()
+		.find(|(k, _)| k == key)
+		.map(|(_, prepared)| prepared.clone())
+}
+
+/// Caches `prepared` under `key`, evicting the oldest entry when full.
+pub fn insert(key: CacheKey, prepared: Arc<PreparedKey>) {
+	let mut cache = CACHE.lock();
+	cache.retain(|(k, _)| *k != key);
+	if cache.len() >= MAX_CACHED_KEYS {
+		cache.remove(0);
+	}
+	cache.push((key, prepared));
+}
+
+/// Drops every cached key.
+pub fn clear() {
+	CACHE.lock().clear();
+}
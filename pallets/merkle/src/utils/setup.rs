@@ -1,10 +1,15 @@
 use arkworks_gadgets::mimc::MiMCParameters;
+#[cfg(feature = "host-functions")]
+use crate::utils::host;
 use crate::{
-	utils::keys::{from_bytes_to_bp_gens, slice_to_bytes_32, ScalarBytes},
+	utils::{
+		keys::{slice_to_bytes_32, KeyError, ScalarBytes},
+		prepared::{PreparedKey, BULLETPROOFS_HASHER},
+	},
 	Config, Error,
 };
 use ark_crypto_primitives::CRH as CRHTrait;
-use ark_groth16::{Proof, VerifyingKey};
+use ark_groth16::{prepare_inputs, verify_proof, PreparedVerifyingKey, Proof};
 use ark_serialize::CanonicalDeserialize;
 use arkworks_gadgets::{
 	merkle_tree::gen_empty_hashes,
@@ -16,7 +21,7 @@ use arkworks_gadgets::{
 	},
 	setup::{
 		common::{
-			setup_params_x17_3, setup_params_x5_3, setup_mimc_220, verify_groth16, Curve as CurveEnum, PoseidonCRH_x17_3,
+			setup_params_x17_3, setup_params_x5_3, setup_mimc_220, Curve as CurveEnum, PoseidonCRH_x17_3,
 			PoseidonCRH_x5_3, TreeConfig_x17, TreeConfig_x5, MiMCCRH_220, MiMCTreeConfig_220,
 		},
 		mixer::get_public_inputs,
@@ -39,13 +44,16 @@ use bulletproofs_gadgets::{
 };
 use codec::{Decode, Encode};
 use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
-use frame_support::traits::Randomness;
 use lazy_static::lazy_static;
 use merlin::Transcript;
 use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_io::hashing::blake2_256;
+use sp_runtime_interface::pass_by::PassByCodec;
 use sp_std::prelude::*;
-use ark_ff::PrimeField;
-use ark_ec::PairingEngine;
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{Field, PrimeField, Zero};
 
 lazy_static! {
 	static ref BLS381_X5_POSEIDON: PoseidonParameters<Bls381> =
@@ -60,17 +68,19 @@ lazy_static! {
 		setup_mimc_220::<Bn254Fr>(CurveEnum::Bn254);
 }
 
+/// Width of the default Bulletproofs Poseidon hasher
+const BULLETPROOFS_POSEIDON_WIDTH: usize = 6;
+
 /// Default hasher instance used to construct the tree
 pub fn default_bulletproofs_poseidon_hasher(bp_gens: BulletproofGens) -> Poseidon {
-	let width = 6;
-	PoseidonBuilder::new(width)
+	PoseidonBuilder::new(BULLETPROOFS_POSEIDON_WIDTH)
 		.bulletproof_gens(bp_gens)
 		.sbox(PoseidonSbox::Exponentiation3)
 		.build()
 }
 
 /// Hash functions for MerkleTree
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 #[derive(Clone, Encode, Decode, PartialEq)]
 pub enum HashFunction {
 	PoseidonDefault,
@@ -82,7 +92,7 @@ pub enum HashFunction {
 }
 
 /// Different curve types
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 #[derive(Clone, Encode, Decode, PartialEq)]
 pub enum Curve {
 	Bls381,
@@ -91,43 +101,258 @@ pub enum Curve {
 }
 
 /// Different curve types
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 #[derive(Clone, Encode, Decode, PartialEq)]
 pub enum Snark {
 	Groth16,
 	Marlin,
 }
 
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 #[derive(Clone, Encode, Decode, PartialEq)]
 pub enum Backend {
 	Arkworks(Curve, Snark),
 	Bulletproofs(Curve),
 }
 
-#[cfg_attr(feature = "std", derive(Debug))]
-#[derive(Clone, Encode, Decode, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[derive(Clone, Encode, Decode, PartialEq, PassByCodec)]
 pub struct Setup {
 	pub hasher: HashFunction,
 	pub backend: Backend,
 }
 
+/// Errors returned by the hashing and verification primitives of [`Setup`].
+///
+/// Unlike the pallet `Error`, these don't depend on the runtime, so they can
+/// be returned by the merkle host functions.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq)]
+pub enum SetupError {
+	/// Setup is not supported
+	Unimplemented,
+	/// Failed to hash the inputs
+	HashingFailed,
+	/// Verifying key is missing, malformed or doesn't match the backend
+	InvalidVerifierKey,
+	/// Invalid private inputs
+	InvalidPrivateInputs,
+	/// Invalid public inputs
+	InvalidPublicInputs,
+	/// Proof doesn't satisfy the constraint system
+	ConstraintSystemUnsatisfied,
+	/// Proof could not be decoded
+	InvalidZkProof,
+	/// Proof verification failed
+	ZkVerificationFailed,
+}
+
+impl<T: Config> From<SetupError> for Error<T> {
+	fn from(err: SetupError) -> Self {
+		match err {
+			SetupError::Unimplemented => Error::<T>::Unimplemented,
+			SetupError::HashingFailed => Error::<T>::HashingFailed,
+			SetupError::InvalidVerifierKey => Error::<T>::InvalidVerifierKey,
+			SetupError::InvalidPrivateInputs => Error::<T>::InvalidPrivateInputs,
+			SetupError::InvalidPublicInputs => Error::<T>::InvalidPublicInputs,
+			SetupError::ConstraintSystemUnsatisfied => Error::<T>::ConstraintSystemUnsatisfied,
+			SetupError::InvalidZkProof => Error::<T>::InvalidZkProof,
+			SetupError::ZkVerificationFailed => Error::<T>::ZkVerificationFailed,
+		}
+	}
+}
+
+impl From<KeyError> for SetupError {
+	fn from(_: KeyError) -> Self {
+		SetupError::InvalidVerifierKey
+	}
+}
+
+/// Format of the leaves of a tree.
+///
+/// Leaves are commitments computed off chain, and the circuits proving
+/// membership live in external crates. The format only decides which public
+/// inputs proofs are verified against, so what a leaf commits to is up to
+/// the circuit the verifying key of the tree was generated for.
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq)]
+pub enum LeafFormat {
+	/// Proofs take no public inputs about the deposit, e.g. for leaves
+	/// `H(secret, nullifier)`. A tree of opaque leaves must only be used by a
+	/// single mixer.
+	Opaque,
+	/// Proofs also take the asset id, amount and chain id of the deposit as
+	/// public inputs, e.g. for leaves `H(secret, nullifier, asset_id, amount,
+	/// chain_id)`. Only Groth16 trees can have bound leaves.
+	Bound,
+}
+
+impl Default for LeafFormat {
+	fn default() -> Self {
+		LeafFormat::Opaque
+	}
+}
+
+/// Asset, amount and chain id a [`LeafFormat::Bound`] leaf commits to
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Encode, Decode, PartialEq, Eq)]
+pub struct LeafBinding {
+	/// Id of the deposited asset
+	pub asset_id: ScalarBytes,
+	/// Deposited amount
+	pub amount: ScalarBytes,
+	/// Id of the chain of the deposit
+	pub chain_id: ScalarBytes,
+}
+
+/// Inputs of a zero-knowledge membership proof
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Encode, Decode, PartialEq, PassByCodec)]
+pub struct ZkInputs {
+	/// Depth of the tree
+	pub depth: u32,
+	/// Root the membership is proven against
+	pub root: ScalarBytes,
+	/// Commitments to the private inputs (Bulletproofs only)
+	pub private_inputs: Vec<ScalarBytes>,
+	/// Hash of the nullifier
+	pub nullifier_hash: ScalarBytes,
+	/// Serialized proof
+	pub proof: Vec<u8>,
+	/// Commitments to the path indices (Bulletproofs only)
+	pub path_indices: Vec<ScalarBytes>,
+	/// Commitments to the path nodes (Bulletproofs only)
+	pub path_nodes: Vec<ScalarBytes>,
+	/// Recipient bound to the proof
+	pub recipient: ScalarBytes,
+	/// Relayer bound to the proof
+	pub relayer: ScalarBytes,
+	/// Asset, amount and chain id bound to the leaf, for bound leaves
+	pub binding: Option<LeafBinding>,
+}
+
+/// Returns the rng drawing the random scalars of the Bulletproofs verifier.
+///
+/// The rng is seeded by a Fiat-Shamir transcript of the proof and everything
+/// it is verified against, so verification is deterministic and the scalars
+/// are only known once the proof is fixed.
+pub fn bulletproofs_verifier_rng(
+	commitments: &[CompressedRistretto],
+	public_inputs: &[Scalar],
+	proof_bytes: &[u8],
+) -> ChaChaRng {
+	let mut transcript = Transcript::new(b"zk_membership_proof_verifier_rng");
+	for commitment in commitments {
+		transcript.append_message(b"commitment", commitment.as_bytes());
+	}
+	for input in public_inputs {
+		transcript.append_message(b"public_input", input.as_bytes());
+	}
+	transcript.append_message(b"proof", proof_bytes);
+	let mut seed = [0u8; 32];
+	transcript.challenge_bytes(b"rng_seed", &mut seed);
+	ChaChaRng::from_seed(seed)
+}
+
 impl Setup {
 	pub fn new(hasher: HashFunction, backend: Backend) -> Self {
 		Self { hasher, backend }
 	}
 
+	/// Whether hashing with this setup needs the tree's verifying key.
+	pub fn hashes_with_key(&self) -> bool {
+		matches!(self.backend, Backend::Bulletproofs(_))
+	}
+
+	/// Whether proofs of this setup are cheaper to verify as a batch than one
+	/// by one.
+	pub fn batches_proofs(&self) -> bool {
+		matches!(self.backend, Backend::Arkworks(_, Snark::Groth16))
+	}
+
+	/// Hashes `xl` and `xr`, decoding the key material from `params`.
+	///
+	/// Prefer [`Setup::hash_with_key`] when hashing more than once with the
+	/// same key.
 	pub fn hash<T: Config>(&self, xl: &ScalarBytes, xr: &ScalarBytes, params: &[u8]) -> Result<ScalarBytes, Error<T>> {
+		if let Some(res) = self.hash_on_host(xl, xr) {
+			return res.map_err(Into::into);
+		}
+		let key = if self.hashes_with_key() {
+			Some(PreparedKey::prepare(&self.backend, params)?)
+		} else {
+			None
+		};
+		Ok(self.compute_hash(xl, xr, key.as_ref())?)
+	}
+
+	/// Hashes `xl` and `xr` with an already prepared key. The key is only
+	/// required when [`Setup::hashes_with_key`] is true.
+	///
+	/// Hashing runs on the host when the node provides the merkle host
+	/// functions and in the runtime otherwise.
+	pub fn hash_with_key<T: Config>(
+		&self,
+		xl: &ScalarBytes,
+		xr: &ScalarBytes,
+		key: Option<&PreparedKey>,
+	) -> Result<ScalarBytes, Error<T>> {
+		if let Some(res) = self.hash_on_host(xl, xr) {
+			return res.map_err(Into::into);
+		}
+		Ok(self.compute_hash(xl, xr, key)?)
+	}
+
+	/// Hashes `xl` and `xr` without the key of a tree, e.g. for callers
+	/// outside of the pallet.
+	///
+	/// Bulletproofs Poseidon hashes don't depend on the generators of the
+	/// key, so they are computed with [`BULLETPROOFS_HASHER`].
+	pub fn hash_bytes(&self, xl: &[u8], xr: &[u8]) -> Result<ScalarBytes, SetupError> {
+		if let Some(res) = self.hash_on_host(xl, xr) {
+			return res;
+		}
+		let key = if self.hashes_with_key() {
+			Some(&*BULLETPROOFS_HASHER)
+		} else {
+			None
+		};
+		self.compute_hash(xl, xr, key)
+	}
+
+	/// Hashes `xl` and `xr` with the merkle host functions, if the runtime is
+	/// built to use them and the host supports this setup.
+	fn hash_on_host(&self, _xl: &[u8], _xr: &[u8]) -> Option<Result<ScalarBytes, SetupError>> {
+		#[cfg(feature = "host-functions")]
+		{
+			match host::merkle_crypto::hash(self.clone(), _xl, _xr) {
+				Err(SetupError::Unimplemented) => None,
+				res => Some(res),
+			}
+		}
+		#[cfg(not(feature = "host-functions"))]
+		{
+			None
+		}
+	}
+
+	/// Hashes `xl` and `xr` in the current execution environment.
+	///
+	/// This is the implementation shared by the runtime and the host
+	/// functions, so both always agree on the result.
+	pub fn compute_hash(&self, xl: &[u8], xr: &[u8], key: Option<&PreparedKey>) -> Result<ScalarBytes, SetupError> {
 		match &self.backend {
 			Backend::Bulletproofs(Curve::Curve25519) => match self.hasher {
 				HashFunction::PoseidonDefault | HashFunction::Poseidon(6, 3) => {
-					let bp_gens = from_bytes_to_bp_gens(params);
-					let hasher = default_bulletproofs_poseidon_hasher(bp_gens);
+					let hasher = match key {
+						Some(PreparedKey::Bulletproofs(hasher)) => hasher,
+						_ => return Err(SetupError::InvalidVerifierKey),
+					};
 					let sl = Scalar::from_bytes_mod_order(slice_to_bytes_32(xl));
 					let sr = Scalar::from_bytes_mod_order(slice_to_bytes_32(xr));
-					Ok(Poseidon_hash_2(sl, sr, &hasher).to_bytes().to_vec())
+					Ok(Poseidon_hash_2(sl, sr, hasher).to_bytes().to_vec())
 				}
-				_ => Err(Error::<T>::Unimplemented),
+				_ => Err(SetupError::Unimplemented),
 			},
 			Backend::Arkworks(curve, _) => {
 				let mut bytes = Vec::new();
@@ -135,84 +360,52 @@ impl Setup {
 				bytes.extend(xr);
 
 				match self.hasher {
-					HashFunction::PoseidonDefault | HashFunction::Poseidon(3, 5) => {
-						match curve {
-							Curve::Bls381 => {
-								let res = PoseidonCRH_x5_3::<Bls381>::evaluate(&BLS381_X5_POSEIDON, &bytes).unwrap();
-								let bytes_res = to_bytes![res];
-								let bytes = match bytes_res {
-									Ok(bytes) => bytes,
-									Err(_) => return Err(Error::<T>::HashingFailed),
-								};
-								Ok(bytes)
-							}
-							Curve::Bn254 => {
-								let res = PoseidonCRH_x5_3::<Bn254Fr>::evaluate(&BN254_X5_POSEIDON, &bytes).unwrap();
-								let bytes_res = to_bytes![res];
-								let bytes = match bytes_res {
-									Ok(bytes) => bytes,
-									Err(_) => return Err(Error::<T>::HashingFailed),
-								};
-								Ok(bytes)
-							}
-							_ => Err(Error::<T>::Unimplemented),
+					HashFunction::PoseidonDefault | HashFunction::Poseidon(3, 5) => match curve {
+						Curve::Bls381 => {
+							let res = PoseidonCRH_x5_3::<Bls381>::evaluate(&BLS381_X5_POSEIDON, &bytes)
+								.map_err(|_| SetupError::HashingFailed)?;
+							to_bytes![res].map_err(|_| SetupError::HashingFailed)
 						}
-					}
-					HashFunction::Poseidon(3, 17) => {
-						match curve {
-							Curve::Bls381 => {
-								let res = PoseidonCRH_x17_3::<Bls381>::evaluate(&BLS381_X17_POSEIDON, &bytes).unwrap();
-								let bytes_res = to_bytes![res];
-								let bytes = match bytes_res {
-									Ok(bytes) => bytes,
-									Err(_) => return Err(Error::<T>::HashingFailed),
-								};
-								Ok(bytes)
-							}
-							Curve::Bn254 => {
-								let res = PoseidonCRH_x17_3::<Bn254Fr>::evaluate(&BN254_X17_POSEIDON, &bytes).unwrap();
-								let bytes_res = to_bytes![res];
-								let bytes = match bytes_res {
-									Ok(bytes) => bytes,
-									Err(_) => return Err(Error::<T>::HashingFailed),
-								};
-								Ok(bytes)
-							}
-							_ => Err(Error::<T>::Unimplemented),
+						Curve::Bn254 => {
+							let res = PoseidonCRH_x5_3::<Bn254Fr>::evaluate(&BN254_X5_POSEIDON, &bytes)
+								.map_err(|_| SetupError::HashingFailed)?;
+							to_bytes![res].map_err(|_| SetupError::HashingFailed)
 						}
-					}
-					HashFunction::MiMC => {
-						match curve {
-							Curve::Bn254 => {
-								let res = MiMCCRH_220::<Bn254Fr>::evaluate(&BN254_MIMC_220, &bytes).unwrap();
-								let bytes_res = to_bytes![res];
-								let bytes = match bytes_res {
-									Ok(bytes) => bytes,
-									Err(_) => return Err(Error::<T>::HashingFailed),
-								};
-								Ok(bytes)
-							}
-							_ => Err(Error::<T>::Unimplemented),
+						_ => Err(SetupError::Unimplemented),
+					},
+					HashFunction::Poseidon(3, 17) => match curve {
+						Curve::Bls381 => {
+							let res = PoseidonCRH_x17_3::<Bls381>::evaluate(&BLS381_X17_POSEIDON, &bytes)
+								.map_err(|_| SetupError::HashingFailed)?;
+							to_bytes![res].map_err(|_| SetupError::HashingFailed)
 						}
-					}
-					_ => Err(Error::<T>::Unimplemented),
+						Curve::Bn254 => {
+							let res = PoseidonCRH_x17_3::<Bn254Fr>::evaluate(&BN254_X17_POSEIDON, &bytes)
+								.map_err(|_| SetupError::HashingFailed)?;
+							to_bytes![res].map_err(|_| SetupError::HashingFailed)
+						}
+						_ => Err(SetupError::Unimplemented),
+					},
+					HashFunction::MiMC => match curve {
+						Curve::Bn254 => {
+							let res = MiMCCRH_220::<Bn254Fr>::evaluate(&BN254_MIMC_220, &bytes)
+								.map_err(|_| SetupError::HashingFailed)?;
+							to_bytes![res].map_err(|_| SetupError::HashingFailed)
+						}
+						_ => Err(SetupError::Unimplemented),
+					},
+					_ => Err(SetupError::Unimplemented),
 				}
 			}
-			_ => Err(Error::<T>::Unimplemented),
+			_ => Err(SetupError::Unimplemented),
 		}
 	}
 
-	pub fn generate_zero_tree<T: Config>(
-		&self,
-		depth: usize,
-		params: &[u8],
-	) -> Result<(Vec<ScalarBytes>, ScalarBytes), Error<T>> {
+	pub fn generate_zero_tree<T: Config>(&self, depth: usize) -> Result<(Vec<ScalarBytes>, ScalarBytes), Error<T>> {
 		match self.backend {
 			Backend::Bulletproofs(Curve::Curve25519) => match self.hasher {
 				HashFunction::PoseidonDefault => {
-					let bp_gens = from_bytes_to_bp_gens(params);
-					let hasher = default_bulletproofs_poseidon_hasher(bp_gens);
-					let zero_tree = gen_zero_tree(hasher.width, &hasher.sbox);
+					let zero_tree = gen_zero_tree(BULLETPROOFS_POSEIDON_WIDTH, &PoseidonSbox::Exponentiation3);
 					Ok((
 						zero_tree[0..depth].iter().map(|x| x.to_vec()).collect(),
 						zero_tree[depth].to_vec(),
@@ -277,141 +470,236 @@ impl Setup {
 		}
 	}
 
-	pub fn verify_zk<T: Config>(
-		&self,
-		depth: usize,
-		root_bytes: ScalarBytes,
-		private_inputs_bytes: Vec<ScalarBytes>,
-		nullifier_hash_bytes: ScalarBytes,
-		proof_bytes: Vec<u8>,
-		verifier_key: Option<Vec<u8>>,
-		path_indices_bytes: Vec<ScalarBytes>,
-		path_nodes_bytes: Vec<ScalarBytes>,
-		recipient_bytes: ScalarBytes,
-		relayer_bytes: ScalarBytes,
-	) -> Result<(), Error<T>> {
-		match self.backend {
-			Backend::Bulletproofs(Curve::Curve25519) => {
-				if verifier_key.is_none() {
-					return Err(Error::<T>::InvalidVerifierKey);
-				}
+	/// Verifies a membership proof against `key`, the prepared form of the
+	/// tree's verifying key.
+	pub fn verify_zk<T: Config>(&self, inputs: &ZkInputs, key: &PreparedKey) -> Result<(), Error<T>> {
+		Ok(self.check_proof(inputs, key)?)
+	}
 
-				let bp_gens = from_bytes_to_bp_gens(&verifier_key.unwrap());
-				let hasher = default_bulletproofs_poseidon_hasher(bp_gens);
-				let root = Scalar::from_bytes_mod_order(slice_to_bytes_32(&root_bytes));
-				let private_inputs = private_inputs_bytes
+	/// Verifies a membership proof with the merkle host functions, if the
+	/// runtime is built to use them and the host supports this setup. `key` is
+	/// the encoded verifying key.
+	pub fn verify_zk_on_host(&self, _key: &[u8], _inputs: &ZkInputs) -> Option<Result<(), SetupError>> {
+		#[cfg(feature = "host-functions")]
+		{
+			match host::merkle_crypto::verify_zk(self.clone(), _key, _inputs.clone()) {
+				Err(SetupError::Unimplemented) => None,
+				res => Some(res),
+			}
+		}
+		#[cfg(not(feature = "host-functions"))]
+		{
+			None
+		}
+	}
+
+	/// Verifies a batch of membership proofs against the same key, succeeding
+	/// only if every proof in the batch is valid.
+	///
+	/// Groth16 proofs are checked together with a random linear combination,
+	/// which needs a single final exponentiation for the whole batch. The
+	/// Bulletproofs R1CS verifier has no batch mode, so those proofs are
+	/// checked one by one.
+	pub fn check_proof_batch(&self, inputs: &[ZkInputs], key: &PreparedKey) -> Result<(), SetupError> {
+		if let [single] = inputs {
+			return self.check_proof(single, key);
+		}
+		match (&self.backend, key) {
+			(Backend::Arkworks(Curve::Bls381, Snark::Groth16), PreparedKey::Groth16Bls381(pvk)) => {
+				self.groth_batch_verify_helper::<Bls12_381>(inputs, pvk)
+			}
+			(Backend::Arkworks(Curve::Bn254, Snark::Groth16), PreparedKey::Groth16Bn254(pvk)) => {
+				self.groth_batch_verify_helper::<Bn254>(inputs, pvk)
+			}
+			_ => inputs.iter().try_for_each(|inputs| self.check_proof(inputs, key)),
+		}
+	}
+
+	/// Verifies a batch of membership proofs with the merkle host functions,
+	/// if the runtime is built to use them and the host supports this setup.
+	pub fn verify_zk_batch_on_host(&self, _key: &[u8], _inputs: &[ZkInputs]) -> Option<Result<(), SetupError>> {
+		#[cfg(feature = "host-functions")]
+		{
+			match host::merkle_crypto::verify_zk_batch(self.clone(), _key, _inputs.to_vec()) {
+				Err(SetupError::Unimplemented) => None,
+				res => Some(res),
+			}
+		}
+		#[cfg(not(feature = "host-functions"))]
+		{
+			None
+		}
+	}
+
+	/// Verifies a batch of membership proofs against `key`, the prepared form
+	/// of the verifying key shared by their trees.
+	pub fn verify_zk_batch<T: Config>(&self, inputs: &[ZkInputs], key: &PreparedKey) -> Result<(), Error<T>> {
+		Ok(self.check_proof_batch(inputs, key)?)
+	}
+
+	/// Verifies a membership proof in the current execution environment.
+	///
+	/// This is the implementation shared by the runtime and the host
+	/// functions, so both always agree on the result.
+	pub fn check_proof(&self, inputs: &ZkInputs, key: &PreparedKey) -> Result<(), SetupError> {
+		match (&self.backend, key) {
+			(Backend::Bulletproofs(Curve::Curve25519), PreparedKey::Bulletproofs(hasher)) => {
+				// The Bulletproofs membership gadget has no circuit for bound leaves
+				if inputs.binding.is_some() {
+					return Err(SetupError::Unimplemented);
+				}
+				let root = Scalar::from_bytes_mod_order(slice_to_bytes_32(&inputs.root));
+				let private_inputs = inputs
+					.private_inputs
 					.iter()
 					.map(|x| CompressedRistretto::from_slice(x))
 					.collect();
-				let nullifier_hash = Scalar::from_bytes_mod_order(slice_to_bytes_32(&nullifier_hash_bytes));
-				let path_indices = path_indices_bytes
+				let nullifier_hash = Scalar::from_bytes_mod_order(slice_to_bytes_32(&inputs.nullifier_hash));
+				let path_indices = inputs
+					.path_indices
 					.iter()
 					.map(|x| CompressedRistretto::from_slice(x))
 					.collect();
-				let path_nodes = path_nodes_bytes
+				let path_nodes = inputs
+					.path_nodes
 					.iter()
 					.map(|x| CompressedRistretto::from_slice(x))
 					.collect();
-				let recipient = Scalar::from_bytes_mod_order(slice_to_bytes_32(&recipient_bytes));
-				let relayer = Scalar::from_bytes_mod_order(slice_to_bytes_32(&relayer_bytes));
+				let recipient = Scalar::from_bytes_mod_order(slice_to_bytes_32(&inputs.recipient));
+				let relayer = Scalar::from_bytes_mod_order(slice_to_bytes_32(&inputs.relayer));
 				self.verify_bulletproofs_poseidon(
-					depth,
+					inputs.depth as usize,
 					root,
 					private_inputs,
 					nullifier_hash,
-					proof_bytes,
+					&inputs.proof,
 					path_indices,
 					path_nodes,
 					recipient,
 					relayer,
-					&hasher,
+					hasher,
 				)
 			}
-			Backend::Arkworks(Curve::Bls381, Snark::Groth16) => {
-				self.groth_verify_helper::<_, Bls12_381>(
-					root_bytes,
-					nullifier_hash_bytes,
-					proof_bytes,
-					verifier_key,
-					recipient_bytes,
-					relayer_bytes
-				)
+			(Backend::Arkworks(Curve::Bls381, Snark::Groth16), PreparedKey::Groth16Bls381(pvk)) => {
+				self.groth_verify_helper::<Bls12_381>(inputs, pvk)
 			}
-			Backend::Arkworks(Curve::Bn254, Snark::Groth16) => {
-				self.groth_verify_helper::<_, Bn254>(
-					root_bytes,
-					nullifier_hash_bytes,
-					proof_bytes,
-					verifier_key,
-					recipient_bytes,
-					relayer_bytes
-				)
+			(Backend::Arkworks(Curve::Bn254, Snark::Groth16), PreparedKey::Groth16Bn254(pvk)) => {
+				self.groth_verify_helper::<Bn254>(inputs, pvk)
 			}
-			_ => return Err(Error::<T>::Unimplemented),
+			(Backend::Bulletproofs(Curve::Curve25519), _)
+			| (Backend::Arkworks(Curve::Bls381, Snark::Groth16), _)
+			| (Backend::Arkworks(Curve::Bn254, Snark::Groth16), _) => Err(SetupError::InvalidVerifierKey),
+			_ => Err(SetupError::Unimplemented),
 		}
 	}
 
-	pub fn groth_verify_helper<T: Config, E: PairingEngine>(
-		&self,
-		root_bytes: ScalarBytes,
-		nullifier_hash_bytes: ScalarBytes,
-		proof_bytes: Vec<u8>,
-		verifier_key: Option<Vec<u8>>,
-		recipient_bytes: ScalarBytes,
-		relayer_bytes: ScalarBytes,
-	) -> Result<(), Error<T>> {
+	/// Public inputs of a Groth16 membership proof. The binding of bound
+	/// leaves follows the inputs of the opaque leaf circuit.
+	fn groth_public_inputs<E: PairingEngine>(inputs: &ZkInputs) -> Result<Vec<E::Fr>, SetupError> {
 		let nullifier_elts =
-			to_field_elements::<E::Fr>(&nullifier_hash_bytes).map_err(|_| Error::<T>::InvalidPublicInputs)?;
-		let root_elts =
-			to_field_elements::<E::Fr>(&root_bytes).map_err(|_| Error::<T>::InvalidPublicInputs)?;
+			to_field_elements::<E::Fr>(&inputs.nullifier_hash).map_err(|_| SetupError::InvalidPublicInputs)?;
+		let root_elts = to_field_elements::<E::Fr>(&inputs.root).map_err(|_| SetupError::InvalidPublicInputs)?;
 		let recipient_elts =
-			to_field_elements::<E::Fr>(&recipient_bytes).map_err(|_| Error::<T>::InvalidPublicInputs)?;
-		let relayer_elts =
-			to_field_elements::<E::Fr>(&relayer_bytes).map_err(|_| Error::<T>::InvalidPublicInputs)?;
+			to_field_elements::<E::Fr>(&inputs.recipient).map_err(|_| SetupError::InvalidPublicInputs)?;
+		let relayer_elts = to_field_elements::<E::Fr>(&inputs.relayer).map_err(|_| SetupError::InvalidPublicInputs)?;
 
-		let nullifier = nullifier_elts.get(0).ok_or(Error::<T>::InvalidPublicInputs)?;
-		let root = root_elts.get(0).ok_or(Error::<T>::InvalidPublicInputs)?;
-		let recipient = recipient_elts.get(0).ok_or(Error::<T>::InvalidPublicInputs)?;
-		let relayer = relayer_elts.get(0).ok_or(Error::<T>::InvalidPublicInputs)?;
+		let nullifier = nullifier_elts.get(0).ok_or(SetupError::InvalidPublicInputs)?;
+		let root = root_elts.get(0).ok_or(SetupError::InvalidPublicInputs)?;
+		let recipient = recipient_elts.get(0).ok_or(SetupError::InvalidPublicInputs)?;
+		let relayer = relayer_elts.get(0).ok_or(SetupError::InvalidPublicInputs)?;
 
-		if verifier_key.is_none() {
-			return Err(Error::<T>::InvalidVerifierKey);
+		let mut public_inputs = get_public_inputs::<E::Fr>(*nullifier, *root, *recipient, *relayer);
+		if let Some(binding) = &inputs.binding {
+			for input in [&binding.asset_id, &binding.amount, &binding.chain_id].iter() {
+				let elts = to_field_elements::<E::Fr>(input).map_err(|_| SetupError::InvalidPublicInputs)?;
+				public_inputs.push(*elts.get(0).ok_or(SetupError::InvalidPublicInputs)?);
+			}
 		}
+		Ok(public_inputs)
+	}
 
-		let vk = VerifyingKey::<E>::deserialize(&verifier_key.unwrap()[..])
-			.map_err(|_| Error::<T>::InvalidVerifierKey)?;
-		let public_inputs = get_public_inputs::<E::Fr>(*nullifier, *root, *recipient, *relayer);
-		let proof = Proof::<E>::deserialize(&proof_bytes[..]).map_err(|_| Error::<T>::InvalidZkProof)?;
-		let res = verify_groth16::<E>(&vk, &public_inputs, &proof);
+	pub fn groth_verify_helper<E: PairingEngine>(
+		&self,
+		inputs: &ZkInputs,
+		pvk: &PreparedVerifyingKey<E>,
+	) -> Result<(), SetupError> {
+		let public_inputs = Self::groth_public_inputs::<E>(inputs)?;
+		let proof = Proof::<E>::deserialize(&inputs.proof[..]).map_err(|_| SetupError::InvalidZkProof)?;
+		let res = verify_proof::<E>(pvk, &proof, &public_inputs).unwrap_or(false);
 		if !res {
-			return Err(Error::<T>::ZkVerificationFailed);
+			return Err(SetupError::ZkVerificationFailed);
+		}
+
+		Ok(())
+	}
+
+	/// Checks `e(A_i, B_i) = e(alpha, beta) * e(L_i, gamma) * e(C_i, delta)`
+	/// for every proof at once, by raising each equation to a random power
+	/// `r_i` and multiplying them together.
+	pub fn groth_batch_verify_helper<E: PairingEngine>(
+		&self,
+		inputs: &[ZkInputs],
+		pvk: &PreparedVerifyingKey<E>,
+	) -> Result<(), SetupError> {
+		// The coefficients depend on every proof of the batch, so they can't be
+		// known before the proofs are fixed.
+		let batch_hash = inputs.using_encoded(blake2_256);
+
+		let mut pairs = Vec::with_capacity(inputs.len() + 2);
+		let mut inputs_acc = E::G1Projective::zero();
+		let mut c_acc = E::G1Projective::zero();
+		let mut r_sum = E::Fr::zero();
+		for (i, item) in inputs.iter().enumerate() {
+			let public_inputs = Self::groth_public_inputs::<E>(item)?;
+			let proof = Proof::<E>::deserialize(&item.proof[..]).map_err(|_| SetupError::InvalidZkProof)?;
+			let prepared_inputs =
+				prepare_inputs(pvk, &public_inputs).map_err(|_| SetupError::InvalidPublicInputs)?;
+
+			let r = E::Fr::from_le_bytes_mod_order(&(batch_hash, i as u32).using_encoded(blake2_256));
+			pairs.push((proof.a.mul(r.into_repr()).into_affine().into(), proof.b.into()));
+			inputs_acc += prepared_inputs.mul(r.into_repr());
+			c_acc += proof.c.mul(r.into_repr());
+			r_sum += r;
+		}
+		pairs.push((inputs_acc.into_affine().into(), pvk.gamma_g2_neg_pc.clone()));
+		pairs.push((c_acc.into_affine().into(), pvk.delta_g2_neg_pc.clone()));
+
+		let qap = E::miller_loop(pairs.iter());
+		let test = E::final_exponentiation(&qap).ok_or(SetupError::ZkVerificationFailed)?;
+		if test != pvk.alpha_g1_beta_g2.pow(r_sum.into_repr()) {
+			return Err(SetupError::ZkVerificationFailed);
 		}
 
 		Ok(())
 	}
 
 	// TODO: move to bulletproofs-gadgets
-	pub fn verify_bulletproofs_poseidon<T: Config>(
+	pub fn verify_bulletproofs_poseidon(
 		&self,
 		depth: usize,
 		cached_root: Scalar,
 		comms: Vec<CompressedRistretto>,
 		nullifier_hash: Scalar,
-		proof_bytes: Vec<u8>,
+		proof_bytes: &[u8],
 		leaf_index_commitments: Vec<CompressedRistretto>,
 		proof_commitments: Vec<CompressedRistretto>,
 		recipient: Scalar,
 		relayer: Scalar,
 		hasher: &Poseidon,
-	) -> Result<(), Error<T>> {
+	) -> Result<(), SetupError> {
 		let pc_gens = PedersenGens::default();
 		let label = b"zk_membership_proof";
 		let mut verifier_transcript = Transcript::new(label);
 		let mut verifier = Verifier::new(&mut verifier_transcript);
 
 		if comms.len() != 3 {
-			return Err(Error::<T>::InvalidPrivateInputs);
+			return Err(SetupError::InvalidPrivateInputs);
 		}
+		let mut rng = bulletproofs_verifier_rng(
+			&[&comms[..], &leaf_index_commitments[..], &proof_commitments[..]].concat(),
+			&[cached_root, nullifier_hash, recipient, relayer],
+			proof_bytes,
+		);
 		let r_val = verifier.commit(comms[0]);
 		let r_alloc = AllocatedScalar {
 			variable: r_val,
@@ -465,24 +753,18 @@ impl Setup {
 			&hasher,
 		);
 		if !gadget_res.is_ok() {
-			return Err(Error::<T>::ConstraintSystemUnsatisfied);
+			return Err(SetupError::ConstraintSystemUnsatisfied);
 		}
 
-		let proof = R1CSProof::from_bytes(&proof_bytes);
+		let proof = R1CSProof::from_bytes(proof_bytes);
 		if !proof.is_ok() {
-			return Err(Error::<T>::InvalidZkProof);
+			return Err(SetupError::InvalidZkProof);
 		}
 		let proof = proof.unwrap();
 
-		let random_seed = T::Randomness::random_seed();
-		let random_bytes = random_seed.clone().0.encode();
-		let mut buf = [0u8; 32];
-		buf.copy_from_slice(&random_bytes);
-		let mut rng = ChaChaRng::from_seed(buf);
-
 		let verify_res = verifier.verify_with_rng(&proof, &hasher.pc_gens, &hasher.bp_gens, &mut rng);
 		if !verify_res.is_ok() {
-			return Err(Error::<T>::ZkVerificationFailed);
+			return Err(SetupError::ZkVerificationFailed);
 		}
 		Ok(())
 	}
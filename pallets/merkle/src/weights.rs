@@ -5,6 +5,11 @@
 //! EXECUTION: None, WASM-EXECUTION: Interpreted, CHAIN: Some("dev"), DB CACHE:
 //! 128
 
+// Weights of the Bulletproofs verification, key preparation and Groth16
+// benchmarks were estimated by hand until they are regenerated with the
+// command below. Preparing a Bulletproofs
+// key makes up most of the base weight of the mixer's `deposit`.
+
 // Executed Command:
 // ./target/release/node-template
 // benchmark
@@ -37,9 +42,16 @@ pub trait WeightInfo {
 	fn set_manager_required() -> Weight;
 	fn set_manager() -> Weight;
 	fn set_stopped() -> Weight;
+	fn set_leaf_format() -> Weight;
 	fn add_members(n: u32) -> Weight;
 	fn verify_path(n: u32) -> Weight;
 	fn on_finalize() -> Weight;
+	fn verify_bulletproofs() -> Weight;
+	fn prepare_bulletproofs_key() -> Weight;
+	fn prepare_groth16_bn254_key(n: u32) -> Weight;
+	fn verify_groth16_bn254(n: u32) -> Weight;
+	fn prepare_groth16_bls381_key(n: u32) -> Weight;
+	fn verify_groth16_bls381(n: u32) -> Weight;
 }
 
 /// Weight functions for pallet_merkle.
@@ -71,6 +83,12 @@ impl<T: frame_system::Config + Config> WeightInfo for Weights<T> {
 			.saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
 
+	fn set_leaf_format() -> Weight {
+		(8_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+
 	fn add_members(n: u32) -> Weight {
 		(384_629_956_000 as Weight)
 			// Standard Error: 141_117_000
@@ -91,4 +109,29 @@ impl<T: frame_system::Config + Config> WeightInfo for Weights<T> {
 			.saturating_add(T::DbWeight::get().reads(2 as Weight))
 			.saturating_add(T::DbWeight::get().writes(1 as Weight))
 	}
+
+	fn verify_bulletproofs() -> Weight {
+		(1_070_000_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(4 as Weight))
+	}
+
+	fn prepare_bulletproofs_key() -> Weight {
+		(400_000_000_000 as Weight)
+	}
+
+	fn prepare_groth16_bn254_key(n: u32) -> Weight {
+		(60_000_000_000 as Weight).saturating_add((500_000_000 as Weight).saturating_mul(n as Weight))
+	}
+
+	fn verify_groth16_bn254(n: u32) -> Weight {
+		(90_000_000_000 as Weight).saturating_add((2_500_000_000 as Weight).saturating_mul(n as Weight))
+	}
+
+	fn prepare_groth16_bls381_key(n: u32) -> Weight {
+		(95_000_000_000 as Weight).saturating_add((800_000_000 as Weight).saturating_mul(n as Weight))
+	}
+
+	fn verify_groth16_bls381(n: u32) -> Weight {
+		(145_000_000_000 as Weight).saturating_add((4_000_000_000 as Weight).saturating_mul(n as Weight))
+	}
 }
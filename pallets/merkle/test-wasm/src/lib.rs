@@ -0,0 +1,44 @@
+//! Wasm build of the merkle pallet's hashing and proof verification
+//!
+//! The crate is built to Wasm without the `host-functions` feature, so the
+//! exported functions run the same code as a runtime which doesn't use the
+//! merkle host functions. Its tests call them in a Wasm executor and check
+//! that the host functions, running natively, always agree with them.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+use merkle::utils::{
+	prepared::PreparedKey,
+	setup::{Setup, SetupError, ZkInputs},
+};
+#[cfg(not(feature = "std"))]
+use sp_std::prelude::*;
+
+// Make the Wasm binary available.
+#[cfg(feature = "std")]
+include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
+
+/// Wasm binary unwrapped. If built with `SKIP_WASM_BUILD`, the function panics.
+#[cfg(feature = "std")]
+pub fn wasm_binary_unwrap() -> &'static [u8] {
+	WASM_BINARY.expect("Development wasm binary is not available. Testing is only supported with the flag disabled.")
+}
+
+#[cfg(not(feature = "std"))]
+sp_core::wasm_export_functions! {
+	fn hash(setup: Setup, key: Vec<u8>, xl: Vec<u8>, xr: Vec<u8>) -> Result<Vec<u8>, SetupError> {
+		let prepared = PreparedKey::prepare(&setup.backend, &key)?;
+		let key = if setup.hashes_with_key() { Some(&prepared) } else { None };
+		setup.compute_hash(&xl, &xr, key)
+	}
+
+	fn verify_zk(setup: Setup, key: Vec<u8>, inputs: ZkInputs) -> Result<(), SetupError> {
+		let prepared = PreparedKey::prepare(&setup.backend, &key)?;
+		setup.check_proof(&inputs, &prepared)
+	}
+
+	fn verify_zk_batch(setup: Setup, key: Vec<u8>, inputs: Vec<ZkInputs>) -> Result<(), SetupError> {
+		let prepared = PreparedKey::prepare(&setup.backend, &key)?;
+		setup.check_proof_batch(&inputs, &prepared)
+	}
+}
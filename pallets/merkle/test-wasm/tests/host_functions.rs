@@ -0,0 +1,253 @@
+//! Checks that the merkle host functions, running natively, agree with the
+//! code runtimes built without them run in Wasm.
+use ark_serialize::CanonicalSerialize;
+use arkworks_gadgets::{
+	ark_std::test_rng,
+	prelude::{
+		ark_bls12_381::{Bls12_381, Fr as Bls381},
+		ark_bn254::{Bn254, Fr as Bn254Fr},
+		ark_ff::to_bytes,
+	},
+	setup::mixer::{prove_groth16_x5, setup_circuit_x5, setup_random_groth16_x5},
+};
+use bulletproofs::{r1cs::Prover, BulletproofGens, PedersenGens};
+use bulletproofs_gadgets::fixed_deposit_tree::builder::FixedDepositTreeBuilder;
+use codec::{Decode, Encode};
+use curve25519_dalek::scalar::Scalar;
+use merkle::utils::{
+	host::merkle_crypto,
+	keys::{get_bp_gen_bytes, get_groth16_vk_bytes, KEY_HEADER_LEN},
+	setup::{default_bulletproofs_poseidon_hasher, Backend, Curve, HashFunction, Setup, SetupError, Snark, ZkInputs},
+};
+use merlin::Transcript;
+use pallet_merkle_test_wasm::wasm_binary_unwrap;
+use rand_chacha::{
+	rand_core::{RngCore, SeedableRng},
+	ChaChaRng,
+};
+use sc_executor::{WasmExecutionMethod, WasmExecutor};
+use sc_executor_common::runtime_blob::RuntimeBlob;
+use sp_state_machine::BasicExternalities;
+use sp_wasm_interface::HostFunctions;
+
+/// Calls `method` of the test Wasm binary with the encoded `args`.
+fn call_in_wasm<R: Decode>(method: &str, args: impl Encode) -> R {
+	let mut host_functions = sp_io::SubstrateHostFunctions::host_functions();
+	host_functions.extend(merkle_crypto::HostFunctions::host_functions());
+	let executor = WasmExecutor::new(WasmExecutionMethod::Compiled, Some(1024), host_functions, 1, None);
+	let blob = RuntimeBlob::uncompress_if_needed(wasm_binary_unwrap()).expect("test binary is valid Wasm");
+	let mut ext = BasicExternalities::default();
+	let output = executor
+		.uncached_call(blob, &mut ext, false, method, &args.encode())
+		.unwrap_or_else(|e| panic!("failed to call `{}`: {}", method, e));
+	R::decode(&mut &output[..]).expect("output of the test binary decodes")
+}
+
+/// Setups to compare host and Wasm hashing for, with a key for each
+fn setups() -> Vec<(Setup, Vec<u8>)> {
+	let mut rng = test_rng();
+	let bp_key = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
+	let (_, bls_vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, arkworks_gadgets::setup::common::Curve::Bls381);
+	let (_, bn_vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, arkworks_gadgets::setup::common::Curve::Bn254);
+	let bls_key = get_groth16_vk_bytes(&bls_vk).unwrap();
+	let bn_key = get_groth16_vk_bytes(&bn_vk).unwrap();
+
+	let bls = Backend::Arkworks(Curve::Bls381, Snark::Groth16);
+	let bn = Backend::Arkworks(Curve::Bn254, Snark::Groth16);
+	vec![
+		(
+			Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+			bp_key.clone(),
+		),
+		(Setup::new(HashFunction::PoseidonDefault, bls.clone()), bls_key.clone()),
+		(Setup::new(HashFunction::Poseidon(3, 17), bls.clone()), bls_key.clone()),
+		(Setup::new(HashFunction::MiMC, bls), bls_key),
+		(Setup::new(HashFunction::PoseidonDefault, bn.clone()), bn_key.clone()),
+		(Setup::new(HashFunction::Poseidon(3, 17), bn.clone()), bn_key.clone()),
+		(Setup::new(HashFunction::MiMC, bn), bn_key),
+		(
+			Setup::new(HashFunction::MiMC, Backend::Bulletproofs(Curve::Curve25519)),
+			bp_key,
+		),
+	]
+}
+
+fn key_bytes(x: u8) -> Vec<u8> {
+	let mut bytes = vec![0u8; 32];
+	bytes[31] = x;
+	bytes
+}
+
+#[test]
+fn host_hash_matches_wasm_hash() {
+	let mut rng = ChaChaRng::from_seed([7u8; 32]);
+	for (setup, key) in setups() {
+		for _ in 0..8 {
+			let mut xl = vec![0u8; 32];
+			let mut xr = vec![0u8; 32];
+			rng.fill_bytes(&mut xl);
+			rng.fill_bytes(&mut xr);
+			// Keep the inputs below every field modulus.
+			xl[31] = 0;
+			xr[31] = 0;
+
+			let native = merkle_crypto::hash(setup.clone(), &xl, &xr);
+			let wasm: Result<Vec<u8>, SetupError> = call_in_wasm("hash", (&setup, &key, &xl, &xr));
+			assert_eq!(native, wasm, "hash mismatch for {:?}", setup);
+		}
+	}
+}
+
+#[test]
+fn host_bulletproofs_verification_matches_wasm() {
+	let pc_gens = PedersenGens::default();
+	let mut prover_transcript = Transcript::new(b"zk_membership_proof");
+	let prover = Prover::new(&pc_gens, &mut prover_transcript);
+
+	let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+	let bp_gens = BulletproofGens::new(16400, 1);
+	let key = get_bp_gen_bytes(&bp_gens);
+	let mut ftree = FixedDepositTreeBuilder::new()
+		.hash_params(default_bulletproofs_poseidon_hasher(bp_gens))
+		.depth(3)
+		.build();
+	let leaf0 = ftree.generate_secrets();
+	let leaf1 = ftree.generate_secrets();
+	ftree.tree.add_leaves(vec![leaf0.to_bytes(), leaf1.to_bytes()], None);
+
+	let root = ftree.tree.root;
+	let (proof, (comms, nullifier_hash, leaf_index_comms, proof_comms)) = ftree.prove_zk(
+		root,
+		leaf1,
+		Scalar::zero(),
+		Scalar::zero(),
+		&ftree.hash_params.bp_gens,
+		prover,
+	);
+
+	let inputs = ZkInputs {
+		depth: 3,
+		root: root.to_bytes().to_vec(),
+		private_inputs: comms.iter().map(|x| x.to_bytes().to_vec()).collect(),
+		nullifier_hash: nullifier_hash.to_bytes().to_vec(),
+		proof: proof.to_bytes(),
+		path_indices: leaf_index_comms.iter().map(|x| x.to_bytes().to_vec()).collect(),
+		path_nodes: proof_comms.iter().map(|x| x.to_bytes().to_vec()).collect(),
+		recipient: key_bytes(0),
+		relayer: key_bytes(0),
+		binding: None,
+	};
+	let mut wrong_recipient = inputs.clone();
+	wrong_recipient.recipient = key_bytes(1);
+	let mut wrong_proof = inputs.clone();
+	wrong_proof.proof.truncate(wrong_proof.proof.len() - 1);
+
+	let wasm: Result<(), SetupError> = call_in_wasm("verify_zk", (&setup, &key, &inputs));
+	assert_eq!(wasm, Ok(()));
+	for inputs in vec![inputs, wrong_recipient, wrong_proof] {
+		let native = merkle_crypto::verify_zk(setup.clone(), &key, inputs.clone());
+		let wasm: Result<(), SetupError> = call_in_wasm("verify_zk", (&setup, &key, &inputs));
+		assert_eq!(native, wasm);
+	}
+}
+
+#[test]
+fn host_groth16_verification_matches_wasm() {
+	let mut rng = test_rng();
+	let curve = arkworks_gadgets::setup::common::Curve::Bn254;
+	let recipient = Bn254Fr::from(0u8);
+	let relayer = Bn254Fr::from(0u8);
+	let (circuit, _, nullifier, root, _) = setup_circuit_x5(&Vec::new(), 0, recipient, relayer, &mut rng, curve);
+	let (pk, vk) = setup_random_groth16_x5::<_, Bn254>(&mut rng, curve);
+	let key = get_groth16_vk_bytes(&vk).unwrap();
+	let proof = prove_groth16_x5(&pk, circuit, &mut rng);
+	let mut proof_bytes = vec![0u8; proof.serialized_size()];
+	proof.serialize(&mut proof_bytes[..]).unwrap();
+
+	let setup = Setup::new(
+		HashFunction::PoseidonDefault,
+		Backend::Arkworks(Curve::Bn254, Snark::Groth16),
+	);
+	let inputs = ZkInputs {
+		depth: 30,
+		root: to_bytes![root].unwrap(),
+		private_inputs: Vec::new(),
+		nullifier_hash: to_bytes![nullifier].unwrap(),
+		proof: proof_bytes,
+		path_indices: Vec::new(),
+		path_nodes: Vec::new(),
+		recipient: to_bytes![recipient].unwrap(),
+		relayer: to_bytes![relayer].unwrap(),
+		binding: None,
+	};
+	let mut wrong_relayer = inputs.clone();
+	wrong_relayer.relayer = to_bytes![Bn254Fr::from(1u8)].unwrap();
+	let mut no_inputs = inputs.clone();
+	no_inputs.root = Vec::new();
+
+	let wasm: Result<(), SetupError> = call_in_wasm("verify_zk", (&setup, &key, &inputs));
+	assert_eq!(wasm, Ok(()));
+	let truncated_key = key[..KEY_HEADER_LEN].to_vec();
+	let wasm: Result<(), SetupError> = call_in_wasm("verify_zk", (&setup, &truncated_key, &inputs));
+	assert_eq!(
+		merkle_crypto::verify_zk(setup.clone(), &truncated_key, inputs.clone()),
+		wasm
+	);
+	for inputs in vec![inputs, wrong_relayer, no_inputs] {
+		let native = merkle_crypto::verify_zk(setup.clone(), &key, inputs.clone());
+		let wasm: Result<(), SetupError> = call_in_wasm("verify_zk", (&setup, &key, &inputs));
+		assert_eq!(native, wasm);
+	}
+}
+
+#[test]
+fn host_batch_verification_matches_wasm() {
+	let mut rng = test_rng();
+	let curve = arkworks_gadgets::setup::common::Curve::Bls381;
+	let (pk, vk) = setup_random_groth16_x5::<_, Bls12_381>(&mut rng, curve);
+	let key = get_groth16_vk_bytes(&vk).unwrap();
+	let setup = Setup::new(
+		HashFunction::PoseidonDefault,
+		Backend::Arkworks(Curve::Bls381, Snark::Groth16),
+	);
+
+	let batch: Vec<ZkInputs> = (0..3u8)
+		.map(|i| {
+			let recipient = Bls381::from(i);
+			let relayer = Bls381::from(0u8);
+			let (circuit, _, nullifier, root, _) =
+				setup_circuit_x5(&Vec::new(), 0, recipient, relayer, &mut rng, curve);
+			let proof = prove_groth16_x5(&pk, circuit, &mut rng);
+			let mut proof_bytes = vec![0u8; proof.serialized_size()];
+			proof.serialize(&mut proof_bytes[..]).unwrap();
+			ZkInputs {
+				depth: 30,
+				root: to_bytes![root].unwrap(),
+				private_inputs: Vec::new(),
+				nullifier_hash: to_bytes![nullifier].unwrap(),
+				proof: proof_bytes,
+				path_indices: Vec::new(),
+				path_nodes: Vec::new(),
+				recipient: to_bytes![recipient].unwrap(),
+				relayer: to_bytes![relayer].unwrap(),
+				binding: None,
+			}
+		})
+		.collect();
+	// Swapping the nullifiers of two proofs keeps the batch's contents but
+	// invalidates both proofs.
+	let mut swapped = batch.clone();
+	let nullifier = swapped[0].nullifier_hash.clone();
+	swapped[0].nullifier_hash = swapped[1].nullifier_hash.clone();
+	swapped[1].nullifier_hash = nullifier;
+
+	let wasm: Result<(), SetupError> = call_in_wasm("verify_zk_batch", (&setup, &key, &batch));
+	assert_eq!(wasm, Ok(()));
+	let wasm: Result<(), SetupError> = call_in_wasm("verify_zk_batch", (&setup, &key, &swapped));
+	assert_eq!(wasm, Err(SetupError::ZkVerificationFailed));
+	for inputs in vec![batch, swapped] {
+		let native = merkle_crypto::verify_zk_batch(setup.clone(), &key, inputs.clone());
+		let wasm: Result<(), SetupError> = call_in_wasm("verify_zk_batch", (&setup, &key, &inputs));
+		assert_eq!(native, wasm);
+	}
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use codec::Codec;
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+use mixer::{MixerApi as MixerRuntimeApi, MixerStats};
+
+/// Mixer RPC methods.
+#[rpc]
+pub trait MixerApi<BlockHash, TreeId, BlockNumber> {
+	/// Get the anonymity set statistics of a mixer.
+	///
+	/// This method calls into a runtime with `Mixer` pallet included.
+	/// Deposits and withdrawals are counted over the last `window` blocks,
+	/// rounded to the statistics buckets of the pallet.
+	/// Optionally, a block hash at which the runtime should be queried can be
+	/// specified.
+	///
+	/// Returns `None` if the mixer doesn't exist.
+	#[rpc(name = "mixer_stats")]
+	fn mixer_stats(
+		&self,
+		mixer_id: TreeId,
+		window: BlockNumber,
+		at: Option<BlockHash>,
+	) -> Result<Option<MixerStats<BlockNumber>>>;
+}
+
+/// A struct that implements the `MixerApi`.
+pub struct MixerClient<C, M> {
+	client: Arc<C>,
+	_marker: std::marker::PhantomData<M>,
+}
+
+impl<C, M> MixerClient<C, M> {
+	/// Create new `Mixer` instance with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		Self {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block, TreeId, BlockNumber> MixerApi<<Block as BlockT>::Hash, TreeId, BlockNumber> for MixerClient<C, Block>
+where
+	Block: BlockT,
+	C: HeaderBackend<Block> + ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: MixerRuntimeApi<Block, TreeId, BlockNumber>,
+	TreeId: Codec,
+	BlockNumber: Codec,
+{
+	fn mixer_stats(
+		&self,
+		mixer_id: TreeId,
+		window: BlockNumber,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Option<MixerStats<BlockNumber>>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+		api.get_mixer_stats(&at, mixer_id, window).map_err(|e| Error {
+			code: ErrorCode::ServerError(1513), // Runtime error
+			message: "Unable to query the mixer stats.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})
+	}
+}
@@ -2,11 +2,12 @@ use super::*;
 use bulletproofs::{r1cs::Prover, PedersenGens};
 use bulletproofs_gadgets::fixed_deposit_tree::builder::FixedDepositTreeBuilder;
 use curve25519_dalek::scalar::Scalar;
-use frame_benchmarking::{account, benchmarks, whitelisted_caller};
-use frame_support::traits::OnFinalize;
+use frame_benchmarking::{benchmarks, whitelisted_caller};
+use frame_support::traits::{OnFinalize, OnInitialize};
 use frame_system::RawOrigin;
 use merkle::utils::{
 	keys::{from_bytes_to_bp_gens, slice_to_bytes_32, ScalarBytes},
+	prepared,
 	setup::default_bulletproofs_poseidon_hasher,
 };
 use merlin::Transcript;
@@ -17,141 +18,344 @@ use merkle::{Config as MerkleConfig, Pallet as Merkle};
 
 const NUM_DEPOSITS: u32 = 10;
 const NUM_WITHDRAWALS: u32 = 5;
+const NUM_MIXERS: u32 = 4;
 
 benchmarks! {
 	deposit {
 		// Benchmarking from 1 to `NUM_DEPOSITS`
 		let d in 1 .. NUM_DEPOSITS;
-		let caller = whitelisted_caller();
+		let caller: T::AccountId = whitelisted_caller();
 
-		Mixer::<T>::initialize_first_stage().unwrap();
-		Mixer::<T>::initialize_second_stage().unwrap();
 		let mixer_id: T::TreeId = 0u32.into();
 		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		T::Currency::deposit(currency_id, &caller, 1_000_000_000u32.into()).unwrap();
 
-		// Making `d` leaves/data points
+		// Making `d` leaves/data points, with memos of the max length
 		let data_points = vec![Scalar::zero().to_bytes().to_vec(); d as usize];
-	}: _(RawOrigin::Signed(caller), mixer_id, data_points)
+		let memos = vec![vec![0u8; <T as MerkleConfig>::MaxMemoLength::get() as usize]; d as usize];
+		// Weights must hold for a cold cache of prepared keys
+		prepared::clear();
+	}: _(RawOrigin::Signed(caller), mixer_id, data_points, memos)
 	verify {
 		// Checking if deposit is sucessfull by checking number of leaves
 		// let mixer_info = Mixer::<T>::get_mixer(mixer_id).unwrap();
 		// assert_eq!(mixer_info.leaves.len(), d as usize);
 	}
 
-	// withdraw {
-	// 	let caller: T::AccountId = whitelisted_caller();
-	// 	Mixer::<T>::initialize_first_stage().unwrap();
-	// 	Mixer::<T>::initialize_second_stage().unwrap();
-
-	// 	let mixer_id: T::TreeId = 0u32.into();
-	// 	let balance: BalanceOf<T> = 1_000_000_000u32.into();
-
-	// 	let pc_gens = PedersenGens::default();
-	// 	let params = Merkle::<T>::get_verifying_key(0u32.into()).unwrap();
-	// 	let poseidon = default_bulletproofs_poseidon_hasher(from_bytes_to_bp_gens(&params));
-
-	// 	let mut prover_transcript = Transcript::new(b"zk_membership_proof");
-	// 	let prover = Prover::new(&pc_gens, &mut prover_transcript);
-	// 	let mut ftree = FixedDepositTreeBuilder::new()
-	// 		.hash_params(poseidon.clone())
-	// 		.depth(<T as MerkleConfig>::MaxTreeDepth::get().into())
-	// 		.build();
-
-	// 	let leaf = ftree.generate_secrets().to_bytes();
-	// 	ftree.tree.add_leaves(vec![leaf], None);
-
-	// 	Mixer::<T>::deposit(RawOrigin::Signed(caller.clone()).into(), mixer_id, vec![leaf.to_vec()]).unwrap();
-
-	// 	let root = Merkle::<T>::get_merkle_root(mixer_id).unwrap();
-	// 	let (proof, (comms_cr, nullifier_hash, leaf_index_comms_cr, proof_comms_cr)) = ftree.prove_zk(
-	// 		Scalar::from_bytes_mod_order(slice_to_bytes_32(&root)),
-	// 		Scalar::from_bytes_mod_order(slice_to_bytes_32(&leaf)),
-	// 		Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
-	// 		Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
-	// 		&ftree.hash_params.bp_gens, prover
-	// 	);
-
-	// 	let comms: Vec<ScalarBytes> = comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect();
-	// 	let leaf_index_comms: Vec<ScalarBytes> = leaf_index_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect();
-	// 	let proof_comms: Vec<ScalarBytes> = proof_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect();
-
-	// 	let block_number: T::BlockNumber = 0u32.into();
-
-	// 	let withdraw_proof = WithdrawProof::<T>::new(
-	// 		mixer_id,
-	// 		block_number,
-	// 		root,
-	// 		comms,
-	// 		nullifier_hash.to_bytes().to_vec(),
-	// 		proof.to_bytes(),
-	// 		leaf_index_comms,
-	// 		proof_comms,
-	// 		None,
-	// 		None
-	// 	);
-	// }: _(
-	// 	RawOrigin::Signed(caller.clone()),
-	// 	withdraw_proof
-	// )
-	// verify {
-	// 	let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
-	// 	let balance_after: BalanceOf<T> = T::Currency::free_balance(currency_id, &caller);
-	// 	assert_eq!(balance_after, balance);
-	// }
+	deposit_many {
+		// Benchmarking from 1 to `NUM_MIXERS` of the genesis mixers, with `d`
+		// leaves each
+		let m in 1 .. NUM_MIXERS;
+		let d in 1 .. NUM_DEPOSITS;
+		let caller: T::AccountId = whitelisted_caller();
 
-	set_stopped {
-		Mixer::<T>::initialize_first_stage().unwrap();
-		Mixer::<T>::initialize_second_stage().unwrap();
-	}:
-	// Calling the function with the root origin
-	_(RawOrigin::Root, true)
+		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		T::Currency::deposit(currency_id, &caller, 1_000_000_000u32.into()).unwrap();
+
+		let deposits: Vec<(T::TreeId, Vec<ScalarBytes>)> = (0..m)
+			.map(|mixer_id| (mixer_id.into(), vec![Scalar::zero().to_bytes().to_vec(); d as usize]))
+			.collect();
+		// Weights must hold for a cold cache of prepared keys
+		prepared::clear();
+	}: _(RawOrigin::Signed(caller), deposits)
 	verify {
-		let mixer_ids = MixerTreeIds::<T>::get();
-		for i in 0..mixer_ids.len() {
-			let group_id: T::TreeId = (i as u32).into();
-			let stopped = Merkle::<T>::stopped(group_id);
-			assert!(stopped);
+		let first_mixer: T::TreeId = 0u32.into();
+		assert_eq!(T::Tree::get_leaf_count(first_mixer).unwrap(), d);
+	}
+
+	withdraw {
+		let caller: T::AccountId = whitelisted_caller();
+
+		let mixer_id: T::TreeId = 0u32.into();
+		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		let balance: BalanceOf<T> = 1_000_000_000u32.into();
+		T::Currency::deposit(currency_id, &caller, balance).unwrap();
+
+		let pc_gens = PedersenGens::default();
+		let params = Merkle::<T>::get_verifying_key(0u32.into()).unwrap();
+		let poseidon = default_bulletproofs_poseidon_hasher(from_bytes_to_bp_gens(&params).unwrap());
+
+		let mut prover_transcript = Transcript::new(b"zk_membership_proof");
+		let prover = Prover::new(&pc_gens, &mut prover_transcript);
+		let mut ftree = FixedDepositTreeBuilder::new()
+			.hash_params(poseidon.clone())
+			.depth(<T as MerkleConfig>::MaxTreeDepth::get().into())
+			.build();
+
+		let leaf = ftree.generate_secrets().to_bytes();
+		ftree.tree.add_leaves(vec![leaf], None);
+
+		Mixer::<T>::deposit(RawOrigin::Signed(caller.clone()).into(), mixer_id, vec![leaf.to_vec()], vec![]).unwrap();
+
+		let root = Merkle::<T>::get_merkle_root(mixer_id).unwrap();
+		let (proof, (comms_cr, nullifier_hash, leaf_index_comms_cr, proof_comms_cr)) = ftree.prove_zk(
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&root)),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&leaf)),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
+			&ftree.hash_params.bp_gens, prover
+		);
+
+		let comms: Vec<ScalarBytes> = comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect();
+		let leaf_index_comms: Vec<ScalarBytes> = leaf_index_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect();
+		let proof_comms: Vec<ScalarBytes> = proof_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect();
+
+		let block_number: T::BlockNumber = 0u32.into();
+
+		let withdraw_proof = WithdrawProof::<T>::new(
+			mixer_id,
+			block_number,
+			root,
+			comms,
+			nullifier_hash.to_bytes().to_vec(),
+			proof.to_bytes(),
+			leaf_index_comms,
+			proof_comms,
+			None,
+			None,
+			Zero::zero(),
+			Zero::zero()
+		);
+		// Weights must hold for a cold cache of prepared keys
+		prepared::clear();
+	}: _(
+		RawOrigin::Signed(caller.clone()),
+		withdraw_proof
+	)
+	verify {
+		let balance_after: BalanceOf<T> = T::Currency::free_balance(currency_id, &caller);
+		assert_eq!(balance_after, balance);
+	}
+
+	batch_withdraw {
+		// Benchmarking from 1 to `NUM_WITHDRAWALS`
+		let n in 1 .. NUM_WITHDRAWALS;
+		let caller: T::AccountId = whitelisted_caller();
+
+		let mixer_id: T::TreeId = 0u32.into();
+		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		let balance: BalanceOf<T> = 1_000_000_000u32.into();
+		T::Currency::deposit(currency_id, &caller, balance).unwrap();
+
+		let pc_gens = PedersenGens::default();
+		let params = Merkle::<T>::get_verifying_key(0u32.into()).unwrap();
+		let poseidon = default_bulletproofs_poseidon_hasher(from_bytes_to_bp_gens(&params).unwrap());
+		let mut ftree = FixedDepositTreeBuilder::new()
+			.hash_params(poseidon.clone())
+			.depth(<T as MerkleConfig>::MaxTreeDepth::get().into())
+			.build();
+
+		let leaves: Vec<_> = (0..n).map(|_| ftree.generate_secrets().to_bytes()).collect();
+		ftree.tree.add_leaves(leaves.clone(), None);
+		Mixer::<T>::deposit(
+			RawOrigin::Signed(caller.clone()).into(),
+			mixer_id,
+			leaves.iter().map(|leaf| leaf.to_vec()).collect(),
+			vec![],
+		).unwrap();
+
+		let root = Merkle::<T>::get_merkle_root(mixer_id).unwrap();
+		let block_number: T::BlockNumber = 0u32.into();
+		let mut withdraw_proofs = Vec::new();
+		for leaf in leaves {
+			let mut prover_transcript = Transcript::new(b"zk_membership_proof");
+			let prover = Prover::new(&pc_gens, &mut prover_transcript);
+			let (proof, (comms_cr, nullifier_hash, leaf_index_comms_cr, proof_comms_cr)) = ftree.prove_zk(
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(&root)),
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(&leaf)),
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
+				&ftree.hash_params.bp_gens, prover
+			);
+			withdraw_proofs.push(WithdrawProof::<T>::new(
+				mixer_id,
+				block_number,
+				root.clone(),
+				comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+				nullifier_hash.to_bytes().to_vec(),
+				proof.to_bytes(),
+				leaf_index_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+				proof_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+				None,
+				None,
+				Zero::zero(),
+				Zero::zero()
+			));
 		}
+		// Weights must hold for a cold cache of prepared keys
+		prepared::clear();
+	}: _(
+		RawOrigin::Signed(caller.clone()),
+		withdraw_proofs,
+		true
+	)
+	verify {
+		let balance_after: BalanceOf<T> = T::Currency::free_balance(currency_id, &caller);
+		assert_eq!(balance_after, balance);
 	}
 
-	transfer_admin {
-		Mixer::<T>::initialize_first_stage().unwrap();
-		Mixer::<T>::initialize_second_stage().unwrap();
-		// This account will be a new admin
-		let new_admin: T::AccountId = account("new_admin", 0, 0);
-	}:
-	// Calling the function with the root origin
-	_(RawOrigin::Root, new_admin.clone())
+	withdraw_unsigned {
+		let caller: T::AccountId = whitelisted_caller();
+		let fee_collector = T::FeeCollector::get();
+
+		let mixer_id: T::TreeId = 0u32.into();
+		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		let balance: BalanceOf<T> = 1_000_000_000u32.into();
+		T::Currency::deposit(currency_id, &caller, balance).unwrap();
+		UnsignedWithdrawalFee::<T>::insert(mixer_id, BalanceOf::<T>::zero());
+
+		let pc_gens = PedersenGens::default();
+		let params = Merkle::<T>::get_verifying_key(0u32.into()).unwrap();
+		let poseidon = default_bulletproofs_poseidon_hasher(from_bytes_to_bp_gens(&params).unwrap());
+
+		let mut prover_transcript = Transcript::new(b"zk_membership_proof");
+		let prover = Prover::new(&pc_gens, &mut prover_transcript);
+		let mut ftree = FixedDepositTreeBuilder::new()
+			.hash_params(poseidon.clone())
+			.depth(<T as MerkleConfig>::MaxTreeDepth::get().into())
+			.build();
+
+		let leaf = ftree.generate_secrets().to_bytes();
+		ftree.tree.add_leaves(vec![leaf], None);
+
+		Mixer::<T>::deposit(RawOrigin::Signed(caller.clone()).into(), mixer_id, vec![leaf.to_vec()], vec![]).unwrap();
+
+		let root = Merkle::<T>::get_merkle_root(mixer_id).unwrap();
+		let (proof, (comms_cr, nullifier_hash, leaf_index_comms_cr, proof_comms_cr)) = ftree.prove_zk(
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&root)),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&leaf)),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&caller.encode())),
+			Scalar::from_bytes_mod_order(slice_to_bytes_32(&fee_collector.encode())),
+			&ftree.hash_params.bp_gens, prover
+		);
+
+		let block_number: T::BlockNumber = 0u32.into();
+		let withdraw_proof = WithdrawProof::<T>::new(
+			mixer_id,
+			block_number,
+			root,
+			comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+			nullifier_hash.to_bytes().to_vec(),
+			proof.to_bytes(),
+			leaf_index_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+			proof_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+			Some(caller.clone()),
+			Some(fee_collector),
+			Zero::zero(),
+			Zero::zero()
+		);
+		// Weights must hold for a cold cache of prepared keys
+		prepared::clear();
+	}: _(
+		RawOrigin::None,
+		withdraw_proof
+	)
+	verify {
+		let balance_after: BalanceOf<T> = T::Currency::free_balance(currency_id, &caller);
+		assert_eq!(balance_after, balance);
+	}
+
+	set_stopped {
+		let mixer_id: T::TreeId = 0u32.into();
+		let origin = T::AdminOrigin::successful_origin();
+	}: _<T::Origin>(origin, mixer_id, true)
 	verify {
-		let admin: T::AccountId = Mixer::<T>::admin();
-		assert_eq!(admin, new_admin);
+		assert!(Merkle::<T>::stopped(mixer_id));
 	}
 
-	on_finalize_uninitialized {
-		let first_block: T::BlockNumber = 0u32.into();
-		let second_block: T::BlockNumber = 1u32.into();
+	set_max_relayer_fee {
+		let mixer_id: T::TreeId = 0u32.into();
+		let origin = T::AdminOrigin::successful_origin();
+		let max_fee: BalanceOf<T> = 1u32.into();
+	}: _<T::Origin>(origin, mixer_id, max_fee)
+	verify {
+		assert_eq!(Mixer::<T>::max_relayer_fee(mixer_id), max_fee);
+	}
+
+	set_paused {
+		let mixer_id: T::TreeId = 0u32.into();
+		let origin = T::PauseOrigin::successful_origin();
+	}: _<T::Origin>(origin, mixer_id, true, false)
+	verify {
+		assert_eq!(Mixer::<T>::paused(mixer_id), PauseState { deposits: true, withdrawals: false });
+	}
+
+	set_limits {
+		let mixer_id: T::TreeId = 0u32.into();
+		let origin = T::AdminOrigin::successful_origin();
+		let limits = MixerLimits {
+			deposits: FlowLimits { per_block: Some(1u32.into()), per_epoch: Some(1u32.into()) },
+			withdrawals: FlowLimits { per_block: Some(1u32.into()), per_epoch: Some(1u32.into()) },
+			epoch_length: 1u32.into(),
+			root_cooldown: 1u32.into(),
+		};
+	}: _<T::Origin>(origin, mixer_id, limits.clone())
+	verify {
+		assert_eq!(Mixer::<T>::limits(mixer_id), limits);
+	}
+
+	set_unsigned_withdrawal_fee {
+		let mixer_id: T::TreeId = 0u32.into();
+		let origin = T::AdminOrigin::successful_origin();
+	}: _<T::Origin>(origin, mixer_id, Some(Zero::zero()))
+	verify {
+		assert_eq!(Mixer::<T>::unsigned_withdrawal_fee(mixer_id), Some(Zero::zero()));
+	}
+
+	reconcile {
+		// Checking all the genesis mixers, with a deposit in each
+		let caller: T::AccountId = whitelisted_caller();
+		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		T::Currency::deposit(currency_id, &caller, 1_000_000_000u32.into()).unwrap();
+		let deposits: Vec<(T::TreeId, Vec<ScalarBytes>)> = MixerTreeIds::<T>::get()
+			.into_iter()
+			.map(|mixer_id| (mixer_id, vec![Scalar::zero().to_bytes().to_vec()]))
+			.collect();
+		Mixer::<T>::do_deposit_many(caller, deposits).unwrap();
+		let origin = T::AdminOrigin::successful_origin();
+	}: _<T::Origin>(origin)
+	verify {
+		assert!(Mixer::<T>::check_invariants().is_empty());
+	}
+
+	sweep_fees {
+		let fee_collector = T::FeeCollector::get();
+		let dest: T::AccountId = whitelisted_caller();
+		let currency_id: CurrencyIdOf<T> = T::NativeCurrencyId::get();
+		let amount: BalanceOf<T> = 1_000_000u32.into();
+		T::Currency::deposit(currency_id, &fee_collector, amount).unwrap();
+		let origin = T::AdminOrigin::successful_origin();
+	}: _<T::Origin>(origin, currency_id, amount, dest.clone())
+	verify {
+		assert_eq!(T::Currency::free_balance(currency_id, &dest), amount);
+	}
+
+	prune_activity {
+		// A bucket of each genesis mixer falls out of the history
+		let mixer_ids = MixerTreeIds::<T>::get();
+		let activity = ActivityCount { deposits: 1, withdrawals: 1 };
+		for mixer_id in &mixer_ids {
+			Activity::<T>::insert(*mixer_id, T::BlockNumber::zero(), activity.clone());
+		}
+		let length = T::StatsBucketLength::get().max(One::one());
+		let block = length.saturating_mul(T::StatsHistoryLength::get().into());
 	}: {
-		Mixer::<T>::on_finalize(first_block);
-		Mixer::<T>::on_finalize(second_block);
+		Mixer::<T>::on_initialize(block);
 	}
 	verify {
-		let first_stage_initialized = Mixer::<T>::first_stage_initialized();
-		let second_stage_initialized = Mixer::<T>::second_stage_initialized();
-		assert!(first_stage_initialized);
-		assert!(second_stage_initialized);
+		for mixer_id in mixer_ids {
+			assert_eq!(Mixer::<T>::expired_activity(mixer_id), activity);
+		}
 	}
 
-	on_finalize_initialized {
-		// We first initialize to reach the first branch of if statement inside `on_finalize`
-		let first_block: T::BlockNumber = 0u32.into();
-		Mixer::<T>::on_finalize(first_block);
-		let second_block: T::BlockNumber = 1u32.into();
+	on_finalize {
+		let block: T::BlockNumber = 1u32.into();
 	}: {
-		Mixer::<T>::on_finalize(second_block);
+		Mixer::<T>::on_finalize(block);
 	}
 	verify {
 		let first_group: T::TreeId = 0u32.into();
-		let data = Merkle::<T>::get_cache(first_group, second_block);
+		let data = Merkle::<T>::get_cache(first_group, block);
 		assert_eq!(data.len(), 1);
 	}
 }
@@ -171,6 +375,13 @@ mod bench_tests {
 		});
 	}
 
+	#[test]
+	fn test_deposit_many() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_deposit_many::<Test>());
+		});
+	}
+
 	#[test]
 	fn test_withdraw() {
 		new_test_ext().execute_with(|| {
@@ -178,6 +389,20 @@ mod bench_tests {
 		});
 	}
 
+	#[test]
+	fn test_batch_withdraw() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_batch_withdraw::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_withdraw_unsigned() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_withdraw_unsigned::<Test>());
+		});
+	}
+
 	#[test]
 	fn test_set_stopped() {
 		new_test_ext().execute_with(|| {
@@ -186,23 +411,58 @@ mod bench_tests {
 	}
 
 	#[test]
-	fn test_transfer_admin() {
+	fn test_set_max_relayer_fee() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_set_max_relayer_fee::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_set_paused() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_set_paused::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_set_limits() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_set_limits::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_set_unsigned_withdrawal_fee() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_set_unsigned_withdrawal_fee::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_reconcile() {
+		new_test_ext().execute_with(|| {
+			assert_ok!(test_benchmark_reconcile::<Test>());
+		});
+	}
+
+	#[test]
+	fn test_sweep_fees() {
 		new_test_ext().execute_with(|| {
-			assert_ok!(test_benchmark_transfer_admin::<Test>());
+			assert_ok!(test_benchmark_sweep_fees::<Test>());
 		});
 	}
 
 	#[test]
-	fn test_on_finalize_uninitialized() {
+	fn test_prune_activity() {
 		new_test_ext().execute_with(|| {
-			assert_ok!(test_benchmark_on_finalize_uninitialized::<Test>());
+			assert_ok!(test_benchmark_prune_activity::<Test>());
 		});
 	}
 
 	#[test]
-	fn test_on_finalize_initialized() {
+	fn test_on_finalize() {
 		new_test_ext().execute_with(|| {
-			assert_ok!(test_benchmark_on_finalize_initialized::<Test>());
+			assert_ok!(test_benchmark_on_finalize::<Test>());
 		});
 	}
 }
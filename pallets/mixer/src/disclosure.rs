@@ -0,0 +1,251 @@
+//! Selective disclosure of mixer deposits with viewing keys
+//!
+//! A deposit can carry its note encrypted to a viewing key. The holder of the
+//! viewing key decrypts the note from the `DepositDisclosure` event and can
+//! hand it to an auditor as a [`Disclosure`], which links the deposited leaf to
+//! the nullifier hash spent by its withdrawal.
+//!
+//! A note holds the secrets of its deposit, nullifier and randomness included,
+//! so a viewing key, or a disclosure opened by the auditor, is enough to
+//! withdraw any deposit it decrypts that hasn't been withdrawn yet. Viewing
+//! keys must be kept as safe as the notes themselves, and only the deposits
+//! which have already been withdrawn can be disclosed.
+//!
+//! Notes are encrypted with ECDH over Ristretto: the ciphertext holds an
+//! ephemeral public key, the note masked with a keystream derived from the
+//! shared secret, and a tag authenticating both.
+use bulletproofs_gadgets::poseidon::{builder::Poseidon, Poseidon_hash_2};
+use codec::{Decode, Encode};
+use curve25519_dalek::{constants::RISTRETTO_BASEPOINT_POINT, ristretto::CompressedRistretto, scalar::Scalar};
+use rand_core::{CryptoRng, RngCore};
+use sp_runtime::traits::{BlakeTwo256, Hash};
+use sp_std::prelude::*;
+
+/// Length of an encoded note
+pub const NOTE_LEN: usize = 64;
+/// Length of an encrypted note: ephemeral key, masked note and tag
+pub const CIPHERTEXT_LEN: usize = 32 + NOTE_LEN + 32;
+
+const KEYSTREAM_DOMAIN: &[u8] = b"webb_mixer_disclosure_keystream";
+const MAC_DOMAIN: &[u8] = b"webb_mixer_disclosure_mac";
+
+/// Secrets of a deposit, from which its leaf and nullifier hash are derived
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Note {
+	/// Randomness of the leaf
+	pub r: Scalar,
+	/// Nullifier of the leaf
+	pub nullifier: Scalar,
+}
+
+impl Note {
+	pub fn new(r: Scalar, nullifier: Scalar) -> Self {
+		Self { r, nullifier }
+	}
+
+	/// The leaf added to the mixer tree by the deposit
+	pub fn leaf(&self, hasher: &Poseidon) -> Scalar {
+		Poseidon_hash_2(self.r, self.nullifier, hasher)
+	}
+
+	/// The nullifier hash spent by the withdrawal of the deposit
+	pub fn nullifier_hash(&self, hasher: &Poseidon) -> Scalar {
+		Poseidon_hash_2(self.nullifier, self.nullifier, hasher)
+	}
+
+	pub fn to_bytes(&self) -> [u8; NOTE_LEN] {
+		let mut bytes = [0u8; NOTE_LEN];
+		bytes[..32].copy_from_slice(self.r.as_bytes());
+		bytes[32..].copy_from_slice(self.nullifier.as_bytes());
+		bytes
+	}
+
+	/// Decodes a note, failing unless both scalars are canonical.
+	pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		if bytes.len() != NOTE_LEN {
+			return None;
+		}
+		let r = Scalar::from_canonical_bytes(to_bytes_32(&bytes[..32]))?;
+		let nullifier = Scalar::from_canonical_bytes(to_bytes_32(&bytes[32..]))?;
+		Some(Self { r, nullifier })
+	}
+}
+
+/// Secret key allowing to decrypt the notes encrypted to its public key.
+///
+/// The decrypted notes can spend their deposits, so the viewing key can too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViewingKey(Scalar);
+
+impl ViewingKey {
+	pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+		Self(Scalar::random(rng))
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		if bytes.len() != 32 {
+			return None;
+		}
+		Scalar::from_canonical_bytes(to_bytes_32(bytes)).map(Self)
+	}
+
+	pub fn to_bytes(&self) -> [u8; 32] {
+		self.0.to_bytes()
+	}
+
+	/// Public key the notes are encrypted to
+	pub fn public_key(&self) -> CompressedRistretto {
+		(self.0 * RISTRETTO_BASEPOINT_POINT).compress()
+	}
+
+	/// Decrypts a note encrypted to the public key of the viewing key.
+	///
+	/// Returns `None` if the ciphertext is malformed, was encrypted to
+	/// another key or was tampered with.
+	pub fn decrypt(&self, ciphertext: &[u8]) -> Option<Note> {
+		if ciphertext.len() != CIPHERTEXT_LEN {
+			return None;
+		}
+		let ephemeral = CompressedRistretto::from_slice(&ciphertext[..32]);
+		let shared = (self.0 * ephemeral.decompress()?).compress();
+		let (keystream, mac_key) = derive_keys(&shared, &ephemeral);
+		let body = &ciphertext[32..32 + NOTE_LEN];
+		if tag(&mac_key, &ephemeral, body) != ciphertext[32 + NOTE_LEN..] {
+			return None;
+		}
+		let note: Vec<u8> = body.iter().zip(keystream.iter()).map(|(b, k)| b ^ k).collect();
+		Note::from_bytes(&note)
+	}
+}
+
+/// Encrypts `note` to the viewing key with `public_key`.
+///
+/// Returns `None` if `public_key` isn't a valid point.
+pub fn encrypt_note<R: RngCore + CryptoRng>(
+	public_key: &CompressedRistretto,
+	note: &Note,
+	rng: &mut R,
+) -> Option<Vec<u8>> {
+	let public_key = public_key.decompress()?;
+	let secret = Scalar::random(rng);
+	let ephemeral = (secret * RISTRETTO_BASEPOINT_POINT).compress();
+	let shared = (secret * public_key).compress();
+	let (keystream, mac_key) = derive_keys(&shared, &ephemeral);
+
+	let mut ciphertext = Vec::with_capacity(CIPHERTEXT_LEN);
+	ciphertext.extend_from_slice(ephemeral.as_bytes());
+	ciphertext.extend(note.to_bytes().iter().zip(keystream.iter()).map(|(n, k)| n ^ k));
+	let tag = tag(&mac_key, &ephemeral, &ciphertext[32..]);
+	ciphertext.extend_from_slice(&tag);
+	Some(ciphertext)
+}
+
+/// Note of a deposit encrypted for an auditor, together with the public data
+/// it can be checked against: the leaf at `leaf_index` of the mixer and the
+/// nullifier hash spent by the withdrawal.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct Disclosure {
+	/// Id of the mixer the deposit was made to
+	pub mixer_id: u32,
+	/// Index of the deposited leaf in the mixer tree
+	pub leaf_index: u32,
+	/// The deposited leaf
+	pub leaf: [u8; 32],
+	/// Nullifier hash of the withdrawal of the deposit
+	pub nullifier_hash: [u8; 32],
+	/// The note encrypted to the auditor
+	pub ciphertext: Vec<u8>,
+}
+
+/// Reasons a disclosure can't be made or fails to open
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisclosureError {
+	/// The deposit hasn't been withdrawn, so its note can still spend it
+	UnspentNote,
+	/// The public key of the auditor isn't a valid point
+	InvalidAuditorKey,
+	/// The note can't be decrypted with the viewing key
+	InvalidCiphertext,
+	/// The note doesn't hash to the disclosed leaf
+	LeafMismatch,
+	/// The note doesn't hash to the disclosed nullifier hash
+	NullifierHashMismatch,
+}
+
+impl Disclosure {
+	/// Discloses the deposit of `note` at `leaf_index` of the mixer to the
+	/// auditor with `auditor_key`.
+	///
+	/// The note is enough to withdraw the deposit, so only withdrawn deposits
+	/// are disclosed: `is_spent` is given the nullifier hash of the note and
+	/// must check it against the spent nullifiers of the mixer.
+	pub fn new<R: RngCore + CryptoRng>(
+		note: &Note,
+		mixer_id: u32,
+		leaf_index: u32,
+		auditor_key: &CompressedRistretto,
+		hasher: &Poseidon,
+		is_spent: impl FnOnce(&[u8; 32]) -> bool,
+		rng: &mut R,
+	) -> Result<Self, DisclosureError> {
+		let nullifier_hash = note.nullifier_hash(hasher).to_bytes();
+		if !is_spent(&nullifier_hash) {
+			return Err(DisclosureError::UnspentNote);
+		}
+		Ok(Self {
+			mixer_id,
+			leaf_index,
+			leaf: note.leaf(hasher).to_bytes(),
+			nullifier_hash,
+			ciphertext: encrypt_note(auditor_key, note, rng).ok_or(DisclosureError::InvalidAuditorKey)?,
+		})
+	}
+
+	/// Decrypts the disclosed note with the viewing key of the auditor and
+	/// checks that it derives both the leaf and the nullifier hash.
+	///
+	/// The auditor still has to check the leaf against the mixer tree and the
+	/// nullifier hash against the spent nullifiers.
+	pub fn open(&self, viewing_key: &ViewingKey, hasher: &Poseidon) -> Result<Note, DisclosureError> {
+		let note = viewing_key
+			.decrypt(&self.ciphertext)
+			.ok_or(DisclosureError::InvalidCiphertext)?;
+		if note.leaf(hasher).to_bytes() != self.leaf {
+			return Err(DisclosureError::LeafMismatch);
+		}
+		if note.nullifier_hash(hasher).to_bytes() != self.nullifier_hash {
+			return Err(DisclosureError::NullifierHashMismatch);
+		}
+		Ok(note)
+	}
+}
+
+/// Derives the keystream masking the note and the key of its tag.
+fn derive_keys(shared: &CompressedRistretto, ephemeral: &CompressedRistretto) -> ([u8; NOTE_LEN], [u8; 32]) {
+	let block = |domain: &[u8], counter: u8| {
+		let mut preimage = Vec::with_capacity(domain.len() + 65);
+		preimage.extend_from_slice(domain);
+		preimage.extend_from_slice(shared.as_bytes());
+		preimage.extend_from_slice(ephemeral.as_bytes());
+		preimage.push(counter);
+		BlakeTwo256::hash(&preimage).to_fixed_bytes()
+	};
+	let mut keystream = [0u8; NOTE_LEN];
+	keystream[..32].copy_from_slice(&block(KEYSTREAM_DOMAIN, 0));
+	keystream[32..].copy_from_slice(&block(KEYSTREAM_DOMAIN, 1));
+	(keystream, block(MAC_DOMAIN, 0))
+}
+
+fn tag(mac_key: &[u8; 32], ephemeral: &CompressedRistretto, body: &[u8]) -> [u8; 32] {
+	let mut preimage = Vec::with_capacity(64 + body.len());
+	preimage.extend_from_slice(mac_key);
+	preimage.extend_from_slice(ephemeral.as_bytes());
+	preimage.extend_from_slice(body);
+	BlakeTwo256::hash(&preimage).to_fixed_bytes()
+}
+
+fn to_bytes_32(bytes: &[u8]) -> [u8; 32] {
+	let mut array = [0u8; 32];
+	array.copy_from_slice(bytes);
+	array
+}
@@ -21,9 +21,20 @@
 //! The Mixer pallet provides functions for:
 //!
 //! - Depositing some currency into the mixer.
-//! - Withdrawing the deposit from the mixer.
-//! - Stopping mixer operations.
-//! - Transfering the admin of the mixer.
+//! - Attaching notes encrypted to a viewing key to deposits, for selective
+//!   disclosure to auditors.
+//! - Withdrawing the deposit from the mixer, one at a time or in batches.
+//! - Withdrawing to a fresh account with an unsigned transaction, paying its
+//!   fee out of the withdrawn amount.
+//! - Stopping the operations of a mixer.
+//! - Pausing the deposits or withdrawals of a mixer in an emergency.
+//! - Capping the fees relayers take from withdrawals.
+//! - Limiting the deposited and withdrawn volume of a mixer per block and per
+//!   epoch, and delaying withdrawals against fresh roots.
+//! - Reporting the size of the anonymity set of a mixer through the
+//!   [`MixerApi`] runtime API.
+//! - Checking that the value locked in the mixers is backed by the balance of
+//!   the pallet.
 //!
 //! ### Terminology
 //!
@@ -31,16 +42,148 @@
 //!   potentially identifiable or 'tainted' cryptocurrency funds with others, so
 //!   as to obscure the trail back to the fund's source.
 //!
+//! - **Viewing key**: Key decrypting the notes attached to deposits. Its
+//!   holder can disclose a withdrawn deposit to an auditor, linking it to its
+//!   withdrawal (see [`disclosure`]). The notes hold the secrets of their
+//!   deposits, so the viewing key can also withdraw them.
+//!
 //! ## Interface
 //!
 //! ### Dispatchable Functions
 //!
-//! - `deposit` - Deposit a fixed amount of cryptocurrency into the mixer.
+//! - `deposit` - Deposit a fixed amount of cryptocurrency into the mixer,
+//!   optionally attaching an encrypted memo to each deposit.
+//! - `deposit_many` - Deposit into several mixers at once.
+//! - `deposit_with_disclosures` - Deposit into the mixer, publishing the note
+//!   of each deposit encrypted to a viewing key.
 //! - `withdraw` - Provide a zero-knowladge proof of the deposit and withdraw
 //!   from the mixer.
-//! - `set_stopped` - Stops the operation of all mixers.
-//! - `transfer_admin` - Transfers the admin role from sender to specified
-//!   account.
+//! - `batch_withdraw` - Withdraw many deposits at once, verifying the proofs
+//!   of Groth16 trees as a batch.
+//! - `withdraw_unsigned` - Withdraw with an unsigned transaction, paying the
+//!   unsigned withdrawal fee of the mixer to the `FeeCollector`.
+//! - `set_stopped` - Stops the operation of a mixer.
+//! - `set_paused` - Pauses the deposits and/or the withdrawals of a mixer.
+//! - `set_max_relayer_fee` - Sets the maximum fee relayers can take from
+//!   withdrawals of a mixer.
+//! - `set_limits` - Sets the volume limits and root cooldown of a mixer.
+//! - `set_unsigned_withdrawal_fee` - Sets the fee of unsigned withdrawals from
+//!   a mixer, or disables them.
+//! - `reconcile` - Checks the invariants of the mixers and reports any
+//!   violation.
+//! - `sweep_fees` - Transfers the fees collected by the `FeeCollector`.
+//!
+//! Mixers are administered by the `AdminOrigin`, which creates them, stops
+//! them, caps their relayer fees and limits their volume. The `PauseOrigin`
+//! can only pause their deposits or withdrawals, e.g. while an incident is
+//! investigated.
+//!
+//! ### Anti-abuse controls
+//!
+//! Each mixer can limit the volume deposited into and withdrawn from it per
+//! block and per epoch (see [`MixerLimits`]). A deposit or withdrawal going
+//! over a limit fails, and a `RateLimitHit` event is emitted when it's
+//! dispatched, not while it's validated in the pool. Withdrawals can also
+//! be required to prove against roots cached at least `root_cooldown` blocks
+//! ago, so that new leaves can't be withdrawn right away. Runtimes can deny
+//! accounts from depositing and withdrawing through the
+//! [`traits::DenyList`] hook.
+//!
+//! ### Relayers
+//!
+//! The relayer of a withdrawal is the account named in its proof, or its
+//! sender if none is. A withdrawal is relayed when its proof names a relayer
+//! which submits it on behalf of another recipient. Once a relayed withdrawal
+//! is paid out, its relayer and fee are passed to the [`traits::OnRelay`]
+//! hook, e.g. to count it toward the record of a registered relayer.
+//!
+//! #### Relayer public input
+//!
+//! The withdrawal circuits have a single relayer public input, so the fee and
+//! refund of a withdrawal are committed to through it. Provers and relayers
+//! must compute it as [`Pallet::relayer_input`] does:
+//!
+//! - Without a fee or refund, the input is the SCALE encoding of the relayer
+//!   account, e.g. its 32 bytes for an `AccountId32`.
+//! - Otherwise, it is the 32 byte `BlakeTwo256` hash of the SCALE encoding of
+//!   the tuple `(relayer, fee, refund)`, with the last byte of the hash set
+//!   to 0. In the tuple, `relayer` is encoded as the account id, and `fee`
+//!   and `refund` as the `Balance` of the mixer's currency, e.g. 16 bytes each
+//!   for a `u128`.
+//!
+//! The circuits read the input as a little-endian integer: Bulletproofs
+//! circuits reduce it modulo the group order and Groth16 circuits take it as
+//! a field element. Clearing the last, most significant byte keeps the hash
+//! below the modulus of every supported field, so it is never reduced and
+//! two different `(relayer, fee, refund)` only share an input if their hashes
+//! collide on 31 bytes.
+//!
+//! ### Anonymity set statistics
+//!
+//! The deposits and withdrawals of each mixer are counted in buckets of
+//! `StatsBucketLength` blocks, from which [`MixerStats`] are computed. Since
+//! withdrawals can't be linked to deposits, the age distribution of unspent
+//! deposits assumes the oldest deposits were withdrawn first, which
+//! underestimates their age.
+//!
+//! Only the last `StatsHistoryLength` buckets are kept. At the start of each
+//! bucket, the one falling out of the history is pruned in `on_initialize`
+//! and its counts are added to the totals of pruned buckets, whose unspent
+//! deposits are reported with the youngest age they can have. Windows longer
+//! than the history only count the buckets kept.
+//!
+//! ### Invariants
+//!
+//! The total value locked of each mixer is updated with checked arithmetic,
+//! and the following invariants must hold:
+//!
+//! - The balance of the pallet account in each currency is at least the sum of
+//!   the total value locked of the mixers of that currency.
+//! - The total value locked of each mixer is the fixed deposit size times the
+//!   number of deposits outstanding, i.e. its leaf count minus the number of
+//!   spent nullifiers.
+//!
+//! The `AdminOrigin` can check them with `reconcile`, which emits an
+//! `InvariantViolated` event for each [`InvariantViolation`]. With the
+//! `try-runtime` feature, they are also checked after runtime upgrades.
+//!
+//! ### Unsigned withdrawals
+//!
+//! A fresh recipient has no native balance to pay for a `withdraw`, so it
+//! would need a relayer. Instead, it can submit `withdraw_unsigned`, whose
+//! proof binds the `FeeCollector` as its relayer and the unsigned withdrawal
+//! fee of the mixer as its fee. The fee is paid out of the withdrawn amount,
+//! in the currency of the mixer.
+//!
+//! Unsigned withdrawals are checked by `validate_unsigned` before entering the
+//! transaction pool: their nullifier must be unspent, their root cached and
+//! their proof valid, whichever peer they come from. Only the cheap checks
+//! are run again before dispatch, the proof being verified by the call
+//! itself. Withdrawals whose root is still in its cooldown, or which go over
+//! a limit of the block, are kept for later blocks. They provide
+//! their nullifier hash as tag, so the pool only keeps one of the withdrawals
+//! spending it.
+//!
+//! Unsigned withdrawals pay no transaction fee, so at most
+//! `MaxUnsignedWithdrawalsPerBlock` of them are dispatched in a block, failed
+//! ones included. The fees
+//! they pay to the `FeeCollector` are transferred out of it by the
+//! `AdminOrigin` with `sweep_fees`.
+//!
+//! ### Bound leaves
+//!
+//! The Groth16 tree of a mixer can be declared with [`LeafFormat::Bound`]
+//! leaves. Withdrawals from such a mixer are verified against the asset id,
+//! deposit size and `ChainId` of the mixer as extra public inputs (see
+//! [`Pallet::leaf_binding`]). Notes are only bound to these values if the
+//! circuit of the tree's verifying key commits to them in the leaves; the
+//! circuits live in external crates. Each mixer has a tree of its own.
+//!
+//! ### Genesis
+//!
+//! The mixers of a chain are declared in the [`GenesisConfig`], together with
+//! the verifying keys of their trees. They are created and initialized when
+//! the genesis storage is built, which fails on an invalid mixer or key.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -52,25 +195,36 @@ pub mod tests;
 
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarking;
+pub mod disclosure;
+pub mod migrations;
 pub mod weights;
 
 pub mod traits;
 
-use bulletproofs::BulletproofGens;
 use codec::{Decode, Encode};
-use frame_support::{dispatch, ensure, traits::Get, weights::Weight, PalletId};
+use frame_support::{
+	dispatch, ensure,
+	traits::{EnsureOrigin, Get},
+	transactional,
+	weights::Weight,
+	PalletId,
+};
 use frame_system::ensure_signed;
 use merkle::{
 	utils::{
 		keys::ScalarBytes,
-		permissions::ensure_admin,
-		setup::{Backend, Curve, HashFunction, Setup},
+		setup::{LeafBinding, LeafFormat, Setup},
 	},
-	Pallet as MerklePallet, Tree as TreeTrait,
+	Pallet as MerklePallet, Tree as TreeTrait, ZkProof,
+};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_runtime::{
+	traits::{AccountIdConversion, BlakeTwo256, CheckedAdd, CheckedMul, CheckedSub, Hash, One, Saturating, Zero},
+	transaction_validity::{InvalidTransaction, TransactionValidityError},
 };
-use sp_runtime::traits::{AccountIdConversion, Zero};
 use sp_std::prelude::*;
-use traits::ExtendedMixer;
+use traits::{DenyList, ExtendedMixer, OnRelay};
 use webb_traits::MultiCurrency;
 use weights::WeightInfo;
 
@@ -100,25 +254,40 @@ pub mod pallet {
 		/// The small deposit length
 		#[pallet::constant]
 		type DepositLength: Get<Self::BlockNumber>;
-		/// Default admin key
-		#[pallet::constant]
-		type DefaultAdmin: Get<Self::AccountId>;
+		/// Origin allowed to create, stop and configure mixers
+		type AdminOrigin: EnsureOrigin<Self::Origin>;
+		/// Origin allowed to pause the deposits or withdrawals of a mixer
+		type PauseOrigin: EnsureOrigin<Self::Origin>;
 		/// Weight information for extrinsics in this pallet
 		type WeightInfo: WeightInfo;
-		// Available mixes sizes (Size is determend by the deposit amount)
-		type MixerSizes: Get<Vec<BalanceOf<Self>>>;
+		/// Maximum number of withdrawals in a `batch_withdraw` call
+		#[pallet::constant]
+		type MaxWithdrawBatchSize: Get<u32>;
+		/// Accounts denied from depositing into and withdrawing from mixers
+		type DenyList: DenyList<Self::AccountId>;
+		/// Number of blocks the deposits and withdrawals are counted over for
+		/// the statistics of a mixer
+		#[pallet::constant]
+		type StatsBucketLength: Get<Self::BlockNumber>;
+		/// Number of statistics buckets kept for each mixer, which bounds the
+		/// windows of the statistics
+		#[pallet::constant]
+		type StatsHistoryLength: Get<u32>;
+		/// Id of the chain, which bound leaves commit to
+		#[pallet::constant]
+		type ChainId: Get<u64>;
+		/// Account receiving the fees of unsigned withdrawals
+		type FeeCollector: Get<Self::AccountId>;
+		/// Priority of unsigned withdrawals in the transaction pool
+		#[pallet::constant]
+		type UnsignedPriority: Get<TransactionPriority>;
+		/// Maximum number of unsigned withdrawals in a block
+		#[pallet::constant]
+		type MaxUnsignedWithdrawalsPerBlock: Get<u32>;
+		/// Hook notified of the relayer of each withdrawal paid out
+		type OnRelay: OnRelay<Self::AccountId, CurrencyIdOf<Self>, BalanceOf<Self>>;
 	}
 
-	/// Flag indicating if the mixer trees are created
-	#[pallet::storage]
-	#[pallet::getter(fn first_stage_initialized)]
-	pub type FirstStageInitialized<T: Config> = StorageValue<_, bool, ValueQuery>;
-
-	/// Flag indicating if the mixers are initialized
-	#[pallet::storage]
-	#[pallet::getter(fn second_stage_initialized)]
-	pub type SecondStageInitialized<T: Config> = StorageValue<_, bool, ValueQuery>;
-
 	/// The map of mixer trees to their metadata
 	#[pallet::storage]
 	#[pallet::getter(fn mixer_trees)]
@@ -129,17 +298,120 @@ pub mod pallet {
 	#[pallet::getter(fn mixer_group_ids)]
 	pub type MixerTreeIds<T: Config> = StorageValue<_, Vec<T::TreeId>, ValueQuery>;
 
-	/// Administrator of the mixer pallet.
-	/// This account that can stop/start operations of the mixer
+	/// Whether the deposits and withdrawals of a mixer are paused
 	#[pallet::storage]
-	#[pallet::getter(fn admin)]
-	pub type Admin<T: Config> = StorageValue<_, T::AccountId, ValueQuery>;
+	#[pallet::getter(fn paused)]
+	pub type Paused<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, PauseState, ValueQuery>;
 
 	/// The TVL per group
 	#[pallet::storage]
 	#[pallet::getter(fn total_value_locked)]
 	pub type TotalValueLocked<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, BalanceOf<T>, ValueQuery>;
 
+	/// The maximum fee a relayer can take from a withdrawal, per mixer.
+	/// Relayer fees are not allowed until the admin sets a cap.
+	#[pallet::storage]
+	#[pallet::getter(fn max_relayer_fee)]
+	pub type MaxRelayerFee<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, BalanceOf<T>, ValueQuery>;
+
+	/// The fee of unsigned withdrawals, per mixer. Unsigned withdrawals are
+	/// not allowed until the admin sets a fee.
+	#[pallet::storage]
+	#[pallet::getter(fn unsigned_withdrawal_fee)]
+	pub type UnsignedWithdrawalFee<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, BalanceOf<T>>;
+
+	/// The block of the last unsigned withdrawals, with their number in that
+	/// block
+	#[pallet::storage]
+	#[pallet::getter(fn unsigned_withdrawals)]
+	pub type UnsignedWithdrawals<T: Config> = StorageValue<_, (T::BlockNumber, u32), ValueQuery>;
+
+	/// The volume limits and root cooldown of each mixer. Mixers are not
+	/// limited until the admin sets limits.
+	#[pallet::storage]
+	#[pallet::getter(fn limits)]
+	pub type Limits<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::TreeId, MixerLimits<BalanceOf<T>, T::BlockNumber>, ValueQuery>;
+
+	/// The volume deposited into and withdrawn from each mixer in the current
+	/// block and epoch
+	#[pallet::storage]
+	#[pallet::getter(fn volume)]
+	pub type Volume<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::TreeId, MixerVolume<BalanceOf<T>, T::BlockNumber>, ValueQuery>;
+
+	/// The number of nullifiers spent in each mixer
+	#[pallet::storage]
+	#[pallet::getter(fn nullifier_count)]
+	pub type NullifierCount<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, u32, ValueQuery>;
+
+	/// The number of deposits and withdrawals of each mixer, per bucket of
+	/// `StatsBucketLength` blocks starting at the given block
+	#[pallet::storage]
+	#[pallet::getter(fn activity)]
+	pub type Activity<T: Config> =
+		StorageDoubleMap<_, Blake2_128Concat, T::TreeId, Twox64Concat, T::BlockNumber, ActivityCount, ValueQuery>;
+
+	/// The number of deposits and withdrawals of each mixer in the buckets
+	/// pruned from `Activity`
+	#[pallet::storage]
+	#[pallet::getter(fn expired_activity)]
+	pub type ExpiredActivity<T: Config> = StorageMap<_, Blake2_128Concat, T::TreeId, ActivityCount, ValueQuery>;
+
+	/// Storage layout release the pallet's storage was last migrated to
+	#[pallet::storage]
+	#[pallet::getter(fn storage_version)]
+	pub type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig<T: Config> {
+		/// Verifying keys added to the merkle pallet, referred to by their
+		/// index from `mixers`
+		pub verifying_keys: Vec<Vec<u8>>,
+		/// Mixers created and initialized at genesis
+		pub mixers: Vec<MixerGenesisConfig<CurrencyIdOf<T>, BalanceOf<T>>>,
+	}
+
+	#[cfg(feature = "std")]
+	impl<T: Config> Default for GenesisConfig<T> {
+		fn default() -> Self {
+			GenesisConfig {
+				verifying_keys: vec![],
+				mixers: vec![],
+			}
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
+		fn build(&self) {
+			let key_ids: Vec<T::KeyId> = self
+				.verifying_keys
+				.iter()
+				.map(|key| T::Tree::add_verifying_key(key.clone()).expect("invalid verifying key in genesis."))
+				.collect();
+
+			for mixer in &self.mixers {
+				assert!(mixer.size > Zero::zero(), "zero deposit size of a mixer in genesis.");
+				let key_id = *key_ids
+					.get(mixer.verifying_key as usize)
+					.expect("unknown verifying key of a mixer in genesis.");
+				let mixer_id = Pallet::<T>::create_mixer(
+					Pallet::<T>::account_id(),
+					mixer.currency_id,
+					mixer.setup.clone(),
+					mixer.depth,
+					mixer.size,
+				)
+				.expect("invalid mixer in genesis.");
+				T::Tree::set_leaf_format(Pallet::<T>::account_id(), mixer_id, mixer.leaf_format)
+					.expect("leaf format of a mixer can't be set in genesis.");
+				T::Tree::initialize_tree(mixer_id, key_id)
+					.expect("mixer tree can't be initialized with its verifying key in genesis.");
+			}
+		}
+	}
+
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	#[pallet::metadata(T::AccountId = "AccountId", T::TreeId = "TreeId", BalanceOf<T> = "Balance")]
@@ -165,6 +437,63 @@ pub mod pallet {
 			T::AccountId,
 			/// Merkle root
 			ScalarBytes,
+			/// Fee paid to the relayer
+			BalanceOf<T>,
+		),
+		/// Withdrawal in a non-atomic batch that failed
+		WithdrawFailed(
+			/// Id of the tree
+			T::TreeId,
+			/// Nullifier hash of the withdrawal
+			ScalarBytes,
+			/// Reason of the failure
+			dispatch::DispatchError,
+		),
+		/// Deposits and withdrawals of the mixer paused or resumed
+		PauseSet(
+			/// Id of the tree
+			T::TreeId,
+			/// Whether the deposits are paused
+			bool,
+			/// Whether the withdrawals are paused
+			bool,
+		),
+		/// Note of a deposited leaf encrypted to a viewing key
+		DepositDisclosure(
+			/// Id of the tree
+			T::TreeId,
+			/// Index of the leaf in the tree
+			u32,
+			/// The encrypted note
+			Vec<u8>,
+		),
+		/// Volume limits of the mixer set
+		LimitsSet(
+			/// Id of the tree
+			T::TreeId,
+		),
+		/// Deposit or withdrawal failed for going over a volume limit
+		RateLimitHit(
+			/// Id of the tree
+			T::TreeId,
+			/// The limit that was hit
+			RateLimit,
+		),
+		/// Invariant of the mixers found violated by a reconciliation
+		InvariantViolated(InvariantViolation<CurrencyIdOf<T>, BalanceOf<T>, T::TreeId>),
+		/// Invariants of the mixers checked
+		Reconciled(
+			/// Number of violations found
+			u32,
+		),
+		/// Fees collected by the `FeeCollector` transferred out of it
+		FeesSwept(
+			/// Currency of the fees
+			CurrencyIdOf<T>,
+			/// Amount transferred
+			BalanceOf<T>,
+			/// Account id of the destination
+			T::AccountId,
 		),
 	}
 
@@ -184,6 +513,46 @@ pub mod pallet {
 		UnauthorizedCall,
 		/// Mixer is stopped
 		MixerStopped,
+		/// Withdrawal batch is empty
+		EmptyBatch,
+		/// Withdrawal batch has more than `MaxWithdrawBatchSize` withdrawals
+		BatchTooLarge,
+		/// Nullifier is used more than once in the same batch
+		DuplicateNullifier,
+		/// Relayer fee is higher than the maximum fee of the mixer
+		FeeExceedsMax,
+		/// Relayer fee is higher than the deposit size of the mixer
+		FeeExceedsDepositSize,
+		/// Number of disclosures differs from the number of deposits
+		DisclosureCountMismatch,
+		/// Disclosure isn't a note encrypted to a viewing key
+		InvalidDisclosure,
+		/// Deposits into the mixer are paused
+		DepositsPaused,
+		/// Withdrawals from the mixer are paused
+		WithdrawalsPaused,
+		/// No deposits were given
+		EmptyDeposits,
+		/// Deposit or withdrawal goes over a volume limit of the mixer
+		RateLimitExceeded,
+		/// Withdrawal proves against a root still in its cooldown
+		RootInCooldown,
+		/// Account is denied from using the mixers
+		AccountDenied,
+		/// Epoch limits are set without an epoch length
+		InvalidLimits,
+		/// Total value locked of the mixer overflowed
+		Overflow,
+		/// Withdrawal takes more than the total value locked of the mixer
+		TvlUnderflow,
+		/// Unsigned withdrawals from the mixer are not allowed
+		UnsignedWithdrawalsDisabled,
+		/// Unsigned withdrawal has no recipient, a refund, or a relayer or fee
+		/// other than the `FeeCollector` and the unsigned withdrawal fee
+		InvalidUnsignedWithdrawal,
+		/// Block already has `MaxUnsignedWithdrawalsPerBlock` unsigned
+		/// withdrawals
+		TooManyUnsignedWithdrawals,
 	}
 
 	#[pallet::pallet]
@@ -193,57 +562,72 @@ pub mod pallet {
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
 		fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
+			let pruned = Self::prune_activity(_n);
 			// We make sure that we return the correct weight for the block according to
 			// on_finalize
-			if Self::second_stage_initialized() {
-				// In case mixer is initialized, we expect the weights for merkle cache update
-				<T as Config>::WeightInfo::on_finalize_initialized()
-			} else {
-				// In case mixer is not initialized, we expect the weights for initialization
-				<T as Config>::WeightInfo::on_finalize_uninitialized()
-			}
+			<T as Config>::WeightInfo::on_finalize().saturating_add(pruned)
 		}
 
 		fn on_finalize(_n: BlockNumberFor<T>) {
-			if Self::first_stage_initialized() && !Self::second_stage_initialized() {
-				let mixer_ids = MixerTreeIds::<T>::get();
-				// check if first tree has been initialized, otherwise intialize the parameters
-				for i in 0..mixer_ids.len() {
-					if let Ok(initialized) = T::Tree::is_initialized(mixer_ids[i]) {
-						if !initialized {
-							match Self::initialize_second_stage() {
-								Ok(_) => {}
-								Err(e) => {
-									log::error!("Error initialising trees: {:?}", e);
-								}
-							}
-						}
-					}
+			// check if any deposits happened (by checking the size of the collection at
+			// this block) if none happened, carry over previous Merkle roots for the cache.
+			let mixer_ids = MixerTreeIds::<T>::get();
+
+			for i in 0..mixer_ids.len() {
+				let cached_roots = <merkle::Pallet<T>>::cached_roots(_n, mixer_ids[i]);
+				// if there are no cached roots, carry forward the current root
+				if cached_roots.len() == 0 {
+					let _ = <merkle::Pallet<T>>::add_root_to_cache(mixer_ids[i], _n);
 				}
 			}
+		}
 
-			if Self::first_stage_initialized() && Self::second_stage_initialized() {
-				// check if any deposits happened (by checking the size of the collection at
-				// this block) if none happened, carry over previous Merkle roots for the cache.
-				let mixer_ids = MixerTreeIds::<T>::get();
+		fn on_runtime_upgrade() -> Weight {
+			migrations::migrate::<T>()
+		}
 
-				for i in 0..mixer_ids.len() {
-					let cached_roots = <merkle::Pallet<T>>::cached_roots(_n, mixer_ids[i]);
-					// if there are no cached roots, carry forward the current root
-					if cached_roots.len() == 0 {
-						let _ = <merkle::Pallet<T>>::add_root_to_cache(mixer_ids[i], _n);
-					}
-				}
-			}
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			ensure!(
+				Self::check_invariants().is_empty(),
+				"Mixer invariants violated after the upgrade"
+			);
+			Ok(())
+		}
+	}
 
-			if !Self::first_stage_initialized() {
-				match Self::initialize_first_stage() {
-					Ok(_) => {}
-					Err(e) => {
-						log::error!("Error initialising: {:?}", e);
-					}
-				}
+	#[pallet::validate_unsigned]
+	impl<T: Config> ValidateUnsigned for Pallet<T> {
+		type Call = Call<T>;
+
+		fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+			let withdraw_proof = match call {
+				Call::withdraw_unsigned(withdraw_proof) => withdraw_proof,
+				_ => return InvalidTransaction::Call.into(),
+			};
+			let withdrawal = Self::validate_unsigned_withdrawal(withdraw_proof)?;
+			// the withdrawal pays no transaction fee, so the pool only takes in and
+			// gossips it if its proof is valid, whichever its source
+			if T::Tree::verify_zk_proof(withdraw_proof.clone().into_zk_proof(&withdrawal)).is_err() {
+				return InvalidTransaction::BadProof.into();
 			}
+
+			ValidTransaction::with_tag_prefix("MixerWithdrawal")
+				.priority(T::UnsignedPriority::get())
+				.and_provides(withdraw_proof.nullifier_hash.clone())
+				.propagate(true)
+				.build()
+		}
+
+		fn pre_dispatch(call: &Self::Call) -> Result<(), TransactionValidityError> {
+			let withdraw_proof = match call {
+				Call::withdraw_unsigned(withdraw_proof) => withdraw_proof,
+				_ => return Err(InvalidTransaction::Call.into()),
+			};
+			// the proof is verified when dispatched, so only the cheap checks are run
+			// before
+			Self::validate_unsigned_withdrawal(withdraw_proof)?;
+			Ok(())
 		}
 	}
 
@@ -253,46 +637,101 @@ pub mod pallet {
 		/// Multiple deposits can be inserted together since `data_points` is an
 		/// array.
 		///
+		/// `memos` is either empty or holds an encrypted memo for each of the
+		/// `data_points`, e.g. the note of the deposit encrypted to its
+		/// recipient. Memos are indexed offchain by the merkle pallet.
+		///
 		/// Fails in case the mixer is stopped or not initialized.
 		///
 		/// Weights:
 		/// - Dependent on argument: `data_points`
 		///
 		/// - Base weight: 417_168_400_000
-		/// - DB weights: 8 reads, 5 writes
+		/// - DB weights: 11 reads, 7 writes
 		/// - Additional weights: 21_400_442_000 * data_points.len()
 		#[pallet::weight(<T as Config>::WeightInfo::deposit(data_points.len() as u32))]
 		pub fn deposit(
 			origin: OriginFor<T>,
 			mixer_id: T::TreeId,
 			data_points: Vec<ScalarBytes>,
+			memos: Vec<Vec<u8>>,
 		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
-			ensure!(!<MerklePallet<T>>::stopped(mixer_id), Error::<T>::MixerStopped);
-			// get mixer info, should always exist if the module is initialized
-			let mixer_info = Self::get_mixer(mixer_id)?;
-			// ensure the sender has enough balance to cover deposit
-			let balance = T::Currency::free_balance(mixer_info.currency_id, &sender);
-			// TODO: Multiplication by usize should be possible
-			// using this hack for now, though we should optimise with regular
-			// multiplication `data_points.len() * mixer_info.fixed_deposit_size`
-			let deposit: BalanceOf<T> = data_points
-				.iter()
-				.map(|_| mixer_info.fixed_deposit_size)
-				.fold(Zero::zero(), |acc, elt| acc + elt);
-			ensure!(balance >= deposit, Error::<T>::InsufficientBalance);
-			// transfer the deposit to the module
-			T::Currency::transfer(mixer_info.currency_id, &sender, &Self::account_id(), deposit)?;
-			// update the total value locked
-			let tvl = Self::total_value_locked(mixer_id);
-			<TotalValueLocked<T>>::insert(mixer_id, tvl + deposit);
-			// add elements to the mixer group's merkle tree and save the leaves
-			T::Tree::add_members(Self::account_id(), mixer_id.into(), data_points.clone())?;
-
-			let deposit_size = mixer_info.fixed_deposit_size;
+			Self::do_deposit(sender, mixer_id, data_points, memos)?;
+			Ok(().into())
+		}
 
-			Self::deposit_event(Event::Deposit(mixer_id, sender, deposit_size));
+		/// Deposits into several mixers at once, e.g. to split an amount
+		/// across their deposit sizes. `deposits` holds the data points of
+		/// each mixer, whose currencies can differ.
+		///
+		/// The balance of the sender is checked once for each currency, and
+		/// either all or none of the deposits are made.
+		///
+		/// Fails if `deposits` is empty, or if any of the mixers is stopped,
+		/// paused or not initialized.
+		///
+		/// Weights:
+		/// - Dependent on arguments: `deposits` and their data points
+		///
+		/// - Base weight: 417_168_400_000
+		/// - DB weights: 1 + 11 * deposits.len() reads, 1 + 6 * deposits.len()
+		///   writes
+		/// - Additional weights: 21_400_442_000 * total number of data points
+		#[pallet::weight(<T as Config>::WeightInfo::deposit_many(
+			deposits.len() as u32,
+			deposits.iter().map(|(_, data_points)| data_points.len() as u32).sum(),
+		))]
+		pub fn deposit_many(
+			origin: OriginFor<T>,
+			deposits: Vec<(T::TreeId, Vec<ScalarBytes>)>,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			Self::do_deposit_many(sender, deposits)?;
+			Ok(().into())
+		}
 
+		/// Deposits into the mixer like `deposit`, attaching to each of the
+		/// `data_points` its note encrypted to a viewing key. The encrypted
+		/// notes are published in `DepositDisclosure` events, along with the
+		/// index of their leaf.
+		///
+		/// Fails if there isn't one disclosure per data point, or if a
+		/// disclosure isn't an encrypted note (see [`disclosure`]).
+		///
+		/// Weights:
+		/// - Dependent on argument: `data_points`
+		///
+		/// - Base weight: 417_168_400_000
+		/// - DB weights: 12 reads, 7 writes
+		/// - Additional weights: 21_400_442_000 * data_points.len()
+		#[pallet::weight(<T as Config>::WeightInfo::deposit_with_disclosures(data_points.len() as u32))]
+		pub fn deposit_with_disclosures(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			data_points: Vec<ScalarBytes>,
+			disclosures: Vec<Vec<u8>>,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			ensure!(
+				disclosures.len() == data_points.len(),
+				Error::<T>::DisclosureCountMismatch
+			);
+			ensure!(
+				disclosures
+					.iter()
+					.all(|ciphertext| ciphertext.len() == disclosure::CIPHERTEXT_LEN),
+				Error::<T>::InvalidDisclosure
+			);
+			let first_leaf_index = T::Tree::get_leaf_count(mixer_id)?;
+			Self::do_deposit(sender, mixer_id, data_points, Vec::new())?;
+			for (i, ciphertext) in disclosures.into_iter().enumerate() {
+				Self::deposit_event(Event::DepositDisclosure(
+					mixer_id,
+					first_leaf_index + i as u32,
+					ciphertext,
+				));
+			}
 			Ok(().into())
 		}
 
@@ -305,57 +744,157 @@ pub mod pallet {
 		/// - Independent of the arguments.
 		///
 		/// - Base weight: 1_078_562_000_000
-		/// - DB weights: 9 reads, 3 writes
-		#[pallet::weight(<T as Config>::WeightInfo::withdraw())]
+		/// - DB weights: 15 reads, 8 writes
+		/// - Additional weights: the `OnRelay` hook
+		#[pallet::weight(<T as Config>::WeightInfo::withdraw().saturating_add(T::OnRelay::on_relay_weight()))]
 		pub fn withdraw(origin: OriginFor<T>, withdraw_proof: WithdrawProof<T>) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
+			let withdrawal = Self::check_withdrawal(&sender, &withdraw_proof)?;
+			Self::ensure_within_limits(
+				withdrawal.mixer_id,
+				Flow::Withdrawal,
+				withdrawal.mixer_info.fixed_deposit_size,
+			)?;
+			// Verify the zero-knowledge proof of membership provided
+			T::Tree::verify_zk_proof(withdraw_proof.into_zk_proof(&withdrawal))?;
+			Self::settle_withdrawal(sender, withdrawal)?;
+			Ok(().into())
+		}
+
+		/// Withdraws many deposits at once. The proofs of all withdrawals are
+		/// verified as a batch, which is cheaper than verifying them one by
+		/// one for Groth16 trees. Bulletproofs proofs are still verified one by
+		/// one, against a key prepared once for the whole batch, so
+		/// `MaxWithdrawBatchSize` must keep batches of them within a block.
+		///
+		/// If `atomic` is set, the call fails unless every withdrawal succeeds.
+		/// Otherwise each withdrawal succeeds or fails on its own, and failed
+		/// ones are reported with a `WithdrawFailed` event.
+		///
+		/// Fails if the batch is empty or larger than `MaxWithdrawBatchSize`.
+		///
+		/// Weights:
+		/// - Dependent on argument: `withdraw_proofs`
+		///
+		/// - Base weight: 400_000_000_000
+		/// - DB weights: 1 read, 15 reads and 8 writes * withdraw_proofs.len()
+		/// - Additional weights: 678_562_000_000 and the `OnRelay` hook *
+		///   withdraw_proofs.len()
+		#[pallet::weight(Pallet::<T>::batch_withdraw_weight(withdraw_proofs.len() as u32))]
+		pub fn batch_withdraw(
+			origin: OriginFor<T>,
+			withdraw_proofs: Vec<WithdrawProof<T>>,
+			atomic: bool,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			ensure!(!withdraw_proofs.is_empty(), Error::<T>::EmptyBatch);
 			ensure!(
-				!<MerklePallet<T>>::stopped(withdraw_proof.mixer_id),
-				Error::<T>::MixerStopped
+				withdraw_proofs.len() as u32 <= T::MaxWithdrawBatchSize::get(),
+				Error::<T>::BatchTooLarge
 			);
-			let recipient = withdraw_proof.recipient.unwrap_or(sender.clone());
-			let relayer = withdraw_proof.relayer.unwrap_or(sender.clone());
-			// get mixer info, should fail if tree isn't initialized
-			let mixer_info = Self::get_mixer(withdraw_proof.mixer_id)?;
-			// check if the nullifier has been used
-			T::Tree::has_used_nullifier(withdraw_proof.mixer_id.into(), withdraw_proof.nullifier_hash.clone())?;
-			// Verify the zero-knowledge proof of membership provided
-			T::Tree::verify_zk(
-				withdraw_proof.mixer_id.into(),
-				withdraw_proof.cached_block,
-				withdraw_proof.cached_root.clone(),
-				withdraw_proof.comms,
-				withdraw_proof.nullifier_hash.clone(),
-				withdraw_proof.proof_bytes,
-				withdraw_proof.leaf_index_commitments,
-				withdraw_proof.proof_commitments,
-				recipient.encode().to_vec(),
-				relayer.encode().to_vec(),
-			)?;
-			// transfer the fixed deposit size to the sender
-			T::Currency::transfer(
-				mixer_info.currency_id,
-				&Self::account_id(),
-				&recipient,
-				mixer_info.fixed_deposit_size,
-			)?;
-			// update the total value locked
-			let tvl = Self::total_value_locked(withdraw_proof.mixer_id);
-			<TotalValueLocked<T>>::insert(withdraw_proof.mixer_id, tvl - mixer_info.fixed_deposit_size);
-			// Add the nullifier on behalf of the module
-			T::Tree::add_nullifier(
-				Self::account_id(),
-				withdraw_proof.mixer_id.into(),
-				withdraw_proof.nullifier_hash,
-			)?;
 
-			Self::deposit_event(Event::Withdraw(
-				withdraw_proof.mixer_id,
-				sender,
-				recipient,
-				relayer,
-				withdraw_proof.cached_root,
-			));
+			// Check everything but the proofs first, so that only withdrawals
+			// which could succeed are verified
+			let mut withdrawals = Vec::with_capacity(withdraw_proofs.len());
+			for (i, withdraw_proof) in withdraw_proofs.iter().enumerate() {
+				let duplicate = withdraw_proofs[..i].iter().any(|other| {
+					other.mixer_id == withdraw_proof.mixer_id && other.nullifier_hash == withdraw_proof.nullifier_hash
+				});
+				let withdrawal = if duplicate {
+					Err(Error::<T>::DuplicateNullifier.into())
+				} else {
+					Self::check_withdrawal(&sender, withdraw_proof).and_then(|withdrawal| {
+						let size = withdrawal.mixer_info.fixed_deposit_size;
+						Self::ensure_within_limits(withdrawal.mixer_id, Flow::Withdrawal, size)?;
+						Ok(withdrawal)
+					})
+				};
+				if atomic {
+					withdrawal.as_ref().map_err(|e| *e)?;
+				}
+				withdrawals.push((
+					withdraw_proof.mixer_id,
+					withdraw_proof.nullifier_hash.clone(),
+					withdrawal,
+				));
+			}
+
+			let proofs = withdraw_proofs
+				.into_iter()
+				.zip(withdrawals.iter())
+				.filter_map(|(withdraw_proof, (_, _, withdrawal))| {
+					let withdrawal = withdrawal.as_ref().ok()?;
+					Some(withdraw_proof.into_zk_proof(withdrawal))
+				})
+				.collect();
+			// One result for each withdrawal that passed the checks, in order
+			let mut verified = T::Tree::verify_zk_batch(proofs).into_iter();
+
+			if atomic {
+				let mut pending = Vec::with_capacity(withdrawals.len());
+				// withdrawals from the same mixer add up against its limits, which are
+				// checked before settling, so that the `RateLimitHit` event isn't rolled
+				// back along with the settled withdrawals
+				let mut amounts: Vec<(T::TreeId, BalanceOf<T>)> = Vec::new();
+				for (mixer_id, _, withdrawal) in withdrawals {
+					let withdrawal = withdrawal?;
+					verified.next().unwrap_or_else(|| Err(Error::<T>::NoneValue.into()))?;
+					let size = withdrawal.mixer_info.fixed_deposit_size;
+					match amounts.iter_mut().find(|(id, _)| *id == mixer_id) {
+						Some((_, amount)) => *amount = amount.checked_add(&size).ok_or(Error::<T>::Overflow)?,
+						None => amounts.push((mixer_id, size)),
+					}
+					pending.push(withdrawal);
+				}
+				for (mixer_id, amount) in amounts {
+					Self::ensure_within_limits(mixer_id, Flow::Withdrawal, amount)?;
+				}
+				Self::settle_withdrawals(sender, pending)?;
+				return Ok(().into());
+			}
+
+			for (mixer_id, nullifier_hash, withdrawal) in withdrawals {
+				let res = withdrawal.and_then(|withdrawal| {
+					verified.next().unwrap_or_else(|| Err(Error::<T>::NoneValue.into()))?;
+					// the withdrawals settled before count against the limits
+					Self::ensure_within_limits(mixer_id, Flow::Withdrawal, withdrawal.mixer_info.fixed_deposit_size)?;
+					Self::settle_withdrawal(sender.clone(), withdrawal)
+				});
+				if let Err(e) = res {
+					Self::deposit_event(Event::WithdrawFailed(mixer_id, nullifier_hash, e));
+				}
+			}
+			Ok(().into())
+		}
+
+		/// Withdraws a deposit like `withdraw`, from an unsigned transaction.
+		/// The proof must bind the `FeeCollector` as relayer and the unsigned
+		/// withdrawal fee of the mixer as fee, which is paid out of the
+		/// withdrawn amount.
+		///
+		/// Fails if unsigned withdrawals from the mixer are not allowed, or if
+		/// the block already has `MaxUnsignedWithdrawalsPerBlock` of them.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 1_078_562_000_000
+		/// - DB weights: 17 reads, 9 writes
+		/// - Additional weights: the `OnRelay` hook
+		#[pallet::weight(<T as Config>::WeightInfo::withdraw_unsigned().saturating_add(T::OnRelay::on_relay_weight()))]
+		pub fn withdraw_unsigned(origin: OriginFor<T>, withdraw_proof: WithdrawProof<T>) -> DispatchResultWithPostInfo {
+			ensure_none(origin)?;
+			// every attempt counts toward the cap of the block, whether it succeeds
+			// or not, since its proof is verified all the same
+			Self::note_unsigned_withdrawal()?;
+			let withdrawal = Self::check_unsigned_withdrawal(&withdraw_proof)?;
+			Self::ensure_within_limits(
+				withdrawal.mixer_id,
+				Flow::Withdrawal,
+				withdrawal.mixer_info.fixed_deposit_size,
+			)?;
+			T::Tree::verify_zk_proof(withdraw_proof.into_zk_proof(&withdrawal))?;
+			Self::settle_withdrawal(T::FeeCollector::get(), withdrawal)?;
 			Ok(().into())
 		}
 
@@ -366,7 +905,7 @@ pub mod pallet {
 			setup: Setup,
 			size: BalanceOf<T>,
 		) -> DispatchResultWithPostInfo {
-			ensure_admin(origin, &Self::admin())?;
+			T::AdminOrigin::ensure_origin(origin)?;
 
 			<Self as ExtendedMixer<_>>::create_new(Self::account_id(), currency_id, setup, size)?;
 			Ok(().into())
@@ -380,47 +919,176 @@ pub mod pallet {
 			size: BalanceOf<T>,
 			key_id: T::KeyId,
 		) -> DispatchResultWithPostInfo {
-			ensure_admin(origin, &Self::admin())?;
+			T::AdminOrigin::ensure_origin(origin)?;
 
 			let tree_id = <Self as ExtendedMixer<_>>::create_new(Self::account_id(), currency_id, setup, size)?;
 			T::Tree::initialize_tree(tree_id, key_id)?;
 			Ok(().into())
 		}
 
-		/// Stops the operation of all the mixers managed by the pallet.
-		/// Can only be called by the admin or the root origin.
+		/// Stops or restarts the deposits and withdrawals of the mixer with id
+		/// of `mixer_id`. Can only be called by the `AdminOrigin`.
 		///
 		/// Weights:
 		/// - Independent of the arguments.
 		///
 		/// - Base weight: 36_000_000
-		/// - DB weights: 6 reads, 4 writes
+		/// - DB weights: 3 reads, 1 write
 		#[pallet::weight(<T as Config>::WeightInfo::set_stopped())]
-		pub fn set_stopped(origin: OriginFor<T>, stopped: bool) -> DispatchResultWithPostInfo {
-			// Ensure the caller is admin or root
-			ensure_admin(origin, &Self::admin())?;
-			// Set the mixer state, `stopped` can be true or false
-			let mixer_ids = MixerTreeIds::<T>::get();
-			for i in 0..mixer_ids.len() {
-				T::Tree::set_stopped(Self::account_id(), mixer_ids[i], stopped)?;
-			}
+		pub fn set_stopped(origin: OriginFor<T>, mixer_id: T::TreeId, stopped: bool) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(MixerTrees::<T>::contains_key(mixer_id), Error::<T>::NoMixerForId);
+			T::Tree::set_stopped(Self::account_id(), mixer_id, stopped)?;
+			Ok(().into())
+		}
+
+		/// Pauses or resumes the deposits and the withdrawals of the mixer with
+		/// id of `mixer_id`, independently of each other. Can only be called
+		/// by the `PauseOrigin`.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 10_000_000
+		/// - DB weights: 3 reads, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::set_paused())]
+		pub fn set_paused(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			deposits: bool,
+			withdrawals: bool,
+		) -> DispatchResultWithPostInfo {
+			T::PauseOrigin::ensure_origin(origin)?;
+			ensure!(MixerTrees::<T>::contains_key(mixer_id), Error::<T>::NoMixerForId);
+			Paused::<T>::insert(mixer_id, PauseState { deposits, withdrawals });
+			Self::deposit_event(Event::PauseSet(mixer_id, deposits, withdrawals));
+			Ok(().into())
+		}
+
+		/// Sets the maximum fee relayers can take from withdrawals of the mixer
+		/// with id of `mixer_id`. Can only be called by the `AdminOrigin`.
+		///
+		/// Fails if the fee is higher than the deposit size of the mixer.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 10_000_000
+		/// - DB weights: 3 reads, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::set_max_relayer_fee())]
+		pub fn set_max_relayer_fee(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			max_fee: BalanceOf<T>,
+		) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			let mixer_info = Self::get_mixer(mixer_id)?;
+			ensure!(
+				max_fee <= mixer_info.fixed_deposit_size,
+				Error::<T>::FeeExceedsDepositSize
+			);
+			MaxRelayerFee::<T>::insert(mixer_id, max_fee);
 			Ok(().into())
 		}
 
-		/// Transfers the admin from the caller to the specified `to` account.
-		/// Can only be called by the current admin or the root origin.
+		/// Sets the volume limits and root cooldown of the mixer with id of
+		/// `mixer_id`. Can only be called by the `AdminOrigin`.
+		///
+		/// Fails if epoch limits are set with an epoch length of zero.
 		///
 		/// Weights:
 		/// - Independent of the arguments.
 		///
-		/// - Base weight: 7_000_000
+		/// - Base weight: 10_000_000
 		/// - DB weights: 1 read, 1 write
-		#[pallet::weight(<T as Config>::WeightInfo::transfer_admin())]
-		pub fn transfer_admin(origin: OriginFor<T>, to: T::AccountId) -> DispatchResultWithPostInfo {
-			// Ensures that the caller is the root or the current admin
-			ensure_admin(origin, &Self::admin())?;
-			// Updating the admin
-			Admin::<T>::set(to);
+		#[pallet::weight(<T as Config>::WeightInfo::set_limits())]
+		pub fn set_limits(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			limits: MixerLimits<BalanceOf<T>, T::BlockNumber>,
+		) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			ensure!(MixerTrees::<T>::contains_key(mixer_id), Error::<T>::NoMixerForId);
+			let epoch_limited = limits.deposits.per_epoch.is_some() || limits.withdrawals.per_epoch.is_some();
+			ensure!(
+				!epoch_limited || !limits.epoch_length.is_zero(),
+				Error::<T>::InvalidLimits
+			);
+			Limits::<T>::insert(mixer_id, limits);
+			Self::deposit_event(Event::LimitsSet(mixer_id));
+			Ok(().into())
+		}
+
+		/// Sets the fee of unsigned withdrawals from the mixer with id of
+		/// `mixer_id`, or disallows them with `None`. Can only be called by the
+		/// `AdminOrigin`.
+		///
+		/// The fee is a relayer fee paid to the `FeeCollector`, so it can't be
+		/// higher than the maximum relayer fee of the mixer.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 10_000_000
+		/// - DB weights: 3 reads, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::set_unsigned_withdrawal_fee())]
+		pub fn set_unsigned_withdrawal_fee(
+			origin: OriginFor<T>,
+			mixer_id: T::TreeId,
+			fee: Option<BalanceOf<T>>,
+		) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			Self::get_mixer(mixer_id)?;
+			ensure!(
+				fee.map_or(true, |fee| fee <= Self::max_relayer_fee(mixer_id)),
+				Error::<T>::FeeExceedsMax
+			);
+			UnsignedWithdrawalFee::<T>::set(mixer_id, fee);
+			Ok(().into())
+		}
+
+		/// Checks the invariants of all the mixers, emitting an
+		/// `InvariantViolated` event for each violation found. Can only be
+		/// called by the `AdminOrigin`.
+		///
+		/// Doesn't change the state of the mixers, mismatches have to be
+		/// investigated and fixed separately.
+		///
+		/// Weights:
+		/// - Dependent on the number of mixers
+		///
+		/// - Base weight: 20_000_000 + 12_000_000 * m
+		/// - DB weights: 1 + 5 * m reads
+		#[pallet::weight(<T as Config>::WeightInfo::reconcile(MixerTreeIds::<T>::decode_len().unwrap_or(0) as u32))]
+		pub fn reconcile(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			let violations = Self::check_invariants();
+			let count = violations.len() as u32;
+			for violation in violations {
+				Self::deposit_event(Event::InvariantViolated(violation));
+			}
+			Self::deposit_event(Event::Reconciled(count));
+			Ok(().into())
+		}
+
+		/// Transfers `amount` of the fees collected by the `FeeCollector` in
+		/// `currency_id` to `dest`. Can only be called by the `AdminOrigin`.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 30_000_000
+		/// - DB weights: 2 reads, 2 writes
+		#[pallet::weight(<T as Config>::WeightInfo::sweep_fees())]
+		pub fn sweep_fees(
+			origin: OriginFor<T>,
+			currency_id: CurrencyIdOf<T>,
+			amount: BalanceOf<T>,
+			dest: T::AccountId,
+		) -> DispatchResultWithPostInfo {
+			T::AdminOrigin::ensure_origin(origin)?;
+			T::Currency::transfer(currency_id, &T::FeeCollector::get(), &dest, amount)?;
+			Self::deposit_event(Event::FeesSwept(currency_id, amount, dest));
 			Ok(().into())
 		}
 	}
@@ -447,8 +1115,12 @@ pub struct WithdrawProof<T: Config> {
 	proof_commitments: Vec<ScalarBytes>,
 	/// The recipient to withdraw amount of currency to
 	recipient: Option<T::AccountId>,
-	/// The recipient to withdraw amount of currency to
+	/// The relayer submitting the withdrawal
 	relayer: Option<T::AccountId>,
+	/// The fee paid to the relayer out of the withdrawn amount
+	fee: BalanceOf<T>,
+	/// The amount of native currency forwarded by the sender to the recipient
+	refund: BalanceOf<T>,
 }
 
 impl<T: Config> WithdrawProof<T> {
@@ -463,6 +1135,8 @@ impl<T: Config> WithdrawProof<T> {
 		proof_commitments: Vec<ScalarBytes>,
 		recipient: Option<T::AccountId>,
 		relayer: Option<T::AccountId>,
+		fee: BalanceOf<T>,
+		refund: BalanceOf<T>,
 	) -> Self {
 		Self {
 			mixer_id,
@@ -475,10 +1149,56 @@ impl<T: Config> WithdrawProof<T> {
 			proof_commitments,
 			recipient,
 			relayer,
+			fee,
+			refund,
 		}
 	}
 }
 
+impl<T: Config> WithdrawProof<T> {
+	/// Membership proof of the withdrawal, bound to its recipient, relayer,
+	/// fee and refund, and for bound leaves to the asset and deposit size of
+	/// the mixer
+	fn into_zk_proof(self, withdrawal: &PendingWithdrawal<T>) -> ZkProof<T> {
+		ZkProof {
+			tree_id: self.mixer_id,
+			cached_block: self.cached_block,
+			cached_root: self.cached_root,
+			comms: self.comms,
+			nullifier_hash: self.nullifier_hash,
+			proof_bytes: self.proof_bytes,
+			leaf_index_commitments: self.leaf_index_commitments,
+			proof_commitments: self.proof_commitments,
+			recipient: withdrawal.recipient.encode(),
+			relayer: Pallet::<T>::relayer_input(&withdrawal.relayer, withdrawal.fee, withdrawal.refund),
+			binding: Pallet::<T>::leaf_binding(withdrawal.mixer_id, &withdrawal.mixer_info),
+		}
+	}
+}
+
+/// Withdrawal that passed every check except for the proof verification
+pub struct PendingWithdrawal<T: Config> {
+	/// The mixer to withdraw from
+	pub mixer_id: T::TreeId,
+	/// Info of the mixer
+	pub mixer_info: MixerInfo<T>,
+	/// The nullifier hash spent by the withdrawal
+	pub nullifier_hash: ScalarBytes,
+	/// The cached root the withdrawal is proven against
+	pub cached_root: ScalarBytes,
+	/// The account receiving the withdrawn amount
+	pub recipient: T::AccountId,
+	/// The relayer submitting the withdrawal
+	pub relayer: T::AccountId,
+	/// The fee paid to the relayer out of the withdrawn amount
+	pub fee: BalanceOf<T>,
+	/// The amount of native currency forwarded by the sender to the recipient
+	pub refund: BalanceOf<T>,
+	/// Whether the withdrawal is submitted by the relayer named in its proof
+	/// on behalf of another recipient
+	pub relayed: bool,
+}
+
 // TODO: Not sure why compiler is complaining without this since it implements
 // Debug
 #[cfg(feature = "std")]
@@ -494,6 +1214,178 @@ pub type BalanceOf<T> = <<T as Config>::Currency as MultiCurrency<<T as frame_sy
 pub type CurrencyIdOf<T> =
 	<<T as pallet::Config>::Currency as MultiCurrency<<T as frame_system::Config>::AccountId>>::CurrencyId;
 
+/// Pause flags of a mixer, set by the `PauseOrigin`
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq)]
+pub struct PauseState {
+	/// Whether deposits into the mixer are paused
+	pub deposits: bool,
+	/// Whether withdrawals from the mixer are paused
+	pub withdrawals: bool,
+}
+
+/// Storage layouts of the pallet, in the order they were released
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Copy, Encode, Decode, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Releases {
+	/// Spent nullifiers are only stored by the merkle pallet
+	V1,
+	/// The number of spent nullifiers of each mixer is stored
+	V2NullifierCounts,
+	/// Statistics buckets older than the history are pruned
+	V3PrunedActivity,
+}
+
+impl Default for Releases {
+	fn default() -> Self {
+		Releases::V1
+	}
+}
+
+/// Direction of the funds of a mixer
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+	/// Funds deposited into the mixer
+	Deposit,
+	/// Funds withdrawn from the mixer
+	Withdrawal,
+}
+
+/// Volume limit of a mixer
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+	/// Limit of the volume deposited in a block
+	DepositsPerBlock,
+	/// Limit of the volume deposited in an epoch
+	DepositsPerEpoch,
+	/// Limit of the volume withdrawn in a block
+	WithdrawalsPerBlock,
+	/// Limit of the volume withdrawn in an epoch
+	WithdrawalsPerEpoch,
+}
+
+/// Limits of the volume flowing into or out of a mixer. `None` means
+/// unlimited.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct FlowLimits<Balance> {
+	/// Maximum volume in a block
+	pub per_block: Option<Balance>,
+	/// Maximum volume in an epoch
+	pub per_epoch: Option<Balance>,
+}
+
+/// Volume limits and root cooldown of a mixer, set by the `AdminOrigin`
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct MixerLimits<Balance, BlockNumber> {
+	/// Limits of the deposited volume
+	pub deposits: FlowLimits<Balance>,
+	/// Limits of the withdrawn volume
+	pub withdrawals: FlowLimits<Balance>,
+	/// Length of an epoch in blocks, epochs start at multiples of it
+	pub epoch_length: BlockNumber,
+	/// Number of blocks a root has to be cached for before withdrawals can
+	/// prove against it
+	pub root_cooldown: BlockNumber,
+}
+
+/// Volume flowing into or out of a mixer
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct FlowVolume<Balance> {
+	/// Volume in the current block
+	pub in_block: Balance,
+	/// Volume in the current epoch
+	pub in_epoch: Balance,
+}
+
+/// Volume of a mixer in a block and an epoch
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct MixerVolume<Balance, BlockNumber> {
+	/// The block of the volume
+	pub block: BlockNumber,
+	/// The epoch of the volume
+	pub epoch: BlockNumber,
+	/// Deposited volume
+	pub deposits: FlowVolume<Balance>,
+	/// Withdrawn volume
+	pub withdrawals: FlowVolume<Balance>,
+}
+
+/// Number of deposits and withdrawals of a mixer in a statistics bucket
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+pub struct ActivityCount {
+	/// Number of deposited leaves
+	pub deposits: u32,
+	/// Number of withdrawals
+	pub withdrawals: u32,
+}
+
+/// Anonymity set statistics of a mixer, reported by the [`MixerApi`]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub struct MixerStats<BlockNumber> {
+	/// Number of leaves in the tree of the mixer
+	pub leaf_count: u32,
+	/// Number of nullifiers spent by withdrawals
+	pub spent_nullifier_count: u32,
+	/// Number of deposits not withdrawn yet
+	pub active_deposits: u32,
+	/// Number of deposits in the requested window
+	pub recent_deposits: u32,
+	/// Number of withdrawals in the requested window
+	pub recent_withdrawals: u32,
+	/// Estimated number of unspent deposits by their age in blocks, from the
+	/// oldest. Ages are those of the start of their statistics bucket.
+	pub unspent_deposit_ages: Vec<(BlockNumber, u32)>,
+}
+
+/// Violated invariant of the mixers, reported by `reconcile`
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub enum InvariantViolation<CurrencyId, Balance, TreeId> {
+	/// Balance of the pallet is lower than the total value locked in the
+	/// mixers of the currency
+	Undercollateralized {
+		currency_id: CurrencyId,
+		balance: Balance,
+		total_value_locked: Balance,
+	},
+	/// Total value locked of the mixer doesn't match its outstanding deposits
+	DepositsMismatch {
+		mixer_id: TreeId,
+		leaf_count: u32,
+		spent_nullifier_count: u32,
+		total_value_locked: Balance,
+	},
+}
+
+/// Mixer declared in the genesis config
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+#[derive(Clone, PartialEq)]
+pub struct MixerGenesisConfig<CurrencyId, Balance> {
+	/// Currency of the deposits
+	pub currency_id: CurrencyId,
+	/// Fixed deposit size of the mixer
+	pub size: Balance,
+	/// Hash function and proving backend of the tree
+	pub setup: Setup,
+	/// Depth of the tree
+	pub depth: u8,
+	/// Index of the verifying key of the tree in the genesis `verifying_keys`
+	pub verifying_key: u32,
+	/// Format of the leaves of the tree, opaque by default
+	#[cfg_attr(feature = "std", serde(default))]
+	pub leaf_format: LeafFormat,
+}
+
 /// Info about the mixer and it's leaf data
 #[derive(Encode, Decode, PartialEq)]
 pub struct MixerInfo<T: Config> {
@@ -545,59 +1437,624 @@ impl<T: Config> Pallet<T> {
 		Ok(mixer_info)
 	}
 
-	pub fn initialize_first_stage() -> dispatch::DispatchResult {
-		ensure!(!Self::first_stage_initialized(), Error::<T>::AlreadyInitialized);
+	/// Transfers the deposits of `data_points` from `sender` to the mixer and
+	/// adds them to its tree, along with their `memos` if there are any.
+	/// Either the whole deposit is made or nothing is written.
+	pub fn do_deposit(
+		sender: T::AccountId,
+		mixer_id: T::TreeId,
+		data_points: Vec<ScalarBytes>,
+		memos: Vec<Vec<u8>>,
+	) -> dispatch::DispatchResult {
+		ensure!(!T::DenyList::is_denied(&sender), Error::<T>::AccountDenied);
+		ensure!(!<MerklePallet<T>>::stopped(mixer_id), Error::<T>::MixerStopped);
+		ensure!(!Self::paused(mixer_id).deposits, Error::<T>::DepositsPaused);
+		// get mixer info, should always exist if the module is initialized
+		let mixer_info = Self::get_mixer(mixer_id)?;
+		// ensure the sender has enough balance to cover deposit
+		let balance = T::Currency::free_balance(mixer_info.currency_id, &sender);
+		let deposit = Self::deposit_amount(&mixer_info, data_points.len())?;
+		ensure!(balance >= deposit, Error::<T>::InsufficientBalance);
+		if !memos.is_empty() {
+			<MerklePallet<T>>::ensure_valid_memos(data_points.len(), &memos)?;
+		}
+		// checked before anything is written, so that the `RateLimitHit` event isn't
+		// rolled back along with the writes
+		Self::ensure_within_limits(mixer_id, Flow::Deposit, deposit)?;
+		Self::apply_deposit(sender, mixer_id, mixer_info, deposit, data_points, memos)
+	}
 
-		// Get default admin from trait params
-		let default_admin = T::DefaultAdmin::get();
-		// Initialize the admin in storage with default one
-		Admin::<T>::set(default_admin);
+	/// Writes a deposit checked by `do_deposit`.
+	#[transactional]
+	fn apply_deposit(
+		sender: T::AccountId,
+		mixer_id: T::TreeId,
+		mixer_info: MixerInfo<T>,
+		deposit: BalanceOf<T>,
+		data_points: Vec<ScalarBytes>,
+		memos: Vec<Vec<u8>>,
+	) -> dispatch::DispatchResult {
+		let volume = Self::volume_after(mixer_id, Flow::Deposit, deposit).map_err(|_| Error::<T>::RateLimitExceeded)?;
+		let tvl = Self::total_value_locked(mixer_id)
+			.checked_add(&deposit)
+			.ok_or(Error::<T>::Overflow)?;
+		// transfer the deposit to the module
+		T::Currency::transfer(mixer_info.currency_id, &sender, &Self::account_id(), deposit)?;
+		Volume::<T>::insert(mixer_id, volume);
+		Self::record_activity(mixer_id, data_points.len() as u32, 0);
+		// update the total value locked
+		<TotalValueLocked<T>>::insert(mixer_id, tvl);
+		// add elements to the mixer group's merkle tree and save the leaves
+		if memos.is_empty() {
+			T::Tree::add_members(Self::account_id(), mixer_id.into(), data_points)?;
+		} else {
+			T::Tree::add_members_with_memos(Self::account_id(), mixer_id.into(), data_points, memos)?;
+		}
 
-		// Getting the sizes from the config
-		let sizes = T::MixerSizes::get();
+		let deposit_size = mixer_info.fixed_deposit_size;
 
-		let hasher = HashFunction::PoseidonDefault;
-		let backend = Backend::Bulletproofs(Curve::Curve25519);
-		let setup = Setup::new(hasher.clone(), backend.clone());
+		Self::deposit_event(Event::Deposit(mixer_id, sender, deposit_size));
+		Ok(())
+	}
 
-		// Iterating over configured sizes and initializing the mixers
-		for size in sizes.into_iter() {
-			<Self as ExtendedMixer<_>>::create_new(
-				Self::account_id(),
-				T::NativeCurrencyId::get(),
-				setup.clone(),
-				size,
-			)?;
+	/// Deposits into several mixers, checking the balance of the sender and
+	/// transferring the deposits once for each currency. Either all or none
+	/// of the deposits are made.
+	pub fn do_deposit_many(
+		sender: T::AccountId,
+		deposits: Vec<(T::TreeId, Vec<ScalarBytes>)>,
+	) -> dispatch::DispatchResult {
+		ensure!(!deposits.is_empty(), Error::<T>::EmptyDeposits);
+		ensure!(!T::DenyList::is_denied(&sender), Error::<T>::AccountDenied);
+		let mut deposit_sizes = Vec::with_capacity(deposits.len());
+		// a mixer can be deposited into more than once, so its deposits add up
+		// against its limits
+		let mut amounts: Vec<(T::TreeId, BalanceOf<T>)> = Vec::new();
+		let mut totals: Vec<(CurrencyIdOf<T>, BalanceOf<T>)> = Vec::new();
+		for (mixer_id, data_points) in &deposits {
+			ensure!(!<MerklePallet<T>>::stopped(*mixer_id), Error::<T>::MixerStopped);
+			ensure!(!Self::paused(*mixer_id).deposits, Error::<T>::DepositsPaused);
+			let mixer_info = Self::get_mixer(*mixer_id)?;
+			let deposit = Self::deposit_amount(&mixer_info, data_points.len())?;
+			match amounts.iter_mut().find(|(id, _)| id == mixer_id) {
+				Some((_, amount)) => *amount = amount.checked_add(&deposit).ok_or(Error::<T>::Overflow)?,
+				None => amounts.push((*mixer_id, deposit)),
+			}
+			match totals
+				.iter_mut()
+				.find(|(currency_id, _)| *currency_id == mixer_info.currency_id)
+			{
+				Some((_, total)) => *total = total.checked_add(&deposit).ok_or(Error::<T>::Overflow)?,
+				None => totals.push((mixer_info.currency_id, deposit)),
+			}
+			deposit_sizes.push((mixer_info.fixed_deposit_size, deposit));
+		}
+		// ensure the sender has enough balance to cover the deposits of each currency
+		for (currency_id, total) in &totals {
+			ensure!(
+				T::Currency::free_balance(*currency_id, &sender) >= *total,
+				Error::<T>::InsufficientBalance
+			);
 		}
+		// checked before anything is written, so that the `RateLimitHit` event isn't
+		// rolled back along with the writes
+		for (mixer_id, amount) in &amounts {
+			Self::ensure_within_limits(*mixer_id, Flow::Deposit, *amount)?;
+		}
+		Self::apply_deposit_many(sender, deposits, deposit_sizes, amounts, totals)
+	}
 
-		FirstStageInitialized::<T>::set(true);
+	/// Writes the deposits checked by `do_deposit_many`, given the deposit
+	/// sizes and amounts of each deposit, the amount deposited into each
+	/// mixer and the total of each currency.
+	#[transactional]
+	fn apply_deposit_many(
+		sender: T::AccountId,
+		deposits: Vec<(T::TreeId, Vec<ScalarBytes>)>,
+		deposit_sizes: Vec<(BalanceOf<T>, BalanceOf<T>)>,
+		amounts: Vec<(T::TreeId, BalanceOf<T>)>,
+		totals: Vec<(CurrencyIdOf<T>, BalanceOf<T>)>,
+	) -> dispatch::DispatchResult {
+		for (mixer_id, amount) in amounts {
+			let volume =
+				Self::volume_after(mixer_id, Flow::Deposit, amount).map_err(|_| Error::<T>::RateLimitExceeded)?;
+			Volume::<T>::insert(mixer_id, volume);
+		}
+		for (currency_id, total) in totals {
+			T::Currency::transfer(currency_id, &sender, &Self::account_id(), total)?;
+		}
+		for ((mixer_id, data_points), (deposit_size, deposit)) in deposits.into_iter().zip(deposit_sizes) {
+			let tvl = Self::total_value_locked(mixer_id)
+				.checked_add(&deposit)
+				.ok_or(Error::<T>::Overflow)?;
+			<TotalValueLocked<T>>::insert(mixer_id, tvl);
+			Self::record_activity(mixer_id, data_points.len() as u32, 0);
+			T::Tree::add_members(Self::account_id(), mixer_id.into(), data_points)?;
+			Self::deposit_event(Event::Deposit(mixer_id, sender.clone(), deposit_size));
+		}
 		Ok(())
 	}
 
-	pub fn initialize_second_stage() -> dispatch::DispatchResult {
-		ensure!(Self::first_stage_initialized(), Error::<T>::AlreadyInitialized);
+	/// Fails with `RateLimitExceeded` if `amount` more flowing into or out of
+	/// the mixer goes over one of its volume limits, which is reported with a
+	/// `RateLimitHit` event.
+	///
+	/// The event is rolled back if a transactional call fails, so this must be
+	/// called before entering one.
+	pub fn ensure_within_limits(mixer_id: T::TreeId, flow: Flow, amount: BalanceOf<T>) -> dispatch::DispatchResult {
+		Self::volume_after(mixer_id, flow, amount).map_err(|limit| {
+			Self::deposit_event(Event::RateLimitHit(mixer_id, limit));
+			Error::<T>::RateLimitExceeded
+		})?;
+		Ok(())
+	}
 
-		let key_data = merkle::utils::keys::get_bp_gen_bytes(&BulletproofGens::new(16400, 1));
-		let key_id = T::Tree::add_verifying_key(key_data)?;
+	/// Volume of the mixer once `amount` more flows into or out of it, in the
+	/// current block and epoch.
+	///
+	/// Fails with the limit of the mixer that goes over, if any.
+	pub fn volume_after(
+		mixer_id: T::TreeId,
+		flow: Flow,
+		amount: BalanceOf<T>,
+	) -> Result<MixerVolume<BalanceOf<T>, T::BlockNumber>, RateLimit> {
+		let limits = Self::limits(mixer_id);
+		let now = <frame_system::Pallet<T>>::block_number();
+		let epoch = if limits.epoch_length.is_zero() {
+			Zero::zero()
+		} else {
+			now / limits.epoch_length
+		};
+		// the volume of past blocks and epochs no longer counts
+		let mut volume = Self::volume(mixer_id);
+		if volume.block != now {
+			volume.block = now;
+			volume.deposits.in_block = Zero::zero();
+			volume.withdrawals.in_block = Zero::zero();
+		}
+		if volume.epoch != epoch {
+			volume.epoch = epoch;
+			volume.deposits.in_epoch = Zero::zero();
+			volume.withdrawals.in_epoch = Zero::zero();
+		}
+
+		let (flow_limits, flow_volume, block_limit, epoch_limit) = match flow {
+			Flow::Deposit => (
+				limits.deposits,
+				&mut volume.deposits,
+				RateLimit::DepositsPerBlock,
+				RateLimit::DepositsPerEpoch,
+			),
+			Flow::Withdrawal => (
+				limits.withdrawals,
+				&mut volume.withdrawals,
+				RateLimit::WithdrawalsPerBlock,
+				RateLimit::WithdrawalsPerEpoch,
+			),
+		};
+		flow_volume.in_block = flow_volume.in_block.saturating_add(amount);
+		flow_volume.in_epoch = flow_volume.in_epoch.saturating_add(amount);
+		let hit = if flow_limits.per_block.map_or(false, |max| flow_volume.in_block > max) {
+			Some(block_limit)
+		} else if flow_limits.per_epoch.map_or(false, |max| flow_volume.in_epoch > max) {
+			Some(epoch_limit)
+		} else {
+			None
+		};
+		match hit {
+			Some(limit) => Err(limit),
+			None => Ok(volume),
+		}
+	}
+
+	/// Checks the invariants of all the mixers, returning the violations
+	/// found
+	pub fn check_invariants() -> Vec<InvariantViolation<CurrencyIdOf<T>, BalanceOf<T>, T::TreeId>> {
+		let mut violations = Vec::new();
+		let mut locked: Vec<(CurrencyIdOf<T>, BalanceOf<T>)> = Vec::new();
+		for mixer_id in MixerTreeIds::<T>::get() {
+			let mixer_info = match Self::get_mixer(mixer_id) {
+				Ok(mixer_info) => mixer_info,
+				Err(_) => continue,
+			};
+			let total_value_locked = Self::total_value_locked(mixer_id);
+			match locked.iter_mut().find(|(id, _)| *id == mixer_info.currency_id) {
+				Some((_, total)) => *total = total.saturating_add(total_value_locked),
+				None => locked.push((mixer_info.currency_id, total_value_locked)),
+			}
+
+			// every leaf not spent yet is a deposit of the fixed size
+			let leaf_count = T::Tree::get_leaf_count(mixer_id).unwrap_or_default();
+			let spent_nullifier_count = Self::nullifier_count(mixer_id);
+			let outstanding = leaf_count
+				.checked_sub(spent_nullifier_count)
+				.and_then(|count| Self::deposit_amount(&mixer_info, count as usize).ok());
+			if outstanding != Some(total_value_locked) {
+				violations.push(InvariantViolation::DepositsMismatch {
+					mixer_id,
+					leaf_count,
+					spent_nullifier_count,
+					total_value_locked,
+				});
+			}
+		}
+
+		let account_id = Self::account_id();
+		for (currency_id, total_value_locked) in locked {
+			let balance = T::Currency::free_balance(currency_id, &account_id);
+			if balance < total_value_locked {
+				violations.push(InvariantViolation::Undercollateralized {
+					currency_id,
+					balance,
+					total_value_locked,
+				});
+			}
+		}
+		violations
+	}
+
+	/// Amount of `count` deposits into the mixer
+	fn deposit_amount(mixer_info: &MixerInfo<T>, count: usize) -> Result<BalanceOf<T>, dispatch::DispatchError> {
+		let count: BalanceOf<T> = (count as u32).into();
+		let amount = mixer_info
+			.fixed_deposit_size
+			.checked_mul(&count)
+			.ok_or(Error::<T>::Overflow)?;
+		Ok(amount)
+	}
+
+	/// Start of the statistics bucket `block_number` is in
+	fn stats_bucket(block_number: T::BlockNumber) -> T::BlockNumber {
+		let length = T::StatsBucketLength::get();
+		if length.is_zero() {
+			return block_number;
+		}
+		block_number - block_number % length
+	}
+
+	/// Start of the latest statistics bucket out of the history at
+	/// `block_number`, if any
+	pub fn expired_bucket(block_number: T::BlockNumber) -> Option<T::BlockNumber> {
+		let length = T::StatsBucketLength::get().max(One::one());
+		let history = length.saturating_mul(T::StatsHistoryLength::get().into());
+		Self::stats_bucket(block_number).checked_sub(&history)
+	}
+
+	/// Adds the counts of the bucket of the mixer starting at `bucket` to the
+	/// totals of the pruned buckets, and removes it
+	pub fn expire_activity(mixer_id: T::TreeId, bucket: T::BlockNumber) {
+		let activity = Activity::<T>::take(mixer_id, bucket);
+		if activity == ActivityCount::default() {
+			return;
+		}
+		ExpiredActivity::<T>::mutate(mixer_id, |expired| {
+			expired.deposits = expired.deposits.saturating_add(activity.deposits);
+			expired.withdrawals = expired.withdrawals.saturating_add(activity.withdrawals);
+		});
+	}
+
+	/// Prunes the bucket of each mixer falling out of the history at the start
+	/// of a new bucket, returning the weight used
+	fn prune_activity(now: T::BlockNumber) -> Weight {
+		if Self::stats_bucket(now) != now {
+			return 0;
+		}
+		let bucket = match Self::expired_bucket(now) {
+			Some(bucket) => bucket,
+			None => return 0,
+		};
 		let mixer_ids = MixerTreeIds::<T>::get();
-		for i in 0..mixer_ids.len() {
-			let tree_id = mixer_ids[i];
-			T::Tree::initialize_tree(tree_id, key_id)?;
+		for mixer_id in &mixer_ids {
+			Self::expire_activity(*mixer_id, bucket);
+		}
+		<T as Config>::WeightInfo::prune_activity(mixer_ids.len() as u32)
+	}
+
+	/// Counts deposits and withdrawals of the mixer in the current bucket
+	fn record_activity(mixer_id: T::TreeId, deposits: u32, withdrawals: u32) {
+		let bucket = Self::stats_bucket(<frame_system::Pallet<T>>::block_number());
+		Activity::<T>::mutate(mixer_id, bucket, |activity| {
+			activity.deposits = activity.deposits.saturating_add(deposits);
+			activity.withdrawals = activity.withdrawals.saturating_add(withdrawals);
+		});
+	}
+
+	/// Anonymity set statistics of the mixer, counting the deposits and
+	/// withdrawals of the buckets overlapping the last `window` blocks.
+	///
+	/// Returns `None` if the mixer doesn't exist.
+	pub fn get_stats(mixer_id: T::TreeId, window: T::BlockNumber) -> Option<MixerStats<T::BlockNumber>> {
+		if !MixerTrees::<T>::contains_key(mixer_id) {
+			return None;
+		}
+		let leaf_count = T::Tree::get_leaf_count(mixer_id).ok()?;
+		let spent_nullifier_count = Self::nullifier_count(mixer_id);
+		let now = <frame_system::Pallet<T>>::block_number();
+		let window_start = Self::stats_bucket(now.saturating_sub(window));
+
+		let mut buckets: Vec<(T::BlockNumber, ActivityCount)> = Activity::<T>::iter_prefix(mixer_id).collect();
+		buckets.sort_by(|(a, _), (b, _)| a.cmp(b));
+		let recent = buckets.iter().filter(|(bucket, _)| *bucket >= window_start);
+		let recent_deposits = recent.clone().map(|(_, activity)| activity.deposits).sum();
+		let recent_withdrawals = recent.map(|(_, activity)| activity.withdrawals).sum();
+
+		// the deposits of the pruned buckets are the oldest, at least as old as the
+		// latest of them
+		let expired_age = Self::expired_bucket(now).map_or_else(Zero::zero, |bucket| now.saturating_sub(bucket));
+		let ages = sp_std::iter::once((expired_age, Self::expired_activity(mixer_id).deposits)).chain(
+			buckets
+				.into_iter()
+				.map(|(bucket, activity)| (now.saturating_sub(bucket), activity.deposits)),
+		);
+
+		// spend the oldest deposits first
+		let mut spent = spent_nullifier_count;
+		let mut unspent_deposit_ages = Vec::new();
+		for (age, deposits) in ages {
+			let spent_here = spent.min(deposits);
+			spent -= spent_here;
+			if deposits > spent_here {
+				unspent_deposit_ages.push((age, deposits - spent_here));
+			}
 		}
 
-		SecondStageInitialized::<T>::set(true);
+		Some(MixerStats {
+			leaf_count,
+			spent_nullifier_count,
+			active_deposits: leaf_count.saturating_sub(spent_nullifier_count),
+			recent_deposits,
+			recent_withdrawals,
+			unspent_deposit_ages,
+		})
+	}
+
+	/// Public input binding the relayer, fee and refund of a withdrawal into
+	/// its proof, as specified in the [relayer public input](crate#relayer-public-input)
+	/// section of the pallet docs.
+	///
+	/// Withdrawals without a fee or refund use the encoded relayer. Otherwise
+	/// the input is the hash of `(relayer, fee, refund)` with its most
+	/// significant byte cleared, which makes it a canonical scalar of every
+	/// supported curve.
+	pub fn relayer_input(relayer: &T::AccountId, fee: BalanceOf<T>, refund: BalanceOf<T>) -> ScalarBytes {
+		if fee.is_zero() && refund.is_zero() {
+			return relayer.encode();
+		}
+		let mut input = BlakeTwo256::hash_of(&(relayer, fee, refund)).to_fixed_bytes();
+		input[31] = 0;
+		input.to_vec()
+	}
+
+	/// Runs the checks of `check_withdrawal` for an unsigned withdrawal, whose
+	/// fee is paid to the `FeeCollector`, which is also the sender of the
+	/// withdrawal. The cap of unsigned withdrawals per block isn't checked.
+	pub fn check_unsigned_withdrawal(
+		withdraw_proof: &WithdrawProof<T>,
+	) -> Result<PendingWithdrawal<T>, dispatch::DispatchError> {
+		let fee =
+			Self::unsigned_withdrawal_fee(withdraw_proof.mixer_id).ok_or(Error::<T>::UnsignedWithdrawalsDisabled)?;
+		let fee_collector = T::FeeCollector::get();
+		ensure!(
+			withdraw_proof.recipient.is_some()
+				&& withdraw_proof.relayer.as_ref() == Some(&fee_collector)
+				&& withdraw_proof.fee == fee
+				&& withdraw_proof.refund.is_zero(),
+			Error::<T>::InvalidUnsignedWithdrawal
+		);
+		Self::check_withdrawal(&fee_collector, withdraw_proof)
+	}
+
+	/// Runs the checks of an unsigned withdrawal except for the proof
+	/// verification, reporting failures as the validity of the transaction.
+	fn validate_unsigned_withdrawal(
+		withdraw_proof: &WithdrawProof<T>,
+	) -> Result<PendingWithdrawal<T>, TransactionValidityError> {
+		// the withdrawal fits in a later block
+		if Self::unsigned_withdrawals_in_block() >= T::MaxUnsignedWithdrawalsPerBlock::get() {
+			return Err(InvalidTransaction::ExhaustsResources.into());
+		}
+		let withdrawal = Self::check_unsigned_withdrawal(withdraw_proof).map_err(|e| {
+			let used_nullifier: dispatch::DispatchError = merkle::Error::<T>::AlreadyUsedNullifier.into();
+			let in_cooldown: dispatch::DispatchError = Error::<T>::RootInCooldown.into();
+			if e == used_nullifier {
+				// a spent nullifier won't become valid again
+				InvalidTransaction::Stale
+			} else if e == in_cooldown {
+				// the root will be usable once its cooldown is over
+				InvalidTransaction::Future
+			} else {
+				InvalidTransaction::Call
+			}
+		})?;
+		// the withdrawal fits in a later block, the `RateLimitHit` event is only
+		// emitted once dispatched
+		let size = withdrawal.mixer_info.fixed_deposit_size;
+		if Self::volume_after(withdraw_proof.mixer_id, Flow::Withdrawal, size).is_err() {
+			return Err(InvalidTransaction::ExhaustsResources.into());
+		}
+		// neither will a root which is no longer cached
+		let cached_roots = T::Tree::get_cached_roots(withdraw_proof.mixer_id, withdraw_proof.cached_block);
+		if !cached_roots.contains(&withdraw_proof.cached_root) {
+			return Err(InvalidTransaction::Stale.into());
+		}
+		Ok(withdrawal)
+	}
+
+	/// Number of unsigned withdrawals in the current block
+	pub fn unsigned_withdrawals_in_block() -> u32 {
+		let (block, count) = Self::unsigned_withdrawals();
+		if block == <frame_system::Pallet<T>>::block_number() {
+			count
+		} else {
+			0
+		}
+	}
+
+	/// Weight of a batch of `n` withdrawals, each of which can be relayed
+	pub fn batch_withdraw_weight(n: u32) -> Weight {
+		let relays = T::OnRelay::on_relay_weight().saturating_mul(n as Weight);
+		<T as Config>::WeightInfo::batch_withdraw(n).saturating_add(relays)
+	}
+
+	/// Counts an unsigned withdrawal toward the current block.
+	///
+	/// Fails if the block already has `MaxUnsignedWithdrawalsPerBlock` of them.
+	fn note_unsigned_withdrawal() -> dispatch::DispatchResult {
+		let count = Self::unsigned_withdrawals_in_block();
+		ensure!(
+			count < T::MaxUnsignedWithdrawalsPerBlock::get(),
+			Error::<T>::TooManyUnsignedWithdrawals
+		);
+		UnsignedWithdrawals::<T>::put((<frame_system::Pallet<T>>::block_number(), count + 1));
 		Ok(())
 	}
-}
 
-impl<T: Config> ExtendedMixer<T> for Pallet<T> {
-	fn create_new(
+	/// Asset, deposit size and chain id the leaves of the mixer commit to, if
+	/// its tree has bound leaves
+	pub fn leaf_binding(mixer_id: T::TreeId, mixer_info: &MixerInfo<T>) -> Option<LeafBinding> {
+		if T::Tree::get_leaf_format(mixer_id) != LeafFormat::Bound {
+			return None;
+		}
+		Some(LeafBinding {
+			asset_id: mixer_info.currency_id.encode(),
+			amount: mixer_info.fixed_deposit_size.encode(),
+			chain_id: T::ChainId::get().encode(),
+		})
+	}
+
+	/// Runs every check of a withdrawal except for the proof verification and
+	/// the volume limits of the mixer, which the dispatch paths check with
+	/// `ensure_within_limits` so that pool validation emits no events.
+	pub fn check_withdrawal(
+		sender: &T::AccountId,
+		withdraw_proof: &WithdrawProof<T>,
+	) -> Result<PendingWithdrawal<T>, dispatch::DispatchError> {
+		ensure!(
+			!<MerklePallet<T>>::stopped(withdraw_proof.mixer_id),
+			Error::<T>::MixerStopped
+		);
+		ensure!(
+			!Self::paused(withdraw_proof.mixer_id).withdrawals,
+			Error::<T>::WithdrawalsPaused
+		);
+		let recipient = withdraw_proof.recipient.clone().unwrap_or(sender.clone());
+		let relayer = withdraw_proof.relayer.clone().unwrap_or(sender.clone());
+		ensure!(
+			!T::DenyList::is_denied(sender) && !T::DenyList::is_denied(&recipient),
+			Error::<T>::AccountDenied
+		);
+		// only roots cached before the cooldown of the mixer can be proven against
+		let cooldown = Self::limits(withdraw_proof.mixer_id).root_cooldown;
+		ensure!(
+			withdraw_proof.cached_block.saturating_add(cooldown) <= <frame_system::Pallet<T>>::block_number(),
+			Error::<T>::RootInCooldown
+		);
+		// get mixer info, should fail if tree isn't initialized
+		let mixer_info = Self::get_mixer(withdraw_proof.mixer_id)?;
+		// the fee is paid out of the deposit, so it can't exceed it
+		ensure!(
+			withdraw_proof.fee <= mixer_info.fixed_deposit_size,
+			Error::<T>::FeeExceedsDepositSize
+		);
+		ensure!(
+			withdraw_proof.fee <= Self::max_relayer_fee(withdraw_proof.mixer_id),
+			Error::<T>::FeeExceedsMax
+		);
+		// check if the nullifier has been used
+		T::Tree::has_used_nullifier(withdraw_proof.mixer_id.into(), withdraw_proof.nullifier_hash.clone())?;
+		let relayed = withdraw_proof.relayer.as_ref() == Some(sender) && recipient != *sender;
+		Ok(PendingWithdrawal {
+			mixer_id: withdraw_proof.mixer_id,
+			mixer_info,
+			nullifier_hash: withdraw_proof.nullifier_hash.clone(),
+			cached_root: withdraw_proof.cached_root.clone(),
+			recipient,
+			relayer,
+			fee: withdraw_proof.fee,
+			refund: withdraw_proof.refund,
+			relayed,
+		})
+	}
+
+	/// Pays out a withdrawal whose proof has been verified and spends its
+	/// nullifier. Either all or none of the changes are applied.
+	#[transactional]
+	pub fn settle_withdrawal(sender: T::AccountId, withdrawal: PendingWithdrawal<T>) -> dispatch::DispatchResult {
+		let mixer_info = withdrawal.mixer_info;
+		let volume = Self::volume_after(withdrawal.mixer_id, Flow::Withdrawal, mixer_info.fixed_deposit_size)
+			.map_err(|_| Error::<T>::RateLimitExceeded)?;
+		Volume::<T>::insert(withdrawal.mixer_id, volume);
+		// transfer the fixed deposit size, minus the relayer fee, to the recipient
+		T::Currency::transfer(
+			mixer_info.currency_id,
+			&Self::account_id(),
+			&withdrawal.recipient,
+			mixer_info.fixed_deposit_size - withdrawal.fee,
+		)?;
+		if !withdrawal.fee.is_zero() {
+			T::Currency::transfer(
+				mixer_info.currency_id,
+				&Self::account_id(),
+				&withdrawal.relayer,
+				withdrawal.fee,
+			)?;
+		}
+		// forward the refund from the sender, so the recipient can pay for gas
+		if !withdrawal.refund.is_zero() {
+			T::Currency::transfer(
+				T::NativeCurrencyId::get(),
+				&sender,
+				&withdrawal.recipient,
+				withdrawal.refund,
+			)?;
+		}
+		// update the total value locked
+		let tvl = Self::total_value_locked(withdrawal.mixer_id)
+			.checked_sub(&mixer_info.fixed_deposit_size)
+			.ok_or(Error::<T>::TvlUnderflow)?;
+		<TotalValueLocked<T>>::insert(withdrawal.mixer_id, tvl);
+		// Add the nullifier on behalf of the module
+		T::Tree::add_nullifier(
+			Self::account_id(),
+			withdrawal.mixer_id.into(),
+			withdrawal.nullifier_hash,
+		)?;
+		NullifierCount::<T>::mutate(withdrawal.mixer_id, |count| *count += 1);
+		Self::record_activity(withdrawal.mixer_id, 0, 1);
+		if withdrawal.relayed {
+			T::OnRelay::on_relay(&withdrawal.relayer, mixer_info.currency_id, withdrawal.fee);
+		}
+
+		Self::deposit_event(Event::Withdraw(
+			withdrawal.mixer_id,
+			sender,
+			withdrawal.recipient,
+			withdrawal.relayer,
+			withdrawal.cached_root,
+			withdrawal.fee,
+		));
+		Ok(())
+	}
+
+	/// Settles withdrawals whose proofs have been verified. Either all or none
+	/// of them are settled.
+	#[transactional]
+	fn settle_withdrawals(sender: T::AccountId, withdrawals: Vec<PendingWithdrawal<T>>) -> dispatch::DispatchResult {
+		for withdrawal in withdrawals {
+			Self::settle_withdrawal(sender.clone(), withdrawal)?;
+		}
+		Ok(())
+	}
+
+	/// Creates a mixer of `currency_id` with a fixed deposit of `size`,
+	/// whose tree of `depth` is managed by `account_id`.
+	pub fn create_mixer(
 		account_id: T::AccountId,
 		currency_id: CurrencyIdOf<T>,
 		setup: Setup,
+		depth: u8,
 		size: BalanceOf<T>,
 	) -> Result<T::TreeId, dispatch::DispatchError> {
-		let depth: u8 = <T as merkle::Config>::MaxTreeDepth::get();
 		let mixer_id: T::TreeId = T::Tree::create_tree(account_id, true, setup, depth)?;
 		let mixer_info = MixerInfo::<T>::new(T::DepositLength::get(), size, currency_id);
 		MixerTrees::<T>::insert(mixer_id, mixer_info);
@@ -608,3 +2065,27 @@ impl<T: Config> ExtendedMixer<T> for Pallet<T> {
 		Ok(mixer_id)
 	}
 }
+
+sp_api::decl_runtime_apis! {
+	pub trait MixerApi<TreeId, BlockNumber>
+	where
+		TreeId: codec::Codec,
+		BlockNumber: codec::Codec,
+	{
+		/// Get the anonymity set statistics of a mixer, counting the deposits
+		/// and withdrawals of the last `window` blocks.
+		fn get_mixer_stats(mixer_id: TreeId, window: BlockNumber) -> Option<MixerStats<BlockNumber>>;
+	}
+}
+
+impl<T: Config> ExtendedMixer<T> for Pallet<T> {
+	fn create_new(
+		account_id: T::AccountId,
+		currency_id: CurrencyIdOf<T>,
+		setup: Setup,
+		size: BalanceOf<T>,
+	) -> Result<T::TreeId, dispatch::DispatchError> {
+		let depth: u8 = <T as merkle::Config>::MaxTreeDepth::get();
+		Self::create_mixer(account_id, currency_id, setup, depth, size)
+	}
+}
@@ -0,0 +1,58 @@
+//! Storage migrations of the mixer pallet
+//!
+//! Each migration runs once, when the stored [`Releases`] is older than the
+//! release introducing it, and bumps the stored version when done.
+use crate::{Activity, Config, MixerTreeIds, NullifierCount, Pallet, Releases, StorageVersion};
+use frame_support::{traits::Get, weights::Weight};
+use merkle::UsedNullifiers;
+use sp_std::prelude::*;
+
+/// Runs the migrations the stored version hasn't been through yet.
+pub fn migrate<T: Config>() -> Weight {
+	let version = StorageVersion::<T>::get();
+	if version >= Releases::V3PrunedActivity {
+		return T::DbWeight::get().reads(1);
+	}
+	if version < Releases::V2NullifierCounts {
+		count_nullifiers::<T>();
+	}
+	prune_expired_activity::<T>();
+	StorageVersion::<T>::put(Releases::V3PrunedActivity);
+	// Counting goes through every nullifier spent in any tree, and pruning
+	// through every statistics bucket
+	T::BlockWeights::get().max_block
+}
+
+/// Stores the number of nullifiers spent in each mixer before they were
+/// counted, from the nullifiers stored by the merkle pallet.
+fn count_nullifiers<T: Config>() {
+	let mixer_ids = MixerTreeIds::<T>::get();
+	let mut counts = vec![0u32; mixer_ids.len()];
+	for ((tree_id, _), _) in UsedNullifiers::<T>::iter() {
+		if let Some(i) = mixer_ids.iter().position(|mixer_id| *mixer_id == tree_id) {
+			counts[i] = counts[i].saturating_add(1);
+		}
+	}
+	for (mixer_id, count) in mixer_ids.into_iter().zip(counts) {
+		NullifierCount::<T>::insert(mixer_id, count);
+	}
+}
+
+/// Prunes the statistics buckets recorded before they were pruned in
+/// `on_initialize` that are already out of the history.
+fn prune_expired_activity<T: Config>() {
+	let now = <frame_system::Pallet<T>>::block_number();
+	let expired = match Pallet::<T>::expired_bucket(now) {
+		Some(bucket) => bucket,
+		None => return,
+	};
+	for mixer_id in MixerTreeIds::<T>::get() {
+		let buckets: Vec<T::BlockNumber> = Activity::<T>::iter_prefix(mixer_id)
+			.map(|(bucket, _)| bucket)
+			.filter(|bucket| *bucket <= expired)
+			.collect();
+		for bucket in buckets {
+			Pallet::<T>::expire_activity(mixer_id, bucket);
+		}
+	}
+}
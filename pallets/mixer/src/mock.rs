@@ -1,9 +1,21 @@
 use super::*;
 use crate as pallet_mixer;
+use bulletproofs::BulletproofGens;
 use frame_benchmarking::whitelisted_caller;
-use frame_support::{construct_runtime, parameter_types, weights::Weight, PalletId};
-use frame_system::mocking::{MockBlock, MockUncheckedExtrinsic};
-use merkle::weights::Weights as MerkleWeights;
+use frame_support::{
+	construct_runtime, ord_parameter_types, parameter_types, traits::GenesisBuild, weights::Weight, PalletId,
+};
+use frame_system::{
+	mocking::{MockBlock, MockUncheckedExtrinsic},
+	EnsureOneOf, EnsureRoot, EnsureSignedBy,
+};
+use merkle::{
+	utils::{
+		keys::get_bp_gen_bytes,
+		setup::{Backend, Curve, HashFunction, LeafFormat},
+	},
+	weights::Weights as MerkleWeights,
+};
 use webb_currencies::BasicCurrencyAdapter;
 
 use sp_core::H256;
@@ -12,6 +24,7 @@ use sp_runtime::{
 	traits::{BlakeTwo256, IdentityLookup},
 	Perbill,
 };
+use std::cell::RefCell;
 use weights::Weights;
 
 pub(crate) type Balance = u64;
@@ -33,10 +46,9 @@ construct_runtime!(
 		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
 		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
 		MerkleTrees: merkle::{Pallet, Call, Storage, Event<T>},
-		Mixer: pallet_mixer::{Pallet, Call, Storage, Event<T>},
+		Mixer: pallet_mixer::{Pallet, Call, Storage, Config<T>, Event<T>, ValidateUnsigned},
 		Currencies: webb_currencies::{Pallet, Storage, Event<T>},
 		Tokens: webb_tokens::{Pallet, Storage, Event<T>},
-		Randomness: pallet_randomness_collective_flip::{Pallet, Call, Storage},
 	}
 );
 
@@ -80,6 +92,7 @@ parameter_types! {
 	pub const MaxReserves: u32 = 50;
 	pub const MaxTreeDepth: u8 = 32;
 	pub const CacheBlockLength: u64 = 5;
+	pub const MaxMemoLength: u32 = 256;
 	// Minimum deposit length is 1 month w/ 6 second blocks
 	pub const MinimumDepositLength: u64 = 10 * 60 * 24 * 28;
 }
@@ -143,32 +156,74 @@ impl merkle::Config for Test {
 	type CacheBlockLength = CacheBlockLength;
 	type Event = Event;
 	type KeyId = u32;
+	type MaxMemoLength = MaxMemoLength;
 	type MaxTreeDepth = MaxTreeDepth;
-	type Randomness = Randomness;
 	type TreeId = u32;
 	type WeightInfo = MerkleWeights<Self>;
 }
 
 parameter_types! {
 	pub const MixerPalletId: PalletId = PalletId(*b"py/mixer");
-	pub const DefaultAdmin: u64 = 4;
-	pub MixerSizes: Vec<Balance> = [1_000, 10_000, 100_000, 1_000_000].to_vec();
+	pub const MaxWithdrawBatchSize: u32 = 5;
+	pub const StatsBucketLength: u64 = 10;
+	pub const StatsHistoryLength: u32 = 3;
+	pub const ChainId: u64 = 42;
+	pub const FeeCollector: u64 = 7;
+	pub const UnsignedPriority: u64 = 100;
+	pub const MaxUnsignedWithdrawalsPerBlock: u32 = 2;
+}
+
+ord_parameter_types! {
+	pub const Admin: u64 = 4;
+	pub const PauseGuardian: u64 = 5;
+}
+
+/// Denies the account 6 from using the mixers
+pub struct TestDenyList;
+impl DenyList<AccountId> for TestDenyList {
+	fn is_denied(who: &AccountId) -> bool {
+		*who == 6
+	}
+}
+
+thread_local! {
+	/// Relayer, currency and fee of each withdrawal paid out
+	pub static RELAYS: RefCell<Vec<(AccountId, CurrencyId, Balance)>> = RefCell::new(Vec::new());
+}
+
+/// Records the relays of the mixers in `RELAYS`
+pub struct TestOnRelay;
+impl OnRelay<AccountId, CurrencyId, Balance> for TestOnRelay {
+	fn on_relay(relayer: &AccountId, currency_id: CurrencyId, fee: Balance) {
+		RELAYS.with(|relays| relays.borrow_mut().push((*relayer, currency_id, fee)));
+	}
+
+	fn on_relay_weight() -> Weight {
+		1_000_000
+	}
 }
 
 impl Config for Test {
+	type AdminOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<Admin, u64>>;
+	type ChainId = ChainId;
 	type Currency = Currencies;
-	type DefaultAdmin = DefaultAdmin;
+	type DenyList = TestDenyList;
 	type DepositLength = MinimumDepositLength;
 	type Event = Event;
-	type MixerSizes = MixerSizes;
+	type FeeCollector = FeeCollector;
+	type MaxUnsignedWithdrawalsPerBlock = MaxUnsignedWithdrawalsPerBlock;
+	type MaxWithdrawBatchSize = MaxWithdrawBatchSize;
 	type NativeCurrencyId = NativeCurrencyId;
+	type OnRelay = TestOnRelay;
 	type PalletId = MixerPalletId;
+	type PauseOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<PauseGuardian, u64>>;
+	type StatsBucketLength = StatsBucketLength;
+	type StatsHistoryLength = StatsHistoryLength;
 	type Tree = MerkleTrees;
+	type UnsignedPriority = UnsignedPriority;
 	type WeightInfo = Weights<Self>;
 }
 
-impl pallet_randomness_collective_flip::Config for Test {}
-
 pub type TokenPallet = webb_tokens::Pallet<Test>;
 pub type MixerCall = pallet_mixer::Call<Test>;
 
@@ -190,6 +245,24 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 	.assimilate_storage(&mut t)
 	.unwrap();
 
+	// the mixers of the native currency, sharing the first verifying key
+	MixerConfig {
+		verifying_keys: vec![get_bp_gen_bytes(&BulletproofGens::new(16400, 1))],
+		mixers: [1_000, 10_000, 100_000, 1_000_000]
+			.iter()
+			.map(|&size| MixerGenesisConfig {
+				currency_id: NativeCurrencyId::get(),
+				size,
+				setup: Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+				depth: MaxTreeDepth::get(),
+				verifying_key: 0,
+				leaf_format: LeafFormat::Opaque,
+			})
+			.collect(),
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+
 	let _token_currency_id: CurrencyId = 1;
 	// TokensConfig::<Test> {
 	// 	endowed_accounts: vec![(0, token_currency_id, 1_000_000_000)],
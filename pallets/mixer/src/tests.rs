@@ -1,6 +1,7 @@
 use super::*;
 use crate::mock::{
-	new_test_ext, AccountId, Balance, Balances, CurrencyId, MerkleTrees, Mixer, MixerCall, Origin, System, Test, Tokens,
+	new_test_ext, AccountId, Balance, Balances, CurrencyId, Event as TestEvent, MerkleTrees, Mixer, MixerCall,
+	MixerConfig, Origin, System, Test, TestOnRelay, Tokens, RELAYS,
 };
 use bulletproofs::{r1cs::Prover, BulletproofGens, PedersenGens};
 use bulletproofs_gadgets::{
@@ -10,18 +11,27 @@ use bulletproofs_gadgets::{
 		sbox::PoseidonSbox,
 	},
 };
-use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
 use frame_support::{
-	assert_err, assert_ok,
-	traits::{OnFinalize, UnfilteredDispatchable},
+	assert_err, assert_noop, assert_ok,
+	traits::{GenesisBuild, OnFinalize, OnInitialize, OnRuntimeUpgrade, UnfilteredDispatchable},
+	weights::GetDispatchInfo,
 };
 use frame_system::RawOrigin;
 use merkle::{
-	utils::keys::{slice_to_bytes_32, ScalarBytes},
-	HighestCachedBlock,
+	utils::{
+		keys::{get_bp_gen_bytes, slice_to_bytes_32, ScalarBytes},
+		setup::{Backend, Curve, HashFunction, LeafFormat},
+	},
+	HighestCachedBlock, LeafFormats,
 };
 use merlin::Transcript;
-use sp_runtime::{traits::BadOrigin, DispatchError};
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+use sp_runtime::{
+	traits::{BadOrigin, ValidateUnsigned},
+	transaction_validity::{InvalidTransaction, TransactionSource},
+	DispatchError,
+};
 use webb_tokens::ExtendedTokenSystem;
 
 fn default_hasher(bp_gens: BulletproofGens) -> Poseidon {
@@ -35,14 +45,13 @@ fn default_hasher(bp_gens: BulletproofGens) -> Poseidon {
 #[test]
 fn should_initialize_successfully() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
-		// the mixer creates 4 groups, they should all initialise to 0
+		// the genesis creates 4 groups, they should all initialise to 0
 		let val = 1_000;
 		for i in 0..4 {
 			let g = MerkleTrees::get_tree(i).unwrap();
 			let mng = MerkleTrees::get_manager(i).unwrap();
 			let m = Mixer::get_mixer(i).unwrap();
+			assert!(g.initialized);
 			assert_eq!(g.leaf_count, 0);
 			assert_eq!(mng.required, true);
 			assert_eq!(m.fixed_deposit_size, val * 10_u64.pow(i))
@@ -50,119 +59,195 @@ fn should_initialize_successfully() {
 	})
 }
 
+fn genesis_mixer(depth: u8, verifying_key: u32) -> MixerGenesisConfig<CurrencyId, Balance> {
+	MixerGenesisConfig {
+		currency_id: 0,
+		size: 1_000,
+		setup: Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+		depth,
+		verifying_key,
+		leaf_format: LeafFormat::Opaque,
+	}
+}
+
+fn build_genesis(verifying_keys: Vec<Vec<u8>>, mixers: Vec<MixerGenesisConfig<CurrencyId, Balance>>) {
+	let config = MixerConfig { verifying_keys, mixers };
+	let _ = GenesisBuild::<Test>::build_storage(&config);
+}
+
 #[test]
-fn should_initialize_successfully_on_finalize() {
-	new_test_ext().execute_with(|| {
-		<Mixer as OnFinalize<u64>>::on_finalize(1);
-		<Mixer as OnFinalize<u64>>::on_finalize(2);
-		// the mixer creates 4 groups, they should all initialise to 0
-		let val = 1_000;
-		for i in 0..4 {
-			let g = MerkleTrees::get_tree(i).unwrap();
-			let mng = MerkleTrees::get_manager(i).unwrap();
-			let m = Mixer::get_mixer(i).unwrap();
-			assert_eq!(g.leaf_count, 0);
-			assert_eq!(mng.required, true);
-			assert_eq!(m.fixed_deposit_size, val * 10_u64.pow(i))
-		}
-	})
+#[should_panic(expected = "invalid verifying key in genesis.")]
+fn should_fail_genesis_with_invalid_verifying_key() {
+	build_genesis(vec![vec![1, 2, 3]], vec![genesis_mixer(32, 0)]);
 }
 
 #[test]
-fn should_be_able_to_change_admin_with_root() {
-	new_test_ext().execute_with(|| {
-		let call = Box::new(MixerCall::transfer_admin(2));
-		let res = call.dispatch_bypass_filter(RawOrigin::Root.into());
-		assert_ok!(res);
-		let admin = Mixer::admin();
-		assert_eq!(admin, 2);
+#[should_panic(expected = "unknown verifying key of a mixer in genesis.")]
+fn should_fail_genesis_with_unknown_verifying_key() {
+	let key = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+	build_genesis(vec![key], vec![genesis_mixer(32, 1)]);
+}
 
-		let call = Box::new(MixerCall::transfer_admin(3));
-		let res = call.dispatch_bypass_filter(RawOrigin::Signed(0).into());
-		assert_err!(res, BadOrigin);
-	})
+#[test]
+#[should_panic(expected = "invalid mixer in genesis.")]
+fn should_fail_genesis_with_invalid_depth() {
+	let key = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+	build_genesis(vec![key], vec![genesis_mixer(33, 0)]);
 }
 
 #[test]
-fn should_be_able_to_stop_mixers_with_root() {
+fn should_be_able_to_stop_mixer_with_root() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		let call = Box::new(MixerCall::set_stopped(true));
+		let call = Box::new(MixerCall::set_stopped(1, true));
 		let res = call.dispatch_bypass_filter(RawOrigin::Root.into());
 		assert_ok!(res);
 
+		// Only the given mixer is stopped
 		for i in 0..4 {
-			let stopped = MerkleTrees::stopped(i);
-			assert!(stopped);
+			assert_eq!(MerkleTrees::stopped(i), i == 1);
 		}
 	})
 }
 
 #[test]
-fn should_be_able_to_change_admin() {
+fn should_only_allow_admin_origin_to_manage_mixers() {
 	new_test_ext().execute_with(|| {
-		let default_admin = 4;
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_err!(Mixer::transfer_admin(Origin::signed(1), 2), BadOrigin);
-		assert_ok!(Mixer::transfer_admin(Origin::signed(default_admin), 2));
-		let admin = Mixer::admin();
-
-		assert_eq!(admin, 2);
+		let admin = 4;
+		let pause_guardian = 5;
+		assert_err!(Mixer::set_stopped(Origin::signed(1), 0, true), BadOrigin);
+		assert_err!(Mixer::set_stopped(Origin::signed(pause_guardian), 0, true), BadOrigin);
+		assert_err!(
+			Mixer::create_new(
+				Origin::signed(pause_guardian),
+				0,
+				Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+				10
+			),
+			BadOrigin
+		);
+		assert_err!(
+			Mixer::set_stopped(Origin::signed(admin), 4, true),
+			Error::<Test>::NoMixerForId
+		);
+		assert_ok!(Mixer::set_stopped(Origin::signed(admin), 0, true));
+		assert!(MerkleTrees::stopped(0));
 	})
 }
 
+fn empty_withdraw_proof(mixer_id: u32) -> WithdrawProof<Test> {
+	WithdrawProof::new(
+		mixer_id,
+		0,
+		Scalar::zero().to_bytes().to_vec(),
+		Vec::new(),
+		Scalar::zero().to_bytes().to_vec(),
+		Vec::new(),
+		Vec::new(),
+		Vec::new(),
+		None,
+		None,
+		0,
+		0,
+	)
+}
+
+#[test]
+fn should_weigh_withdrawals_with_their_relays() {
+	let relay_weight = <TestOnRelay as OnRelay<AccountId, CurrencyId, Balance>>::on_relay_weight();
+	let weights = [
+		(
+			MixerCall::withdraw(empty_withdraw_proof(0)),
+			<Test as Config>::WeightInfo::withdraw() + relay_weight,
+		),
+		(
+			MixerCall::withdraw_unsigned(empty_withdraw_proof(0)),
+			<Test as Config>::WeightInfo::withdraw_unsigned() + relay_weight,
+		),
+		(
+			MixerCall::batch_withdraw(vec![empty_withdraw_proof(0), empty_withdraw_proof(1)], true),
+			<Test as Config>::WeightInfo::batch_withdraw(2) + 2 * relay_weight,
+		),
+	];
+	for (call, weight) in &weights {
+		assert_eq!(call.get_dispatch_info().weight, *weight);
+	}
+}
+
 #[test]
 fn should_stop_and_start_mixer() {
 	new_test_ext().execute_with(|| {
-		let default_admin = 4;
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
+		let admin = 4;
 		let mut tree = FixedDepositTreeBuilder::new().build();
 		let leaf = tree.generate_secrets().to_bytes().to_vec();
-		assert_ok!(Mixer::deposit(Origin::signed(0), 0, vec![leaf.clone()]));
+		assert_ok!(Mixer::deposit(Origin::signed(0), 0, vec![leaf.clone()], vec![]));
 
 		// Stopping deposits and withdrawal
-		assert_ok!(Mixer::set_stopped(Origin::signed(default_admin), true));
+		assert_ok!(Mixer::set_stopped(Origin::signed(admin), 0, true));
 		assert_err!(
-			Mixer::deposit(Origin::signed(0), 0, vec![]),
+			Mixer::deposit(Origin::signed(0), 0, vec![], vec![]),
 			Error::<Test>::MixerStopped
 		);
 		assert_err!(
-			Mixer::withdraw(
-				Origin::signed(0),
-				WithdrawProof::new(
-					0,
-					0,
-					Scalar::zero().to_bytes().to_vec(),
-					Vec::new(),
-					Scalar::zero().to_bytes().to_vec(),
-					Vec::new(),
-					Vec::new(),
-					Vec::new(),
-					None,
-					None,
-				)
-			),
+			Mixer::withdraw(Origin::signed(0), empty_withdraw_proof(0)),
 			Error::<Test>::MixerStopped
 		);
 
 		// Starting mixer
-		assert_ok!(Mixer::set_stopped(Origin::signed(default_admin), false));
-		assert_ok!(Mixer::deposit(Origin::signed(0), 0, vec![leaf]));
+		assert_ok!(Mixer::set_stopped(Origin::signed(admin), 0, false));
+		assert_ok!(Mixer::deposit(Origin::signed(0), 0, vec![leaf], vec![]));
+	})
+}
+
+#[test]
+fn should_pause_deposits_and_withdrawals_separately() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let pause_guardian = 5;
+		let leaf = [1u8; 32].to_vec();
+
+		assert_err!(Mixer::set_paused(Origin::signed(4), 0, true, false), BadOrigin);
+		assert_err!(
+			Mixer::set_paused(Origin::signed(pause_guardian), 4, true, false),
+			Error::<Test>::NoMixerForId
+		);
+
+		// Pausing withdrawals leaves deposits open
+		assert_ok!(Mixer::set_paused(Origin::signed(pause_guardian), 0, false, true));
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![leaf.clone()], vec![]));
+		assert_err!(
+			Mixer::withdraw(Origin::signed(1), empty_withdraw_proof(0)),
+			Error::<Test>::WithdrawalsPaused
+		);
+
+		// Pausing deposits leaves withdrawals open, and other mixers untouched
+		assert_ok!(Mixer::set_paused(Origin::root(), 0, true, false));
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 0, vec![leaf.clone()], vec![]),
+			Error::<Test>::DepositsPaused
+		);
+		assert_err!(
+			Mixer::withdraw(Origin::signed(1), empty_withdraw_proof(0)),
+			merkle::Error::<Test>::InvalidMerkleRoot
+		);
+		assert_ok!(Mixer::deposit(Origin::signed(1), 1, vec![leaf], vec![]));
+		assert_eq!(Mixer::paused(0), PauseState {
+			deposits: true,
+			withdrawals: false
+		});
+		let event = TestEvent::Mixer(Event::PauseSet(0, true, false));
+		assert!(System::events().iter().any(|record| record.event == event));
 	})
 }
 
 #[test]
 fn should_fail_to_deposit_with_insufficient_balance() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 
 		for i in 0..4 {
 			let leaf = tree.generate_secrets().to_bytes().to_vec();
 			assert_err!(
-				Mixer::deposit(Origin::signed(4), i, vec![leaf]),
+				Mixer::deposit(Origin::signed(4), i, vec![leaf], vec![]),
 				DispatchError::Module {
 					index: 3,
 					error: 4,
@@ -176,14 +261,12 @@ fn should_fail_to_deposit_with_insufficient_balance() {
 #[test]
 fn should_deposit_into_each_mixer_successfully() {
 	new_test_ext().execute_with(|| {
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 
 		for i in 0..4 {
 			let leaf = tree.generate_secrets().to_bytes().to_vec();
 			let balance_before = Balances::free_balance(1);
-			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf]));
+			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf], vec![]));
 			let balance_after = Balances::free_balance(1);
 
 			// ensure state updates
@@ -197,19 +280,86 @@ fn should_deposit_into_each_mixer_successfully() {
 	})
 }
 
+#[test]
+fn should_deposit_into_many_mixers_at_once() {
+	new_test_ext().execute_with(|| {
+		let mut tree = FixedDepositTreeBuilder::new().build();
+		let mut leaves = || tree.generate_secrets().to_bytes().to_vec();
+
+		let deposits = vec![(0, vec![leaves(), leaves()]), (1, vec![leaves()]), (0, vec![leaves()])];
+		let balance_before = Balances::free_balance(1);
+		assert_ok!(Mixer::deposit_many(Origin::signed(1), deposits));
+		let balance_after = Balances::free_balance(1);
+
+		assert_eq!(balance_before, balance_after + 3 * 1_000 + 10_000);
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 3);
+		assert_eq!(MerkleTrees::get_tree(1).unwrap().leaf_count, 1);
+		assert_eq!(Mixer::total_value_locked(0), 3 * 1_000);
+		assert_eq!(Mixer::total_value_locked(1), 10_000);
+	})
+}
+
+#[test]
+fn should_not_deposit_into_any_mixer_if_one_fails() {
+	new_test_ext().execute_with(|| {
+		let mut tree = FixedDepositTreeBuilder::new().build();
+		let mut leaves = || tree.generate_secrets().to_bytes().to_vec();
+
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(1), vec![]),
+			Error::<Test>::EmptyDeposits
+		);
+		// the balance covers each deposit, but not all of them together
+		assert_ok!(Balances::transfer(Origin::signed(1), 4, 10_500));
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(4), vec![(0, vec![leaves()]), (1, vec![leaves()])]),
+			Error::<Test>::InsufficientBalance
+		);
+		assert_ok!(Mixer::set_stopped(Origin::root(), 1, true));
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(1), vec![(0, vec![leaves()]), (1, vec![leaves()])]),
+			Error::<Test>::MixerStopped
+		);
+
+		assert_eq!(Balances::free_balance(4), 10_500);
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 0);
+		assert_eq!(Mixer::total_value_locked(0), 0);
+	})
+}
+
+#[test]
+fn should_deposit_with_memos() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let leaves = vec![[1u8; 32].to_vec(), [2u8; 32].to_vec()];
+
+		assert_noop!(
+			Mixer::deposit(Origin::signed(1), 0, leaves.clone(), vec![vec![1]]),
+			merkle::Error::<Test>::MemoCountMismatch
+		);
+		assert_noop!(
+			Mixer::deposit(Origin::signed(1), 0, leaves.clone(), vec![vec![1], vec![0; 257]]),
+			merkle::Error::<Test>::MemoTooLong
+		);
+		let memos = vec![vec![1, 2, 3], vec![4, 5, 6]];
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, leaves, memos.clone()));
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 2);
+		let event = TestEvent::MerkleTrees(merkle::Event::NewMemos(0, 0, memos));
+		assert!(System::events().iter().any(|record| record.event == event));
+	})
+}
+
 #[test]
 fn should_withdraw_from_each_mixer_successfully() {
 	new_test_ext().execute_with(|| {
 		// set the system block number so randomness could work.
 		System::set_block_number(1);
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let pc_gens = PedersenGens::default();
 
 		let tree_id = 0;
 		let key_id = 0;
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params);
+		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		for i in 0..4 {
@@ -221,7 +371,7 @@ fn should_withdraw_from_each_mixer_successfully() {
 			let leaf = ftree.generate_secrets().to_bytes();
 			ftree.tree.add_leaves(vec![leaf], None);
 
-			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf.to_vec()]));
+			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf.to_vec()], vec![]));
 
 			let root = MerkleTrees::get_merkle_root(i).unwrap();
 			let (proof, (comms_cr, nullifier_hash, leaf_index_comms_cr, proof_comms_cr)) = ftree.prove_zk(
@@ -257,6 +407,8 @@ fn should_withdraw_from_each_mixer_successfully() {
 					proof_comms,
 					Some(2),
 					Some(0),
+					0,
+					0,
 				)
 			));
 			let balance_after = Balances::free_balance(2);
@@ -272,13 +424,11 @@ fn should_withdraw_from_each_mixer_successfully() {
 fn should_cache_roots_if_no_new_deposits_show() {
 	new_test_ext().execute_with(|| {
 		System::set_block_number(1);
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 		let mut merkle_roots: Vec<ScalarBytes> = vec![];
 		for i in 0..4 {
 			let leaf = tree.generate_secrets().to_bytes().to_vec();
-			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf]));
+			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf], vec![]));
 			let root = MerkleTrees::get_merkle_root(i).unwrap();
 			merkle_roots.push(root);
 			let cache = MerkleTrees::cached_roots(1, i);
@@ -307,13 +457,11 @@ fn should_cache_roots_if_no_new_deposits_show() {
 fn should_not_have_cache_once_cache_length_exceeded() {
 	new_test_ext().execute_with(|| {
 		System::set_block_number(1);
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let mut tree = FixedDepositTreeBuilder::new().build();
 		let mut merkle_roots: Vec<ScalarBytes> = vec![];
 		for i in 0..4 {
 			let leaf = tree.generate_secrets().to_bytes().to_vec();
-			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf]));
+			assert_ok!(Mixer::deposit(Origin::signed(1), i, vec![leaf], vec![]));
 			let root = MerkleTrees::get_merkle_root(i).unwrap();
 			merkle_roots.push(root);
 			let cache = MerkleTrees::cached_roots(1, i);
@@ -361,8 +509,6 @@ fn should_make_mixer_with_non_native_token() {
 		assert_ok!(<Tokens as ExtendedTokenSystem<AccountId, CurrencyId, Balance>>::mint(
 			1, 0, 10000000
 		));
-		assert_ok!(Mixer::initialize_first_stage());
-		assert_ok!(Mixer::initialize_second_stage());
 		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
 		assert_ok!(<Mixer as ExtendedMixer<Test>>::create_new(
 			Mixer::account_id(),
@@ -380,7 +526,7 @@ fn should_make_mixer_with_non_native_token() {
 		));
 
 		let params = MerkleTrees::get_verifying_key(key_id).unwrap();
-		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params);
+		let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params).unwrap();
 		let h = default_hasher(bp_gens);
 
 		let pc_gens = PedersenGens::default();
@@ -396,7 +542,12 @@ fn should_make_mixer_with_non_native_token() {
 
 		// Getting native balance before deposit
 		let native_balance_before = Balances::free_balance(&sender);
-		assert_ok!(Mixer::deposit(Origin::signed(sender), tree_id, vec![leaf.to_vec()]));
+		assert_ok!(Mixer::deposit(
+			Origin::signed(sender),
+			tree_id,
+			vec![leaf.to_vec()],
+			vec![]
+		));
 		// Native balance after deposit, to make sure its not touched
 		let native_balance_after = Balances::free_balance(&sender);
 		assert_eq!(native_balance_before, native_balance_after);
@@ -435,6 +586,8 @@ fn should_make_mixer_with_non_native_token() {
 				proof_comms,
 				Some(recipient),
 				Some(0),
+				0,
+				0,
 			)
 		));
 		let balance_after = Tokens::free_balance(currency_id, &recipient);
@@ -448,27 +601,1159 @@ fn should_make_mixer_with_non_native_token() {
 	});
 }
 
+/// Deposits `count` new leaves into `mixer_id` from account 1 and returns a
+/// withdraw proof for each of them, paying out to `recipient`.
+fn deposit_and_prove(mixer_id: u32, count: usize, recipient: AccountId) -> Vec<WithdrawProof<Test>> {
+	deposit_and_prove_with_fee(mixer_id, count, recipient, 0, 0)
+}
+
+/// Same as `deposit_and_prove`, with proofs bound to paying `fee` to relayer 0
+/// and to a `refund` forwarded by it to `recipient`.
+fn deposit_and_prove_with_fee(
+	mixer_id: u32,
+	count: usize,
+	recipient: AccountId,
+	fee: Balance,
+	refund: Balance,
+) -> Vec<WithdrawProof<Test>> {
+	deposit_and_prove_with_relayer(mixer_id, count, recipient, 0, fee, refund)
+}
+
+/// Same as `deposit_and_prove_with_fee`, with proofs bound to `relayer`.
+fn deposit_and_prove_with_relayer(
+	mixer_id: u32,
+	count: usize,
+	recipient: AccountId,
+	relayer: AccountId,
+	fee: Balance,
+	refund: Balance,
+) -> Vec<WithdrawProof<Test>> {
+	let params = MerkleTrees::get_verifying_key(0).unwrap();
+	let bp_gens = merkle::utils::keys::from_bytes_to_bp_gens(&params).unwrap();
+	let mut ftree = FixedDepositTreeBuilder::new()
+		.hash_params(default_hasher(bp_gens))
+		.depth(32)
+		.build();
+
+	let leaves: Vec<_> = (0..count).map(|_| ftree.generate_secrets().to_bytes()).collect();
+	ftree.tree.add_leaves(leaves.clone(), None);
+	assert_ok!(Mixer::deposit(
+		Origin::signed(1),
+		mixer_id,
+		leaves.iter().map(|leaf| leaf.to_vec()).collect(),
+		vec![]
+	));
+
+	let root = MerkleTrees::get_merkle_root(mixer_id).unwrap();
+	let pc_gens = PedersenGens::default();
+	leaves
+		.iter()
+		.map(|leaf| {
+			let mut prover_transcript = Transcript::new(b"zk_membership_proof");
+			let prover = Prover::new(&pc_gens, &mut prover_transcript);
+			let (proof, (comms_cr, nullifier_hash, leaf_index_comms_cr, proof_comms_cr)) = ftree.prove_zk(
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(&root)),
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(leaf)),
+				Scalar::from(recipient),
+				Scalar::from_bytes_mod_order(slice_to_bytes_32(&Mixer::relayer_input(&relayer, fee, refund))),
+				&ftree.hash_params.bp_gens,
+				prover,
+			);
+			WithdrawProof::new(
+				mixer_id,
+				System::block_number(),
+				root.clone(),
+				comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+				nullifier_hash.to_bytes().to_vec(),
+				proof.to_bytes(),
+				leaf_index_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+				proof_comms_cr.iter().map(|x| x.to_bytes().to_vec()).collect(),
+				Some(recipient),
+				Some(relayer),
+				fee,
+				refund,
+			)
+		})
+		.collect()
+}
+
+fn withdraw_failed_events() -> Vec<(u32, ScalarBytes, DispatchError)> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			TestEvent::Mixer(Event::WithdrawFailed(mixer_id, nullifier_hash, e)) => Some((mixer_id, nullifier_hash, e)),
+			_ => None,
+		})
+		.collect()
+}
+
 #[test]
-fn should_initialize_in_two_steps_on_finalize() {
+fn should_batch_withdraw_from_many_mixers() {
 	new_test_ext().execute_with(|| {
 		System::set_block_number(1);
-		<Mixer as OnFinalize<u64>>::on_finalize(1);
-		assert!(Mixer::first_stage_initialized());
 
-		for i in 0..4 {
-			let tree_id = i;
-			let tree = MerkleTrees::get_tree(tree_id).unwrap();
-			assert!(!tree.initialized);
+		let mut proofs = deposit_and_prove(0, 2, 2);
+		proofs.extend(deposit_and_prove(1, 1, 2));
+		let nullifiers: Vec<_> = proofs.iter().map(|p| (p.mixer_id, p.nullifier_hash.clone())).collect();
+
+		let balance_before = Balances::free_balance(2);
+		assert_ok!(Mixer::batch_withdraw(Origin::signed(2), proofs, true));
+
+		let size_0 = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		let size_1 = Mixer::get_mixer(1).unwrap().fixed_deposit_size;
+		assert_eq!(Balances::free_balance(2), balance_before + 2 * size_0 + size_1);
+		assert_eq!(Mixer::total_value_locked(0), 0);
+		assert_eq!(Mixer::total_value_locked(1), 0);
+		for (mixer_id, nullifier_hash) in nullifiers {
+			assert!(<MerkleTrees as TreeTrait<Test>>::has_used_nullifier(mixer_id, nullifier_hash).is_err());
+		}
+		assert!(withdraw_failed_events().is_empty());
+	})
+}
+
+#[test]
+fn should_settle_valid_withdrawals_of_non_atomic_batch() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+
+		let mut proofs = deposit_and_prove(0, 2, 2);
+		// The proof is bound to account 2, so it can't pay out to account 3
+		proofs[1].recipient = Some(3);
+		let invalid_nullifier = proofs[1].nullifier_hash.clone();
+
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		let balance_before = Balances::free_balance(2);
+		assert_ok!(Mixer::batch_withdraw(Origin::signed(2), proofs, false));
+
+		assert_eq!(Balances::free_balance(2), balance_before + size);
+		assert_eq!(Balances::free_balance(3), 0);
+		assert_eq!(Mixer::total_value_locked(0), size);
+		assert_ok!(<MerkleTrees as TreeTrait<Test>>::has_used_nullifier(
+			0,
+			invalid_nullifier.clone()
+		));
+		let failed = withdraw_failed_events();
+		assert_eq!(failed.len(), 1);
+		assert_eq!((failed[0].0, failed[0].1.clone()), (0, invalid_nullifier));
+	})
+}
+
+#[test]
+fn should_fail_atomic_batch_with_invalid_withdrawal() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+
+		let mut proofs = deposit_and_prove(0, 2, 2);
+		proofs[1].recipient = Some(3);
+		let nullifiers: Vec<_> = proofs.iter().map(|p| p.nullifier_hash.clone()).collect();
+
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		let balance_before = Balances::free_balance(2);
+		assert!(Mixer::batch_withdraw(Origin::signed(2), proofs, true).is_err());
+
+		assert_eq!(Balances::free_balance(2), balance_before);
+		assert_eq!(Mixer::total_value_locked(0), 2 * size);
+		for nullifier_hash in nullifiers {
+			assert_ok!(<MerkleTrees as TreeTrait<Test>>::has_used_nullifier(0, nullifier_hash));
+		}
+	})
+}
+
+#[test]
+fn should_reject_duplicate_nullifiers_in_batch() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+
+		let proof = deposit_and_prove(0, 1, 2).remove(0);
+		let proofs = vec![proof.clone(), proof.clone()];
+		assert_err!(
+			Mixer::batch_withdraw(Origin::signed(2), proofs.clone(), true),
+			Error::<Test>::DuplicateNullifier
+		);
+
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		let balance_before = Balances::free_balance(2);
+		assert_ok!(Mixer::batch_withdraw(Origin::signed(2), proofs, false));
+		assert_eq!(Balances::free_balance(2), balance_before + size);
+		assert_eq!(
+			withdraw_failed_events(),
+			vec![(0, proof.nullifier_hash, Error::<Test>::DuplicateNullifier.into())]
+		);
+	})
+}
+
+#[test]
+fn should_reject_empty_and_oversized_batches() {
+	new_test_ext().execute_with(|| {
+		assert_err!(
+			Mixer::batch_withdraw(Origin::signed(2), Vec::new(), false),
+			Error::<Test>::EmptyBatch
+		);
+		let proof = WithdrawProof::new(
+			0,
+			0,
+			Scalar::zero().to_bytes().to_vec(),
+			Vec::new(),
+			Scalar::zero().to_bytes().to_vec(),
+			Vec::new(),
+			Vec::new(),
+			Vec::new(),
+			None,
+			None,
+			0,
+			0,
+		);
+		let max = <Test as Config>::MaxWithdrawBatchSize::get() as usize;
+		assert_err!(
+			Mixer::batch_withdraw(Origin::signed(2), vec![proof; max + 1], false),
+			Error::<Test>::BatchTooLarge
+		);
+	})
+}
+
+#[test]
+fn should_pay_relayer_fee_and_forward_refund() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::root(), 0, 100));
+
+		let proof = deposit_and_prove_with_fee(0, 1, 2, 50, 10).remove(0);
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		let recipient_before = Balances::free_balance(2);
+		let relayer_before = Balances::free_balance(0);
+		assert_ok!(Mixer::withdraw(Origin::signed(0), proof));
+
+		assert_eq!(Balances::free_balance(2), recipient_before + size - 50 + 10);
+		assert_eq!(Balances::free_balance(0), relayer_before + 50 - 10);
+		assert_eq!(Mixer::total_value_locked(0), 0);
+	})
+}
+
+#[test]
+fn should_notify_relayers_of_paid_out_withdrawals() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let relayed = deposit_and_prove_with_fee(0, 1, 2, 50, 0).remove(0);
+		// Failed withdrawals aren't relays
+		assert_err!(
+			Mixer::withdraw(Origin::signed(0), relayed.clone()),
+			Error::<Test>::FeeExceedsMax
+		);
+		assert!(RELAYS.with(|relays| relays.borrow().is_empty()));
+
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::root(), 0, 50));
+		// Paid out withdrawals submitted by the relayer named in their proof are
+		// counted toward it
+		assert_ok!(Mixer::withdraw(Origin::signed(0), relayed));
+		assert_eq!(RELAYS.with(|relays| relays.borrow().clone()), vec![(0, 0, 50)]);
+
+		// but not withdrawals submitted by their recipient,
+		let proof = deposit_and_prove(0, 1, 2).remove(0);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proof));
+		// nor those relayed to the relayer itself,
+		let proof = deposit_and_prove_with_relayer(0, 1, 2, 2, 0, 0).remove(0);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proof));
+		// nor those whose proof doesn't name a relayer
+		let mut proof = deposit_and_prove(0, 1, 2).remove(0);
+		proof.relayer = None;
+		assert_ok!(Mixer::withdraw(Origin::signed(0), proof));
+		assert_eq!(RELAYS.with(|relays| relays.borrow().clone()), vec![(0, 0, 50)]);
+	})
+}
+
+#[test]
+fn should_not_withdraw_with_fee_above_max() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+
+		// No fee is allowed until the admin sets a cap
+		let proof = deposit_and_prove_with_fee(0, 1, 2, 50, 0).remove(0);
+		assert_err!(
+			Mixer::withdraw(Origin::signed(0), proof.clone()),
+			Error::<Test>::FeeExceedsMax
+		);
+
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::root(), 0, 40));
+		assert_err!(
+			Mixer::withdraw(Origin::signed(0), proof.clone()),
+			Error::<Test>::FeeExceedsMax
+		);
+
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::root(), 0, 50));
+		assert_ok!(Mixer::withdraw(Origin::signed(0), proof));
+	})
+}
+
+#[test]
+fn should_not_withdraw_with_fee_or_refund_not_bound_by_proof() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::root(), 0, 100));
+
+		let proof = deposit_and_prove_with_fee(0, 1, 2, 50, 10).remove(0);
+		let recipient_before = Balances::free_balance(2);
+
+		let mut higher_fee = proof.clone();
+		higher_fee.fee = 60;
+		assert!(Mixer::withdraw(Origin::signed(0), higher_fee).is_err());
+
+		let mut no_refund = proof.clone();
+		no_refund.refund = 0;
+		assert!(Mixer::withdraw(Origin::signed(0), no_refund).is_err());
+
+		assert_eq!(Balances::free_balance(2), recipient_before);
+		assert_ok!(Mixer::withdraw(Origin::signed(0), proof));
+	})
+}
+
+#[test]
+fn should_set_max_relayer_fee_within_deposit_size() {
+	new_test_ext().execute_with(|| {
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+		assert_err!(Mixer::set_max_relayer_fee(Origin::signed(1), 0, 10), BadOrigin);
+		assert_err!(
+			Mixer::set_max_relayer_fee(Origin::root(), 0, size + 1),
+			Error::<Test>::FeeExceedsDepositSize
+		);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::root(), 0, size));
+		assert_eq!(Mixer::max_relayer_fee(0), size);
+	})
+}
+
+fn disclosure_events() -> Vec<(u32, u32, Vec<u8>)> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			TestEvent::Mixer(Event::DepositDisclosure(mixer_id, leaf_index, ciphertext)) => {
+				Some((mixer_id, leaf_index, ciphertext))
+			}
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn should_decrypt_notes_only_with_their_viewing_key() {
+	let mut rng = ChaChaRng::from_seed([1u8; 32]);
+	let viewing_key = disclosure::ViewingKey::random(&mut rng);
+	let other_key = disclosure::ViewingKey::random(&mut rng);
+	let note = disclosure::Note::new(Scalar::random(&mut rng), Scalar::random(&mut rng));
+
+	let ciphertext = disclosure::encrypt_note(&viewing_key.public_key(), &note, &mut rng).unwrap();
+	assert_eq!(ciphertext.len(), disclosure::CIPHERTEXT_LEN);
+	assert_eq!(viewing_key.decrypt(&ciphertext), Some(note));
+	assert_eq!(other_key.decrypt(&ciphertext), None);
+
+	let mut tampered = ciphertext.clone();
+	tampered[40] ^= 1;
+	assert_eq!(viewing_key.decrypt(&tampered), None);
+	assert_eq!(viewing_key.decrypt(&ciphertext[1..]), None);
+
+	let key_bytes = viewing_key.to_bytes();
+	assert_eq!(disclosure::ViewingKey::from_bytes(&key_bytes), Some(viewing_key));
+}
+
+#[test]
+fn should_deposit_with_disclosures() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let hasher = default_hasher(BulletproofGens::new(4096, 1));
+		let mut rng = ChaChaRng::from_seed([2u8; 32]);
+		let viewing_key = disclosure::ViewingKey::random(&mut rng);
+
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![[1u8; 32].to_vec()], vec![]));
+		let notes: Vec<_> = (0..2)
+			.map(|_| disclosure::Note::new(Scalar::random(&mut rng), Scalar::random(&mut rng)))
+			.collect();
+		let leaves: Vec<_> = notes
+			.iter()
+			.map(|note| note.leaf(&hasher).to_bytes().to_vec())
+			.collect();
+		let ciphertexts: Vec<_> = notes
+			.iter()
+			.map(|note| disclosure::encrypt_note(&viewing_key.public_key(), note, &mut rng).unwrap())
+			.collect();
+
+		assert_err!(
+			Mixer::deposit_with_disclosures(Origin::signed(1), 0, leaves.clone(), ciphertexts[..1].to_vec()),
+			Error::<Test>::DisclosureCountMismatch
+		);
+		assert_err!(
+			Mixer::deposit_with_disclosures(Origin::signed(1), 0, leaves.clone(), vec![vec![0u8; 32]; 2]),
+			Error::<Test>::InvalidDisclosure
+		);
+		assert_ok!(Mixer::deposit_with_disclosures(
+			Origin::signed(1),
+			0,
+			leaves.clone(),
+			ciphertexts.clone()
+		));
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 3);
+
+		// The leaves after the plain deposit are disclosed, and their notes
+		// decrypt to the deposited leaves
+		let events = disclosure_events();
+		assert_eq!(events, vec![
+			(0, 1, ciphertexts[0].clone()),
+			(0, 2, ciphertexts[1].clone())
+		]);
+		for ((_, _, ciphertext), leaf) in events.iter().zip(leaves.iter()) {
+			let note = viewing_key.decrypt(ciphertext).unwrap();
+			assert_eq!(&note.leaf(&hasher).to_bytes().to_vec(), leaf);
+		}
+	})
+}
+
+#[test]
+fn should_open_disclosure_only_for_its_deposit() {
+	let hasher = default_hasher(BulletproofGens::new(4096, 1));
+	let mut rng = ChaChaRng::from_seed([3u8; 32]);
+	let auditor_key = disclosure::ViewingKey::random(&mut rng);
+	let note = disclosure::Note::new(Scalar::random(&mut rng), Scalar::random(&mut rng));
+
+	// The note of a deposit which hasn't been withdrawn could still spend it
+	assert_eq!(
+		disclosure::Disclosure::new(&note, 0, 5, &auditor_key.public_key(), &hasher, |_| false, &mut rng),
+		Err(disclosure::DisclosureError::UnspentNote)
+	);
+
+	let nullifier_hash = note.nullifier_hash(&hasher).to_bytes();
+	let is_spent = |hash: &[u8; 32]| *hash == nullifier_hash;
+	let invalid_key = CompressedRistretto([255u8; 32]);
+	assert_eq!(
+		disclosure::Disclosure::new(&note, 0, 5, &invalid_key, &hasher, is_spent, &mut rng),
+		Err(disclosure::DisclosureError::InvalidAuditorKey)
+	);
+	let disclosure =
+		disclosure::Disclosure::new(&note, 0, 5, &auditor_key.public_key(), &hasher, is_spent, &mut rng).unwrap();
+	assert_eq!(disclosure.nullifier_hash, note.nullifier_hash(&hasher).to_bytes());
+	assert_eq!(disclosure.open(&auditor_key, &hasher), Ok(note));
+
+	let other_key = disclosure::ViewingKey::random(&mut rng);
+	assert_eq!(
+		disclosure.open(&other_key, &hasher),
+		Err(disclosure::DisclosureError::InvalidCiphertext)
+	);
+
+	let mut wrong_leaf = disclosure.clone();
+	wrong_leaf.leaf = [1u8; 32];
+	assert_eq!(
+		wrong_leaf.open(&auditor_key, &hasher),
+		Err(disclosure::DisclosureError::LeafMismatch)
+	);
+
+	let mut wrong_nullifier_hash = disclosure.clone();
+	wrong_nullifier_hash.nullifier_hash = [1u8; 32];
+	assert_eq!(
+		wrong_nullifier_hash.open(&auditor_key, &hasher),
+		Err(disclosure::DisclosureError::NullifierHashMismatch)
+	);
+}
+
+fn rate_limit_hit_events() -> Vec<(u32, RateLimit)> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			TestEvent::Mixer(Event::RateLimitHit(mixer_id, limit)) => Some((mixer_id, limit)),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn should_only_allow_admin_origin_to_set_valid_limits() {
+	new_test_ext().execute_with(|| {
+		let admin = 4;
+		let mut limits = MixerLimits {
+			deposits: FlowLimits {
+				per_block: None,
+				per_epoch: Some(3_000),
+			},
+			..Default::default()
+		};
+
+		assert_err!(Mixer::set_limits(Origin::signed(1), 0, limits.clone()), BadOrigin);
+		assert_err!(
+			Mixer::set_limits(Origin::signed(admin), 4, limits.clone()),
+			Error::<Test>::NoMixerForId
+		);
+		assert_err!(
+			Mixer::set_limits(Origin::signed(admin), 0, limits.clone()),
+			Error::<Test>::InvalidLimits
+		);
+		limits.epoch_length = 10;
+		assert_ok!(Mixer::set_limits(Origin::signed(admin), 0, limits.clone()));
+		assert_eq!(Mixer::limits(0), limits);
+	})
+}
+
+#[test]
+fn should_limit_deposit_volume_per_block_and_epoch() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let limits = MixerLimits {
+			deposits: FlowLimits {
+				per_block: Some(2_000),
+				per_epoch: Some(3_000),
+			},
+			epoch_length: 10,
+			..Default::default()
+		};
+		assert_ok!(Mixer::set_limits(Origin::root(), 0, limits));
+		let mut tree = FixedDepositTreeBuilder::new().build();
+		let mut leaves = || tree.generate_secrets().to_bytes().to_vec();
+
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![leaves(), leaves()], vec![]));
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 0, vec![leaves()], vec![]),
+			Error::<Test>::RateLimitExceeded
+		);
+		// Other mixers are not limited
+		assert_ok!(Mixer::deposit(Origin::signed(1), 1, vec![leaves(), leaves()], vec![]));
+
+		System::set_block_number(2);
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![leaves()], vec![]));
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 0, vec![leaves()], vec![]),
+			Error::<Test>::RateLimitExceeded
+		);
+		assert_eq!(rate_limit_hit_events(), vec![
+			(0, RateLimit::DepositsPerBlock),
+			(0, RateLimit::DepositsPerEpoch)
+		]);
+
+		// The next epoch starts at block 10
+		System::set_block_number(10);
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![leaves()], vec![]));
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 4);
+		assert_eq!(Mixer::volume(0).deposits, FlowVolume {
+			in_block: 1_000,
+			in_epoch: 1_000
+		});
+	})
+}
+
+#[test]
+fn should_limit_withdrawal_volume_and_wait_for_root_cooldown() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let limits = MixerLimits {
+			withdrawals: FlowLimits {
+				per_block: Some(1_000),
+				per_epoch: None,
+			},
+			root_cooldown: 2,
+			..Default::default()
+		};
+		assert_ok!(Mixer::set_limits(Origin::root(), 0, limits));
+		let proofs = deposit_and_prove(0, 2, 2);
+
+		assert_err!(
+			Mixer::withdraw(Origin::signed(2), proofs[0].clone()),
+			Error::<Test>::RootInCooldown
+		);
+
+		System::set_block_number(3);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[0].clone()));
+		assert_err!(
+			Mixer::withdraw(Origin::signed(2), proofs[1].clone()),
+			Error::<Test>::RateLimitExceeded
+		);
+		assert_eq!(rate_limit_hit_events(), vec![(0, RateLimit::WithdrawalsPerBlock)]);
+
+		System::set_block_number(4);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[1].clone()));
+		assert_eq!(Mixer::total_value_locked(0), 0);
+	})
+}
+
+#[test]
+fn should_report_rate_limit_hits_of_deposit_many() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let limits = MixerLimits {
+			deposits: FlowLimits {
+				per_block: Some(1_000),
+				per_epoch: None,
+			},
+			..Default::default()
+		};
+		assert_ok!(Mixer::set_limits(Origin::root(), 0, limits));
+		let mut tree = FixedDepositTreeBuilder::new().build();
+		let mut leaves = || tree.generate_secrets().to_bytes().to_vec();
+
+		// The deposits into the same mixer add up against its limits
+		let deposits = vec![(0, vec![leaves()]), (1, vec![leaves()]), (0, vec![leaves()])];
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(1), deposits),
+			Error::<Test>::RateLimitExceeded
+		);
+		assert_eq!(rate_limit_hit_events(), vec![(0, RateLimit::DepositsPerBlock)]);
+		assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 0);
+		assert_eq!(MerkleTrees::get_tree(1).unwrap().leaf_count, 0);
+		assert_eq!(Mixer::volume(0).deposits, FlowVolume::default());
+
+		assert_ok!(Mixer::deposit_many(Origin::signed(1), vec![
+			(0, vec![leaves()]),
+			(1, vec![leaves()])
+		]));
+		assert_eq!(Mixer::volume(0).deposits.in_block, 1_000);
+	})
+}
+
+#[test]
+fn should_report_rate_limit_hits_of_batch_withdraw() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let limits = MixerLimits {
+			withdrawals: FlowLimits {
+				per_block: Some(1_000),
+				per_epoch: None,
+			},
+			..Default::default()
+		};
+		assert_ok!(Mixer::set_limits(Origin::root(), 0, limits));
+		let proofs = deposit_and_prove(0, 2, 2);
+		let size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+
+		// The withdrawals from the same mixer add up against its limits
+		assert_err!(
+			Mixer::batch_withdraw(Origin::signed(2), proofs.clone(), true),
+			Error::<Test>::RateLimitExceeded
+		);
+		assert_eq!(rate_limit_hit_events(), vec![(0, RateLimit::WithdrawalsPerBlock)]);
+		assert_eq!(Mixer::total_value_locked(0), 2 * size);
+
+		// Only the withdrawals within the limits are settled
+		assert_ok!(Mixer::batch_withdraw(Origin::signed(2), proofs.clone(), false));
+		assert_eq!(Mixer::total_value_locked(0), size);
+		assert_eq!(rate_limit_hit_events(), vec![
+			(0, RateLimit::WithdrawalsPerBlock),
+			(0, RateLimit::WithdrawalsPerBlock)
+		]);
+		assert_eq!(
+			withdraw_failed_events(),
+			vec![(0, proofs[1].nullifier_hash.clone(), Error::<Test>::RateLimitExceeded.into())]
+		);
+	})
+}
+
+#[test]
+fn should_deny_accounts_of_the_deny_list() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let denied = 6;
+		let leaf = [1u8; 32].to_vec();
+
+		assert_err!(
+			Mixer::deposit(Origin::signed(denied), 0, vec![leaf.clone()], vec![]),
+			Error::<Test>::AccountDenied
+		);
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(denied), vec![(0, vec![leaf])]),
+			Error::<Test>::AccountDenied
+		);
+		let proofs = deposit_and_prove(0, 1, denied);
+		assert_err!(
+			Mixer::withdraw(Origin::signed(2), proofs[0].clone()),
+			Error::<Test>::AccountDenied
+		);
+		assert_err!(
+			Mixer::batch_withdraw(Origin::signed(2), proofs, true),
+			Error::<Test>::AccountDenied
+		);
+	})
+}
+
+#[test]
+fn should_report_anonymity_set_stats() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let proofs = deposit_and_prove(0, 3, 2);
+		assert_eq!(Mixer::get_stats(9, 5), None);
+
+		System::set_block_number(12);
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![[1u8; 32].to_vec()], vec![]));
+
+		System::set_block_number(15);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[0].clone()));
+		assert_eq!(Mixer::nullifier_count(0), 1);
+
+		// The window of the last 5 blocks covers the bucket starting at block 10
+		let stats = Mixer::get_stats(0, 5).unwrap();
+		assert_eq!(stats, MixerStats {
+			leaf_count: 4,
+			spent_nullifier_count: 1,
+			active_deposits: 3,
+			recent_deposits: 1,
+			recent_withdrawals: 1,
+			unspent_deposit_ages: vec![(15, 2), (5, 1)],
+		});
+		let stats = Mixer::get_stats(0, 15).unwrap();
+		assert_eq!((stats.recent_deposits, stats.recent_withdrawals), (4, 1));
+		// Other mixers are untouched
+		assert_eq!(Mixer::get_stats(1, 15).unwrap().unspent_deposit_ages, vec![]);
+	})
+}
+
+#[test]
+fn should_prune_activity_out_of_the_history() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let proofs = deposit_and_prove(0, 3, 2);
+		System::set_block_number(12);
+		assert_ok!(Mixer::deposit(Origin::signed(1), 0, vec![[1u8; 32].to_vec()], vec![]));
+		System::set_block_number(15);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[0].clone()));
+
+		// Nothing is out of the 3 buckets of history yet, or between buckets
+		let finalize_weight = <Test as Config>::WeightInfo::on_finalize();
+		assert_eq!(Mixer::on_initialize(20), finalize_weight);
+		assert_eq!(Mixer::on_initialize(35), finalize_weight);
+		assert_eq!(Mixer::activity(0, 0).deposits, 3);
+
+		System::set_block_number(30);
+		let mixer_count = MixerTreeIds::<Test>::get().len() as u32;
+		let prune_weight = <Test as Config>::WeightInfo::prune_activity(mixer_count);
+		assert_eq!(Mixer::on_initialize(30), finalize_weight + prune_weight);
+		assert_eq!(Mixer::activity(0, 0), ActivityCount::default());
+		assert_eq!(Mixer::expired_activity(0), ActivityCount {
+			deposits: 3,
+			withdrawals: 0,
+		});
+		assert_eq!(Mixer::activity(0, 10).deposits, 1);
+
+		// The pruned deposits are the oldest, with the age of their bucket
+		System::set_block_number(31);
+		let stats = Mixer::get_stats(0, 100).unwrap();
+		assert_eq!(stats, MixerStats {
+			leaf_count: 4,
+			spent_nullifier_count: 1,
+			active_deposits: 3,
+			recent_deposits: 1,
+			recent_withdrawals: 1,
+			unspent_deposit_ages: vec![(31, 2), (21, 1)],
+		});
+	})
+}
+
+#[test]
+fn should_migrate_expired_activity() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::<Test>::put(Releases::V2NullifierCounts);
+		NullifierCount::<Test>::insert(0, 5);
+		for bucket in &[0, 10, 20, 30] {
+			Activity::<Test>::insert(0, *bucket, ActivityCount {
+				deposits: 1,
+				withdrawals: 1,
+			});
+		}
+
+		// Buckets up to the one starting at block 10 are out of the history
+		System::set_block_number(45);
+		Mixer::on_runtime_upgrade();
+		assert_eq!(Mixer::storage_version(), Releases::V3PrunedActivity);
+		assert_eq!(Mixer::expired_activity(0), ActivityCount {
+			deposits: 2,
+			withdrawals: 2,
+		});
+		let buckets: Vec<u64> = vec![0, 10, 20, 30]
+			.into_iter()
+			.filter(|bucket| Activity::<Test>::contains_key(0, bucket))
+			.collect();
+		assert_eq!(buckets, vec![20, 30]);
+		// Nullifiers are counted once
+		assert_eq!(Mixer::nullifier_count(0), 5);
+	})
+}
+
+fn reconcile_events() -> (Vec<InvariantViolation<CurrencyId, Balance, u32>>, Vec<u32>) {
+	let mut violations = Vec::new();
+	let mut reconciled = Vec::new();
+	for record in System::events() {
+		match record.event {
+			TestEvent::Mixer(Event::InvariantViolated(violation)) => violations.push(violation),
+			TestEvent::Mixer(Event::Reconciled(count)) => reconciled.push(count),
+			_ => {}
 		}
+	}
+	(violations, reconciled)
+}
+
+#[test]
+fn should_hold_invariants_after_deposits_and_withdrawals() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let proofs = deposit_and_prove(0, 2, 2);
+		let leaf = [1u8; 32].to_vec();
+		assert_ok!(Mixer::deposit_many(Origin::signed(1), vec![(1, vec![leaf])]));
+		assert_eq!(Mixer::check_invariants(), vec![]);
+
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[0].clone()));
+		assert_eq!(Mixer::check_invariants(), vec![]);
+
+		assert_err!(Mixer::reconcile(Origin::signed(1)), BadOrigin);
+		assert_ok!(Mixer::reconcile(Origin::signed(4)));
+		assert_ok!(Mixer::reconcile(RawOrigin::Root.into()));
+		assert_eq!(reconcile_events(), (vec![], vec![0, 0]));
+	})
+}
+
+#[test]
+fn should_report_violated_invariants() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		deposit_and_prove(0, 2, 2);
+		let mixer_info = Mixer::get_mixer(0).unwrap();
+		let balance = Balances::free_balance(Mixer::account_id());
+		assert_eq!(Mixer::total_value_locked(0), 2 * mixer_info.fixed_deposit_size);
+
+		TotalValueLocked::<Test>::insert(0, balance + 1);
+		assert_ok!(Mixer::reconcile(RawOrigin::Root.into()));
+		assert_eq!(
+			reconcile_events(),
+			(
+				vec![
+					InvariantViolation::DepositsMismatch {
+						mixer_id: 0,
+						leaf_count: 2,
+						spent_nullifier_count: 0,
+						total_value_locked: balance + 1,
+					},
+					InvariantViolation::Undercollateralized {
+						currency_id: mixer_info.currency_id,
+						balance,
+						total_value_locked: balance + 1,
+					},
+				],
+				vec![2]
+			)
+		);
+	})
+}
+
+#[test]
+fn should_migrate_nullifier_counts() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let proofs = deposit_and_prove(0, 3, 2);
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[0].clone()));
+		assert_ok!(Mixer::withdraw(Origin::signed(2), proofs[1].clone()));
+
+		// Nullifiers were spent before they were counted
+		NullifierCount::<Test>::remove(0);
+		assert!(!Mixer::check_invariants().is_empty());
+
+		Mixer::on_runtime_upgrade();
+		assert_eq!(Mixer::storage_version(), Releases::V3PrunedActivity);
+		assert_eq!(Mixer::nullifier_count(0), 2);
+		assert_eq!(Mixer::nullifier_count(1), 0);
+		assert_eq!(Mixer::check_invariants(), vec![]);
+
+		// Migrations only run once
+		NullifierCount::<Test>::remove(0);
+		Mixer::on_runtime_upgrade();
+		assert_eq!(Mixer::nullifier_count(0), 0);
+	})
+}
+
+#[test]
+fn should_fail_deposits_and_withdrawals_on_tvl_overflow_or_underflow() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let proofs = deposit_and_prove(0, 1, 2);
+
+		TotalValueLocked::<Test>::insert(0, Balance::max_value());
+		let balance_before = Balances::free_balance(1);
+		assert_err!(
+			Mixer::deposit(Origin::signed(1), 0, vec![[1u8; 32].to_vec()], vec![]),
+			Error::<Test>::Overflow
+		);
+		assert_err!(
+			Mixer::deposit_many(Origin::signed(1), vec![(0, vec![[1u8; 32].to_vec()])]),
+			Error::<Test>::Overflow
+		);
+		assert_eq!(Balances::free_balance(1), balance_before);
+
+		TotalValueLocked::<Test>::insert(0, 0);
+		assert_err!(
+			Mixer::withdraw(Origin::signed(2), proofs[0].clone()),
+			Error::<Test>::TvlUnderflow
+		);
+		assert_ok!(<MerkleTrees as TreeTrait<Test>>::has_used_nullifier(
+			0,
+			proofs[0].nullifier_hash.clone()
+		));
+	})
+}
+
+#[test]
+#[should_panic(expected = "leaf format of a mixer can't be set in genesis.")]
+fn should_fail_genesis_with_bound_leaves_on_bulletproofs_tree() {
+	let key = get_bp_gen_bytes(&BulletproofGens::new(16, 1));
+	let bound = MixerGenesisConfig {
+		leaf_format: LeafFormat::Bound,
+		..genesis_mixer(32, 0)
+	};
+	build_genesis(vec![key], vec![genesis_mixer(32, 0), bound]);
+}
+
+#[test]
+fn should_bind_withdrawals_to_the_mixer_asset_and_size() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_err!(
+			<MerkleTrees as TreeTrait<Test>>::set_leaf_format(Mixer::account_id(), 0, LeafFormat::Bound),
+			merkle::Error::<Test>::LeafFormatUnsupported
+		);
+		// Tests the binding of bulletproofs mixers, which can't be made bound with
+		// `set_leaf_format`
+		LeafFormats::<Test>::insert(0, LeafFormat::Bound);
+		let mixer_info = Mixer::get_mixer(0).unwrap();
+		assert_eq!(
+			Mixer::leaf_binding(0, &mixer_info),
+			Some(LeafBinding {
+				asset_id: mixer_info.currency_id.encode(),
+				amount: mixer_info.fixed_deposit_size.encode(),
+				chain_id: 42u64.encode(),
+			})
+		);
+		assert_eq!(Mixer::leaf_binding(1, &Mixer::get_mixer(1).unwrap()), None);
+
+		// Bulletproofs proofs can't take the binding of bound leaves
+		let proofs = deposit_and_prove(0, 1, 2);
+		assert_err!(
+			Mixer::withdraw(Origin::signed(2), proofs[0].clone()),
+			merkle::Error::<Test>::Unimplemented
+		);
+	})
+}
+
+#[test]
+fn should_withdraw_unsigned_to_fresh_account_paying_fee() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let (fee_collector, recipient) = (7, 8);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::signed(4), 0, 100));
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(100)));
+		let proofs = deposit_and_prove_with_relayer(0, 1, recipient, fee_collector, 100, 0);
+		assert_eq!(Balances::free_balance(recipient), 0);
+
+		let call = MixerCall::withdraw_unsigned(proofs[0].clone());
+		let valid = Mixer::validate_unsigned(TransactionSource::External, &call).unwrap();
+		let tag = proofs[0].nullifier_hash.clone();
+		assert_eq!(valid.provides, vec![("MixerWithdrawal", tag).encode()]);
+
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[0].clone()));
+		assert_eq!(Balances::free_balance(recipient), 900);
+		assert_eq!(Balances::free_balance(fee_collector), 100);
+		assert_eq!(
+			Mixer::validate_unsigned(TransactionSource::External, &call),
+			InvalidTransaction::Stale.into()
+		);
+	})
+}
+
+#[test]
+fn should_limit_unsigned_withdrawals_per_block() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let (fee_collector, recipient) = (7, 8);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::signed(4), 0, 100));
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(100)));
+		let proofs = deposit_and_prove_with_relayer(0, 3, recipient, fee_collector, 100, 0);
+
+		// Failed withdrawals count toward the cap too
+		let mut redirected = proofs[1].clone();
+		redirected.recipient = Some(9);
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::none(), redirected),
+			merkle::Error::<Test>::ZkVerificationFailed
+		);
+		assert_eq!(Mixer::unsigned_withdrawals_in_block(), 1);
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[0].clone()));
+		assert_eq!(Mixer::unsigned_withdrawals_in_block(), 2);
+		let call = MixerCall::withdraw_unsigned(proofs[1].clone());
+		assert_eq!(
+			Mixer::validate_unsigned(TransactionSource::External, &call),
+			InvalidTransaction::ExhaustsResources.into()
+		);
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::none(), proofs[1].clone()),
+			Error::<Test>::TooManyUnsignedWithdrawals
+		);
 
 		System::set_block_number(2);
-		<Mixer as OnFinalize<u64>>::on_finalize(2);
-		assert!(Mixer::second_stage_initialized());
+		assert_eq!(Mixer::unsigned_withdrawals_in_block(), 0);
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[1].clone()));
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[2].clone()));
+		assert_eq!(Balances::free_balance(recipient), 3 * 900);
+	})
+}
 
-		for i in 0..4 {
-			let tree_id = i;
-			let tree = MerkleTrees::get_tree(tree_id).unwrap();
-			assert!(tree.initialized);
+#[test]
+fn should_sweep_fees_of_unsigned_withdrawals() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let (fee_collector, recipient) = (7, 8);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::signed(4), 0, 100));
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(100)));
+		let proofs = deposit_and_prove_with_relayer(0, 1, recipient, fee_collector, 100, 0);
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[0].clone()));
+		let currency_id = Mixer::get_mixer(0).unwrap().currency_id;
+
+		assert_err!(Mixer::sweep_fees(Origin::signed(1), currency_id, 100, 3), BadOrigin);
+		assert!(Mixer::sweep_fees(Origin::signed(4), currency_id, 101, 3).is_err());
+		assert_ok!(Mixer::sweep_fees(Origin::signed(4), currency_id, 100, 3));
+		assert_eq!(Balances::free_balance(fee_collector), 0);
+		assert_eq!(Balances::free_balance(3), 100);
+		let event = TestEvent::Mixer(Event::FeesSwept(currency_id, 100, 3));
+		assert!(System::events().iter().any(|record| record.event == event));
+	})
+}
+
+#[test]
+fn should_reject_unsigned_withdrawals_with_invalid_proofs_from_the_pool() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let (fee_collector, recipient) = (7, 8);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::signed(4), 0, 100));
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(100)));
+		let proofs = deposit_and_prove_with_relayer(0, 1, recipient, fee_collector, 100, 0);
+
+		// The proof is bound to its recipient
+		let mut redirected = proofs[0].clone();
+		redirected.recipient = Some(9);
+		let redirected = MixerCall::withdraw_unsigned(redirected);
+		for source in &[
+			TransactionSource::Local,
+			TransactionSource::InBlock,
+			TransactionSource::External,
+		] {
+			assert_eq!(
+				Mixer::validate_unsigned(*source, &redirected),
+				InvalidTransaction::BadProof.into()
+			);
 		}
+		// The proof is only verified again when dispatched
+		assert_ok!(Mixer::pre_dispatch(&redirected));
+		assert_err!(
+			redirected.dispatch_bypass_filter(Origin::none()),
+			merkle::Error::<Test>::ZkVerificationFailed
+		);
+
+		// No roots were cached at block 0
+		let mut uncached = proofs[0].clone();
+		uncached.cached_block = 0;
+		assert_eq!(
+			Mixer::validate_unsigned(TransactionSource::External, &MixerCall::withdraw_unsigned(uncached)),
+			InvalidTransaction::Stale.into()
+		);
+
+		let call = MixerCall::withdraw_unsigned(proofs[0].clone());
+		assert_ok!(Mixer::validate_unsigned(TransactionSource::External, &call));
+		// Nothing was spent or paid out while validating
+		assert_ok!(<MerkleTrees as TreeTrait<Test>>::has_used_nullifier(
+			0,
+			proofs[0].nullifier_hash.clone()
+		));
+		assert_eq!(Balances::free_balance(recipient), 0);
+	})
+}
+
+#[test]
+fn should_keep_unsigned_withdrawals_valid_in_later_blocks_in_the_pool() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let (fee_collector, recipient) = (7, 8);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::signed(4), 0, 100));
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(100)));
+		let limits = MixerLimits {
+			withdrawals: FlowLimits {
+				per_block: Some(1_000),
+				per_epoch: None,
+			},
+			root_cooldown: 2,
+			..Default::default()
+		};
+		assert_ok!(Mixer::set_limits(Origin::root(), 0, limits));
+		let proofs = deposit_and_prove_with_relayer(0, 2, recipient, fee_collector, 100, 0);
+		let call = MixerCall::withdraw_unsigned(proofs[1].clone());
+
+		// The root is in its cooldown until block 3
+		assert_eq!(
+			Mixer::validate_unsigned(TransactionSource::External, &call),
+			InvalidTransaction::Future.into()
+		);
+		assert_eq!(Mixer::pre_dispatch(&call), Err(InvalidTransaction::Future.into()));
+
+		System::set_block_number(3);
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[0].clone()));
+		assert_eq!(
+			Mixer::validate_unsigned(TransactionSource::External, &call),
+			InvalidTransaction::ExhaustsResources.into()
+		);
+		assert_eq!(
+			Mixer::pre_dispatch(&call),
+			Err(InvalidTransaction::ExhaustsResources.into())
+		);
+		// Only dispatching reports the limit hit
+		assert_eq!(rate_limit_hit_events(), vec![]);
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::none(), proofs[1].clone()),
+			Error::<Test>::RateLimitExceeded
+		);
+		assert_eq!(rate_limit_hit_events(), vec![(0, RateLimit::WithdrawalsPerBlock)]);
+
+		System::set_block_number(4);
+		assert_ok!(Mixer::pre_dispatch(&call));
+		assert_ok!(Mixer::withdraw_unsigned(Origin::none(), proofs[1].clone()));
+		assert_eq!(Mixer::pre_dispatch(&call), Err(InvalidTransaction::Stale.into()));
+	})
+}
+
+#[test]
+fn should_reject_invalid_unsigned_withdrawals() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		let (fee_collector, recipient) = (7, 8);
+		let proofs = deposit_and_prove_with_relayer(0, 1, recipient, fee_collector, 100, 0);
+		let call = MixerCall::withdraw_unsigned(proofs[0].clone());
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::none(), proofs[0].clone()),
+			Error::<Test>::UnsignedWithdrawalsDisabled
+		);
+		assert_eq!(
+			Mixer::validate_unsigned(TransactionSource::External, &call),
+			InvalidTransaction::Call.into()
+		);
+
+		assert_err!(
+			Mixer::set_unsigned_withdrawal_fee(Origin::signed(1), 0, Some(100)),
+			BadOrigin
+		);
+		assert_err!(
+			Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(100)),
+			Error::<Test>::FeeExceedsMax
+		);
+		assert_ok!(Mixer::set_max_relayer_fee(Origin::signed(4), 0, 100));
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, Some(50)));
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::signed(recipient), proofs[0].clone()),
+			BadOrigin
+		);
+		// The proof pays a fee of 100 to the fee collector
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::none(), proofs[0].clone()),
+			Error::<Test>::InvalidUnsignedWithdrawal
+		);
+		// Relayers can't take the fee of unsigned withdrawals
+		let relayed = deposit_and_prove_with_fee(0, 1, recipient, 50, 0);
+		assert_err!(
+			Mixer::withdraw_unsigned(Origin::none(), relayed[0].clone()),
+			Error::<Test>::InvalidUnsignedWithdrawal
+		);
+		assert_ok!(Mixer::set_unsigned_withdrawal_fee(Origin::signed(4), 0, None));
+		assert_eq!(Mixer::unsigned_withdrawal_fee(0), None);
 	})
 }
@@ -1,5 +1,5 @@
 use super::*;
-use frame_support::dispatch;
+use frame_support::{dispatch, weights::Weight};
 use merkle::utils::setup::Setup;
 
 pub trait ExtendedMixer<T: Config> {
@@ -10,3 +10,35 @@ pub trait ExtendedMixer<T: Config> {
 		size: BalanceOf<T>,
 	) -> Result<T::TreeId, dispatch::DispatchError>;
 }
+
+/// Hook for runtimes to deny accounts from depositing into and withdrawing
+/// from mixers, e.g. accounts holding stolen funds
+pub trait DenyList<AccountId> {
+	/// Whether `who` is denied
+	fn is_denied(who: &AccountId) -> bool;
+}
+
+impl<AccountId> DenyList<AccountId> for () {
+	fn is_denied(_who: &AccountId) -> bool {
+		false
+	}
+}
+
+/// Hook notified of the relayer of each relayed withdrawal paid out by the
+/// mixers, e.g. to keep a record of the relays of registered relayers
+pub trait OnRelay<AccountId, CurrencyId, Balance> {
+	/// Called once a withdrawal relayed by `relayer`, which took a `fee` in
+	/// `currency_id` out of it, is paid out
+	fn on_relay(relayer: &AccountId, currency_id: CurrencyId, fee: Balance);
+
+	/// Weight of a call to `on_relay`, added to the weight of each withdrawal
+	fn on_relay_weight() -> Weight;
+}
+
+impl<AccountId, CurrencyId, Balance> OnRelay<AccountId, CurrencyId, Balance> for () {
+	fn on_relay(_relayer: &AccountId, _currency_id: CurrencyId, _fee: Balance) {}
+
+	fn on_relay_weight() -> Weight {
+		0
+	}
+}
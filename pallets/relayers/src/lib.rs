@@ -0,0 +1,458 @@
+//! # Relayers Pallet
+//!
+//! The Relayers pallet is a registry of the relayers submitting mixer
+//! withdrawals on behalf of their recipients.
+//!
+//! - [`Config`]
+//! - [`Call`]
+//! - [`Pallet`]
+//!
+//! ## Overview
+//!
+//! The Relayers pallet provides functions for:
+//!
+//! - Registering a relayer along with its endpoint and fee schedule, backed by
+//!   a stake in any currency.
+//! - Keeping a record of the withdrawals relayed by each relayer.
+//! - Slashing the stake of a misbehaving relayer.
+//! - Listing the relayers through the [`RelayersApi`] runtime API.
+//!
+//! ### Terminology
+//!
+//! - **Relayer**: Account submitting withdrawals to a mixer for their
+//!   recipients, which usually can't pay for the transaction themselves, in
+//!   exchange for a fee taken out of the withdrawn amount.
+//!
+//! - **Fee schedule**: Fee a relayer charges per withdrawal, in each currency
+//!   it relays withdrawals of.
+//!
+//! - **Record**: Number of withdrawals relayed by a relayer and of times it was
+//!   slashed, from which users judge whether to trust it (see
+//!   [`RelayRecord`]).
+//!
+//! ## Interface
+//!
+//! ### Dispatchable Functions
+//!
+//! - `register` - Registers the sender as a relayer, reserving its stake.
+//! - `update` - Updates the endpoint and fee schedule of a relayer.
+//! - `add_stake` - Reserves more stake for a relayer.
+//! - `unregister` - Starts the unbonding of the stake of a relayer.
+//! - `withdraw_stake` - Unreserves the stake of a relayer once unbonded and
+//!   removes it from the registry.
+//! - `slash` - Slashes the stake of a misbehaving relayer. Can only be called
+//!   by the `SlashOrigin`.
+//!
+//! ### Stakes
+//!
+//! The stake of a relayer is reserved in the currency of its choice, and must
+//! be at least the `MinStake` of that currency. Currencies whose `MinStake` is
+//! zero can't be staked. An unregistered relayer keeps its stake reserved for
+//! `UnbondingPeriod` blocks, during which it can still be slashed. Slashed
+//! stakes are burned.
+//!
+//! ### Relays
+//!
+//! The pallet implements the [`OnRelay`] hook of the mixers. Each withdrawal
+//! paid out by a mixer, and submitted by the account in the `relayer` field
+//! of its proof on behalf of another recipient, counts toward the record of
+//! that account, as long as it is a registered relayer. The fee schedule is
+//! informative only, fees are bound by the withdrawal proofs and capped by
+//! the mixers.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(test)]
+pub mod mock;
+
+#[cfg(test)]
+pub mod tests;
+
+pub mod weights;
+
+use codec::{Decode, Encode};
+use frame_support::{dispatch, ensure, traits::EnsureOrigin, weights::Weight};
+use frame_system::ensure_signed;
+use mixer::traits::OnRelay;
+use sp_runtime::traits::{Saturating, Zero};
+use sp_std::prelude::*;
+use webb_traits::{GetByKey, MultiCurrency, MultiReservableCurrency};
+use weights::WeightInfo;
+
+pub use pallet::*;
+
+/// Implementation of Relayers pallet
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+
+	/// The pallet's configuration trait.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// The overarching event type.
+		type Event: IsType<<Self as frame_system::Config>::Event> + From<Event<Self>>;
+		/// Currency type for reserving stakes
+		type Currency: MultiReservableCurrency<Self::AccountId>;
+		/// Minimum stake of a relayer in each currency, zero for currencies
+		/// which can't be staked
+		type MinStake: GetByKey<CurrencyIdOf<Self>, BalanceOf<Self>>;
+		/// Number of blocks the stake of an unregistered relayer stays
+		/// reserved for, and can be slashed
+		#[pallet::constant]
+		type UnbondingPeriod: Get<Self::BlockNumber>;
+		/// Maximum length of the endpoint of a relayer
+		#[pallet::constant]
+		type MaxEndpointLength: Get<u32>;
+		/// Maximum number of currencies in the fee schedule of a relayer
+		#[pallet::constant]
+		type MaxFees: Get<u32>;
+		/// Origin allowed to slash relayers
+		type SlashOrigin: EnsureOrigin<Self::Origin>;
+		/// Weight information for extrinsics in this pallet
+		type WeightInfo: WeightInfo;
+	}
+
+	/// The map of relayers to their info
+	#[pallet::storage]
+	#[pallet::getter(fn relayers)]
+	pub type Relayers<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, RelayerInfoOf<T>>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	#[pallet::metadata(
+		T::AccountId = "AccountId",
+		T::BlockNumber = "BlockNumber",
+		CurrencyIdOf<T> = "CurrencyId",
+		BalanceOf<T> = "Balance"
+	)]
+	pub enum Event<T: Config> {
+		/// New relayer registered
+		Registered(
+			/// Account id of the relayer
+			T::AccountId,
+			/// Currency of the stake
+			CurrencyIdOf<T>,
+			/// Amount staked
+			BalanceOf<T>,
+		),
+		/// Endpoint and fee schedule of the relayer updated
+		Updated(
+			/// Account id of the relayer
+			T::AccountId,
+		),
+		/// Stake of the relayer increased
+		StakeAdded(
+			/// Account id of the relayer
+			T::AccountId,
+			/// Amount added to the stake
+			BalanceOf<T>,
+		),
+		/// Relayer unregistered, its stake is unbonding
+		Unregistered(
+			/// Account id of the relayer
+			T::AccountId,
+			/// Block from which the stake can be withdrawn
+			T::BlockNumber,
+		),
+		/// Stake of the relayer withdrawn, and the relayer removed
+		StakeWithdrawn(
+			/// Account id of the relayer
+			T::AccountId,
+			/// Amount unreserved
+			BalanceOf<T>,
+		),
+		/// Stake of the relayer slashed
+		Slashed(
+			/// Account id of the relayer
+			T::AccountId,
+			/// Amount slashed
+			BalanceOf<T>,
+		),
+	}
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// Account is already a relayer
+		AlreadyRegistered,
+		/// Account is not a relayer
+		NotRegistered,
+		/// Endpoint is longer than `MaxEndpointLength`
+		EndpointTooLong,
+		/// Fee schedule has more than `MaxFees` currencies
+		TooManyFees,
+		/// Fee schedule has more than one fee for a currency
+		DuplicateFeeCurrency,
+		/// Currency can't be staked
+		UnsupportedStakeCurrency,
+		/// Stake is lower than the minimum stake of its currency
+		StakeTooLow,
+		/// Relayer is already unregistered
+		AlreadyUnbonding,
+		/// Relayer is not unregistered
+		NotUnbonding,
+		/// Stake of the relayer is still unbonding
+		StillUnbonding,
+	}
+
+	#[pallet::pallet]
+	#[pallet::generate_store(pub(super) trait Store)]
+	pub struct Pallet<T>(PhantomData<T>);
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Registers the sender as a relayer taking withdrawals at `endpoint`
+		/// for the fees of `fees`, and reserves `stake` of `stake_currency`
+		/// from it.
+		///
+		/// Fails if the sender is already a relayer, or if the stake is lower
+		/// than the `MinStake` of its currency.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 45_000_000
+		/// - DB weights: 2 reads, 2 writes
+		#[pallet::weight(<T as Config>::WeightInfo::register())]
+		pub fn register(
+			origin: OriginFor<T>,
+			endpoint: Vec<u8>,
+			fees: Vec<(CurrencyIdOf<T>, BalanceOf<T>)>,
+			stake_currency: CurrencyIdOf<T>,
+			stake: BalanceOf<T>,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			ensure!(!Relayers::<T>::contains_key(&sender), Error::<T>::AlreadyRegistered);
+			Self::check_profile(&endpoint, &fees)?;
+			let min_stake = T::MinStake::get(&stake_currency);
+			ensure!(!min_stake.is_zero(), Error::<T>::UnsupportedStakeCurrency);
+			ensure!(stake >= min_stake, Error::<T>::StakeTooLow);
+			T::Currency::reserve(stake_currency, &sender, stake)?;
+
+			Relayers::<T>::insert(
+				&sender,
+				RelayerInfo {
+					endpoint,
+					fees,
+					stake_currency,
+					stake,
+					record: RelayRecord::default(),
+					unbonding_until: None,
+				},
+			);
+			Self::deposit_event(Event::Registered(sender, stake_currency, stake));
+			Ok(().into())
+		}
+
+		/// Updates the endpoint and fee schedule of the sender.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 20_000_000
+		/// - DB weights: 1 read, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::update())]
+		pub fn update(
+			origin: OriginFor<T>,
+			endpoint: Vec<u8>,
+			fees: Vec<(CurrencyIdOf<T>, BalanceOf<T>)>,
+		) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			let mut info = Self::get_relayer(&sender)?;
+			Self::check_profile(&endpoint, &fees)?;
+			info.endpoint = endpoint;
+			info.fees = fees;
+			Relayers::<T>::insert(&sender, info);
+			Self::deposit_event(Event::Updated(sender));
+			Ok(().into())
+		}
+
+		/// Reserves `amount` more of the stake currency of the sender, e.g. to
+		/// get back over the minimum stake after a slash.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 35_000_000
+		/// - DB weights: 2 reads, 2 writes
+		#[pallet::weight(<T as Config>::WeightInfo::add_stake())]
+		pub fn add_stake(origin: OriginFor<T>, amount: BalanceOf<T>) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			let mut info = Self::get_relayer(&sender)?;
+			T::Currency::reserve(info.stake_currency, &sender, amount)?;
+			info.stake = info.stake.saturating_add(amount);
+			Relayers::<T>::insert(&sender, info);
+			Self::deposit_event(Event::StakeAdded(sender, amount));
+			Ok(().into())
+		}
+
+		/// Unregisters the sender, whose stake can be withdrawn after the
+		/// `UnbondingPeriod`. It stays in the registry until then, and can
+		/// still be slashed.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 20_000_000
+		/// - DB weights: 1 read, 1 write
+		#[pallet::weight(<T as Config>::WeightInfo::unregister())]
+		pub fn unregister(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			let mut info = Self::get_relayer(&sender)?;
+			ensure!(info.unbonding_until.is_none(), Error::<T>::AlreadyUnbonding);
+			let until = <frame_system::Pallet<T>>::block_number().saturating_add(T::UnbondingPeriod::get());
+			info.unbonding_until = Some(until);
+			Relayers::<T>::insert(&sender, info);
+			Self::deposit_event(Event::Unregistered(sender, until));
+			Ok(().into())
+		}
+
+		/// Unreserves the stake of the sender once unbonded, and removes it
+		/// from the registry.
+		///
+		/// Fails if the sender isn't unregistered or its stake is still
+		/// unbonding.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 35_000_000
+		/// - DB weights: 2 reads, 2 writes
+		#[pallet::weight(<T as Config>::WeightInfo::withdraw_stake())]
+		pub fn withdraw_stake(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+			let sender = ensure_signed(origin)?;
+			let info = Self::get_relayer(&sender)?;
+			let until = info.unbonding_until.ok_or(Error::<T>::NotUnbonding)?;
+			ensure!(
+				until <= <frame_system::Pallet<T>>::block_number(),
+				Error::<T>::StillUnbonding
+			);
+			T::Currency::unreserve(info.stake_currency, &sender, info.stake);
+			Relayers::<T>::remove(&sender);
+			Self::deposit_event(Event::StakeWithdrawn(sender, info.stake));
+			Ok(().into())
+		}
+
+		/// Slashes up to `amount` of the stake of `relayer` for misbehaving,
+		/// e.g. for front-running the withdrawals it was given. Can only be
+		/// called by the `SlashOrigin`.
+		///
+		/// The slashed stake is burned, and the slash is counted in the record
+		/// of the relayer.
+		///
+		/// Weights:
+		/// - Independent of the arguments.
+		///
+		/// - Base weight: 40_000_000
+		/// - DB weights: 2 reads, 2 writes
+		#[pallet::weight(<T as Config>::WeightInfo::slash())]
+		pub fn slash(origin: OriginFor<T>, relayer: T::AccountId, amount: BalanceOf<T>) -> DispatchResultWithPostInfo {
+			T::SlashOrigin::ensure_origin(origin)?;
+			let mut info = Self::get_relayer(&relayer)?;
+			let amount = amount.min(info.stake);
+			let unslashed = T::Currency::slash_reserved(info.stake_currency, &relayer, amount);
+			let slashed = amount.saturating_sub(unslashed);
+			info.stake = info.stake.saturating_sub(slashed);
+			info.record.slashes = info.record.slashes.saturating_add(1);
+			Relayers::<T>::insert(&relayer, info);
+			Self::deposit_event(Event::Slashed(relayer, slashed));
+			Ok(().into())
+		}
+	}
+}
+
+/// Number of withdrawals relayed and of slashes of a relayer
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RelayRecord {
+	/// Number of withdrawals paid out with the relayer as their relayer
+	pub relays: u32,
+	/// Number of times the relayer was slashed
+	pub slashes: u32,
+}
+
+/// Info of a registered relayer
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+pub struct RelayerInfo<CurrencyId, Balance, BlockNumber> {
+	/// Endpoint the relayer takes withdrawals at, e.g. a URL
+	pub endpoint: Vec<u8>,
+	/// Fee the relayer charges per withdrawal, in each currency it relays
+	pub fees: Vec<(CurrencyId, Balance)>,
+	/// Currency of the stake
+	pub stake_currency: CurrencyId,
+	/// Amount staked, reserved from the relayer
+	pub stake: Balance,
+	/// Record of the relayer
+	pub record: RelayRecord,
+	/// Block from which the stake can be withdrawn, if the relayer is
+	/// unregistered
+	pub unbonding_until: Option<BlockNumber>,
+}
+
+/// Type alias for the webb_traits::MultiCurrency::Balance type
+pub type BalanceOf<T> = <<T as Config>::Currency as MultiCurrency<<T as frame_system::Config>::AccountId>>::Balance;
+/// Type alias for the webb_traits::MultiCurrency::CurrencyId type
+pub type CurrencyIdOf<T> =
+	<<T as pallet::Config>::Currency as MultiCurrency<<T as frame_system::Config>::AccountId>>::CurrencyId;
+/// Type alias for the info of a relayer of the runtime
+pub type RelayerInfoOf<T> = RelayerInfo<CurrencyIdOf<T>, BalanceOf<T>, <T as frame_system::Config>::BlockNumber>;
+
+impl<T: Config> Pallet<T> {
+	/// Info of the relayer `who`, failing if it isn't registered
+	pub fn get_relayer(who: &T::AccountId) -> Result<RelayerInfoOf<T>, dispatch::DispatchError> {
+		Self::relayers(who).ok_or_else(|| Error::<T>::NotRegistered.into())
+	}
+
+	/// All the registered relayers, including those which are unbonding
+	pub fn get_relayers() -> Vec<(T::AccountId, RelayerInfoOf<T>)> {
+		Relayers::<T>::iter().collect()
+	}
+
+	fn check_profile(endpoint: &[u8], fees: &[(CurrencyIdOf<T>, BalanceOf<T>)]) -> dispatch::DispatchResult {
+		ensure!(
+			endpoint.len() <= T::MaxEndpointLength::get() as usize,
+			Error::<T>::EndpointTooLong
+		);
+		ensure!(fees.len() <= T::MaxFees::get() as usize, Error::<T>::TooManyFees);
+		for (i, (currency_id, _)) in fees.iter().enumerate() {
+			ensure!(
+				fees[..i].iter().all(|(other, _)| other != currency_id),
+				Error::<T>::DuplicateFeeCurrency
+			);
+		}
+		Ok(())
+	}
+}
+
+impl<T: Config> OnRelay<T::AccountId, CurrencyIdOf<T>, BalanceOf<T>> for Pallet<T> {
+	fn on_relay(relayer: &T::AccountId, _currency_id: CurrencyIdOf<T>, _fee: BalanceOf<T>) {
+		Relayers::<T>::mutate(relayer, |info| {
+			if let Some(info) = info {
+				info.record.relays = info.record.relays.saturating_add(1);
+			}
+		});
+	}
+
+	fn on_relay_weight() -> Weight {
+		<T as Config>::WeightInfo::on_relay()
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	pub trait RelayersApi<AccountId, CurrencyId, Balance, BlockNumber>
+	where
+		AccountId: codec::Codec,
+		CurrencyId: codec::Codec,
+		Balance: codec::Codec,
+		BlockNumber: codec::Codec,
+	{
+		/// Get the registered relayers along with their endpoint, fee
+		/// schedule, stake and record.
+		fn get_relayers() -> Vec<(AccountId, RelayerInfo<CurrencyId, Balance, BlockNumber>)>;
+	}
+}
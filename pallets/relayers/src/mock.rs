@@ -0,0 +1,181 @@
+use super::*;
+use crate as pallet_relayers;
+use frame_support::{construct_runtime, ord_parameter_types, parameter_types, weights::Weight, PalletId};
+use frame_system::{
+	mocking::{MockBlock, MockUncheckedExtrinsic},
+	EnsureOneOf, EnsureRoot, EnsureSignedBy,
+};
+use webb_currencies::BasicCurrencyAdapter;
+
+use sp_core::H256;
+use sp_runtime::{
+	testing::Header,
+	traits::{AccountIdConversion, BlakeTwo256, IdentityLookup},
+	Perbill,
+};
+use weights::Weights;
+
+pub(crate) type Balance = u64;
+pub type Amount = i128;
+pub type CurrencyId = u64;
+pub type AccountId = u64;
+pub type BlockNumber = u64;
+
+// Configure a mock runtime to test the pallet.
+type UncheckedExtrinsic = MockUncheckedExtrinsic<Test>;
+type Block = MockBlock<Test>;
+
+construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+		Currencies: webb_currencies::{Pallet, Storage, Event<T>},
+		Tokens: webb_tokens::{Pallet, Storage, Event<T>},
+		Relayers: pallet_relayers::{Pallet, Call, Storage, Event<T>},
+	}
+);
+
+parameter_types! {
+	pub Prefix: u8 = 100;
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: Weight = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+}
+
+impl frame_system::Config for Test {
+	type AccountData = pallet_balances::AccountData<u64>;
+	type AccountId = AccountId;
+	type BaseCallFilter = ();
+	type BlockHashCount = BlockHashCount;
+	type BlockLength = ();
+	type BlockNumber = BlockNumber;
+	type BlockWeights = ();
+	type Call = Call;
+	type DbWeight = ();
+	type Event = Event;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type Header = Header;
+	type Index = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type OnKilledAccount = ();
+	type OnNewAccount = ();
+	type OnSetCode = ();
+	type Origin = Origin;
+	type PalletInfo = PalletInfo;
+	type SS58Prefix = Prefix;
+	type SystemWeightInfo = ();
+	type Version = ();
+}
+
+parameter_types! {
+	pub const ExistentialDeposit: Balance = 0;
+	pub const MaxLocks: u32 = 50;
+	pub const MaxReserves: u32 = 50;
+}
+
+impl pallet_balances::Config for Test {
+	type AccountStore = System;
+	type Balance = Balance;
+	type DustRemoval = ();
+	type Event = Event;
+	type ExistentialDeposit = ExistentialDeposit;
+	type MaxLocks = MaxLocks;
+	type MaxReserves = MaxReserves;
+	type ReserveIdentifier = [u8; 8];
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const NativeCurrencyId: CurrencyId = 0;
+}
+
+parameter_types! {
+	pub const TokensPalletId: PalletId = PalletId(*b"py/token");
+	pub const CurrencyDeposit: u64 = 0;
+	pub const ApprovalDeposit: u64 = 1;
+	pub const StringLimit: u32 = 50;
+	pub const MetadataDepositBase: u64 = 1;
+	pub const MetadataDepositPerByte: u64 = 1;
+}
+
+parameter_types! {
+	pub DustAccount: AccountId = PalletId(*b"webb/dst").into_account();
+}
+
+impl webb_tokens::Config for Test {
+	type Amount = i128;
+	type ApprovalDeposit = ApprovalDeposit;
+	type Balance = Balance;
+	type CurrencyDeposit = CurrencyDeposit;
+	type CurrencyId = CurrencyId;
+	type DustAccount = DustAccount;
+	type Event = Event;
+	type Extra = ();
+	type ForceOrigin = frame_system::EnsureRoot<AccountId>;
+	type MetadataDepositBase = MetadataDepositBase;
+	type MetadataDepositPerByte = MetadataDepositPerByte;
+	type NativeCurrency = BasicCurrencyAdapter<Test, Balances, Amount, BlockNumber>;
+	type PalletId = TokensPalletId;
+	type StringLimit = StringLimit;
+	type WeightInfo = ();
+}
+
+impl webb_currencies::Config for Test {
+	type Event = Event;
+	type GetNativeCurrencyId = NativeCurrencyId;
+	type MultiCurrency = Tokens;
+	type NativeCurrency = BasicCurrencyAdapter<Test, Balances, Amount, BlockNumber>;
+	type WeightInfo = ();
+}
+
+webb_traits::parameter_type_with_key! {
+	// Only the native currency can be staked
+	pub MinStake: |currency_id: CurrencyId| -> Balance {
+		if *currency_id == NativeCurrencyId::get() {
+			100
+		} else {
+			0
+		}
+	};
+}
+
+parameter_types! {
+	pub const UnbondingPeriod: u64 = 10;
+	pub const MaxEndpointLength: u32 = 32;
+	pub const MaxFees: u32 = 2;
+}
+
+ord_parameter_types! {
+	pub const Governance: u64 = 4;
+}
+
+impl Config for Test {
+	type Currency = Currencies;
+	type Event = Event;
+	type MaxEndpointLength = MaxEndpointLength;
+	type MaxFees = MaxFees;
+	type MinStake = MinStake;
+	type SlashOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<Governance, u64>>;
+	type UnbondingPeriod = UnbondingPeriod;
+	type WeightInfo = Weights<Self>;
+}
+
+// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	use pallet_balances::GenesisConfig as BalancesConfig;
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+
+	BalancesConfig::<Test> {
+		balances: vec![(1, 1_000), (2, 1_000), (3, 50)],
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+
+	t.into()
+}
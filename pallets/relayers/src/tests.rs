@@ -0,0 +1,208 @@
+use super::*;
+use crate::mock::{new_test_ext, AccountId, Balance, Balances, Event as TestEvent, Origin, Relayers, System, Test};
+use frame_support::{assert_err, assert_ok};
+use sp_runtime::traits::BadOrigin;
+
+fn endpoint() -> Vec<u8> {
+	b"https://relayer.example".to_vec()
+}
+
+fn register(relayer: AccountId, stake: Balance) {
+	assert_ok!(Relayers::register(
+		Origin::signed(relayer),
+		endpoint(),
+		vec![(0, 10)],
+		0,
+		stake
+	));
+}
+
+fn relay(relayer: AccountId) {
+	<Relayers as OnRelay<AccountId, u64, Balance>>::on_relay(&relayer, 0, 10);
+}
+
+fn slashed_events() -> Vec<(AccountId, Balance)> {
+	System::events()
+		.into_iter()
+		.filter_map(|record| match record.event {
+			TestEvent::Relayers(Event::Slashed(relayer, amount)) => Some((relayer, amount)),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn should_register_relayer_reserving_its_stake() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		register(1, 100);
+
+		assert_eq!(
+			Relayers::relayers(1),
+			Some(RelayerInfo {
+				endpoint: endpoint(),
+				fees: vec![(0, 10)],
+				stake_currency: 0,
+				stake: 100,
+				record: RelayRecord::default(),
+				unbonding_until: None,
+			})
+		);
+		assert_eq!(Balances::reserved_balance(1), 100);
+		assert_eq!(Balances::free_balance(1), 900);
+		assert_eq!(
+			System::events().last().unwrap().event,
+			TestEvent::Relayers(Event::Registered(1, 0, 100))
+		);
+	})
+}
+
+#[test]
+fn should_not_register_with_invalid_profile_or_stake() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_err!(
+			Relayers::register(Origin::signed(1), vec![0u8; 33], vec![], 0, 100),
+			Error::<Test>::EndpointTooLong
+		);
+		assert_err!(
+			Relayers::register(Origin::signed(1), endpoint(), vec![(0, 10), (1, 10), (2, 10)], 0, 100),
+			Error::<Test>::TooManyFees
+		);
+		assert_err!(
+			Relayers::register(Origin::signed(1), endpoint(), vec![(0, 10), (0, 20)], 0, 100),
+			Error::<Test>::DuplicateFeeCurrency
+		);
+		assert_err!(
+			Relayers::register(Origin::signed(1), endpoint(), vec![], 1, 100),
+			Error::<Test>::UnsupportedStakeCurrency
+		);
+		assert_err!(
+			Relayers::register(Origin::signed(1), endpoint(), vec![], 0, 99),
+			Error::<Test>::StakeTooLow
+		);
+		// Account 3 can't afford the minimum stake
+		assert!(Relayers::register(Origin::signed(3), endpoint(), vec![], 0, 100).is_err());
+		assert_eq!(Relayers::relayers(3), None);
+
+		register(1, 100);
+		assert_err!(
+			Relayers::register(Origin::signed(1), endpoint(), vec![], 0, 100),
+			Error::<Test>::AlreadyRegistered
+		);
+		assert_eq!(Balances::reserved_balance(1), 100);
+	})
+}
+
+#[test]
+fn should_update_profile_and_add_stake() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert_err!(
+			Relayers::update(Origin::signed(1), endpoint(), vec![]),
+			Error::<Test>::NotRegistered
+		);
+		assert_err!(Relayers::add_stake(Origin::signed(1), 50), Error::<Test>::NotRegistered);
+
+		register(1, 100);
+		assert_ok!(Relayers::update(
+			Origin::signed(1),
+			b"https://other.example".to_vec(),
+			vec![(0, 5), (1, 20)]
+		));
+		assert_err!(
+			Relayers::update(Origin::signed(1), endpoint(), vec![(1, 5), (1, 20)]),
+			Error::<Test>::DuplicateFeeCurrency
+		);
+		assert_ok!(Relayers::add_stake(Origin::signed(1), 50));
+
+		let info = Relayers::relayers(1).unwrap();
+		assert_eq!(info.endpoint, b"https://other.example".to_vec());
+		assert_eq!(info.fees, vec![(0, 5), (1, 20)]);
+		assert_eq!(info.stake, 150);
+		assert_eq!(Balances::reserved_balance(1), 150);
+	})
+}
+
+#[test]
+fn should_count_relays_of_registered_relayers() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		register(1, 100);
+		relay(1);
+		relay(1);
+		// Relays of accounts which aren't relayers are ignored
+		relay(2);
+
+		let record = Relayers::relayers(1).unwrap().record;
+		assert_eq!(record, RelayRecord { relays: 2, slashes: 0 });
+		assert_eq!(Relayers::relayers(2), None);
+	})
+}
+
+#[test]
+fn should_slash_relayer_stake() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		register(1, 100);
+		assert_err!(Relayers::slash(Origin::signed(1), 1, 40), BadOrigin);
+		assert_err!(Relayers::slash(Origin::signed(4), 2, 40), Error::<Test>::NotRegistered);
+
+		assert_ok!(Relayers::slash(Origin::signed(4), 1, 40));
+		// Slashes are capped by the stake
+		assert_ok!(Relayers::slash(Origin::root(), 1, 100));
+
+		let info = Relayers::relayers(1).unwrap();
+		assert_eq!(info.stake, 0);
+		assert_eq!(info.record.slashes, 2);
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), 900);
+		assert_eq!(slashed_events(), vec![(1, 40), (1, 60)]);
+	})
+}
+
+#[test]
+fn should_withdraw_stake_once_unbonded() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		register(1, 100);
+		assert_err!(Relayers::withdraw_stake(Origin::signed(1)), Error::<Test>::NotUnbonding);
+
+		assert_ok!(Relayers::unregister(Origin::signed(1)));
+		assert_eq!(Relayers::relayers(1).unwrap().unbonding_until, Some(11));
+		assert_err!(Relayers::unregister(Origin::signed(1)), Error::<Test>::AlreadyUnbonding);
+		assert_err!(
+			Relayers::withdraw_stake(Origin::signed(1)),
+			Error::<Test>::StillUnbonding
+		);
+		// Unbonding stakes can still be slashed
+		assert_ok!(Relayers::slash(Origin::root(), 1, 30));
+
+		System::set_block_number(11);
+		assert_ok!(Relayers::withdraw_stake(Origin::signed(1)));
+		assert_eq!(Relayers::relayers(1), None);
+		assert_eq!(Balances::reserved_balance(1), 0);
+		assert_eq!(Balances::free_balance(1), 970);
+	})
+}
+
+#[test]
+fn should_list_relayers() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(1);
+		assert!(Relayers::get_relayers().is_empty());
+		register(1, 100);
+		register(2, 200);
+		relay(2);
+
+		let mut relayers = Relayers::get_relayers();
+		relayers.sort_by_key(|(relayer, _)| *relayer);
+		assert_eq!(
+			relayers
+				.iter()
+				.map(|(relayer, info)| (*relayer, info.stake, info.record.relays))
+				.collect::<Vec<_>>(),
+			vec![(1, 100, 0), (2, 200, 1)]
+		);
+	})
+}
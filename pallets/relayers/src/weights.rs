@@ -0,0 +1,73 @@
+//! Weights for pallet_relayers
+//!
+//! Estimated by hand until the pallet is benchmarked. Each call reads and
+//! writes the relayer, and those moving stake the balances of its currency.
+//! So does `on_relay`, counted in the weights of the mixer withdrawals.
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use crate::Config;
+use frame_support::{
+	traits::Get,
+	weights::{constants::RocksDbWeight, Weight},
+};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_relayers.
+pub trait WeightInfo {
+	fn register() -> Weight;
+	fn update() -> Weight;
+	fn add_stake() -> Weight;
+	fn unregister() -> Weight;
+	fn withdraw_stake() -> Weight;
+	fn slash() -> Weight;
+	fn on_relay() -> Weight;
+}
+
+/// Weight functions for pallet_relayers.
+/// For use in production
+pub struct Weights<T>(PhantomData<T>);
+impl<T: frame_system::Config + Config> WeightInfo for Weights<T> {
+	fn register() -> Weight {
+		(45_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+
+	fn update() -> Weight {
+		(20_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+
+	fn add_stake() -> Weight {
+		(35_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+
+	fn unregister() -> Weight {
+		(20_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+
+	fn withdraw_stake() -> Weight {
+		(35_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+
+	fn slash() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+	}
+
+	fn on_relay() -> Weight {
+		(10_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+}
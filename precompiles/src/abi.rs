@@ -0,0 +1,307 @@
+//! Ethereum ABI encoding of precompile inputs and outputs
+//!
+//! Precompile inputs start with the 4 byte selector of the called function,
+//! followed by its arguments encoded in 32 byte words, as produced by
+//! Solidity for external calls.
+use evm::ExitError;
+use sp_core::U256;
+use sp_io::hashing::keccak_256;
+use sp_std::{prelude::Vec, vec};
+
+/// Length of an ABI word
+pub const WORD_LEN: usize = 32;
+
+/// Function selector, the first 4 bytes of the hash of the function signature
+pub type Selector = [u8; 4];
+
+/// Returns the selector of the function with `signature`, e.g.
+/// `hash(bytes32,bytes32)`.
+pub fn selector(signature: &str) -> Selector {
+	let hash = keccak_256(signature.as_bytes());
+	[hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Reads the arguments of a precompile call
+pub struct Reader<'a> {
+	input: &'a [u8],
+	cursor: usize,
+}
+
+impl<'a> Reader<'a> {
+	/// Splits `input` into the selector of the called function and a reader
+	/// over its arguments.
+	pub fn new_with_selector(input: &'a [u8]) -> Result<(Selector, Self), ExitError> {
+		if input.len() < 4 {
+			return Err(ExitError::Other("Input is too short for a selector".into()));
+		}
+		let selector = [input[0], input[1], input[2], input[3]];
+		Ok((selector, Self::new(&input[4..])))
+	}
+
+	/// Creates a reader over ABI encoded arguments.
+	pub fn new(input: &'a [u8]) -> Self {
+		Self { input, cursor: 0 }
+	}
+
+	/// Reads a `bytes32` argument.
+	pub fn read_bytes32(&mut self) -> Result<[u8; 32], ExitError> {
+		let mut bytes = [0u8; 32];
+		bytes.copy_from_slice(self.read_raw(WORD_LEN)?);
+		Ok(bytes)
+	}
+
+	/// Reads a `uint256` argument.
+	pub fn read_u256(&mut self) -> Result<U256, ExitError> {
+		Ok(U256::from_big_endian(&self.read_bytes32()?))
+	}
+
+	/// Reads a `uint32` argument, or any unsigned integer argument whose value
+	/// fits in 32 bits.
+	pub fn read_u32(&mut self) -> Result<u32, ExitError> {
+		let value = self.read_u256()?;
+		if value > U256::from(u32::MAX) {
+			return Err(ExitError::Other("Integer argument is out of range".into()));
+		}
+		Ok(value.low_u32())
+	}
+
+	/// Reads a dynamic `bytes` argument.
+	pub fn read_bytes(&mut self) -> Result<Vec<u8>, ExitError> {
+		let mut inner = self.read_dynamic()?;
+		let len = inner.read_len()?;
+		Ok(inner.read_raw(len)?.to_vec())
+	}
+
+	/// Reads a dynamic `bytes32[]` argument.
+	pub fn read_bytes32_array(&mut self) -> Result<Vec<[u8; 32]>, ExitError> {
+		let mut inner = self.read_dynamic()?;
+		let len = inner.read_len()?;
+		// Every element takes a word, so `len` is bounded by the input length
+		(0..len).map(|_| inner.read_bytes32()).collect()
+	}
+
+	/// Reads the offset of a dynamic argument and returns a reader positioned
+	/// at its contents.
+	fn read_dynamic(&mut self) -> Result<Self, ExitError> {
+		let offset = self.read_len()?;
+		Ok(Self {
+			input: self.input,
+			cursor: offset,
+		})
+	}
+
+	/// Reads a length or offset, which can't exceed the input length.
+	fn read_len(&mut self) -> Result<usize, ExitError> {
+		let value = self.read_u256()?;
+		if value > U256::from(self.input.len()) {
+			return Err(ExitError::Other("Length or offset is out of bounds".into()));
+		}
+		Ok(value.low_u64() as usize)
+	}
+
+	/// Reads `len` bytes, moving past their padding to a whole word.
+	fn read_raw(&mut self, len: usize) -> Result<&'a [u8], ExitError> {
+		let end = self.cursor.saturating_add(len);
+		let bytes = self
+			.input
+			.get(self.cursor..end)
+			.ok_or_else(|| ExitError::Other("Input is too short for its arguments".into()))?;
+		self.cursor = self.cursor.saturating_add((len + WORD_LEN - 1) / WORD_LEN * WORD_LEN);
+		Ok(bytes)
+	}
+}
+
+/// Builds ABI encoded arguments or return values
+#[derive(Default)]
+pub struct Writer {
+	selector: Option<Selector>,
+	head: Vec<u8>,
+	/// Contents of dynamic values, with the position of their offset in the
+	/// head
+	tails: Vec<(usize, Vec<u8>)>,
+}
+
+impl Writer {
+	/// Creates a writer for return values.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Creates a writer for the arguments of a call to `selector`.
+	pub fn new_with_selector(selector: Selector) -> Self {
+		Self {
+			selector: Some(selector),
+			..Self::default()
+		}
+	}
+
+	/// Writes a `bytes32` value.
+	pub fn write_bytes32(mut self, value: [u8; 32]) -> Self {
+		self.head.extend_from_slice(&value);
+		self
+	}
+
+	/// Writes a `uint256` value.
+	pub fn write_u256(self, value: U256) -> Self {
+		let mut word = [0u8; 32];
+		value.to_big_endian(&mut word);
+		self.write_bytes32(word)
+	}
+
+	/// Writes a `uint32` value.
+	pub fn write_u32(self, value: u32) -> Self {
+		self.write_u256(value.into())
+	}
+
+	/// Writes a `bool` value.
+	pub fn write_bool(self, value: bool) -> Self {
+		self.write_u256((value as u8).into())
+	}
+
+	/// Writes a dynamic `bytes` value.
+	pub fn write_bytes(self, value: &[u8]) -> Self {
+		let mut tail = Vec::with_capacity(WORD_LEN + value.len() + WORD_LEN);
+		tail.extend_from_slice(&Self::new().write_u256(value.len().into()).head);
+		tail.extend_from_slice(value);
+		tail.resize(WORD_LEN + (value.len() + WORD_LEN - 1) / WORD_LEN * WORD_LEN, 0);
+		self.write_tail(tail)
+	}
+
+	/// Writes a dynamic `bytes32[]` value.
+	pub fn write_bytes32_array(self, values: &[[u8; 32]]) -> Self {
+		let tail = values
+			.iter()
+			.fold(Self::new().write_u256(values.len().into()), |writer, value| {
+				writer.write_bytes32(*value)
+			})
+			.head;
+		self.write_tail(tail)
+	}
+
+	fn write_tail(mut self, tail: Vec<u8>) -> Self {
+		self.tails.push((self.head.len(), tail));
+		// Placeholder for the offset, set once the head is complete
+		self.head.extend_from_slice(&[0u8; WORD_LEN]);
+		self
+	}
+
+	/// Returns the encoded values.
+	pub fn build(self) -> Vec<u8> {
+		let mut output = Vec::new();
+		if let Some(selector) = self.selector {
+			output.extend_from_slice(&selector);
+		}
+		let head_start = output.len();
+		output.extend_from_slice(&self.head);
+
+		// Offsets are relative to the start of the head
+		for (position, tail) in self.tails {
+			let offset = U256::from(output.len() - head_start);
+			let start = head_start + position;
+			offset.to_big_endian(&mut output[start..start + WORD_LEN]);
+			output.extend_from_slice(&tail);
+		}
+		output
+	}
+}
+
+/// Encodes a `bytes32` return value.
+pub fn encode_bytes32(value: [u8; 32]) -> Vec<u8> {
+	value.to_vec()
+}
+
+/// Encodes a `bool` return value.
+pub fn encode_bool(value: bool) -> Vec<u8> {
+	let mut word = vec![0u8; WORD_LEN];
+	word[WORD_LEN - 1] = value as u8;
+	word
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn word(value: u64) -> [u8; 32] {
+		let mut word = [0u8; 32];
+		U256::from(value).to_big_endian(&mut word);
+		word
+	}
+
+	#[test]
+	fn should_compute_selector() {
+		// Selector of the ERC20 `transfer` function
+		assert_eq!(selector("transfer(address,uint256)"), [0xa9, 0x05, 0x9c, 0xbb]);
+	}
+
+	#[test]
+	fn should_read_words_after_selector() {
+		let mut input = vec![1, 2, 3, 4];
+		input.extend_from_slice(&[5u8; 32]);
+		input.extend_from_slice(&[6u8; 32]);
+
+		let (selector, mut reader) = Reader::new_with_selector(&input).unwrap();
+		assert_eq!(selector, [1, 2, 3, 4]);
+		assert_eq!(reader.read_bytes32().unwrap(), [5u8; 32]);
+		assert_eq!(reader.read_bytes32().unwrap(), [6u8; 32]);
+		assert!(reader.read_bytes32().is_err());
+	}
+
+	#[test]
+	fn should_read_dynamic_arguments() {
+		// f(uint256 a, bytes b, bytes32[] c) with a = 7, b = [9; 33], c = [[3; 32]]
+		let mut input = Vec::new();
+		input.extend_from_slice(&word(7));
+		input.extend_from_slice(&word(96));
+		input.extend_from_slice(&word(192));
+		input.extend_from_slice(&word(33));
+		input.extend_from_slice(&[9u8; 33]);
+		input.extend_from_slice(&[0u8; 31]);
+		input.extend_from_slice(&word(1));
+		input.extend_from_slice(&[3u8; 32]);
+
+		let mut reader = Reader::new(&input);
+		assert_eq!(reader.read_u32().unwrap(), 7);
+		assert_eq!(reader.read_bytes().unwrap(), vec![9u8; 33]);
+		assert_eq!(reader.read_bytes32_array().unwrap(), vec![[3u8; 32]]);
+	}
+
+	#[test]
+	fn should_read_written_arguments() {
+		let input = Writer::new_with_selector([1, 2, 3, 4])
+			.write_u32(7)
+			.write_bytes(&[9u8; 33])
+			.write_bytes32_array(&[[3u8; 32], [4u8; 32]])
+			.write_bool(true)
+			.build();
+
+		let (selector, mut reader) = Reader::new_with_selector(&input).unwrap();
+		assert_eq!(selector, [1, 2, 3, 4]);
+		assert_eq!(reader.read_u32().unwrap(), 7);
+		assert_eq!(reader.read_bytes().unwrap(), vec![9u8; 33]);
+		assert_eq!(reader.read_bytes32_array().unwrap(), vec![[3u8; 32], [4u8; 32]]);
+		assert_eq!(reader.read_u32().unwrap(), 1);
+	}
+
+	#[test]
+	fn should_not_read_out_of_bounds_dynamic_arguments() {
+		// Offset past the end of the input
+		let mut input = word(64).to_vec();
+		input.extend_from_slice(&word(1));
+		assert!(Reader::new(&input).read_bytes().is_err());
+
+		// Length larger than the remaining input
+		let mut input = word(32).to_vec();
+		input.extend_from_slice(&word(64));
+		assert!(Reader::new(&input).read_bytes32_array().is_err());
+
+		// Integer that doesn't fit
+		let mut input = [0u8; 32];
+		input[27] = 1;
+		assert!(Reader::new(&input).read_u32().is_err());
+	}
+
+	#[test]
+	fn should_not_read_selector_of_short_input() {
+		assert!(Reader::new_with_selector(&[1, 2, 3]).is_err());
+	}
+}
@@ -0,0 +1,331 @@
+//! Precompiles verifying Groth16 proofs
+//!
+//! There is one precompile for each supported curve. Proofs are verified
+//! against a key registered in the merkle pallet or against a key passed
+//! inline, so EVM contracts can reuse circuits registered on chain:
+//!
+//! - `verify(uint32 keyId, bytes proof, bytes32[] inputs) returns (bool)`
+//! - `verifyWithKey(bytes key, bytes proof, bytes32[] inputs) returns (bool)`
+//!
+//! Keys use the encoding of the merkle pallet, proofs are serialized with
+//! arkworks and public inputs are canonical field elements serialized in 32
+//! little-endian bytes. The call returns whether the proof is valid and fails
+//! on malformed input.
+use crate::abi::{self, Reader};
+use alloc::sync::Arc;
+use evm::{executor::PrecompileOutput, Context, ExitError, ExitSucceed};
+use fp_evm::Precompile;
+use frame_support::{traits::Get, weights::Weight};
+use merkle::{
+	utils::{
+		prepared::PreparedKey,
+		setup::{Backend, Curve, SetupError, Snark},
+	},
+	weights::WeightInfo,
+	Config, Pallet as MerklePallet,
+};
+use pallet_evm::GasWeightMapping;
+use sp_std::{marker::PhantomData, prelude::Vec};
+
+/// Signature of the function verifying against a registered key
+pub const VERIFY_SIGNATURE: &str = "verify(uint32,bytes,bytes32[])";
+/// Signature of the function verifying against an inline key
+pub const VERIFY_WITH_KEY_SIGNATURE: &str = "verifyWithKey(bytes,bytes,bytes32[])";
+
+/// Curve of a [`Groth16Precompile`] and the weights of verifying on it.
+///
+/// Preparing a key decodes each of its `gamma_abc` points and computes a
+/// pairing, and verifying costs a constant number of pairings plus a scalar
+/// multiplication for each public input. A key has one point more than its
+/// circuit has public inputs and its length is checked against its points, so
+/// the size of the key and both weights grow with the number of inputs.
+/// Preparing the key is always charged, whether or not it is cached. The
+/// weights are those of the Groth16 benchmarks of the merkle pallet.
+pub trait Groth16Curve {
+	fn curve() -> Curve;
+
+	/// Weight of preparing a key with `inputs` public inputs
+	fn prepare_weight<T: Config>(inputs: u32) -> Weight;
+
+	/// Weight of verifying a proof with `inputs` public inputs
+	fn verify_weight<T: Config>(inputs: u32) -> Weight;
+}
+
+/// Groth16 over BN254
+pub struct Groth16Bn254;
+impl Groth16Curve for Groth16Bn254 {
+	fn curve() -> Curve {
+		Curve::Bn254
+	}
+
+	fn prepare_weight<T: Config>(inputs: u32) -> Weight {
+		T::WeightInfo::prepare_groth16_bn254_key(inputs)
+	}
+
+	fn verify_weight<T: Config>(inputs: u32) -> Weight {
+		T::WeightInfo::verify_groth16_bn254(inputs)
+	}
+}
+
+/// Groth16 over BLS12-381
+pub struct Groth16Bls381;
+impl Groth16Curve for Groth16Bls381 {
+	fn curve() -> Curve {
+		Curve::Bls381
+	}
+
+	fn prepare_weight<T: Config>(inputs: u32) -> Weight {
+		T::WeightInfo::prepare_groth16_bls381_key(inputs)
+	}
+
+	fn verify_weight<T: Config>(inputs: u32) -> Weight {
+		T::WeightInfo::verify_groth16_bls381(inputs)
+	}
+}
+
+/// Verifying key of a call
+enum KeySource {
+	/// Key registered in the merkle pallet, with its bytes
+	Registered(u32, Vec<u8>),
+	/// Key passed with the call
+	Inline(Vec<u8>),
+}
+
+/// Verifies Groth16 proofs on the curve of `C`, against keys of the merkle
+/// pallet of `T`, charging gas through `G`.
+pub struct Groth16Precompile<T: Config, C: Groth16Curve, G: GasWeightMapping>(
+	PhantomData<T>,
+	PhantomData<C>,
+	PhantomData<G>,
+);
+
+impl<T: Config, C: Groth16Curve, G: GasWeightMapping> Groth16Precompile<T, C, G> {
+	fn backend() -> Backend {
+		Backend::Arkworks(C::curve(), Snark::Groth16)
+	}
+
+	fn charge(weight: Weight, target_gas: Option<u64>) -> Result<u64, ExitError> {
+		let cost = G::weight_to_gas(weight);
+		if let Some(target_gas) = target_gas {
+			if cost > target_gas {
+				return Err(ExitError::OutOfGas);
+			}
+		}
+		Ok(cost)
+	}
+}
+
+impl<T: Config, C: Groth16Curve, G: GasWeightMapping> Precompile for Groth16Precompile<T, C, G> {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<u64>,
+		_context: &Context,
+	) -> core::result::Result<PrecompileOutput, ExitError> {
+		let (selector, mut reader) = Reader::new_with_selector(input)?;
+		let key = if selector == abi::selector(VERIFY_SIGNATURE) {
+			let key_id = reader.read_u32()?;
+			let key = MerklePallet::<T>::get_verifying_key(key_id.into())
+				.map_err(|_| ExitError::Other("Invalid verifying key".into()))?;
+			KeySource::Registered(key_id, key)
+		} else if selector == abi::selector(VERIFY_WITH_KEY_SIGNATURE) {
+			KeySource::Inline(reader.read_bytes()?)
+		} else {
+			return Err(ExitError::Other("Unknown function selector".into()));
+		};
+		let proof = reader.read_bytes()?;
+		let public_inputs = reader.read_bytes32_array()?;
+
+		// The key is checked against the inputs and charged by size before it
+		// is prepared
+		let (key_bytes, key_weight) = match &key {
+			// The key and its hash are read from storage
+			KeySource::Registered(_, key) => (key, T::DbWeight::get().reads(2)),
+			KeySource::Inline(key) => (key, 0),
+		};
+		let input_count = PreparedKey::groth16_input_count(&Self::backend(), key_bytes)
+			.map_err(|_| ExitError::Other("Invalid verifying key".into()))?;
+		if input_count != public_inputs.len() {
+			return Err(ExitError::Other("Invalid public inputs".into()));
+		}
+		let weight = C::prepare_weight::<T>(input_count as u32)
+			.saturating_add(C::verify_weight::<T>(input_count as u32))
+			.saturating_add(key_weight);
+		let cost = Self::charge(weight, target_gas)?;
+
+		let prepared = match key {
+			KeySource::Registered(key_id, _) => {
+				MerklePallet::<T>::get_prepared_key(key_id.into(), &Self::backend()).ok()
+			}
+			KeySource::Inline(key) => PreparedKey::prepare(&Self::backend(), &key).ok().map(Arc::new),
+		}
+		.ok_or_else(|| ExitError::Other("Invalid verifying key".into()))?;
+		let res = prepared.verify_groth16(&proof, &public_inputs);
+		let valid = match res {
+			Ok(()) => true,
+			Err(SetupError::ZkVerificationFailed) => false,
+			Err(SetupError::InvalidZkProof) => return Err(ExitError::Other("Invalid proof".into())),
+			Err(SetupError::InvalidPublicInputs) => return Err(ExitError::Other("Invalid public inputs".into())),
+			Err(_) => return Err(ExitError::Other("Invalid verifying key".into())),
+		};
+
+		Ok(PrecompileOutput {
+			exit_status: ExitSucceed::Returned,
+			cost,
+			output: abi::encode_bool(valid),
+			logs: Vec::new(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{
+		abi::Writer,
+		mock::{new_test_ext, test_context, MerkleTrees, Origin, Test, TestGasWeightMapping},
+	};
+	use ark_ec::PairingEngine;
+	use ark_serialize::CanonicalSerialize;
+	use arkworks_gadgets::{
+		ark_std::test_rng,
+		prelude::{ark_bls12_381::Bls12_381, ark_bn254::Bn254, ark_ff::to_bytes},
+		setup::{
+			common::Curve as CurveEnum,
+			mixer::{get_public_inputs, prove_groth16_x5, setup_circuit_x5, setup_random_groth16_x5},
+		},
+	};
+	use frame_support::assert_ok;
+	use merkle::utils::keys::{get_groth16_vk_bytes, slice_to_bytes_32};
+
+	type Bn254Precompile = Groth16Precompile<Test, Groth16Bn254, TestGasWeightMapping>;
+	type Bls381Precompile = Groth16Precompile<Test, Groth16Bls381, TestGasWeightMapping>;
+
+	/// Encoded verifying key, proof and public inputs of a mixer proof
+	fn mixer_proof<E: PairingEngine>(curve: CurveEnum) -> (Vec<u8>, Vec<u8>, Vec<[u8; 32]>) {
+		let mut rng = test_rng();
+		let recipient = E::Fr::from(0u8);
+		let relayer = E::Fr::from(0u8);
+		let (circuit, _, nullifier_hash, root, _) =
+			setup_circuit_x5(&Vec::new(), 0, recipient, relayer, &mut rng, curve);
+		let (pk, vk) = setup_random_groth16_x5::<_, E>(&mut rng, curve);
+
+		let proof = prove_groth16_x5(&pk, circuit, &mut rng);
+		let mut proof_bytes = Vec::new();
+		proof.serialize(&mut proof_bytes).unwrap();
+		let inputs = get_public_inputs::<E::Fr>(nullifier_hash, root, recipient, relayer)
+			.iter()
+			.map(|input| slice_to_bytes_32(&to_bytes![input].unwrap()))
+			.collect();
+		(get_groth16_vk_bytes(&vk).unwrap(), proof_bytes, inputs)
+	}
+
+	fn verify_input(key_id: u32, proof: &[u8], inputs: &[[u8; 32]]) -> Vec<u8> {
+		Writer::new_with_selector(abi::selector(VERIFY_SIGNATURE))
+			.write_u32(key_id)
+			.write_bytes(proof)
+			.write_bytes32_array(inputs)
+			.build()
+	}
+
+	fn verify_with_key_input(key: &[u8], proof: &[u8], inputs: &[[u8; 32]]) -> Vec<u8> {
+		Writer::new_with_selector(abi::selector(VERIFY_WITH_KEY_SIGNATURE))
+			.write_bytes(key)
+			.write_bytes(proof)
+			.write_bytes32_array(inputs)
+			.build()
+	}
+
+	#[test]
+	fn should_verify_with_registered_key() {
+		new_test_ext().execute_with(|| {
+			let (key, proof, inputs) = mixer_proof::<Bn254>(CurveEnum::Bn254);
+			assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key));
+
+			let output = Bn254Precompile::execute(&verify_input(0, &proof, &inputs), None, &test_context()).unwrap();
+			assert_eq!(output.output, abi::encode_bool(true));
+			let weight = Groth16Bn254::prepare_weight::<Test>(inputs.len() as u32)
+				+ Groth16Bn254::verify_weight::<Test>(inputs.len() as u32)
+				+ <Test as frame_system::Config>::DbWeight::get().reads(2);
+			assert_eq!(output.cost, weight);
+
+			let mut wrong_inputs = inputs.clone();
+			wrong_inputs.swap(0, 1);
+			let output =
+				Bn254Precompile::execute(&verify_input(0, &proof, &wrong_inputs), None, &test_context()).unwrap();
+			assert_eq!(output.output, abi::encode_bool(false));
+
+			// No key is registered under id 1
+			assert!(Bn254Precompile::execute(&verify_input(1, &proof, &inputs), None, &test_context()).is_err());
+		});
+	}
+
+	#[test]
+	fn should_verify_with_inline_key() {
+		new_test_ext().execute_with(|| {
+			let (key, proof, inputs) = mixer_proof::<Bls12_381>(CurveEnum::Bls381);
+			let input = verify_with_key_input(&key, &proof, &inputs);
+			let output = Bls381Precompile::execute(&input, None, &test_context()).unwrap();
+			assert_eq!(output.output, abi::encode_bool(true));
+
+			// Keys of another curve are rejected
+			assert!(Bn254Precompile::execute(&input, None, &test_context()).is_err());
+		});
+	}
+
+	#[test]
+	fn should_reject_malformed_calls() {
+		new_test_ext().execute_with(|| {
+			let (key, proof, inputs) = mixer_proof::<Bn254>(CurveEnum::Bn254);
+
+			let res = Bn254Precompile::execute(
+				&verify_with_key_input(&key, &proof[1..], &inputs),
+				None,
+				&test_context(),
+			);
+			assert_eq!(res.err(), Some(ExitError::Other("Invalid proof".into())));
+
+			let res = Bn254Precompile::execute(
+				&verify_with_key_input(&key, &proof, &inputs[1..]),
+				None,
+				&test_context(),
+			);
+			assert_eq!(res.err(), Some(ExitError::Other("Invalid public inputs".into())));
+
+			// Inputs are checked against the key before its points are decoded
+			let mut bad_points = key.clone();
+			let last = bad_points.len() - 1;
+			bad_points[last] ^= 0xff;
+			let res = Bn254Precompile::execute(
+				&verify_with_key_input(&bad_points, &proof, &inputs[1..]),
+				None,
+				&test_context(),
+			);
+			assert_eq!(res.err(), Some(ExitError::Other("Invalid public inputs".into())));
+
+			let res = Bn254Precompile::execute(
+				&verify_with_key_input(&key[..key.len() - 1], &proof, &inputs),
+				None,
+				&test_context(),
+			);
+			assert_eq!(res.err(), Some(ExitError::Other("Invalid verifying key".into())));
+
+			let mut input = verify_with_key_input(&key, &proof, &inputs);
+			input[0] ^= 1;
+			assert!(Bn254Precompile::execute(&input, None, &test_context()).is_err());
+		});
+	}
+
+	#[test]
+	fn should_enforce_target_gas() {
+		new_test_ext().execute_with(|| {
+			let (key, proof, inputs) = mixer_proof::<Bn254>(CurveEnum::Bn254);
+			let input = verify_with_key_input(&key, &proof, &inputs);
+			let weight = Groth16Bn254::prepare_weight::<Test>(inputs.len() as u32)
+				+ Groth16Bn254::verify_weight::<Test>(inputs.len() as u32);
+
+			let res = Bn254Precompile::execute(&input, Some(weight - 1), &test_context());
+			assert_eq!(res.err(), Some(ExitError::OutOfGas));
+			assert!(Bn254Precompile::execute(&input, Some(weight), &test_context()).is_ok());
+		});
+	}
+}
@@ -0,0 +1,186 @@
+//! Precompiles hashing with the functions used by the merkle pallet
+//!
+//! There is one precompile for each supported hash function and curve. Each
+//! one exposes `hash(bytes32 left, bytes32 right) returns (bytes32)`, where
+//! the arguments and the result are field elements serialized as by the
+//! merkle pallet (little-endian). Hashes match the ones of merkle trees
+//! created with the same setup.
+use crate::abi::{self, Reader};
+use evm::{executor::PrecompileOutput, Context, ExitError, ExitSucceed};
+use fp_evm::Precompile;
+use frame_support::weights::Weight;
+use merkle::utils::{
+	keys::slice_to_bytes_32,
+	setup::{Backend, Curve, HashFunction, Setup, Snark},
+};
+use pallet_evm::GasWeightMapping;
+use sp_std::{marker::PhantomData, prelude::Vec};
+
+/// Signature of the function exposed by the hash precompiles
+pub const HASH_SIGNATURE: &str = "hash(bytes32,bytes32)";
+
+/// Weight of hashing two field elements. Derived from the `add_members`
+/// benchmark of the merkle pallet, where each added member costs
+/// `20_135_984_000` for hashing up a tree of depth 32.
+pub const HASH_WEIGHT: Weight = 629_249_500;
+
+/// Hash function and curve of a [`HashPrecompile`]
+pub trait HashSetup {
+	fn setup() -> Setup;
+}
+
+/// Poseidon hash of the Bulletproofs backend over Curve25519
+pub struct PoseidonCurve25519;
+impl HashSetup for PoseidonCurve25519 {
+	fn setup() -> Setup {
+		Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519))
+	}
+}
+
+/// Poseidon hash with width 3 and exponent 5 over BLS12-381
+pub struct PoseidonX5Bls381;
+impl HashSetup for PoseidonX5Bls381 {
+	fn setup() -> Setup {
+		Setup::new(
+			HashFunction::Poseidon(3, 5),
+			Backend::Arkworks(Curve::Bls381, Snark::Groth16),
+		)
+	}
+}
+
+/// Poseidon hash with width 3 and exponent 5 over BN254
+pub struct PoseidonX5Bn254;
+impl HashSetup for PoseidonX5Bn254 {
+	fn setup() -> Setup {
+		Setup::new(
+			HashFunction::Poseidon(3, 5),
+			Backend::Arkworks(Curve::Bn254, Snark::Groth16),
+		)
+	}
+}
+
+/// Poseidon hash with width 3 and exponent 17 over BLS12-381
+pub struct PoseidonX17Bls381;
+impl HashSetup for PoseidonX17Bls381 {
+	fn setup() -> Setup {
+		Setup::new(
+			HashFunction::Poseidon(3, 17),
+			Backend::Arkworks(Curve::Bls381, Snark::Groth16),
+		)
+	}
+}
+
+/// Poseidon hash with width 3 and exponent 17 over BN254
+pub struct PoseidonX17Bn254;
+impl HashSetup for PoseidonX17Bn254 {
+	fn setup() -> Setup {
+		Setup::new(
+			HashFunction::Poseidon(3, 17),
+			Backend::Arkworks(Curve::Bn254, Snark::Groth16),
+		)
+	}
+}
+
+/// MiMC hash with 220 rounds over BN254
+pub struct MiMCBn254;
+impl HashSetup for MiMCBn254 {
+	fn setup() -> Setup {
+		Setup::new(HashFunction::MiMC, Backend::Arkworks(Curve::Bn254, Snark::Groth16))
+	}
+}
+
+/// Hashes two field elements with the setup of `S`, charging gas through `G`.
+pub struct HashPrecompile<S: HashSetup, G: GasWeightMapping>(PhantomData<S>, PhantomData<G>);
+
+impl<S: HashSetup, G: GasWeightMapping> Precompile for HashPrecompile<S, G> {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<u64>,
+		_context: &Context,
+	) -> core::result::Result<PrecompileOutput, ExitError> {
+		let cost = G::weight_to_gas(HASH_WEIGHT);
+		if let Some(target_gas) = target_gas {
+			if cost > target_gas {
+				return Err(ExitError::OutOfGas);
+			}
+		}
+
+		let (selector, mut reader) = Reader::new_with_selector(input)?;
+		if selector != abi::selector(HASH_SIGNATURE) {
+			return Err(ExitError::Other("Unknown function selector".into()));
+		}
+		let left = reader.read_bytes32()?;
+		let right = reader.read_bytes32()?;
+
+		let hash = S::setup()
+			.hash_bytes(&left, &right)
+			.map_err(|_| ExitError::Other("Hashing failed".into()))?;
+
+		Ok(PrecompileOutput {
+			exit_status: ExitSucceed::Returned,
+			cost,
+			output: abi::encode_bytes32(slice_to_bytes_32(&hash)),
+			logs: Vec::new(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{
+		default_bulletproofs_poseidon_hasher,
+		mock::{test_context, TestGasWeightMapping},
+	};
+	use merkle::utils::prepared::PreparedKey;
+
+	fn hash_input(left: [u8; 32], right: [u8; 32]) -> Vec<u8> {
+		let mut input = abi::selector(HASH_SIGNATURE).to_vec();
+		input.extend_from_slice(&left);
+		input.extend_from_slice(&right);
+		input
+	}
+
+	fn execute<S: HashSetup>(input: &[u8], target_gas: Option<u64>) -> Result<PrecompileOutput, ExitError> {
+		HashPrecompile::<S, TestGasWeightMapping>::execute(input, target_gas, &test_context())
+	}
+
+	fn should_match_setup<S: HashSetup>(key: Option<&PreparedKey>) {
+		let left = [1u8; 32];
+		let right = [2u8; 32];
+		let output = execute::<S>(&hash_input(left, right), None).unwrap();
+		let expected = S::setup().compute_hash(&left, &right, key).unwrap();
+		assert_eq!(output.output, expected);
+		assert_eq!(output.cost, HASH_WEIGHT);
+	}
+
+	#[test]
+	fn should_hash_like_merkle_trees() {
+		let bp_key = PreparedKey::Bulletproofs(default_bulletproofs_poseidon_hasher());
+		should_match_setup::<PoseidonCurve25519>(Some(&bp_key));
+		should_match_setup::<PoseidonX5Bls381>(None);
+		should_match_setup::<PoseidonX5Bn254>(None);
+		should_match_setup::<PoseidonX17Bls381>(None);
+		should_match_setup::<PoseidonX17Bn254>(None);
+		should_match_setup::<MiMCBn254>(None);
+	}
+
+	#[test]
+	fn should_enforce_target_gas() {
+		let input = hash_input([1u8; 32], [2u8; 32]);
+		let res = execute::<PoseidonX5Bn254>(&input, Some(HASH_WEIGHT - 1));
+		assert_eq!(res.err(), Some(ExitError::OutOfGas));
+		assert!(execute::<PoseidonX5Bn254>(&input, Some(HASH_WEIGHT)).is_ok());
+	}
+
+	#[test]
+	fn should_reject_malformed_input() {
+		let mut input = hash_input([1u8; 32], [2u8; 32]);
+		input.truncate(4 + 48);
+		assert!(execute::<PoseidonX5Bn254>(&input, None).is_err());
+
+		let mut input = hash_input([1u8; 32], [2u8; 32]);
+		input[0] ^= 1;
+		assert!(execute::<PoseidonX5Bn254>(&input, None).is_err());
+	}
+}
@@ -19,7 +19,7 @@
 
 extern crate alloc;
 
-use crate::{encoding::Decode, types::WithdrawProof};
+use crate::{abi::Reader, types::WithdrawProof};
 use alloc::vec::Vec;
 use bulletproofs::BulletproofGens;
 use bulletproofs_gadgets::poseidon::{
@@ -28,29 +28,45 @@ use bulletproofs_gadgets::poseidon::{
 };
 use evm::{executor::PrecompileOutput, Context, ExitError, ExitSucceed};
 use fp_evm::Precompile;
-use frame_support::{
-	traits::{MaxEncodedLen, Randomness},
-	Parameter,
-};
 use lazy_static::lazy_static;
-use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
-use sp_runtime::traits::{
-	AtLeast32BitUnsigned, Bounded, CheckEqual, MaybeDisplay, MaybeMallocSizeOf, MaybeSerializeDeserialize, Member,
-	SimpleBitOps,
-};
-use sp_std::{fmt::Debug, marker::PhantomData};
-
-pub struct BulletproofMerkleTreeMembershipPrecompile<O, B, R: Randomness<O, B>>(
-	PhantomData<O>,
-	PhantomData<B>,
-	PhantomData<R>,
-);
+use merkle::{weights::WeightInfo, Config};
+use pallet_evm::GasWeightMapping;
+use sp_std::marker::PhantomData;
+
+/// Verifies Bulletproofs membership proofs of the mixer, charging gas for
+/// the verification through `G` at the weight of the `verify_bulletproofs`
+/// benchmark of the merkle pallet of `T`.
+///
+/// The precompile has a single function, taking the commitments, public
+/// inputs and proof of a withdrawal and returning whether the proof is valid:
+///
+/// `verify(bytes32[] comms, bytes32[] nodeComms, bytes32[] indexComms, bytes32
+/// root, bytes32 nullifierHash, bytes32 recipient, bytes32 relayer, bytes
+/// proof) returns (bool)`
+///
+/// Scalars and compressed points are in their 32 byte encodings. The depth of
+/// the tree is the number of node commitments. The random scalars of the
+/// verifier are derived from the call itself, so the result only depends on
+/// the input.
+pub struct BulletproofMerkleTreeMembershipPrecompile<T: Config, G: GasWeightMapping>(PhantomData<T>, PhantomData<G>);
 
 use codec::Input;
 
-mod encoding;
+pub mod abi;
+pub mod groth16;
+pub mod hash;
+pub mod mixer;
+#[cfg(test)]
+mod mock;
+pub mod tree;
 mod types;
 
+/// Signature of the membership proof verification function
+pub const VERIFY_SIGNATURE: &str = "verify(bytes32[],bytes32[],bytes32[],bytes32,bytes32,bytes32,bytes32,bytes)";
+
+/// Maximum depth of a tree whose membership proofs can be verified
+pub const MAX_TREE_DEPTH: u8 = 32;
+
 lazy_static! {
 	static ref POSEIDON_HASHER: Poseidon = default_bulletproofs_poseidon_hasher();
 }
@@ -65,68 +81,32 @@ pub fn default_bulletproofs_poseidon_hasher() -> Poseidon {
 		.build()
 }
 
-impl<O, B, R: Randomness<O, B>> Precompile for BulletproofMerkleTreeMembershipPrecompile<O, B, R>
-where
-	O: Parameter
-		+ Member
-		+ MaybeSerializeDeserialize
-		+ Debug
-		+ MaybeDisplay
-		+ SimpleBitOps
-		+ Ord
-		+ Default
-		+ Copy
-		+ CheckEqual
-		+ sp_std::hash::Hash
-		+ AsRef<[u8]>
-		+ AsMut<[u8]>
-		+ MaybeMallocSizeOf
-		+ MaxEncodedLen,
-	B: Parameter
-		+ Member
-		+ MaybeSerializeDeserialize
-		+ Debug
-		+ MaybeDisplay
-		+ AtLeast32BitUnsigned
-		+ Default
-		+ Bounded
-		+ Copy
-		+ sp_std::hash::Hash
-		+ sp_std::str::FromStr
-		+ MaybeMallocSizeOf
-		+ MaxEncodedLen,
-{
+impl<T: Config, G: GasWeightMapping> Precompile for BulletproofMerkleTreeMembershipPrecompile<T, G> {
 	fn execute(
 		input: &[u8],
-		_target_gas: Option<u64>,
+		target_gas: Option<u64>,
 		_context: &Context,
 	) -> core::result::Result<PrecompileOutput, ExitError> {
-		let mut seed = [0u8; 32];
-		seed.copy_from_slice(&input[0..32]);
-
-		let withdraw_proof_res = WithdrawProof::decode(&mut input[32..].to_vec());
-		let withdraw_proof = match withdraw_proof_res {
-			Ok(wp) => wp,
-			Err(_) => return Err(ExitError::Other("Failed to decode withdraw proof".into())),
-		};
-		let random_seed = R::random_seed();
-		let random_bytes = random_seed.0.encode();
-		let mut buf = [0u8; 32];
-		for (buff_element, data) in buf.iter_mut().zip(random_bytes.iter()) {
-			*buff_element = *data
+		// Charge for the verification of a proof of the maximum depth, with a
+		// cold key, before doing any work
+		let cost = G::weight_to_gas(T::WeightInfo::verify_bulletproofs());
+		if let Some(target_gas) = target_gas {
+			if cost > target_gas {
+				return Err(ExitError::OutOfGas);
+			}
 		}
-		println!("{:?}", buf);
-		let mut rng = ChaChaRng::from_seed(buf);
-		let verify_res = withdraw_proof.verify(&POSEIDON_HASHER, &mut rng);
-		println!("{:?}", verify_res);
-		if verify_res.is_err() {
-			return Err(verify_res.err().unwrap());
+
+		let (selector, mut reader) = Reader::new_with_selector(input)?;
+		if selector != abi::selector(VERIFY_SIGNATURE) {
+			return Err(ExitError::Other("Unknown function selector".into()));
 		}
+		let withdraw_proof = WithdrawProof::decode(&mut reader)?;
+		let valid = withdraw_proof.verify(&POSEIDON_HASHER).is_ok();
 
 		Ok(PrecompileOutput {
 			exit_status: ExitSucceed::Returned,
-			cost: 0,
-			output: Vec::new(),
+			cost,
+			output: abi::encode_bool(valid),
 			logs: Vec::new(),
 		})
 	}
@@ -136,59 +116,83 @@ where
 mod test {
 	use super::*;
 	use crate::{
-		encoding::Encode,
-		types::{test::generate_proof_data, WithdrawProof},
+		mock::{test_context, Test, TestGasWeightMapping},
+		types::{
+			test::{decode, encode, generate_proof_data},
+			WithdrawProof,
+		},
 	};
+	use curve25519_dalek::scalar::Scalar;
 	use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
-	use sp_core::{uint::U256, H160, H256};
 
-	struct Rng;
-	impl Randomness<H256, u64> for Rng {
-		fn random(_: &[u8]) -> (H256, u64) {
-			(H256::from([1u8; 32]), 12)
+	type TestPrecompile = BulletproofMerkleTreeMembershipPrecompile<Test, TestGasWeightMapping>;
+
+	fn verify_weight() -> u64 {
+		<Test as Config>::WeightInfo::verify_bulletproofs()
+	}
+
+	fn test_withdraw_proof(test_rng: &mut ChaChaRng) -> WithdrawProof {
+		let (tree_depth, comms, leaf_index_comms, proof_comms, nullifier_hash, recipient, relayer, root, proof, _) =
+			generate_proof_data(test_rng);
+		WithdrawProof {
+			depth: tree_depth,
+			private_inputs: comms,
+			index_private_inputs: leaf_index_comms,
+			node_private_inputs: proof_comms,
+			nullifier_hash,
+			recipient,
+			relayer,
+			root,
+			proof,
 		}
 	}
 
-	type TestPrecompile = BulletproofMerkleTreeMembershipPrecompile<H256, u64, Rng>;
-
-	// TODO: Not passing, needs investigation.
-	// #[test]
-	// fn should_verify_with_precompile() {
-	// 	let seed = [1u8; 32];
-	// 	let mut test_rng = ChaChaRng::from_seed(seed);
-	// 	let (tree_depth, comms, leaf_index_comms, proof_comms, nullifier_hash,
-	// recipient, relayer, root, proof, _) = 		generate_proof_data(&mut test_rng);
-	// 	let withdraw_proof = WithdrawProof {
-	// 		depth: tree_depth,
-	// 		private_inputs: comms,
-	// 		index_private_inputs: leaf_index_comms,
-	// 		node_private_inputs: proof_comms,
-	// 		nullifier_hash,
-	// 		recipient,
-	// 		relayer,
-	// 		root,
-	// 		proof: proof.clone(),
-	// 	};
-
-	// 	let encoded_wp = withdraw_proof.encode();
-	// 	let mut buf = vec![];
-	// 	buf.extend_from_slice(&seed);
-	// 	buf.extend_from_slice(&encoded_wp);
-
-	// 	let context: Context = Context {
-	// 		address: Default::default(),
-	// 		caller: Default::default(),
-	// 		apparent_value: From::from(0),
-	// 	};
-	// 	println!("{:?}", buf);
-	// 	// Calling precompile
-	// 	match TestPrecompile::execute(&buf, None, &context) {
-	// 		Ok(_) => {},
-	// 		Err(_) => {
-	// 			panic!("BulletproofMerkleTreeMembershipPrecompile::execute() returned
-	// error"); 		}
-	// 	}
-	// }
+	#[test]
+	fn should_verify_with_precompile() {
+		let mut test_rng = ChaChaRng::from_seed([1u8; 32]);
+		let input = encode(&test_withdraw_proof(&mut test_rng));
+
+		let output = TestPrecompile::execute(&input, None, &test_context()).unwrap();
+		assert_eq!(output.exit_status, ExitSucceed::Returned);
+		assert_eq!(output.output, abi::encode_bool(true));
+		assert_eq!(output.cost, verify_weight());
+	}
+
+	#[test]
+	fn should_return_false_for_invalid_proof() {
+		let mut test_rng = ChaChaRng::from_seed([1u8; 32]);
+		let mut withdraw_proof = test_withdraw_proof(&mut test_rng);
+		withdraw_proof.nullifier_hash = Scalar::random(&mut test_rng);
+
+		let output = TestPrecompile::execute(&encode(&withdraw_proof), None, &test_context()).unwrap();
+		assert_eq!(output.output, abi::encode_bool(false));
+	}
+
+	#[test]
+	fn should_enforce_target_gas() {
+		let mut test_rng = ChaChaRng::from_seed([1u8; 32]);
+		let input = encode(&test_withdraw_proof(&mut test_rng));
+
+		let res = TestPrecompile::execute(&input, Some(verify_weight() - 1), &test_context());
+		assert_eq!(res.err(), Some(ExitError::OutOfGas));
+		assert!(TestPrecompile::execute(&input, Some(verify_weight()), &test_context()).is_ok());
+	}
+
+	#[test]
+	fn should_not_verify_short_input() {
+		let res = TestPrecompile::execute(&[0u8; 16], None, &test_context());
+		assert!(res.is_err());
+	}
+
+	#[test]
+	fn should_reject_unknown_selector() {
+		let mut test_rng = ChaChaRng::from_seed([1u8; 32]);
+		let mut input = encode(&test_withdraw_proof(&mut test_rng));
+		input[0] ^= 1;
+
+		let res = TestPrecompile::execute(&input, None, &test_context());
+		assert_eq!(res.err(), Some(ExitError::Other("Unknown function selector".into())));
+	}
 
 	#[test]
 	fn should_verify_with_verify_directly() {
@@ -207,16 +211,12 @@ mod test {
 			proof: proof.clone(),
 		};
 
-		let encoded_wp = withdraw_proof.encode();
-
-		let mut encoded_copy = encoded_wp.clone();
-		let decoded_wp = WithdrawProof::decode(&mut encoded_copy).unwrap();
+		let decoded_wp = decode(&encode(&withdraw_proof)).unwrap();
 		assert_eq!(decoded_wp.depth, tree_depth);
 		assert_eq!(decoded_wp.proof.to_bytes(), proof.to_bytes());
 
 		// Calling verify
-		let mut test_rng = ChaChaRng::from_seed([2u8; 32]);
-		let verify_res = withdraw_proof.verify(&POSEIDON_HASHER, &mut test_rng);
+		let verify_res = withdraw_proof.verify(&POSEIDON_HASHER);
 		assert!(verify_res.is_ok());
 	}
 }
@@ -0,0 +1,345 @@
+//! Precompile dispatching deposits and withdrawals of the mixer pallet
+//!
+//! Lets Ethereum accounts use the mixers from Solidity. The caller is mapped
+//! to its Substrate account with the address mapping of the EVM, and the
+//! mixer call is dispatched on its behalf. The precompile must be called
+//! directly and without value: through `DELEGATECALL` or `CALLCODE` a contract
+//! could dispatch on behalf of its own caller.
+//!
+//! - `deposit(uint32 mixerId, bytes32[] leaves)`
+//! - `withdraw(uint32 mixerId, uint32 cachedBlock, bytes32 cachedRoot,
+//!   bytes32[] comms, bytes32 nullifierHash, bytes proof, bytes32[]
+//!   leafIndexCommitments, bytes32[] proofCommitments, bytes32 recipient,
+//!   bytes32 relayer, uint256 fee, uint256 refund)`
+//!
+//! Recipient and relayer are encoded Substrate account ids. A zero recipient
+//! or relayer stands for the caller's own account. Gas is charged from the
+//! weight of the mixer call, and the `Deposit` and `Withdraw` events of the
+//! mixer are mirrored by EVM logs:
+//!
+//! - `Deposit(uint32 indexed mixerId, address indexed sender, uint256 amount)`
+//! - `Withdraw(uint32 indexed mixerId, address indexed sender, bytes32
+//!   recipient, bytes32 relayer, bytes32 root, uint256 fee)`
+use crate::abi::{self, Reader, Writer};
+use codec::{Decode, Encode};
+use core::convert::TryFrom;
+use evm::{backend::Log, executor::PrecompileOutput, Context, ExitError, ExitSucceed};
+use fp_evm::Precompile;
+use frame_support::{
+	dispatch::DispatchResultWithPostInfo,
+	storage::{with_transaction, TransactionOutcome},
+	traits::{Get, UnfilteredDispatchable},
+	weights::GetDispatchInfo,
+};
+use frame_system::RawOrigin;
+use merkle::utils::keys::slice_to_bytes_32;
+use mixer::{BalanceOf, Call as MixerCall, Config, Pallet as MixerPallet, WithdrawProof};
+use pallet_evm::{AddressMapping, GasWeightMapping};
+use sp_core::{H160, H256, U256};
+use sp_io::hashing::keccak_256;
+use sp_runtime::traits::SaturatedConversion;
+use sp_std::{marker::PhantomData, prelude::Vec, vec};
+
+/// Signature of the deposit function
+pub const DEPOSIT_SIGNATURE: &str = "deposit(uint32,bytes32[])";
+/// Signature of the withdraw function
+pub const WITHDRAW_SIGNATURE: &str =
+	"withdraw(uint32,uint32,bytes32,bytes32[],bytes32,bytes,bytes32[],bytes32[],bytes32,bytes32,uint256,uint256)";
+/// Signature of the log mirroring the `Deposit` event
+pub const DEPOSIT_EVENT: &str = "Deposit(uint32,address,uint256)";
+/// Signature of the log mirroring the `Withdraw` event
+pub const WITHDRAW_EVENT: &str = "Withdraw(uint32,address,bytes32,bytes32,bytes32,uint256)";
+
+/// Dispatches calls of the mixer pallet of `T` for EVM callers, mapping their
+/// address with `A` and charging gas through `G`. The precompile is placed at
+/// the address given by `P`.
+pub struct MixerPrecompile<T: Config, A: AddressMapping<T::AccountId>, G: GasWeightMapping, P: Get<H160>>(
+	PhantomData<T>,
+	PhantomData<A>,
+	PhantomData<G>,
+	PhantomData<P>,
+);
+
+impl<T: Config, A: AddressMapping<T::AccountId>, G: GasWeightMapping, P: Get<H160>> MixerPrecompile<T, A, G, P> {
+	/// Reads an encoded account id, `None` if it is zero.
+	fn read_account(reader: &mut Reader) -> Result<Option<T::AccountId>, ExitError> {
+		let bytes = reader.read_bytes32()?;
+		if bytes == [0u8; 32] {
+			return Ok(None);
+		}
+		T::AccountId::decode(&mut &bytes[..])
+			.map(Some)
+			.map_err(|_| ExitError::Other("Invalid account id".into()))
+	}
+
+	/// Reads an amount of the mixer currency.
+	fn read_balance(reader: &mut Reader) -> Result<BalanceOf<T>, ExitError> {
+		let value = reader.read_u256()?;
+		if value > U256::from(u128::MAX) {
+			return Err(ExitError::Other("Amount is out of range".into()));
+		}
+		BalanceOf::<T>::try_from(value.low_u128()).map_err(|_| ExitError::Other("Amount is out of range".into()))
+	}
+
+	fn account_word(account: &T::AccountId) -> [u8; 32] {
+		slice_to_bytes_32(&account.encode())
+	}
+
+	fn balance_word(balance: BalanceOf<T>) -> U256 {
+		U256::from(balance.saturated_into::<u128>())
+	}
+
+	fn topic(value: u32) -> H256 {
+		H256::from_low_u64_be(value as u64)
+	}
+
+	/// Dispatches `call` from `who`, reverting its changes if it fails.
+	fn dispatch(call: MixerCall<T>, who: T::AccountId) -> Result<(), ExitError> {
+		let res = with_transaction(|| {
+			let res: DispatchResultWithPostInfo = call.dispatch_bypass_filter(RawOrigin::Signed(who).into());
+			if res.is_ok() {
+				TransactionOutcome::Commit(res)
+			} else {
+				TransactionOutcome::Rollback(res)
+			}
+		});
+		res.map(|_| ())
+			.map_err(|e| ExitError::Other(<&'static str>::from(e.error).into()))
+	}
+}
+
+impl<T: Config, A: AddressMapping<T::AccountId>, G: GasWeightMapping, P: Get<H160>> Precompile
+	for MixerPrecompile<T, A, G, P>
+{
+	fn execute(
+		input: &[u8],
+		target_gas: Option<u64>,
+		context: &Context,
+	) -> core::result::Result<PrecompileOutput, ExitError> {
+		// Calls through `DELEGATECALL` or `CALLCODE` run in the context of the
+		// calling contract, with the caller of that contract as caller
+		if context.address != P::get() {
+			return Err(ExitError::Other("Precompile must be called directly".into()));
+		}
+		// Value sent along would be stuck at the address of the precompile
+		if !context.apparent_value.is_zero() {
+			return Err(ExitError::Other("Precompile doesn't accept value".into()));
+		}
+		let (selector, mut reader) = Reader::new_with_selector(input)?;
+		let mixer_id = reader.read_u32()?;
+		let sender = A::into_account_id(context.caller);
+
+		let (call, log) = if selector == abi::selector(DEPOSIT_SIGNATURE) {
+			let leaves = reader.read_bytes32_array()?;
+			let deposit_size = MixerPallet::<T>::get_mixer(mixer_id.into())
+				.map_err(|_| ExitError::Other("Mixer doesn't exist".into()))?
+				.fixed_deposit_size;
+			let call = MixerCall::<T>::deposit(
+				mixer_id.into(),
+				leaves.iter().map(|leaf| leaf.to_vec()).collect(),
+				Vec::new(),
+			);
+			let log = Log {
+				address: context.address,
+				topics: vec![
+					H256(keccak_256(DEPOSIT_EVENT.as_bytes())),
+					Self::topic(mixer_id),
+					H256::from(context.caller),
+				],
+				data: Writer::new().write_u256(Self::balance_word(deposit_size)).build(),
+			};
+			(call, log)
+		} else if selector == abi::selector(WITHDRAW_SIGNATURE) {
+			let cached_block = reader.read_u32()?;
+			let cached_root = reader.read_bytes32()?;
+			let comms = reader.read_bytes32_array()?;
+			let nullifier_hash = reader.read_bytes32()?;
+			let proof_bytes = reader.read_bytes()?;
+			let leaf_index_commitments = reader.read_bytes32_array()?;
+			let proof_commitments = reader.read_bytes32_array()?;
+			let recipient = Self::read_account(&mut reader)?;
+			let relayer = Self::read_account(&mut reader)?;
+			let fee = Self::read_balance(&mut reader)?;
+			let refund = Self::read_balance(&mut reader)?;
+
+			let to_vecs = |words: Vec<[u8; 32]>| -> Vec<Vec<u8>> { words.iter().map(|word| word.to_vec()).collect() };
+			let log = Log {
+				address: context.address,
+				topics: vec![
+					H256(keccak_256(WITHDRAW_EVENT.as_bytes())),
+					Self::topic(mixer_id),
+					H256::from(context.caller),
+				],
+				data: Writer::new()
+					.write_bytes32(Self::account_word(recipient.as_ref().unwrap_or(&sender)))
+					.write_bytes32(Self::account_word(relayer.as_ref().unwrap_or(&sender)))
+					.write_bytes32(cached_root)
+					.write_u256(Self::balance_word(fee))
+					.build(),
+			};
+			let withdraw_proof = WithdrawProof::<T>::new(
+				mixer_id.into(),
+				cached_block.into(),
+				cached_root.to_vec(),
+				to_vecs(comms),
+				nullifier_hash.to_vec(),
+				proof_bytes,
+				to_vecs(leaf_index_commitments),
+				to_vecs(proof_commitments),
+				recipient,
+				relayer,
+				fee,
+				refund,
+			);
+			(MixerCall::<T>::withdraw(withdraw_proof), log)
+		} else {
+			return Err(ExitError::Other("Unknown function selector".into()));
+		};
+
+		let cost = G::weight_to_gas(call.get_dispatch_info().weight);
+		if let Some(target_gas) = target_gas {
+			if cost > target_gas {
+				return Err(ExitError::OutOfGas);
+			}
+		}
+		Self::dispatch(call, sender)?;
+
+		Ok(PrecompileOutput {
+			exit_status: ExitSucceed::Returned,
+			cost,
+			output: Vec::new(),
+			logs: vec![log],
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::mock::{
+		new_test_ext_with_mixers, Balances, MerkleTrees, Mixer, MixerPrecompileAddress, Test, TestGasWeightMapping,
+		TruncatedAddressMapping,
+	};
+	use mixer::weights::WeightInfo;
+
+	type TestMixerPrecompile =
+		MixerPrecompile<Test, TruncatedAddressMapping, TestGasWeightMapping, MixerPrecompileAddress>;
+
+	fn context(caller: u64) -> Context {
+		Context {
+			address: MixerPrecompileAddress::get(),
+			caller: H160::from_low_u64_be(caller),
+			apparent_value: From::from(0),
+		}
+	}
+
+	fn leaf(x: u8) -> [u8; 32] {
+		let mut bytes = [0u8; 32];
+		bytes[0] = x;
+		bytes
+	}
+
+	fn deposit_input(mixer_id: u32, leaves: &[[u8; 32]]) -> Vec<u8> {
+		Writer::new_with_selector(abi::selector(DEPOSIT_SIGNATURE))
+			.write_u32(mixer_id)
+			.write_bytes32_array(leaves)
+			.build()
+	}
+
+	#[test]
+	fn should_deposit_for_evm_caller() {
+		new_test_ext_with_mixers().execute_with(|| {
+			let balance_before = Balances::free_balance(1);
+
+			let input = deposit_input(0, &[leaf(1), leaf(2)]);
+			let output = TestMixerPrecompile::execute(&input, None, &context(1)).unwrap();
+
+			let deposit_size = Mixer::get_mixer(0).unwrap().fixed_deposit_size;
+			assert_eq!(Balances::free_balance(1), balance_before - 2 * deposit_size);
+			assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 2);
+			assert_eq!(output.cost, <Test as mixer::Config>::WeightInfo::deposit(2));
+
+			assert_eq!(output.logs.len(), 1);
+			let log = &output.logs[0];
+			assert_eq!(log.address, MixerPrecompileAddress::get());
+			assert_eq!(log.topics, vec![
+				H256(keccak_256(DEPOSIT_EVENT.as_bytes())),
+				H256::from_low_u64_be(0),
+				H256::from(H160::from_low_u64_be(1)),
+			]);
+			assert_eq!(log.data, Writer::new().write_u256(U256::from(deposit_size)).build());
+		});
+	}
+
+	#[test]
+	fn should_enforce_target_gas() {
+		new_test_ext_with_mixers().execute_with(|| {
+			let input = deposit_input(0, &[leaf(1)]);
+			let weight = <Test as mixer::Config>::WeightInfo::deposit(1);
+
+			let res = TestMixerPrecompile::execute(&input, Some(weight - 1), &context(1));
+			assert_eq!(res.err(), Some(ExitError::OutOfGas));
+			assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 0);
+			assert!(TestMixerPrecompile::execute(&input, Some(weight), &context(1)).is_ok());
+		});
+	}
+
+	#[test]
+	fn should_revert_failed_calls() {
+		new_test_ext_with_mixers().execute_with(|| {
+			let balance_before = Balances::free_balance(5);
+
+			// Account 5 has no balance to deposit
+			let res = TestMixerPrecompile::execute(&deposit_input(0, &[leaf(1)]), None, &context(5));
+			assert_eq!(res.err(), Some(ExitError::Other("InsufficientBalance".into())));
+			assert_eq!(Balances::free_balance(5), balance_before);
+			assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 0);
+
+			let res = TestMixerPrecompile::execute(&deposit_input(10, &[leaf(1)]), None, &context(1));
+			assert_eq!(res.err(), Some(ExitError::Other("Mixer doesn't exist".into())));
+
+			let input = Writer::new_with_selector(abi::selector(WITHDRAW_SIGNATURE))
+				.write_u32(0)
+				.write_u32(0)
+				.write_bytes32(leaf(1))
+				.write_bytes32_array(&[])
+				.write_bytes32(leaf(2))
+				.write_bytes(&[])
+				.write_bytes32_array(&[])
+				.write_bytes32_array(&[])
+				.write_bytes32([0u8; 32])
+				.write_bytes32([0u8; 32])
+				.write_u256(U256::from(u128::MAX) + 1)
+				.write_u256(U256::zero())
+				.build();
+			let res = TestMixerPrecompile::execute(&input, None, &context(1));
+			assert_eq!(res.err(), Some(ExitError::Other("Amount is out of range".into())));
+		});
+	}
+	#[test]
+	fn should_only_accept_direct_calls_without_value() {
+		new_test_ext_with_mixers().execute_with(|| {
+			let balance_before = Balances::free_balance(1);
+			let input = deposit_input(0, &[leaf(1)]);
+
+			// A contract delegating to the precompile would deposit on behalf
+			// of its own caller
+			let delegated = Context {
+				address: H160::from_low_u64_be(2),
+				..context(1)
+			};
+			let res = TestMixerPrecompile::execute(&input, None, &delegated);
+			assert_eq!(res.err(), Some(ExitError::Other("Precompile must be called directly".into())));
+
+			let with_value = Context {
+				apparent_value: U256::from(1),
+				..context(1)
+			};
+			let res = TestMixerPrecompile::execute(&input, None, &with_value);
+			assert_eq!(res.err(), Some(ExitError::Other("Precompile doesn't accept value".into())));
+
+			assert_eq!(Balances::free_balance(1), balance_before);
+			assert_eq!(MerkleTrees::get_tree(0).unwrap().leaf_count, 0);
+		});
+	}
+}
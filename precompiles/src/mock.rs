@@ -0,0 +1,250 @@
+use bulletproofs::BulletproofGens;
+use frame_support::{
+	construct_runtime, ord_parameter_types, parameter_types, traits::GenesisBuild, weights::Weight, PalletId,
+};
+use frame_system::{self as system, EnsureOneOf, EnsureRoot, EnsureSignedBy};
+use merkle::{
+	utils::{
+		keys::get_bp_gen_bytes,
+		setup::{Backend, Curve, HashFunction, LeafFormat, Setup},
+	},
+	weights::Weights,
+};
+use mixer::{weights::Weights as MixerWeights, MixerGenesisConfig};
+use pallet_evm::{AddressMapping, GasWeightMapping};
+use sp_core::{H160, H256};
+use sp_runtime::{
+	testing::Header,
+	traits::{AccountIdConversion, BlakeTwo256, IdentityLookup},
+};
+use system::mocking::{MockBlock, MockUncheckedExtrinsic};
+use webb_currencies::BasicCurrencyAdapter;
+
+pub type Balance = u64;
+pub type Amount = i128;
+pub type CurrencyId = u64;
+pub type AccountId = u64;
+pub type BlockNumber = u64;
+
+// Configure a mock runtime to test the precompiles.
+type UncheckedExtrinsic = MockUncheckedExtrinsic<Test>;
+type Block = MockBlock<Test>;
+
+construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: system::{Pallet, Call, Config, Storage, Event<T>},
+		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+		MerkleTrees: merkle::{Pallet, Call, Storage, Event<T>},
+		Mixer: mixer::{Pallet, Call, Storage, Config<T>, Event<T>, ValidateUnsigned},
+		Currencies: webb_currencies::{Pallet, Storage, Event<T>},
+		Tokens: webb_tokens::{Pallet, Storage, Event<T>},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const Prefix: u8 = 100;
+	pub const MaxTreeDepth: u8 = 32;
+	pub const CacheBlockLength: u64 = 5;
+	pub const MaxMemoLength: u32 = 256;
+}
+
+impl frame_system::Config for Test {
+	type AccountData = pallet_balances::AccountData<Balance>;
+	type AccountId = AccountId;
+	type BaseCallFilter = ();
+	type BlockHashCount = BlockHashCount;
+	type BlockLength = ();
+	type BlockNumber = BlockNumber;
+	type BlockWeights = ();
+	type Call = Call;
+	type DbWeight = ();
+	type Event = Event;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type Header = Header;
+	type Index = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type OnKilledAccount = ();
+	type OnNewAccount = ();
+	type OnSetCode = ();
+	type Origin = Origin;
+	type PalletInfo = PalletInfo;
+	type SS58Prefix = Prefix;
+	type SystemWeightInfo = ();
+	type Version = ();
+}
+
+impl merkle::Config for Test {
+	type CacheBlockLength = CacheBlockLength;
+	type Event = Event;
+	type KeyId = u32;
+	type MaxMemoLength = MaxMemoLength;
+	type MaxTreeDepth = MaxTreeDepth;
+	type TreeId = u32;
+	type WeightInfo = Weights<Self>;
+}
+
+parameter_types! {
+	pub const ExistentialDeposit: Balance = 0;
+	pub const MaxLocks: u32 = 50;
+	pub const MaxReserves: u32 = 50;
+}
+
+impl pallet_balances::Config for Test {
+	type AccountStore = System;
+	type Balance = Balance;
+	type DustRemoval = ();
+	type Event = Event;
+	type ExistentialDeposit = ExistentialDeposit;
+	type MaxLocks = MaxLocks;
+	type MaxReserves = MaxReserves;
+	type ReserveIdentifier = [u8; 8];
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const TokensPalletId: PalletId = PalletId(*b"py/token");
+	pub const CurrencyDeposit: u64 = 0;
+	pub const ApprovalDeposit: u64 = 1;
+	pub const StringLimit: u32 = 50;
+	pub const MetadataDepositBase: u64 = 1;
+	pub const MetadataDepositPerByte: u64 = 1;
+	pub DustAccount: AccountId = PalletId(*b"webb/dst").into_account();
+	pub const NativeCurrencyId: CurrencyId = 0;
+}
+
+impl webb_tokens::Config for Test {
+	type Amount = Amount;
+	type ApprovalDeposit = ApprovalDeposit;
+	type Balance = Balance;
+	type CurrencyDeposit = CurrencyDeposit;
+	type CurrencyId = CurrencyId;
+	type DustAccount = DustAccount;
+	type Event = Event;
+	type Extra = ();
+	type ForceOrigin = frame_system::EnsureRoot<AccountId>;
+	type MetadataDepositBase = MetadataDepositBase;
+	type MetadataDepositPerByte = MetadataDepositPerByte;
+	type NativeCurrency = BasicCurrencyAdapter<Test, Balances, Amount, BlockNumber>;
+	type PalletId = TokensPalletId;
+	type StringLimit = StringLimit;
+	type WeightInfo = ();
+}
+
+impl webb_currencies::Config for Test {
+	type Event = Event;
+	type GetNativeCurrencyId = NativeCurrencyId;
+	type MultiCurrency = Tokens;
+	type NativeCurrency = BasicCurrencyAdapter<Test, Balances, Amount, BlockNumber>;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const MixerPalletId: PalletId = PalletId(*b"py/mixer");
+	pub const MinimumDepositLength: u64 = 10 * 60 * 24 * 28;
+	pub const MaxWithdrawBatchSize: u32 = 5;
+	pub const StatsBucketLength: u64 = 10;
+	pub const StatsHistoryLength: u32 = 3;
+	pub const ChainId: u64 = 42;
+	pub const FeeCollector: u64 = 7;
+	pub const UnsignedPriority: u64 = 100;
+	pub const MaxUnsignedWithdrawalsPerBlock: u32 = 2;
+	pub MixerPrecompileAddress: H160 = H160::from_low_u64_be(1034);
+}
+
+ord_parameter_types! {
+	pub const Admin: u64 = 4;
+	pub const PauseGuardian: u64 = 5;
+}
+
+impl mixer::Config for Test {
+	type AdminOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<Admin, u64>>;
+	type ChainId = ChainId;
+	type Currency = Currencies;
+	type DenyList = ();
+	type DepositLength = MinimumDepositLength;
+	type Event = Event;
+	type FeeCollector = FeeCollector;
+	type MaxUnsignedWithdrawalsPerBlock = MaxUnsignedWithdrawalsPerBlock;
+	type MaxWithdrawBatchSize = MaxWithdrawBatchSize;
+	type NativeCurrencyId = NativeCurrencyId;
+	type OnRelay = ();
+	type PalletId = MixerPalletId;
+	type PauseOrigin = EnsureOneOf<u64, EnsureRoot<u64>, EnsureSignedBy<PauseGuardian, u64>>;
+	type StatsBucketLength = StatsBucketLength;
+	type StatsHistoryLength = StatsHistoryLength;
+	type Tree = MerkleTrees;
+	type UnsignedPriority = UnsignedPriority;
+	type WeightInfo = MixerWeights<Self>;
+}
+
+/// Maps an EVM address to the account whose id is its low 8 bytes
+pub struct TruncatedAddressMapping;
+impl AddressMapping<AccountId> for TruncatedAddressMapping {
+	fn into_account_id(address: H160) -> AccountId {
+		address.to_low_u64_be()
+	}
+}
+
+/// Charges one gas per unit of weight
+pub struct TestGasWeightMapping;
+impl GasWeightMapping for TestGasWeightMapping {
+	fn gas_to_weight(gas: u64) -> Weight {
+		gas
+	}
+
+	fn weight_to_gas(weight: Weight) -> u64 {
+		weight
+	}
+}
+
+pub fn test_context() -> evm::Context {
+	evm::Context {
+		address: Default::default(),
+		caller: Default::default(),
+		apparent_value: From::from(0),
+	}
+}
+
+fn genesis_storage() -> sp_runtime::Storage {
+	let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_balances::GenesisConfig::<Test> {
+		balances: vec![(1, 1_000_000_000_000_000_000), (2, 1_000_000_000_000_000_000)],
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+	t
+}
+
+// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	genesis_storage().into()
+}
+
+// Build genesis storage according to the mock runtime, with mixers of the
+// native currency sharing the first verifying key.
+pub fn new_test_ext_with_mixers() -> sp_io::TestExternalities {
+	let mut t = genesis_storage();
+	MixerConfig {
+		verifying_keys: vec![get_bp_gen_bytes(&BulletproofGens::new(16400, 1))],
+		mixers: [1_000, 10_000, 100_000, 1_000_000]
+			.iter()
+			.map(|&size| MixerGenesisConfig {
+				currency_id: NativeCurrencyId::get(),
+				size,
+				setup: Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519)),
+				depth: MaxTreeDepth::get(),
+				verifying_key: 0,
+				leaf_format: LeafFormat::Opaque,
+			})
+			.collect(),
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+	t.into()
+}
@@ -0,0 +1,210 @@
+//! Precompile reading the state of merkle trees
+//!
+//! Lets EVM contracts read the trees of the merkle pallet, so they can check
+//! roots and nullifiers before submitting proofs:
+//!
+//! - `getMerkleRoot(uint32 treeId) returns (bytes32)`
+//! - `getCachedRoots(uint32 treeId, uint32 blockNumber) returns (bytes32[])`
+//! - `getLeafCount(uint32 treeId) returns (uint32)`
+//! - `hasUsedNullifier(uint32 treeId, bytes32 nullifierHash) returns (bool)`
+//! - `isStopped(uint32 treeId) returns (bool)`
+//!
+//! Calls on a tree that doesn't exist fail, except `getCachedRoots` which
+//! returns no roots and `isStopped` which returns false.
+use crate::abi::{self, Reader, Writer};
+use evm::{executor::PrecompileOutput, Context, ExitError, ExitSucceed};
+use fp_evm::Precompile;
+use frame_support::{traits::Get, weights::Weight};
+use merkle::{utils::keys::slice_to_bytes_32, Config, Error, Pallet as MerklePallet, Tree};
+use pallet_evm::GasWeightMapping;
+use sp_std::{marker::PhantomData, prelude::Vec};
+
+/// Signature of the function returning the root of a tree
+pub const GET_MERKLE_ROOT_SIGNATURE: &str = "getMerkleRoot(uint32)";
+/// Signature of the function returning the roots cached at a block
+pub const GET_CACHED_ROOTS_SIGNATURE: &str = "getCachedRoots(uint32,uint32)";
+/// Signature of the function returning the number of leaves of a tree
+pub const GET_LEAF_COUNT_SIGNATURE: &str = "getLeafCount(uint32)";
+/// Signature of the function returning whether a nullifier is used
+pub const HAS_USED_NULLIFIER_SIGNATURE: &str = "hasUsedNullifier(uint32,bytes32)";
+/// Signature of the function returning whether a tree is stopped
+pub const IS_STOPPED_SIGNATURE: &str = "isStopped(uint32)";
+
+/// Reads the trees of the merkle pallet of `T`, charging gas through `G`.
+pub struct MerkleTreePrecompile<T: Config, G: GasWeightMapping>(PhantomData<T>, PhantomData<G>);
+
+impl<T: Config, G: GasWeightMapping> MerkleTreePrecompile<T, G> {
+	/// Charges the weight of `reads` storage reads.
+	fn charge(reads: Weight, target_gas: Option<u64>) -> Result<u64, ExitError> {
+		let cost = G::weight_to_gas(T::DbWeight::get().reads(reads));
+		if let Some(target_gas) = target_gas {
+			if cost > target_gas {
+				return Err(ExitError::OutOfGas);
+			}
+		}
+		Ok(cost)
+	}
+}
+
+impl<T: Config, G: GasWeightMapping> Precompile for MerkleTreePrecompile<T, G> {
+	fn execute(
+		input: &[u8],
+		target_gas: Option<u64>,
+		_context: &Context,
+	) -> core::result::Result<PrecompileOutput, ExitError> {
+		let tree_doesnt_exist = || ExitError::Other("Tree doesn't exist".into());
+		let (selector, mut reader) = Reader::new_with_selector(input)?;
+		let tree_id: T::TreeId = reader.read_u32()?.into();
+
+		let (cost, output) = if selector == abi::selector(GET_MERKLE_ROOT_SIGNATURE) {
+			let cost = Self::charge(1, target_gas)?;
+			let root = <MerklePallet<T> as Tree<T>>::get_root(tree_id).map_err(|_| tree_doesnt_exist())?;
+			(cost, abi::encode_bytes32(slice_to_bytes_32(&root)))
+		} else if selector == abi::selector(GET_CACHED_ROOTS_SIGNATURE) {
+			let block_number: T::BlockNumber = reader.read_u32()?.into();
+			let cost = Self::charge(1, target_gas)?;
+			let roots: Vec<[u8; 32]> = <MerklePallet<T> as Tree<T>>::get_cached_roots(tree_id, block_number)
+				.iter()
+				.map(|root| slice_to_bytes_32(root))
+				.collect();
+			(cost, Writer::new().write_bytes32_array(&roots).build())
+		} else if selector == abi::selector(GET_LEAF_COUNT_SIGNATURE) {
+			let cost = Self::charge(1, target_gas)?;
+			let leaf_count = <MerklePallet<T> as Tree<T>>::get_leaf_count(tree_id).map_err(|_| tree_doesnt_exist())?;
+			(cost, Writer::new().write_u32(leaf_count).build())
+		} else if selector == abi::selector(HAS_USED_NULLIFIER_SIGNATURE) {
+			let nullifier_hash = reader.read_bytes32()?;
+			// The tree and the nullifier are read from storage
+			let cost = Self::charge(2, target_gas)?;
+			let used = match <MerklePallet<T> as Tree<T>>::has_used_nullifier(tree_id, nullifier_hash.to_vec()) {
+				Ok(()) => false,
+				Err(e) if e == Error::<T>::AlreadyUsedNullifier.into() => true,
+				Err(_) => return Err(tree_doesnt_exist()),
+			};
+			(cost, abi::encode_bool(used))
+		} else if selector == abi::selector(IS_STOPPED_SIGNATURE) {
+			let cost = Self::charge(1, target_gas)?;
+			let stopped = <MerklePallet<T> as Tree<T>>::is_stopped(tree_id);
+			(cost, abi::encode_bool(stopped))
+		} else {
+			return Err(ExitError::Other("Unknown function selector".into()));
+		};
+
+		Ok(PrecompileOutput {
+			exit_status: ExitSucceed::Returned,
+			cost,
+			output,
+			logs: Vec::new(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::mock::{new_test_ext, test_context, MerkleTrees, Origin, System, Test, TestGasWeightMapping};
+	use bulletproofs::BulletproofGens;
+	use frame_support::assert_ok;
+	use merkle::utils::{
+		keys::get_bp_gen_bytes,
+		setup::{Backend, Curve, HashFunction, Setup},
+	};
+
+	type TreePrecompile = MerkleTreePrecompile<Test, TestGasWeightMapping>;
+
+	fn leaf(x: u8) -> [u8; 32] {
+		let mut bytes = [0u8; 32];
+		bytes[0] = x;
+		bytes
+	}
+
+	/// Creates tree 0 at block 1 with `leaves`.
+	fn create_tree(leaves: Vec<[u8; 32]>) {
+		let leaves = leaves.iter().map(|leaf| leaf.to_vec()).collect();
+		System::set_block_number(1);
+		let setup = Setup::new(HashFunction::PoseidonDefault, Backend::Bulletproofs(Curve::Curve25519));
+		assert_ok!(MerkleTrees::create_tree(Origin::signed(1), false, setup, Some(3)));
+		let key_data = get_bp_gen_bytes(&BulletproofGens::new(4096, 1));
+		assert_ok!(MerkleTrees::add_verifying_key(Origin::signed(1), key_data));
+		assert_ok!(MerkleTrees::initialize_tree(Origin::signed(1), 0, 0));
+		assert_ok!(MerkleTrees::add_members(Origin::signed(1), 0, leaves));
+	}
+
+	fn call(signature: &str, tree_id: u32) -> Writer {
+		Writer::new_with_selector(abi::selector(signature)).write_u32(tree_id)
+	}
+
+	fn execute(input: Vec<u8>) -> Result<Vec<u8>, ExitError> {
+		TreePrecompile::execute(&input, None, &test_context()).map(|output| output.output)
+	}
+
+	#[test]
+	fn should_read_roots_and_leaf_count() {
+		new_test_ext().execute_with(|| {
+			create_tree(vec![leaf(1), leaf(2), leaf(3)]);
+			let root = slice_to_bytes_32(&MerkleTrees::get_merkle_root(0).unwrap());
+
+			let output = execute(call(GET_MERKLE_ROOT_SIGNATURE, 0).build()).unwrap();
+			assert_eq!(output, abi::encode_bytes32(root));
+
+			let output = execute(call(GET_CACHED_ROOTS_SIGNATURE, 0).write_u32(1).build()).unwrap();
+			assert_eq!(output, Writer::new().write_bytes32_array(&[root]).build());
+			let output = execute(call(GET_CACHED_ROOTS_SIGNATURE, 0).write_u32(2).build()).unwrap();
+			assert_eq!(output, Writer::new().write_bytes32_array(&[]).build());
+
+			let output = execute(call(GET_LEAF_COUNT_SIGNATURE, 0).build()).unwrap();
+			assert_eq!(output, Writer::new().write_u32(3).build());
+		});
+	}
+
+	#[test]
+	fn should_read_nullifiers_and_stopped() {
+		new_test_ext().execute_with(|| {
+			create_tree(vec![leaf(1)]);
+			let nullifier_hash = leaf(4);
+			let used = |nullifier_hash| {
+				execute(
+					call(HAS_USED_NULLIFIER_SIGNATURE, 0)
+						.write_bytes32(nullifier_hash)
+						.build(),
+				)
+				.unwrap()
+			};
+
+			assert_eq!(used(nullifier_hash), abi::encode_bool(false));
+			assert_ok!(<MerkleTrees as Tree<Test>>::add_nullifier(
+				1,
+				0,
+				nullifier_hash.to_vec()
+			));
+			assert_eq!(used(nullifier_hash), abi::encode_bool(true));
+
+			let output = execute(call(IS_STOPPED_SIGNATURE, 0).build()).unwrap();
+			assert_eq!(output, abi::encode_bool(false));
+			assert_ok!(MerkleTrees::set_stopped(Origin::signed(1), 0, true));
+			let output = execute(call(IS_STOPPED_SIGNATURE, 0).build()).unwrap();
+			assert_eq!(output, abi::encode_bool(true));
+		});
+	}
+
+	#[test]
+	fn should_fail_on_missing_tree() {
+		new_test_ext().execute_with(|| {
+			let tree_doesnt_exist = Err(ExitError::Other("Tree doesn't exist".into()));
+			assert_eq!(execute(call(GET_MERKLE_ROOT_SIGNATURE, 1).build()), tree_doesnt_exist);
+			assert_eq!(execute(call(GET_LEAF_COUNT_SIGNATURE, 1).build()), tree_doesnt_exist);
+			assert_eq!(
+				execute(call(HAS_USED_NULLIFIER_SIGNATURE, 1).write_bytes32(leaf(4)).build()),
+				tree_doesnt_exist
+			);
+			assert_eq!(
+				execute(
+					Writer::new_with_selector(abi::selector("unknown(uint32)"))
+						.write_u32(0)
+						.build()
+				),
+				Err(ExitError::Other("Unknown function selector".into()))
+			);
+		});
+	}
+}
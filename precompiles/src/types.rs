@@ -1,7 +1,4 @@
-use super::{
-	encoding::{Decode, Encode},
-	*,
-};
+use super::*;
 use bulletproofs::r1cs::{R1CSProof, Verifier};
 use bulletproofs_gadgets::{
 	fixed_deposit_tree::mixer_verif_gadget,
@@ -10,9 +7,9 @@ use bulletproofs_gadgets::{
 };
 use curve25519_dalek::{ristretto::CompressedRistretto, scalar::Scalar};
 use evm::ExitError;
+use merkle::utils::setup::bulletproofs_verifier_rng;
 use merlin::Transcript;
-use rand_chacha::rand_core::{CryptoRng, RngCore};
-use sp_std::prelude::Vec;
+use sp_std::{prelude::Vec, vec};
 
 #[derive(Debug)]
 pub struct WithdrawProof {
@@ -28,7 +25,9 @@ pub struct WithdrawProof {
 }
 
 impl WithdrawProof {
-	pub fn verify<T: RngCore + CryptoRng>(&self, hasher: &Poseidon, rng: &mut T) -> Result<(), ExitError> {
+	/// Verifies the proof, drawing the random scalars of the verifier from a
+	/// transcript of the proof and its inputs.
+	pub fn verify(&self, hasher: &Poseidon) -> Result<(), ExitError> {
 		let label = b"zk_membership_proof";
 		let mut verifier_transcript = Transcript::new(label);
 		let mut verifier = Verifier::new(&mut verifier_transcript);
@@ -92,8 +91,15 @@ impl WithdrawProof {
 			return Err(ExitError::Other("ConstraintSystemUnsatisfied".into()));
 		}
 
-		let verify_res = verifier.verify_with_rng(&self.proof, &hasher.pc_gens, &hasher.bp_gens, rng);
-		println!("{:?}", verify_res);
+		let commitments = [
+			&self.private_inputs[..],
+			&self.index_private_inputs[..],
+			&self.node_private_inputs[..],
+		]
+		.concat();
+		let public_inputs = [self.root, self.nullifier_hash, self.recipient, self.relayer];
+		let mut rng = bulletproofs_verifier_rng(&commitments, &public_inputs, &self.proof.to_bytes());
+		let verify_res = verifier.verify_with_rng(&self.proof, &hasher.pc_gens, &hasher.bp_gens, &mut rng);
 		if !verify_res.is_ok() {
 			return Err(ExitError::Other("ZkVerificationFailed".into()));
 		}
@@ -101,116 +107,84 @@ impl WithdrawProof {
 	}
 }
 
-impl Decode for WithdrawProof {
-	fn decode(input: &mut Vec<u8>) -> Result<Self, String> {
-		let depth = u8::decode(input)?;
-
-		// private inputs
-		let private_input_bytes1 = <[u8; 32] as Decode>::decode(input)?;
-		let private_input_bytes2 = <[u8; 32] as Decode>::decode(input)?;
-		let private_input_bytes3 = <[u8; 32] as Decode>::decode(input)?;
-
-		// path private inputs
-		let mut nodes_bytes = Vec::new();
-		for _ in 0..depth {
-			let node = <[u8; 32] as Decode>::decode(input)?;
-			nodes_bytes.push(node);
+impl WithdrawProof {
+	/// Reads the arguments of a `verify` call, see [`VERIFY_SIGNATURE`].
+	///
+	/// The depth of the tree is the number of path commitments.
+	pub fn decode(reader: &mut Reader) -> Result<Self, ExitError> {
+		let comms = reader.read_bytes32_array()?;
+		let nodes = reader.read_bytes32_array()?;
+		let indices = reader.read_bytes32_array()?;
+		let root = reader.read_bytes32()?;
+		let nullifier_hash = reader.read_bytes32()?;
+		let recipient = reader.read_bytes32()?;
+		let relayer = reader.read_bytes32()?;
+		let proof_bytes = reader.read_bytes()?;
+
+		if comms.len() != 3 {
+			return Err(ExitError::Other("Expected 3 commitments".into()));
 		}
-		let mut index_bytes = Vec::new();
-		for _ in 0..depth {
-			let index = <[u8; 32] as Decode>::decode(input)?;
-			index_bytes.push(index);
+		if nodes.len() != indices.len() {
+			return Err(ExitError::Other("Path and index commitments differ in length".into()));
 		}
-
-		// public inputs
-		let root_bytes = <[u8; 32] as Decode>::decode(input)?;
-		let nullifier_hash_bytes = <[u8; 32] as Decode>::decode(input)?;
-		let recipient_bytes = <[u8; 32] as Decode>::decode(input)?;
-		let relayer_bytes = <[u8; 32] as Decode>::decode(input)?;
-
-		// Taking the rest of bytes for R1CSProof
-		let proof_vec = <Vec<u8>>::decode(input)?;
-
-		// Convering to Bulletproofs types
-		// private inputs
-		let private_input1 = CompressedRistretto::from_slice(&private_input_bytes1);
-		let private_input2 = CompressedRistretto::from_slice(&private_input_bytes2);
-		let private_input3 = CompressedRistretto::from_slice(&private_input_bytes3);
-		let private_inputs = vec![private_input1, private_input2, private_input3];
-
-		// path private inputs
-		let nodes = nodes_bytes.iter().map(|x| CompressedRistretto::from_slice(x)).collect();
-		let indicies = index_bytes.iter().map(|x| CompressedRistretto::from_slice(x)).collect();
-
-		// public inputs
-		let root = Scalar::from_bytes_mod_order(root_bytes);
-		let nullifier_hash = Scalar::from_bytes_mod_order(nullifier_hash_bytes);
-		let recipient = Scalar::from_bytes_mod_order(recipient_bytes);
-		let relayer = Scalar::from_bytes_mod_order(relayer_bytes);
-
-		// proof
-		let proof = R1CSProof::from_bytes(&proof_vec).map_err(|_| "Invalid proof bytes")?;
+		if nodes.len() > MAX_TREE_DEPTH as usize {
+			return Err(ExitError::Other("Tree depth is too large".into()));
+		}
+		let to_points = |words: Vec<[u8; 32]>| -> Vec<CompressedRistretto> {
+			words.iter().map(|x| CompressedRistretto::from_slice(x)).collect()
+		};
+		let proof = R1CSProof::from_bytes(&proof_bytes).map_err(|_| ExitError::Other("Invalid proof".into()))?;
 
 		Ok(WithdrawProof {
-			depth,
-			root,
-			private_inputs,
-			nullifier_hash,
-			node_private_inputs: nodes,
-			index_private_inputs: indicies,
-			recipient,
-			relayer,
+			depth: nodes.len() as u8,
+			private_inputs: to_points(comms),
+			node_private_inputs: to_points(nodes),
+			index_private_inputs: to_points(indices),
+			root: Scalar::from_bytes_mod_order(root),
+			nullifier_hash: Scalar::from_bytes_mod_order(nullifier_hash),
+			recipient: Scalar::from_bytes_mod_order(recipient),
+			relayer: Scalar::from_bytes_mod_order(relayer),
 			proof,
 		})
 	}
 }
 
-impl Encode for WithdrawProof {
-	fn size_hint(&self) -> usize {
-		// 3 private inputs + 4 public inputs
-		let inputs_size = 32 * 7;
-		let path_size = self.depth as usize * 64;
-		let proof_size = self.proof.serialized_size();
-		inputs_size + path_size + proof_size
-	}
-
-	fn encode(&self) -> Vec<u8> {
-		let mut encoded = Vec::with_capacity(self.size_hint());
-		encoded.push(self.depth);
-
-		// private inputs
-		for private_input in &self.private_inputs {
-			encoded.extend(&private_input.to_bytes());
-		}
-		// private path inputs
-		for index_input in &self.index_private_inputs {
-			encoded.extend(&index_input.to_bytes());
-		}
-		for node_input in &self.node_private_inputs {
-			encoded.extend(&node_input.to_bytes());
-		}
-		// public inputs
-		encoded.extend(&self.root.to_bytes());
-		encoded.extend(&self.nullifier_hash.to_bytes());
-		encoded.extend(&self.recipient.to_bytes());
-		encoded.extend(&self.relayer.to_bytes());
-		//proof
-		encoded.extend(&self.proof.to_bytes());
-		encoded
-	}
-}
-
 #[cfg(test)]
 pub mod test {
 	use super::*;
-	use crate::default_bulletproofs_poseidon_hasher;
+	use crate::{abi::Writer, default_bulletproofs_poseidon_hasher};
 	use bulletproofs::r1cs::Prover;
 	use bulletproofs_gadgets::{
 		poseidon::{allocate_statics_for_prover, Poseidon_hash_2},
 		smt::builder::SparseMerkleTreeBuilder,
 		utils::get_bits,
 	};
-	use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+	use rand_chacha::{
+		rand_core::{CryptoRng, RngCore, SeedableRng},
+		ChaChaRng,
+	};
+
+	/// Encodes the input of a `verify` call for `withdraw_proof`.
+	pub fn encode(withdraw_proof: &WithdrawProof) -> Vec<u8> {
+		let to_words =
+			|points: &[CompressedRistretto]| -> Vec<[u8; 32]> { points.iter().map(|p| p.to_bytes()).collect() };
+		Writer::new_with_selector(abi::selector(VERIFY_SIGNATURE))
+			.write_bytes32_array(&to_words(&withdraw_proof.private_inputs))
+			.write_bytes32_array(&to_words(&withdraw_proof.node_private_inputs))
+			.write_bytes32_array(&to_words(&withdraw_proof.index_private_inputs))
+			.write_bytes32(withdraw_proof.root.to_bytes())
+			.write_bytes32(withdraw_proof.nullifier_hash.to_bytes())
+			.write_bytes32(withdraw_proof.recipient.to_bytes())
+			.write_bytes32(withdraw_proof.relayer.to_bytes())
+			.write_bytes(&withdraw_proof.proof.to_bytes())
+			.build()
+	}
+
+	/// Decodes the input of a `verify` call.
+	pub fn decode(input: &[u8]) -> Result<WithdrawProof, ExitError> {
+		let (_, mut reader) = Reader::new_with_selector(input)?;
+		WithdrawProof::decode(&mut reader)
+	}
 
 	pub fn generate_proof_data<T: RngCore + CryptoRng>(
 		test_rng: &mut T,
@@ -372,13 +346,13 @@ pub mod test {
 			proof: proof.clone(),
 		};
 
-		let mut encoded_wp = withdraw_proof.encode();
+		let encoded_wp = encode(&withdraw_proof);
 
-		let decoded_wp = WithdrawProof::decode(&mut encoded_wp).unwrap();
+		let decoded_wp = decode(&encoded_wp).unwrap();
 		assert_eq!(decoded_wp.depth, tree_depth);
 		assert_eq!(decoded_wp.proof.to_bytes(), proof.to_bytes());
 
-		let verify_res = withdraw_proof.verify(&poseidon, &mut test_rng);
+		let verify_res = withdraw_proof.verify(&poseidon);
 		assert!(verify_res.is_ok());
 	}
 
@@ -400,13 +374,13 @@ pub mod test {
 			proof: proof.clone(),
 		};
 
-		let mut encoded_wp = withdraw_proof.encode();
+		let encoded_wp = encode(&withdraw_proof);
 
-		let decoded_wp = WithdrawProof::decode(&mut encoded_wp).unwrap();
+		let decoded_wp = decode(&encoded_wp).unwrap();
 		assert_eq!(decoded_wp.depth, tree_depth);
 		assert_eq!(decoded_wp.proof.to_bytes(), proof.to_bytes());
 
-		let verify_res = withdraw_proof.verify(&poseidon, &mut test_rng);
+		let verify_res = withdraw_proof.verify(&poseidon);
 		assert!(verify_res.is_err());
 	}
 
@@ -427,10 +401,16 @@ pub mod test {
 			proof: proof.clone(),
 		};
 
-		let mut encoded_wp = withdraw_proof.encode();
-		encoded_wp = encoded_wp.drain(encoded_wp.len() - 10..).collect();
+		let mut encoded_wp = encode(&withdraw_proof);
+		encoded_wp.truncate(encoded_wp.len() - 10);
+		assert!(decode(&encoded_wp).is_err());
 
-		let decoded_wp = WithdrawProof::decode(&mut encoded_wp);
-		assert!(decoded_wp.is_err());
+		// Path and index commitments must have the same length
+		let mut withdraw_proof = withdraw_proof;
+		withdraw_proof.index_private_inputs.pop();
+		assert_eq!(
+			decode(&encode(&withdraw_proof)).err(),
+			Some(ExitError::Other("Path and index commitments differ in length".into()))
+		);
 	}
 }
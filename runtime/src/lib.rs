@@ -13,15 +13,12 @@ pub use pallet_transaction_payment::{CurrencyAdapter, Multiplier, TargetedFeeAdj
 use pallet_transaction_payment::{FeeDetails, RuntimeDispatchInfo};
 use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
-use sp_core::{
-	crypto::{AccountId32, KeyTypeId},
-	OpaqueMetadata, H160, H256, U256,
-};
+use sp_core::{crypto::KeyTypeId, OpaqueMetadata, H160, H256, U256};
 use sp_inherents::{CheckInherentsResult, InherentData};
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
-	traits::{BlakeTwo256, Block as BlockT, IdentifyAccount, IdentityLookup, NumberFor, Verify},
-	transaction_validity::{TransactionSource, TransactionValidity},
+	traits::{AccountIdConversion, BlakeTwo256, Block as BlockT, IdentifyAccount, IdentityLookup, NumberFor, Verify},
+	transaction_validity::{TransactionPriority, TransactionSource, TransactionValidity},
 	ApplyExtrinsicResult, FixedPointNumber, MultiSignature, Perbill, Perquintill,
 };
 use sp_std::prelude::*;
@@ -54,12 +51,15 @@ use webb_currencies::BasicCurrencyAdapter;
 
 use pallet_ethereum::TransactionStatus;
 use pallet_evm::{Account as EVMAccount, EnsureAddressTruncated, HashedAddressMapping, Runner};
+use precompiles::AnonPrecompiles;
 
 use sp_core::crypto::Public;
 #[cfg(any(feature = "std", test))]
 pub use sp_runtime::BuildStorage;
 use sp_runtime::ConsensusEngineId;
 
+pub mod precompiles;
+
 pub mod currency {
 	use super::Balance;
 
@@ -81,6 +81,9 @@ use merkle::weights::Weights as MerkleWeights;
 /// Importing a mixers pallet
 pub use mixer;
 use mixer::weights::Weights as MixerWeights;
+/// Importing a relayers pallet
+pub use relayers;
+use relayers::weights::Weights as RelayersWeights;
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -349,14 +352,15 @@ impl pallet_sudo::Config for Runtime {
 parameter_types! {
 	pub const MaxTreeDepth: u8 = 32;
 	pub const CacheBlockLength: BlockNumber = 100;
+	pub const MaxMemoLength: u32 = 256;
 }
 
 impl merkle::Config for Runtime {
 	type CacheBlockLength = CacheBlockLength;
 	type Event = Event;
 	type KeyId = u32;
+	type MaxMemoLength = MaxMemoLength;
 	type MaxTreeDepth = MaxTreeDepth;
-	type Randomness = RandomnessCollectiveFlip;
 	type TreeId = u32;
 	type WeightInfo = MerkleWeights<Self>;
 }
@@ -400,27 +404,81 @@ impl webb_currencies::Config for Runtime {
 parameter_types! {
 	pub const MixerPalletId: PalletId = PalletId(*b"py/mixer");
 	pub const MinimumDepositLength: BlockNumber = 10 * 60 * 24 * 28;
-	pub const DefaultAdminKey: AccountId32 = AccountId32::new([0; 32]);
-	pub MixerSizes: Vec<Balance> = [
-		DOLLARS * 1_000,
-		DOLLARS * 10_000,
-		DOLLARS * 100_000,
-		DOLLARS * 1_000_000
-	].to_vec();
+	// The runtime's mixers verify Bulletproofs proofs one by one, against a key
+	// prepared once per batch. Batches are capped to what fits in a normal
+	// extrinsic, which must be at least one withdrawal (see the tests below).
+	pub MaxWithdrawBatchSize: u32 = {
+		let max_extrinsic = RuntimeBlockWeights::get()
+			.get(DispatchClass::Normal)
+			.max_extrinsic
+			.unwrap_or(MAXIMUM_BLOCK_WEIGHT);
+		(1..=16u32)
+			.take_while(|n| Mixer::batch_withdraw_weight(*n) <= max_extrinsic)
+			.last()
+			.unwrap_or(0)
+	};
+	// Deposits and withdrawals are counted hourly for the mixer statistics
+	pub const StatsBucketLength: BlockNumber = HOURS;
+	// The mixer statistics cover at most the last 30 days
+	pub const StatsHistoryLength: u32 = 30 * 24;
+	// The fees of unsigned withdrawals are collected by an account of their own
+	pub MixerFeeCollector: AccountId = PalletId(*b"py/mxfee").into_account();
+	pub const MixerUnsignedPriority: TransactionPriority = TransactionPriority::max_value() / 2;
+	// Unsigned withdrawals pay no transaction fee, so they can only take a few
+	// slots of each block
+	pub const MaxUnsignedWithdrawalsPerBlock: u32 = 4;
 }
 
 impl mixer::Config for Runtime {
+	type AdminOrigin = frame_system::EnsureRoot<AccountId>;
+	type ChainId = ChainId;
 	type Currency = Currencies;
-	type DefaultAdmin = DefaultAdminKey;
+	type DenyList = ();
 	type DepositLength = MinimumDepositLength;
 	type Event = Event;
-	type MixerSizes = MixerSizes;
+	type FeeCollector = MixerFeeCollector;
+	type MaxUnsignedWithdrawalsPerBlock = MaxUnsignedWithdrawalsPerBlock;
+	type MaxWithdrawBatchSize = MaxWithdrawBatchSize;
 	type NativeCurrencyId = NativeCurrencyId;
+	type OnRelay = Relayers;
 	type PalletId = MixerPalletId;
+	type PauseOrigin = frame_system::EnsureRoot<AccountId>;
+	type StatsBucketLength = StatsBucketLength;
+	type StatsHistoryLength = StatsHistoryLength;
 	type Tree = Merkle;
+	type UnsignedPriority = MixerUnsignedPriority;
 	type WeightInfo = MixerWeights<Self>;
 }
 
+webb_traits::parameter_type_with_key! {
+	// Relayers stake the native currency, other currencies can be allowed
+	// once their minimum stake is agreed on
+	pub RelayerMinStake: |currency_id: CurrencyId| -> Balance {
+		if *currency_id == NativeCurrencyId::get() {
+			100 * DOLLARS
+		} else {
+			0
+		}
+	};
+}
+
+parameter_types! {
+	pub const RelayerUnbondingPeriod: BlockNumber = 7 * DAYS;
+	pub const MaxRelayerEndpointLength: u32 = 256;
+	pub const MaxRelayerFees: u32 = 16;
+}
+
+impl relayers::Config for Runtime {
+	type Currency = Currencies;
+	type Event = Event;
+	type MaxEndpointLength = MaxRelayerEndpointLength;
+	type MaxFees = MaxRelayerFees;
+	type MinStake = RelayerMinStake;
+	type SlashOrigin = frame_system::EnsureRoot<AccountId>;
+	type UnbondingPeriod = RelayerUnbondingPeriod;
+	type WeightInfo = RelayersWeights<Self>;
+}
+
 /// Current approximation of the gas/s consumption considering
 /// EVM execution over compiled WASM (on 4.4Ghz CPU).
 /// Given the 500ms Weight, from which 75% only are used for transactions,
@@ -458,15 +516,7 @@ impl pallet_evm::Config for Runtime {
 	type FeeCalculator = pallet_dynamic_fee::Pallet<Self>;
 	type GasWeightMapping = AnonGasWeightMapping;
 	type OnChargeTransaction = ();
-	type Precompiles = (
-		pallet_evm_precompile_simple::ECRecover,
-		pallet_evm_precompile_simple::Sha256,
-		pallet_evm_precompile_simple::Ripemd160,
-		pallet_evm_precompile_simple::Identity,
-		pallet_evm_precompile_simple::ECRecoverPublicKey,
-		/* pallet_evm_precompile_sha3fips::Sha3FIPS256,
-		 * pallet_evm_precompile_sha3fips::Sha3FIPS512, */
-	);
+	type Precompiles = AnonPrecompiles;
 	type Runner = pallet_evm::runner::stack::Runner<Self>;
 	type WithdrawOrigin = EnsureAddressTruncated;
 }
@@ -526,8 +576,9 @@ construct_runtime!(
 
 		Currencies: webb_currencies::{Pallet, Storage, Event<T>},
 		Tokens: webb_tokens::{Pallet, Storage, Event<T>},
-		Mixer: mixer::{Pallet, Call, Storage, Event<T>},
+		Mixer: mixer::{Pallet, Call, Storage, Config<T>, Event<T>, ValidateUnsigned},
 		Merkle: merkle::{Pallet, Call, Storage, Event<T>},
+		Relayers: relayers::{Pallet, Call, Storage, Event<T>},
 	}
 );
 
@@ -865,6 +916,18 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl mixer::MixerApi<Block, u32, BlockNumber> for Runtime {
+		fn get_mixer_stats(mixer_id: u32, window: BlockNumber) -> Option<mixer::MixerStats<BlockNumber>> {
+			Mixer::get_stats(mixer_id, window)
+		}
+	}
+
+	impl relayers::RelayersApi<Block, AccountId, CurrencyId, Balance, BlockNumber> for Runtime {
+		fn get_relayers() -> Vec<(AccountId, relayers::RelayerInfo<CurrencyId, Balance, BlockNumber>)> {
+			Relayers::get_relayers()
+		}
+	}
+
 	#[cfg(feature = "runtime-benchmarks")]
 	impl frame_benchmarking::Benchmark<Block> for Runtime {
 		fn dispatch_benchmark(
@@ -902,3 +965,19 @@ impl_runtime_apis! {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn withdrawal_batches_fit_in_a_normal_extrinsic() {
+		let max_extrinsic = RuntimeBlockWeights::get()
+			.get(DispatchClass::Normal)
+			.max_extrinsic
+			.unwrap_or(MAXIMUM_BLOCK_WEIGHT);
+		let max_batch_size = MaxWithdrawBatchSize::get();
+		assert!(max_batch_size >= 1, "a single withdrawal must fit in a normal extrinsic");
+		assert!(Mixer::batch_withdraw_weight(max_batch_size) <= max_extrinsic);
+	}
+}
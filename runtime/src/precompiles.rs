@@ -0,0 +1,127 @@
+//! EVM precompiles of the runtime
+//!
+//! The standard Ethereum precompiles live at their usual addresses. Our own
+//! precompiles are placed from address 1024 onwards, out of the way of any
+//! precompile Ethereum may add in the future.
+use crate::{AnonGasWeightMapping, Runtime};
+use evm::{executor::PrecompileOutput, Context, ExitError};
+use fp_evm::{Precompile, PrecompileSet};
+use frame_support::parameter_types;
+use pallet_evm_precompile_bp_merkle_tree::{
+	groth16::{Groth16Bls381, Groth16Bn254, Groth16Precompile},
+	hash::{
+		HashPrecompile, MiMCBn254, PoseidonCurve25519, PoseidonX17Bls381, PoseidonX17Bn254, PoseidonX5Bls381,
+		PoseidonX5Bn254,
+	},
+	mixer::MixerPrecompile,
+	tree::MerkleTreePrecompile,
+	BulletproofMerkleTreeMembershipPrecompile,
+};
+use pallet_evm_precompile_simple::{ECRecover, ECRecoverPublicKey, Identity, Ripemd160, Sha256};
+use sp_core::H160;
+
+/// Address of the mixer membership proof verification precompile
+pub const BULLETPROOF_MERKLE_TREE_MEMBERSHIP_ADDRESS: u64 = 1024;
+/// Address of the Bulletproofs Poseidon hash precompile
+pub const POSEIDON_CURVE25519_ADDRESS: u64 = 1025;
+/// Address of the Poseidon x5 hash precompile over BLS12-381
+pub const POSEIDON_X5_BLS381_ADDRESS: u64 = 1026;
+/// Address of the Poseidon x5 hash precompile over BN254
+pub const POSEIDON_X5_BN254_ADDRESS: u64 = 1027;
+/// Address of the Poseidon x17 hash precompile over BLS12-381
+pub const POSEIDON_X17_BLS381_ADDRESS: u64 = 1028;
+/// Address of the Poseidon x17 hash precompile over BN254
+pub const POSEIDON_X17_BN254_ADDRESS: u64 = 1029;
+/// Address of the MiMC hash precompile over BN254
+pub const MIMC_BN254_ADDRESS: u64 = 1030;
+/// Address of the Groth16 verification precompile over BN254
+pub const GROTH16_BN254_ADDRESS: u64 = 1031;
+/// Address of the Groth16 verification precompile over BLS12-381
+pub const GROTH16_BLS381_ADDRESS: u64 = 1032;
+/// Address of the merkle tree state precompile
+pub const MERKLE_TREE_ADDRESS: u64 = 1033;
+/// Address of the mixer deposit and withdrawal precompile
+pub const MIXER_ADDRESS: u64 = 1034;
+
+parameter_types! {
+	pub MixerPrecompileAddress: H160 = hash(MIXER_ADDRESS);
+}
+
+/// Verifies Bulletproofs membership proofs of the mixer
+pub type MembershipPrecompile = BulletproofMerkleTreeMembershipPrecompile<Runtime, AnonGasWeightMapping>;
+
+/// Hashes with the Bulletproofs Poseidon hash
+pub type PoseidonCurve25519Precompile = HashPrecompile<PoseidonCurve25519, AnonGasWeightMapping>;
+/// Hashes with the Poseidon x5 hash over BLS12-381
+pub type PoseidonX5Bls381Precompile = HashPrecompile<PoseidonX5Bls381, AnonGasWeightMapping>;
+/// Hashes with the Poseidon x5 hash over BN254
+pub type PoseidonX5Bn254Precompile = HashPrecompile<PoseidonX5Bn254, AnonGasWeightMapping>;
+/// Hashes with the Poseidon x17 hash over BLS12-381
+pub type PoseidonX17Bls381Precompile = HashPrecompile<PoseidonX17Bls381, AnonGasWeightMapping>;
+/// Hashes with the Poseidon x17 hash over BN254
+pub type PoseidonX17Bn254Precompile = HashPrecompile<PoseidonX17Bn254, AnonGasWeightMapping>;
+/// Hashes with the MiMC hash over BN254
+pub type MiMCBn254Precompile = HashPrecompile<MiMCBn254, AnonGasWeightMapping>;
+/// Verifies Groth16 proofs over BN254
+pub type Groth16Bn254Precompile = Groth16Precompile<Runtime, Groth16Bn254, AnonGasWeightMapping>;
+/// Verifies Groth16 proofs over BLS12-381
+pub type Groth16Bls381Precompile = Groth16Precompile<Runtime, Groth16Bls381, AnonGasWeightMapping>;
+/// Reads the roots, leaves and nullifiers of the merkle trees
+pub type MerkleTreeStatePrecompile = MerkleTreePrecompile<Runtime, AnonGasWeightMapping>;
+/// Deposits into and withdraws from the mixers for EVM accounts
+pub type MixerDispatchPrecompile = MixerPrecompile<
+	Runtime,
+	<Runtime as pallet_evm::Config>::AddressMapping,
+	AnonGasWeightMapping,
+	MixerPrecompileAddress,
+>;
+
+/// Precompiles available to the EVM, by address
+pub struct AnonPrecompiles;
+
+impl PrecompileSet for AnonPrecompiles {
+	fn execute(
+		address: H160,
+		input: &[u8],
+		target_gas: Option<u64>,
+		context: &Context,
+	) -> Option<Result<PrecompileOutput, ExitError>> {
+		match address {
+			a if a == hash(1) => Some(ECRecover::execute(input, target_gas, context)),
+			a if a == hash(2) => Some(Sha256::execute(input, target_gas, context)),
+			a if a == hash(3) => Some(Ripemd160::execute(input, target_gas, context)),
+			a if a == hash(4) => Some(Identity::execute(input, target_gas, context)),
+			a if a == hash(5) => Some(ECRecoverPublicKey::execute(input, target_gas, context)),
+			a if a == hash(BULLETPROOF_MERKLE_TREE_MEMBERSHIP_ADDRESS) => {
+				Some(MembershipPrecompile::execute(input, target_gas, context))
+			}
+			a if a == hash(POSEIDON_CURVE25519_ADDRESS) => {
+				Some(PoseidonCurve25519Precompile::execute(input, target_gas, context))
+			}
+			a if a == hash(POSEIDON_X5_BLS381_ADDRESS) => {
+				Some(PoseidonX5Bls381Precompile::execute(input, target_gas, context))
+			}
+			a if a == hash(POSEIDON_X5_BN254_ADDRESS) => {
+				Some(PoseidonX5Bn254Precompile::execute(input, target_gas, context))
+			}
+			a if a == hash(POSEIDON_X17_BLS381_ADDRESS) => {
+				Some(PoseidonX17Bls381Precompile::execute(input, target_gas, context))
+			}
+			a if a == hash(POSEIDON_X17_BN254_ADDRESS) => {
+				Some(PoseidonX17Bn254Precompile::execute(input, target_gas, context))
+			}
+			a if a == hash(MIMC_BN254_ADDRESS) => Some(MiMCBn254Precompile::execute(input, target_gas, context)),
+			a if a == hash(GROTH16_BN254_ADDRESS) => Some(Groth16Bn254Precompile::execute(input, target_gas, context)),
+			a if a == hash(GROTH16_BLS381_ADDRESS) => {
+				Some(Groth16Bls381Precompile::execute(input, target_gas, context))
+			}
+			a if a == hash(MERKLE_TREE_ADDRESS) => Some(MerkleTreeStatePrecompile::execute(input, target_gas, context)),
+			a if a == hash(MIXER_ADDRESS) => Some(MixerDispatchPrecompile::execute(input, target_gas, context)),
+			_ => None,
+		}
+	}
+}
+
+fn hash(a: u64) -> H160 {
+	H160::from_low_u64_be(a)
+}